@@ -1,17 +1,24 @@
 use async_trait::async_trait;
 use std::fmt;
-use std::sync::mpsc::{channel, Receiver, TryRecvError};
 use std::sync::Arc;
 use tokio::runtime;
 use tokio::sync::Mutex;
+use tokio_stream::StreamExt;
 
-use timesman_bstore::{Store, StoreEvent};
-use timesman_server::{GrpcServer, TimesManServer};
-use timesman_type::{File, Post, Times};
+use timesman_bstore::{
+    Store, StoreError, StoreEvent, StoreEventStream, TimesStore,
+};
+use timesman_server::{
+    AuthService, EventHub, GrpcServer, Listen, TimesManServer,
+};
+use timesman_type::{Tid, UserId};
 
+use crate::log::tmlog;
+
+/// Serves `store` over gRPC while the app uses it, and logs the changes
+/// made to it from either side.
 pub struct ArbiterStore {
-    store: Arc<Mutex<dyn Store + Send + Sync>>,
-    rx: Arc<Mutex<Receiver<StoreEvent>>>,
+    store: Arc<Mutex<dyn Store>>,
 }
 
 impl ArbiterStore {
@@ -19,95 +26,110 @@ impl ArbiterStore {
         rt: &runtime::Runtime,
         store: Arc<Mutex<dyn Store>>,
         listen: &str,
-    ) -> Self {
-        let (tx, rx) = channel();
+        auth_service: Arc<AuthService>,
+    ) -> Result<Self, String> {
+        let listen: Listen = listen.parse()?;
+        let (events, hub) = rt
+            .block_on(async {
+                let events = store.lock().await.subscribe().await?;
+                let hub = EventHub::follow(store.clone()).await?;
+                Ok::<_, StoreError>((events, hub))
+            })
+            .map_err(|e| format!("{e}"))?;
+        rt.spawn(follow(events));
 
         let s = store.clone();
-        let l = listen.to_string();
         rt.spawn(async move {
-            let server = GrpcServer {};
-            server.run(&l, s, Some(tx)).await
+            let server = GrpcServer {
+                auth_service,
+                events: hub,
+                tls: None,
+                max_file_size: timesman_server::DEFAULT_MAX_FILE_SIZE,
+            };
+            if let Err(e) = server.run(&listen, s).await {
+                tmlog(format!("Server on {listen} stopped: {e}"));
+            }
         });
-        Self {
-            store,
-            rx: Arc::new(Mutex::new(rx)),
-        }
+
+        Ok(Self { store })
     }
 }
 
-#[async_trait]
-impl Store for ArbiterStore {
-    async fn check(&mut self) -> Result<(), String> {
-        let mut store = self.store.lock().await;
-        store.check().await?;
+/// Logs each change to the store until it goes away.
+async fn follow(mut events: StoreEventStream) {
+    while let Some(event) = events.next().await {
+        tmlog(describe(&event));
+    }
+}
 
-        let rx = self.rx.lock().await;
-        loop {
-            match rx.try_recv() {
-                Ok(_) => {
-                    todo!();
-                }
-                Err(TryRecvError::Empty) => {
-                    break;
-                }
-                Err(_e) => {}
-            }
+fn describe(event: &StoreEvent) -> String {
+    match event {
+        StoreEvent::TimesCreated(times) => {
+            format!("Times {} created: {}", times.id, times.title)
+        }
+        StoreEvent::TimesUpdated(times) => {
+            format!("Times {} updated: {}", times.id, times.title)
+        }
+        StoreEvent::TimesDeleted(tid) => format!("Times {tid} deleted"),
+        StoreEvent::PostCreated(tid, post) => {
+            format!("Post {} created in times {tid}", post.id)
+        }
+        StoreEvent::PostUpdated(tid, post) => {
+            format!("Post {} updated in times {tid}", post.id)
+        }
+        StoreEvent::PostDeleted(tid, pid) => {
+            format!("Post {pid} deleted from times {tid}")
+        }
+        StoreEvent::TagCreated(tid, tag) => {
+            format!("Tag {} created in times {tid}: {}", tag.id, tag.name)
+        }
+        StoreEvent::TagUpdated(tid, tag) => {
+            format!("Tag {} renamed in times {tid}: {}", tag.id, tag.name)
+        }
+        StoreEvent::TagDeleted(tid, tagid) => {
+            format!("Tag {tagid} deleted from times {tid}")
+        }
+        StoreEvent::TodoCreated(tid, todo) => {
+            format!("Todo {} created in times {tid}", todo.id)
+        }
+        StoreEvent::TodoUpdated(tid, todo) => {
+            format!("Todo {} updated in times {tid}", todo.id)
+        }
+        StoreEvent::TodoDeleted(tid, tdid) => {
+            format!("Todo {tdid} deleted from times {tid}")
+        }
+        StoreEvent::Missed => {
+            "Some changes to the store were missed".to_string()
         }
-
-        Ok(())
     }
+}
 
-    // for Times
-    async fn get_times(&mut self) -> Result<Vec<Times>, String> {
-        let mut store = self.store.lock().await;
-        store.get_times().await
-    }
-    async fn create_times(&mut self, title: String) -> Result<Times, String> {
-        let mut store = self.store.lock().await;
-        store.create_times(title).await
-    }
-    async fn delete_times(&mut self, tid: u64) -> Result<(), String> {
-        let mut store = self.store.lock().await;
-        store.delete_times(tid).await
-    }
-    async fn update_times(&mut self, times: Times) -> Result<Times, String> {
-        let mut store = self.store.lock().await;
-        store.update_times(times).await
+#[async_trait]
+impl Store for ArbiterStore {
+    async fn check(&mut self) -> Result<(), StoreError> {
+        self.store.lock().await.check().await
     }
 
-    // for Post
-    async fn get_posts(&mut self, tid: u64) -> Result<Vec<Post>, String> {
-        let mut store = self.store.lock().await;
-        store.get_posts(tid).await
-    }
-    async fn create_post(
+    async fn get(
         &mut self,
-        tid: u64,
-        post: String,
-        file: Option<(String, File)>,
-    ) -> Result<Post, String> {
-        let mut store = self.store.lock().await;
-        store.create_post(tid, post, file).await
-    }
-    async fn delete_post(&mut self, tid: u64, pid: u64) -> Result<(), String> {
-        let mut store = self.store.lock().await;
-        store.delete_post(tid, pid).await
+    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, StoreError> {
+        self.store.lock().await.get().await
     }
-    async fn update_post(
+
+    async fn create(
         &mut self,
-        tid: u64,
-        post: Post,
-    ) -> Result<Post, String> {
-        let mut store = self.store.lock().await;
-        store.update_post(tid, post).await
+        title: String,
+        owner: Option<UserId>,
+    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, StoreError> {
+        self.store.lock().await.create(title, owner).await
     }
 
-    async fn get_latest_post(
-        &mut self,
-        tid: u64,
-    ) -> Result<Option<Post>, String> {
-        let mut store = self.store.lock().await;
-        store.get_latest_post(tid).await
+    async fn delete(&mut self, tid: Tid) -> Result<(), StoreError> {
+        self.store.lock().await.delete(tid).await
+    }
+
+    async fn subscribe(&mut self) -> Result<StoreEventStream, StoreError> {
+        self.store.lock().await.subscribe().await
     }
 }
 
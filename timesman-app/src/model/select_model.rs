@@ -119,7 +119,7 @@ impl SelectModel {
                         let mut store = store.lock().await;
 
                         let tstore =
-                            store.create(title.to_string()).await.unwrap();
+                            store.create(title.to_string(), None).await.unwrap();
 
                         let times = {
                             let mut tstore = tstore.lock().await;
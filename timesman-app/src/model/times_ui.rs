@@ -336,7 +336,7 @@ impl TimesUI {
                     }
                 });
             }
-            FileType::Other(_) => {
+            FileType::Other(_) | FileType::Omitted { .. } => {
                 ui.label(format!("File: {}", file.name));
             }
         }
@@ -584,7 +584,7 @@ impl TimesUI {
                         });
                     });
             }
-            FileType::Other(_) => {
+            FileType::Other(_) | FileType::Omitted { .. } => {
                 return;
             }
         }
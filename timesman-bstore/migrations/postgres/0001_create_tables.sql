@@ -0,0 +1,45 @@
+-- Posts and todos belong to a times and go with it. Attachments go with
+-- their post.
+
+CREATE TABLE times (
+    id BIGSERIAL PRIMARY KEY,
+    title TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    owner UUID
+);
+
+CREATE TABLE tags (
+    id BIGSERIAL PRIMARY KEY,
+    tid BIGINT NOT NULL REFERENCES times(id) ON DELETE CASCADE,
+    name TEXT NOT NULL
+);
+CREATE INDEX tags_tid ON tags(tid);
+
+CREATE TABLE posts (
+    id BIGSERIAL PRIMARY KEY,
+    tid BIGINT NOT NULL REFERENCES times(id) ON DELETE CASCADE,
+    post TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP,
+    tagid BIGINT REFERENCES tags(id) ON DELETE SET NULL
+);
+CREATE INDEX posts_tid_created_at ON posts(tid, created_at);
+CREATE INDEX posts_tagid ON posts(tagid);
+
+CREATE TABLE attachments (
+    pid BIGINT PRIMARY KEY REFERENCES posts(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    data BYTEA NOT NULL
+);
+
+CREATE TABLE todos (
+    id BIGSERIAL PRIMARY KEY,
+    tid BIGINT NOT NULL REFERENCES times(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    detail TEXT,
+    created_at TIMESTAMP NOT NULL,
+    done_at TIMESTAMP
+);
+CREATE INDEX todos_tid ON todos(tid);
@@ -0,0 +1,57 @@
+use timesman_type::{FileKind, FileType};
+
+use super::StoreError;
+
+/// How an attachment's type is stored, next to its bytes.
+pub(crate) fn file_kind(ftype: &FileType) -> &'static str {
+    match ftype.kind() {
+        FileKind::Text => "text",
+        FileKind::Image => "image",
+        FileKind::Other => "other",
+    }
+}
+
+/// The bytes to store for an attachment, which must have its data.
+pub(crate) fn file_data(ftype: &FileType) -> Result<&[u8], StoreError> {
+    match ftype {
+        FileType::Text(text) => Ok(text.as_bytes()),
+        FileType::Image(data) | FileType::Other(data) => Ok(data),
+        FileType::Omitted { .. } => Err(StoreError::Conflict(
+            "The data of the file is left out".to_string(),
+        )),
+    }
+}
+
+fn parse_kind(kind: &str) -> Result<FileKind, StoreError> {
+    match kind {
+        "text" => Ok(FileKind::Text),
+        "image" => Ok(FileKind::Image),
+        "other" => Ok(FileKind::Other),
+        _ => Err(StoreError::Corrupt(format!("Unknown file kind: {kind}"))),
+    }
+}
+
+/// Reads back what `file_kind` and `file_data` stored.
+pub(crate) fn file_type(
+    kind: &str,
+    data: Vec<u8>,
+) -> Result<FileType, StoreError> {
+    match parse_kind(kind)? {
+        FileKind::Text => String::from_utf8(data)
+            .map(FileType::Text)
+            .map_err(|e| StoreError::Corrupt(format!("Invalid text: {e}"))),
+        FileKind::Image => Ok(FileType::Image(data)),
+        FileKind::Other => Ok(FileType::Other(data)),
+    }
+}
+
+/// The type of an attachment of `size` bytes whose data is left out.
+pub(crate) fn omitted_type(
+    kind: &str,
+    size: u64,
+) -> Result<FileType, StoreError> {
+    Ok(FileType::Omitted {
+        kind: parse_kind(kind)?,
+        size,
+    })
+}
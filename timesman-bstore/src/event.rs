@@ -0,0 +1,63 @@
+use std::pin::Pin;
+
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use timesman_type::{Pid, Post, Tag, TagId, Tdid, Tid, Times, Todo};
+
+/// How many events a subscriber may fall behind before it misses some.
+const EVENT_CAPACITY: usize = 256;
+
+/// A change made to a store.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreEvent {
+    TimesCreated(Times),
+    TimesUpdated(Times),
+    TimesDeleted(Tid),
+    PostCreated(Tid, Post),
+    PostUpdated(Tid, Post),
+    PostDeleted(Tid, Pid),
+    TagCreated(Tid, Tag),
+    TagUpdated(Tid, Tag),
+    TagDeleted(Tid, TagId),
+    TodoCreated(Tid, Todo),
+    TodoUpdated(Tid, Todo),
+    TodoDeleted(Tid, Tdid),
+    /// Some events were lost, because the subscriber fell behind or the
+    /// store could not follow its source. Re-read what is shown.
+    Missed,
+}
+
+pub type StoreEventStream = Pin<Box<dyn Stream<Item = StoreEvent> + Send>>;
+
+/// The sending side of a store's events, shared by the stores it hands
+/// out.
+#[derive(Clone)]
+pub(crate) struct EventSender {
+    tx: broadcast::Sender<StoreEvent>,
+}
+
+impl EventSender {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { tx }
+    }
+
+    /// Sends `event` to the current subscribers, if any.
+    pub fn send(&self, event: StoreEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    #[cfg(feature = "grpc")]
+    pub fn has_subscribers(&self) -> bool {
+        self.tx.receiver_count() > 0
+    }
+
+    pub fn subscribe(&self) -> StoreEventStream {
+        // Lagging behind is the only error.
+        let stream = BroadcastStream::new(self.tx.subscribe())
+            .map(|r| r.unwrap_or(StoreEvent::Missed));
+        Box::pin(stream)
+    }
+}
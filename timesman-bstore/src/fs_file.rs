@@ -1,82 +1,222 @@
-use std::io::{Read, Write};
-use std::{fs, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
-use serde_json;
-use timesman_type::{File, FileType, Pid, Tid};
+use tokio::io::{AsyncReadExt, BufReader};
 
+use timesman_type::{File, Pid, Tid};
+
+use super::attachment::{file_data, file_kind, file_type, omitted_type};
+use super::{write_bytes, StoreError};
+
+/*
+ * Storage structure:
+ * /{tid}/{pid}                  - The attachment of a post, as is
+ * /{tid}/{pid}.meta             - Its name and kind
+ */
+
+/// Keeps the attachments of posts as plain files, out of the database.
 pub(crate) struct FsFileStorage {
     base: PathBuf,
 }
 
 #[derive(Serialize, Deserialize)]
-struct FileMeta {
-    name: String,
-    ftype: FileType,
+pub(crate) struct FileMeta {
+    pub name: String,
+    kind: String,
+    pub size: u64,
+}
+
+impl FileMeta {
+    /// The file this describes, without its data.
+    pub fn info(&self) -> Result<File, StoreError> {
+        let ftype = omitted_type(&self.kind, self.size)?;
+        Ok(File::new(self.name.clone(), ftype))
+    }
 }
 
 fn get_meta_file_path(pid: Pid) -> String {
-    format!("{}.meta", pid)
+    format!("{pid}.meta")
+}
+
+async fn remove_file(path: &Path) -> Result<(), StoreError> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
 }
 
 impl FsFileStorage {
-    pub fn new(base: PathBuf) -> Result<Self, String> {
-        if !base.exists() {
-            fs::create_dir(&base).map_err(|e| format!("{e}"))?;
-        }
+    pub async fn new(base: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let base = base.into();
+        tokio::fs::create_dir_all(&base).await?;
 
         Ok(Self { base })
     }
 
-    pub fn save_file(
+    fn dir(&self, tid: Tid) -> PathBuf {
+        self.base.join(tid.to_string())
+    }
+
+    pub async fn save_file(
         &self,
         tid: Tid,
         pid: Pid,
-        file: File,
-    ) -> Result<(), String> {
-        let dir = self.base.join(format!("{tid}"));
+        file: &File,
+    ) -> Result<(), StoreError> {
+        let dir = self.dir(tid);
+        tokio::fs::create_dir_all(&dir).await?;
 
-        if !dir.exists() {
-            fs::create_dir(&dir).map_err(|e| format!("{e}"))?;
-        }
-
-        let data_file = dir.join(format!("{pid}"));
-        fs::write(&data_file, &file.data).map_err(|e| format!("{e}"))?;
+        // The data goes first, so that the meta never describes a file that
+        // is not there.
+        let data = file_data(&file.ftype)?;
+        write_bytes(&dir.join(pid.to_string()), data).await?;
 
-        let meta_file = dir.join(get_meta_file_path(pid));
         let meta = FileMeta {
-            name: file.name,
-            ftype: file.ftype,
+            name: file.name.clone(),
+            kind: file_kind(&file.ftype).to_string(),
+            size: data.len() as u64,
         };
-        let meta_file =
-            fs::File::open(meta_file).map_err(|e| format!("{e}"))?;
-        serde_json::to_writer(meta_file, &meta).map_err(|e| format!("{e}"))?;
+        self.save_meta(tid, pid, &meta).await
+    }
+
+    async fn load_meta(
+        &self,
+        tid: Tid,
+        pid: Pid,
+    ) -> Result<FileMeta, StoreError> {
+        let path = self.dir(tid).join(get_meta_file_path(pid));
+
+        let text = match tokio::fs::read(path).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(StoreError::NotFound(format!("file of post {pid}")))
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(serde_json::from_slice(&text)?)
+    }
+
+    async fn save_meta(
+        &self,
+        tid: Tid,
+        pid: Pid,
+        meta: &FileMeta,
+    ) -> Result<(), StoreError> {
+        let text = serde_json::to_string(meta)?;
+        let path = self.dir(tid).join(get_meta_file_path(pid));
+        write_bytes(&path, text.as_bytes()).await
+    }
+
+    /// Opens the attachment of post `pid` for reading as it is needed.
+    pub async fn open_file(
+        &self,
+        tid: Tid,
+        pid: Pid,
+    ) -> Result<(FileMeta, BufReader<tokio::fs::File>), StoreError> {
+        let meta = self.load_meta(tid, pid).await?;
+
+        let path = self.dir(tid).join(pid.to_string());
+        let data = tokio::fs::File::open(path).await?;
+
+        Ok((meta, BufReader::new(data)))
+    }
 
-        Ok(())
+    /// The attachment of post `pid` without its data, which is not read.
+    pub async fn load_info(
+        &self,
+        tid: Tid,
+        pid: Pid,
+    ) -> Result<File, StoreError> {
+        self.load_meta(tid, pid).await?.info()
     }
 
-    pub fn load_file(&self, tid: Tid, pid: Pid) -> Result<File, String> {
-        let dir = self.base.join(format!("{tid}"));
-
-        let meta_file = dir.join(get_meta_file_path(pid));
-        let meta_file =
-            fs::File::open(&meta_file).map_err(|e| format!("{e}"))?;
-
-        let meta: FileMeta =
-            serde_json::from_reader(&meta_file).map_err(|e| format!("{e}"))?;
-
-        let data_file = dir.join(format!("{pid}"));
-        let mut data = vec![];
-        let mut data_file =
-            fs::File::open(&data_file).map_err(|e| format!("{e}"))?;
-        data_file
-            .read_to_end(&mut data)
-            .map_err(|e| format!("{e}"))?;
-
-        Ok(File {
-            name: meta.name,
-            data,
-            ftype: meta.ftype,
-        })
+    /// Renames the attachment of post `pid`, keeping its data.
+    pub async fn rename_file(
+        &self,
+        tid: Tid,
+        pid: Pid,
+        name: &str,
+    ) -> Result<(), StoreError> {
+        let mut meta = self.load_meta(tid, pid).await?;
+        meta.name = name.to_string();
+        self.save_meta(tid, pid, &meta).await
+    }
+
+    pub async fn load_file(
+        &self,
+        tid: Tid,
+        pid: Pid,
+    ) -> Result<File, StoreError> {
+        let (meta, mut reader) = self.open_file(tid, pid).await?;
+
+        let mut data = Vec::with_capacity(meta.size as usize);
+        reader.read_to_end(&mut data).await?;
+
+        Ok(File::new(meta.name, file_type(&meta.kind, data)?))
+    }
+
+    pub async fn delete_file(
+        &self,
+        tid: Tid,
+        pid: Pid,
+    ) -> Result<(), StoreError> {
+        let dir = self.dir(tid);
+        remove_file(&dir.join(get_meta_file_path(pid))).await?;
+        remove_file(&dir.join(pid.to_string())).await
+    }
+
+    /// Deletes the attachments of every post of times `tid`.
+    pub async fn delete_times(&self, tid: Tid) -> Result<(), StoreError> {
+        match tokio::fs::remove_dir_all(self.dir(tid)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use timesman_type::FileType;
+
+    #[tokio::test]
+    async fn test_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage =
+            FsFileStorage::new(dir.path().join("files")).await.unwrap();
+        let image = File::new(
+            "photo.png".to_string(),
+            FileType::Image(vec![0x89, 0x50, 0x4e, 0x47]),
+        );
+
+        storage.save_file(1, 2, &image).await.unwrap();
+        assert_eq!(storage.load_file(1, 2).await.unwrap(), image);
+
+        let (meta, _) = storage.open_file(1, 2).await.unwrap();
+        assert_eq!(meta.name, "photo.png");
+        assert_eq!(meta.size, 4);
+
+        // Saving again replaces the file.
+        let text = File::new(
+            "notes.txt".to_string(),
+            FileType::Text("hello".to_string()),
+        );
+        storage.save_file(1, 2, &text).await.unwrap();
+        assert_eq!(storage.load_file(1, 2).await.unwrap(), text);
+
+        storage.rename_file(1, 2, "renamed.txt").await.unwrap();
+        let info = storage.load_info(1, 2).await.unwrap();
+        assert_eq!(
+            info,
+            File::new("renamed.txt".to_string(), text.ftype).info()
+        );
+
+        storage.delete_file(1, 2).await.unwrap();
+        assert!(matches!(
+            storage.load_file(1, 2).await,
+            Err(StoreError::NotFound(_))
+        ));
+        storage.delete_file(1, 2).await.unwrap();
     }
 }
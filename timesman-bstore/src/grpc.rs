@@ -1,11 +1,13 @@
 use super::{Arc, Mutex, PostStore, Store, TimesStore, TodoStore};
+use super::{EventSender, StoreError, StoreEventStream};
 use async_trait::async_trait;
 
+use timesman_grpc::client::{Client, Credentials};
 use timesman_grpc::grpc;
-use timesman_grpc::grpc::times_man_client::TimesManClient;
-use tonic;
+use timesman_grpc::tls::ClientTls;
+use timesman_grpc::ConversionError;
 
-use timesman_type::{File, Post, Tid, Times};
+use timesman_type::{File, Post, Tid, Times, UserId};
 
 mod times;
 use times::GrpcTimesStore;
@@ -13,20 +15,67 @@ mod post;
 use post::GrpcPostStore;
 mod todo;
 use todo::GrpcTodoStore;
+mod watch;
+
+type GrpcClient = Client;
+
+impl From<tonic::Status> for StoreError {
+    fn from(status: tonic::Status) -> Self {
+        let message = status.message().to_string();
+        match status.code() {
+            tonic::Code::NotFound => Self::NotFound(message),
+            tonic::Code::AlreadyExists => Self::AlreadyExists(message),
+            tonic::Code::FailedPrecondition | tonic::Code::Aborted => {
+                Self::Conflict(message)
+            }
+            tonic::Code::PermissionDenied | tonic::Code::Unauthenticated => {
+                Self::PermissionDenied(message)
+            }
+            tonic::Code::DataLoss => Self::Corrupt(message),
+            tonic::Code::Unimplemented => Self::NotSupported,
+            _ => Self::Backend(status.to_string()),
+        }
+    }
+}
 
-type GrpcClient =
-    Arc<Mutex<TimesManClient<tonic::transport::channel::Channel>>>;
+/// The server answered with something that is not a valid item.
+impl From<ConversionError> for StoreError {
+    fn from(e: ConversionError) -> Self {
+        Self::Backend(format!("Invalid reply from the server: {e}"))
+    }
+}
 
+/// Its events come from the server, so they include changes made by
+/// other clients. They start with the first `subscribe`.
 pub struct GrpcStore {
     client: GrpcClient,
+    events: EventSender,
+    watcher: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl GrpcStore {
-    pub async fn new(server: String) -> Result<Self, String> {
-        let tclient = TimesManClient::connect(server).await
-            .map_err(|e| format!("Failed to connect to gRPC server: {}", e))?;
-        let client = Arc::new(Mutex::new(tclient));
-        Ok(Self { client })
+    /// Connects to `server`. `tls` adds a CA bundle or client certificate
+    /// and needs an `https://` URL. Without `credentials` the server only
+    /// answers calls that need no login.
+    pub async fn new(
+        server: String,
+        tls: Option<ClientTls>,
+        credentials: Option<Credentials>,
+    ) -> Result<Self, String> {
+        let client = Client::connect(server, tls.as_ref()).await?;
+        if let Some(credentials) = credentials {
+            client.authenticate(credentials).await?;
+        }
+        Ok(Self::with_client(client))
+    }
+
+    /// Uses `client`, with the session it has.
+    pub fn with_client(client: Client) -> Self {
+        Self {
+            client,
+            events: EventSender::new(),
+            watcher: None,
+        }
     }
 
     fn new_times_store(
@@ -44,27 +93,29 @@ impl GrpcStore {
 
 #[async_trait]
 impl Store for GrpcStore {
-    async fn check(&mut self) -> Result<(), String> {
+    async fn check(&mut self) -> Result<(), StoreError> {
         self.get().await?;
         Ok(())
     }
 
     async fn get(
         &mut self,
-    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, String> {
+    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, StoreError> {
         let client = self.client.clone();
 
         let stores: Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>> = {
-            let mut c = self.client.lock().await;
-
-            let gtimes = c.get_times(()).await.unwrap();
-
-            let times: Vec<Times> = gtimes
-                .into_inner()
+            let gtimes =
+                self.client
+                    .call_idempotent((), |mut c, r| async move {
+                        c.get_times(r).await
+                    })
+                    .await?;
+
+            let times = gtimes
                 .timeses
-                .iter()
-                .map(|t| t.clone().into())
-                .collect();
+                .into_iter()
+                .map(Times::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
 
             times
                 .iter()
@@ -82,30 +133,42 @@ impl Store for GrpcStore {
         Ok(stores)
     }
 
+    /// The server makes the caller the owner, so `owner` is not sent.
     async fn create(
         &mut self,
         title: String,
-    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, String> {
-        let client = self.client.clone();
+        _owner: Option<UserId>,
+    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, StoreError> {
         let title = grpc::TimesTitle { title };
+        let times = self
+            .client
+            .call(title, |mut c, r| async move { c.create_times(r).await })
+            .await?;
 
-        let mut c = client.lock().await;
-        let times = c
-            .create_times(tonic::Request::new(title))
-            .await
-            .map_err(|e| format!("{e}"))?;
-
-        Ok(self.new_times_store(times.into_inner().into()))
+        let times = Times::try_from(times)?;
+        Ok(self.new_times_store(times))
     }
 
-    async fn delete(&mut self, tid: Tid) -> Result<(), String> {
-        let mut c = self.client.lock().await;
+    async fn delete(&mut self, tid: Tid) -> Result<(), StoreError> {
         let id = grpc::TimesId { id: tid };
-        c.delete_times(tonic::Request::new(id))
-            .await
-            .map_err(|e| format!("{e}"))?;
+        self.client
+            .call(id, |mut c, r| async move { c.delete_times(r).await })
+            .await?;
         Ok(())
     }
+
+    async fn subscribe(&mut self) -> Result<StoreEventStream, StoreError> {
+        let stream = self.events.subscribe();
+
+        // The watch gives up once nobody listens, so it may need a restart.
+        if self.watcher.as_ref().map_or(true, |w| w.is_finished()) {
+            let client = self.client.clone();
+            let events = self.events.clone();
+            self.watcher = Some(tokio::spawn(watch::watch(client, events)));
+        }
+
+        Ok(stream)
+    }
 }
 
 
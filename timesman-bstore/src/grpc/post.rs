@@ -1,8 +1,11 @@
 use super::async_trait;
-use super::{GrpcClient, PostStore};
-use timesman_type::{File, Pid, Post, Tag, Tid};
-use tonic;
+use super::{GrpcClient, PostStore, StoreError};
+use timesman_type::{File, Pid, Post, Tag, TagId, Tid};
 use timesman_grpc::grpc;
+use timesman_grpc::grpc::file_chunk;
+
+/// Posts fetched per `ListPosts` call.
+const PAGE_SIZE: u32 = 1000;
 
 pub(crate) struct GrpcPostStore {
     client: GrpcClient,
@@ -13,91 +16,257 @@ impl GrpcPostStore {
     pub fn new(client: GrpcClient, tid: Tid) -> Self {
         Self { client, tid }
     }
+
+    /// Converts `post`, downloading its file if it has one.
+    async fn with_file(
+        &mut self,
+        post: grpc::Post,
+    ) -> Result<Post, StoreError> {
+        let mut post = Post::try_from(post)?;
+        if post.file.is_some() {
+            post.file = Some(self.download(post.id).await?);
+        }
+        Ok(post)
+    }
+
+    async fn download(&mut self, pid: Pid) -> Result<File, StoreError> {
+        let param = grpc::DownloadFileParams { tid: self.tid, pid };
+        let mut stream = self
+            .client
+            .call_idempotent(param, |mut c, r| async move {
+                c.download_file(r).await
+            })
+            .await?;
+
+        let mut info = None;
+        let mut data = vec![];
+        while let Some(message) = stream.message().await? {
+            match message.chunk {
+                Some(file_chunk::Chunk::Info(i)) => info = Some(i),
+                Some(file_chunk::Chunk::Data(chunk)) => {
+                    data.extend_from_slice(&chunk)
+                }
+                None => {}
+            }
+        }
+
+        let info = info.ok_or_else(|| {
+            StoreError::Backend("File info is missing".to_string())
+        })?;
+        Ok(timesman_grpc::file::from_parts(&info, data))
+    }
 }
 
 #[async_trait]
 impl PostStore for GrpcPostStore {
-    async fn get(&mut self, pid: Pid) -> Result<Post, String> {
-        // NOTE: gRPC service doesn't have GetPost endpoint for single posts
-        // This is a limitation of the current gRPC API design
-        // TODO: Add GetPost endpoint to proto file and server for better performance
-        let posts = self.get_all().await?;
-        posts.into_iter()
-            .find(|p| p.id == pid)
-            .ok_or_else(|| format!("Post with id {} not found", pid))
+    async fn get(&mut self, pid: Pid) -> Result<Post, StoreError> {
+        let param = grpc::GetPostParams { tid: self.tid, pid };
+        let post =
+            self.client
+                .call_idempotent(param, |mut c, r| async move {
+                    c.get_post(r).await
+                })
+                .await?;
+
+        self.with_file(post).await
+    }
+
+    async fn get_all(&mut self) -> Result<Vec<Post>, StoreError> {
+        let mut request = grpc::ListPostsRequest {
+            tid: self.tid,
+            page_size: PAGE_SIZE,
+            ..Default::default()
+        };
+
+        let mut posts = vec![];
+        loop {
+            let page = self
+                .client
+                .call_idempotent(request.clone(), |mut c, r| async move {
+                    c.list_posts(r).await
+                })
+                .await?;
+
+            // Only the info of the files; `get` downloads the data.
+            for post in page.posts {
+                posts.push(Post::try_from(post)?);
+            }
+
+            if page.next_page_token.is_empty() {
+                return Ok(posts);
+            }
+            request.page_token = page.next_page_token;
+        }
     }
 
-    async fn get_all(&mut self) -> Result<Vec<Post>, String> {
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, StoreError> {
         let tid = grpc::TimesId { id: self.tid };
-        let mut c = self.client.lock().await;
-        let posts = c
-            .get_posts(tonic::Request::new(tid))
-            .await
-            .map_err(|e| format!("{e}"))?;
-
-        let posts = posts
-            .into_inner()
-            .posts
-            .iter()
-            .map(|t| t.clone().into())
-            .collect();
-        Ok(posts)
+        let tags = self
+            .client
+            .call_idempotent(tid, |mut c, r| async move { c.get_tags(r).await })
+            .await?;
+
+        let tags = tags.tags.into_iter().map(Tag::from).collect();
+        Ok(tags)
     }
 
-    async fn get_tags(&mut self) -> Result<Vec<Tag>, String> {
-        // gRPC service doesn't currently support tag endpoints
-        // Return empty vector for now to prevent panics
-        Ok(vec![])
+    async fn create_tag(&mut self, name: String) -> Result<Tag, StoreError> {
+        let param = grpc::CreateTagParams {
+            tid: self.tid,
+            name,
+        };
+        let tag = self
+            .client
+            .call(param, |mut c, r| async move { c.create_tag(r).await })
+            .await?;
+
+        Ok(tag.into())
     }
 
-    async fn create_tag(&mut self, name: String) -> Result<Tag, String> {
-        // gRPC service doesn't currently support tag creation
-        // Return a placeholder tag to prevent panics
-        // In a full implementation, this would need gRPC endpoints
-        Err(format!("Tag creation not supported in gRPC mode: {}", name))
+    async fn rename_tag(
+        &mut self,
+        tagid: TagId,
+        name: String,
+    ) -> Result<Tag, StoreError> {
+        let param = grpc::RenameTagParams {
+            tid: self.tid,
+            tagid,
+            name,
+        };
+        let tag = self
+            .client
+            .call(param, |mut c, r| async move { c.rename_tag(r).await })
+            .await?;
+
+        Ok(tag.into())
+    }
+
+    async fn delete_tag(&mut self, tagid: TagId) -> Result<(), StoreError> {
+        let param = grpc::DeleteTagParams {
+            tid: self.tid,
+            tagid,
+        };
+        self.client
+            .call(param, |mut c, r| async move { c.delete_tag(r).await })
+            .await?;
+        Ok(())
+    }
+
+    async fn assign_tag(
+        &mut self,
+        pid: Pid,
+        tagid: Option<TagId>,
+    ) -> Result<Post, StoreError> {
+        let param = grpc::AssignTagParams {
+            tid: self.tid,
+            pid,
+            tagid,
+        };
+        let post = self
+            .client
+            .call(param, |mut c, r| async move { c.assign_tag(r).await })
+            .await?;
+
+        Post::try_from(post).map_err(StoreError::from)
     }
 
     async fn post(
         &mut self,
         post: String,
-        _file: Option<File>,
-    ) -> Result<Post, String> {
-        let param = grpc::CreatePostPrams {
-            id: self.tid,
-            text: post,
+        file: Option<File>,
+    ) -> Result<Post, StoreError> {
+        let Some(file) = file else {
+            let param = grpc::CreatePostPrams {
+                id: self.tid,
+                text: post,
+            };
+            let created_post = self
+                .client
+                .call(param, |mut c, r| async move { c.create_post(r).await })
+                .await?;
+
+            return Post::try_from(created_post).map_err(StoreError::from);
         };
-        let mut c = self.client.lock().await;
-        let created_post = c
-            .create_post(tonic::Request::new(param))
-            .await
-            .map_err(|e| format!("{e}"))?;
+        if !file.has_data() {
+            return Err(StoreError::Conflict(
+                "The data of the file is left out".to_string(),
+            ));
+        }
+
+        // The upload is built again if the call has to be made again.
+        let tid = self.tid;
+        let created_post = self
+            .client
+            .call((post, file.clone()), |mut c, (post, file)| async move {
+                let upload = timesman_grpc::file::upload(tid, post, file);
+                c.create_post_with_file(upload).await
+            })
+            .await?;
 
-        Ok(created_post.into_inner().into())
+        let mut created_post =
+            Post::try_from(created_post)?;
+        created_post.file = Some(file);
+        Ok(created_post)
     }
 
-    async fn delete(&mut self, pid: Pid) -> Result<(), String> {
+    async fn delete(&mut self, pid: Pid) -> Result<(), StoreError> {
         let param = grpc::DeletePostParam {
             tid: self.tid,
             pid,
         };
-        let mut c = self.client.lock().await;
-        c.delete_post(tonic::Request::new(param))
-            .await
-            .map_err(|e| format!("{e}"))?;
+        self.client
+            .call(param, |mut c, r| async move { c.delete_post(r).await })
+            .await?;
         Ok(())
     }
 
-    async fn update(&mut self, post: Post) -> Result<Post, String> {
+    async fn update(&mut self, post: Post) -> Result<Post, StoreError> {
         let param = grpc::UpdatePostParam {
             tid: self.tid,
             post: Some(post.into()),
         };
-        let mut c = self.client.lock().await;
-        let updated_post = c
-            .update_post(tonic::Request::new(param))
-            .await
-            .map_err(|e| format!("{e}"))?;
+        let updated_post = self
+            .client
+            .call(param, |mut c, r| async move { c.update_post(r).await })
+            .await?;
+
+        Post::try_from(updated_post).map_err(StoreError::from)
+    }
+
+    async fn edit(
+        &mut self,
+        pid: Pid,
+        text: String,
+        tag: Option<Option<TagId>>,
+    ) -> Result<Post, StoreError> {
+        let tagid = match tag {
+            Some(tagid) => tagid,
+            None => {
+                let param = grpc::GetPostParams { tid: self.tid, pid };
+                self.client
+                    .call_idempotent(param, |mut c, r| async move {
+                        c.get_post(r).await
+                    })
+                    .await?
+                    .tagid
+            }
+        };
+
+        // The server takes only the text and tag of the post.
+        let param = grpc::UpdatePostParam {
+            tid: self.tid,
+            post: Some(grpc::Post {
+                id: pid,
+                post: text,
+                tagid,
+                ..Default::default()
+            }),
+        };
+        let edited_post = self
+            .client
+            .call(param, |mut c, r| async move { c.update_post(r).await })
+            .await?;
 
-        Ok(updated_post.into_inner().into())
+        Post::try_from(edited_post).map_err(StoreError::from)
     }
 }
@@ -1,9 +1,9 @@
 use super::{async_trait, Arc, Mutex};
-use super::{GrpcClient, PostStore, TimesStore, TodoStore};
+use super::{GrpcClient, PostStore, StoreError, TimesStore, TodoStore};
 use super::{GrpcPostStore, GrpcTodoStore};
 
+use timesman_grpc::grpc;
 use timesman_type::{File, Post, Tid, Times};
-use tonic;
 
 pub(crate) struct GrpcTimesStore {
     client: GrpcClient,
@@ -18,25 +18,25 @@ impl GrpcTimesStore {
 
 #[async_trait]
 impl TimesStore for GrpcTimesStore {
-    async fn get(&mut self) -> Result<Times, String> {
+    async fn get(&mut self) -> Result<Times, StoreError> {
         Ok(self.times.clone())
     }
 
-    async fn update(&mut self, times: Times) -> Result<Times, String> {
-        let mut c = self.client.lock().await;
-        let updated_times = c
-            .update_times(tonic::Request::new(times.into()))
-            .await
-            .map_err(|e| format!("{e}"))?;
-        
-        let result: Times = updated_times.into_inner().into();
+    async fn update(&mut self, times: Times) -> Result<Times, StoreError> {
+        let times: grpc::Times = times.into();
+        let updated_times = self
+            .client
+            .call(times, |mut c, r| async move { c.update_times(r).await })
+            .await?;
+
+        let result = Times::try_from(updated_times)?;
         self.times = result.clone();
         Ok(result)
     }
 
     async fn pstore(
         &mut self,
-    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, String> {
+    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, StoreError> {
         Ok(Arc::new(Mutex::new(GrpcPostStore::new(
             self.client.clone(),
             self.times.id,
@@ -45,7 +45,7 @@ impl TimesStore for GrpcTimesStore {
 
     async fn tdstore(
         &mut self,
-    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, String> {
+    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, StoreError> {
         Ok(Arc::new(Mutex::new(GrpcTodoStore::new(
             self.client.clone(),
             self.times.id,
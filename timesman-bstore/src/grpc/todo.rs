@@ -1,11 +1,10 @@
 use super::GrpcClient;
-use super::TodoStore;
+use super::{StoreError, TodoStore};
 
 use async_trait::async_trait;
 use timesman_type::{Tdid, Tid, Todo};
 
 use timesman_grpc::grpc;
-use tonic;
 
 pub(crate) struct GrpcTodoStore {
     client: GrpcClient,
@@ -20,76 +19,81 @@ impl GrpcTodoStore {
 
 #[async_trait]
 impl TodoStore for GrpcTodoStore {
-    async fn get(&mut self) -> Result<Vec<Todo>, String> {
-        let mut c = self.client.lock().await;
-
+    async fn get(&mut self) -> Result<Vec<Todo>, StoreError> {
         let tid = grpc::TimesId { id: self.tid };
-        let gtodos = c.get_todos(tonic::Request::new(tid)).await.map_err(|e| format!("{e}"))?;
+        let gtodos = self
+            .client
+            .call_idempotent(
+                tid,
+                |mut c, r| async move { c.get_todos(r).await },
+            )
+            .await?;
 
-        let todos = gtodos
-            .into_inner()
+        gtodos
             .todos
-            .iter()
-            .map(|t| t.clone().into())
-            .collect();
-
-        Ok(todos)
+            .into_iter()
+            .map(Todo::try_from)
+            .collect::<Result<_, _>>()
+            .map_err(StoreError::from)
     }
 
-    async fn new(&mut self, content: String) -> Result<Todo, String> {
-        let mut c = self.client.lock().await;
+    async fn new(&mut self, content: String) -> Result<Todo, StoreError> {
         let param = grpc::CreateTodoParams {
             tid: self.tid,
             content,
             detail: None,
         };
-        let todo = c
-            .create_todo(tonic::Request::new(param))
-            .await
-            .map_err(|e| format!("{e}"))?;
+        let todo = self
+            .client
+            .call(param, |mut c, r| async move { c.create_todo(r).await })
+            .await?;
 
-        Ok(todo.into_inner().into())
+        Todo::try_from(todo).map_err(StoreError::from)
     }
 
-    async fn done(&mut self, tdid: Tdid, done: bool) -> Result<Todo, String> {
-        let mut c = self.client.lock().await;
+    async fn done(
+        &mut self,
+        tdid: Tdid,
+        done: bool,
+    ) -> Result<Todo, StoreError> {
         let param = grpc::DoneTodoParams {
             tid: self.tid,
             tdid,
             done,
         };
-        let todo = c
-            .done_todo(tonic::Request::new(param))
-            .await
-            .map_err(|e| format!("{e}"))?;
+        let todo = self
+            .client
+            .call(param, |mut c, r| async move { c.done_todo(r).await })
+            .await?;
 
-        Ok(todo.into_inner().into())
+        Todo::try_from(todo).map_err(StoreError::from)
     }
 
-    async fn update(&mut self, todo: Todo) -> Result<Todo, String> {
-        let mut c = self.client.lock().await;
-        
-        // For now, we'll use UpdateTodoDetail if the detail field changed
-        if let Some(detail) = &todo.detail {
-            let param = grpc::UpdateTodoDetailParams {
-                tid: self.tid,
-                tdid: todo.id,
-                detail: detail.clone(),
-            };
-            let updated_todo = c
-                .update_todo_detail(tonic::Request::new(param))
-                .await
-                .map_err(|e| format!("{e}"))?;
-            
-            Ok(updated_todo.into_inner().into())
-        } else {
-            // If no detail, return the todo as-is since we can't update content via gRPC yet
-            Ok(todo)
-        }
+    async fn update(&mut self, todo: Todo) -> Result<Todo, StoreError> {
+        let param = grpc::UpdateTodoParams {
+            tid: self.tid,
+            tdid: todo.id,
+            content: todo.content,
+            detail: todo.detail,
+            done: todo.done_at.is_some(),
+        };
+        let todo = self
+            .client
+            .call(param, |mut c, r| async move { c.update_todo(r).await })
+            .await?;
+
+        Todo::try_from(todo).map_err(StoreError::from)
     }
 
-    async fn delete(&mut self, _tdid: Tdid) -> Result<(), String> {
-        // Note: Delete method not implemented in gRPC proto
-        Err("Todo delete not supported via gRPC".to_string())
+    async fn delete(&mut self, tdid: Tdid) -> Result<(), StoreError> {
+        let param = grpc::DeleteTodoParams {
+            tid: self.tid,
+            tdid,
+        };
+        self.client
+            .call(param, |mut c, r| async move { c.delete_todo(r).await })
+            .await?;
+
+        Ok(())
     }
 }
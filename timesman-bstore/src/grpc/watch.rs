@@ -0,0 +1,266 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use timesman_grpc::client::Client;
+use timesman_grpc::grpc;
+use timesman_type::Tid;
+
+use super::super::{EventSender, StoreEvent};
+
+/// How long to wait before reconnecting a watch that broke.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Hands each message of `stream` to `f` until the stream ends.
+async fn read<T>(
+    stream: &mut tonic::Streaming<T>,
+    mut f: impl FnMut(T),
+) -> Result<(), tonic::Status> {
+    while let Some(message) = stream.message().await? {
+        f(message);
+    }
+    Ok(())
+}
+
+/// Decides whether to watch again after a watch stopped. Watches that can
+/// no longer resume start over from `after_seq = None`.
+async fn reconnect(
+    result: Result<(), tonic::Status>,
+    after_seq: &mut Option<u64>,
+    events: &EventSender,
+) -> bool {
+    if !events.has_subscribers() {
+        return false;
+    }
+
+    match result.map_err(|status| status.code()) {
+        Err(tonic::Code::OutOfRange) => {
+            events.send(StoreEvent::Missed);
+            *after_seq = None;
+            true
+        }
+        Err(
+            tonic::Code::NotFound
+            | tonic::Code::PermissionDenied
+            | tonic::Code::Unauthenticated
+            | tonic::Code::Unimplemented,
+        ) => false,
+        _ => {
+            tokio::time::sleep(RETRY_DELAY).await;
+            true
+        }
+    }
+}
+
+/// Makes the event for `item`. A malformed item is reported as missed
+/// events, so that the subscriber reloads.
+fn or_missed<T, U: TryFrom<T>>(
+    item: T,
+    event: impl FnOnce(U) -> StoreEvent,
+) -> StoreEvent {
+    U::try_from(item).map_or(StoreEvent::Missed, event)
+}
+
+fn times_event(event: grpc::TimesEvent) -> Option<StoreEvent> {
+    Some(match event.kind() {
+        grpc::EventKind::Created => {
+            or_missed(event.times?, StoreEvent::TimesCreated)
+        }
+        grpc::EventKind::Updated => {
+            or_missed(event.times?, StoreEvent::TimesUpdated)
+        }
+        grpc::EventKind::Deleted => StoreEvent::TimesDeleted(event.tid),
+    })
+}
+
+fn post_event(event: grpc::PostEvent) -> Option<StoreEvent> {
+    let tid = event.tid;
+    Some(match event.kind() {
+        grpc::EventKind::Created => {
+            or_missed(event.post?, |post| StoreEvent::PostCreated(tid, post))
+        }
+        grpc::EventKind::Updated => {
+            or_missed(event.post?, |post| StoreEvent::PostUpdated(tid, post))
+        }
+        grpc::EventKind::Deleted => StoreEvent::PostDeleted(tid, event.pid),
+    })
+}
+
+fn tag_event(event: grpc::TagEvent) -> Option<StoreEvent> {
+    let tid = event.tid;
+    Some(match event.kind() {
+        grpc::EventKind::Created => {
+            or_missed(event.tag?, |tag| StoreEvent::TagCreated(tid, tag))
+        }
+        grpc::EventKind::Updated => {
+            or_missed(event.tag?, |tag| StoreEvent::TagUpdated(tid, tag))
+        }
+        grpc::EventKind::Deleted => StoreEvent::TagDeleted(tid, event.tagid),
+    })
+}
+
+fn todo_event(event: grpc::TodoEvent) -> Option<StoreEvent> {
+    let tid = event.tid;
+    Some(match event.kind() {
+        grpc::EventKind::Created => {
+            or_missed(event.todo?, |todo| StoreEvent::TodoCreated(tid, todo))
+        }
+        grpc::EventKind::Updated => {
+            or_missed(event.todo?, |todo| StoreEvent::TodoUpdated(tid, todo))
+        }
+        grpc::EventKind::Deleted => StoreEvent::TodoDeleted(tid, event.tdid),
+    })
+}
+
+/// Follows the times on the server, and the posts, tags and todos of each
+/// of them, until nobody listens to `events` anymore.
+pub(super) async fn watch(client: Client, events: EventSender) {
+    let mut watched = HashSet::new();
+    let mut after_seq = None;
+
+    loop {
+        let request = grpc::WatchTimesRequest { after_seq };
+        let result = match client
+            .call(request, |mut c, r| async move { c.watch_times(r).await })
+            .await
+        {
+            Ok(mut stream) => {
+                // Watch what existed before, once the stream is open so
+                // that no new times slips through.
+                if after_seq.is_none() {
+                    follow_existing(&client, &events, &mut watched).await;
+                }
+
+                read(&mut stream, |event| {
+                    after_seq = Some(event.seq);
+                    if event.kind() == grpc::EventKind::Created {
+                        follow(&client, &events, &mut watched, event.tid);
+                    }
+                    if let Some(event) = times_event(event) {
+                        events.send(event);
+                    }
+                })
+                .await
+            }
+            Err(status) => Err(status),
+        };
+
+        if let Err(status) = &result {
+            log::warn!("Watching times stopped: {status}");
+        }
+        if !reconnect(result, &mut after_seq, &events).await {
+            return;
+        }
+    }
+}
+
+async fn follow_existing(
+    client: &Client,
+    events: &EventSender,
+    watched: &mut HashSet<Tid>,
+) {
+    let timeses = client
+        .call_idempotent((), |mut c, r| async move { c.get_times(r).await })
+        .await;
+    match timeses {
+        Ok(timeses) => {
+            for times in timeses.timeses {
+                follow(client, events, watched, times.id);
+            }
+        }
+        Err(status) => log::warn!("Cannot list times: {status}"),
+    }
+}
+
+fn follow(
+    client: &Client,
+    events: &EventSender,
+    watched: &mut HashSet<Tid>,
+    tid: Tid,
+) {
+    if watched.insert(tid) {
+        tokio::spawn(watch_posts(client.clone(), tid, events.clone()));
+        tokio::spawn(watch_tags(client.clone(), tid, events.clone()));
+        tokio::spawn(watch_todos(client.clone(), tid, events.clone()));
+    }
+}
+
+async fn watch_posts(client: Client, tid: Tid, events: EventSender) {
+    let mut after_seq = None;
+
+    loop {
+        let request = grpc::WatchPostsRequest { tid, after_seq };
+        let result = match client
+            .call(request, |mut c, r| async move { c.watch_posts(r).await })
+            .await
+        {
+            Ok(mut stream) => {
+                read(&mut stream, |event| {
+                    after_seq = Some(event.seq);
+                    if let Some(event) = post_event(event) {
+                        events.send(event);
+                    }
+                })
+                .await
+            }
+            Err(status) => Err(status),
+        };
+
+        if !reconnect(result, &mut after_seq, &events).await {
+            return;
+        }
+    }
+}
+
+async fn watch_tags(client: Client, tid: Tid, events: EventSender) {
+    let mut after_seq = None;
+
+    loop {
+        let request = grpc::WatchTagsRequest { tid, after_seq };
+        let result = match client
+            .call(request, |mut c, r| async move { c.watch_tags(r).await })
+            .await
+        {
+            Ok(mut stream) => {
+                read(&mut stream, |event| {
+                    after_seq = Some(event.seq);
+                    if let Some(event) = tag_event(event) {
+                        events.send(event);
+                    }
+                })
+                .await
+            }
+            Err(status) => Err(status),
+        };
+
+        if !reconnect(result, &mut after_seq, &events).await {
+            return;
+        }
+    }
+}
+
+async fn watch_todos(client: Client, tid: Tid, events: EventSender) {
+    let mut after_seq = None;
+
+    loop {
+        let request = grpc::WatchTodosRequest { tid, after_seq };
+        let result = match client
+            .call(request, |mut c, r| async move { c.watch_todos(r).await })
+            .await
+        {
+            Ok(mut stream) => {
+                read(&mut stream, |event| {
+                    after_seq = Some(event.seq);
+                    if let Some(event) = todo_event(event) {
+                        events.send(event);
+                    }
+                })
+                .await
+            }
+            Err(status) => Err(status),
+        };
+
+        if !reconnect(result, &mut after_seq, &events).await {
+            return;
+        }
+    }
+}
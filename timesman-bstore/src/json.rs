@@ -0,0 +1,325 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use async_trait::async_trait;
+
+use timesman_type::{Tid, Times, UserId};
+
+use super::{write_bytes, EventSender, StoreError, StoreEvent};
+use super::{FileReader, StoreEventStream};
+use super::{PostStore, Store, TimesStore, TodoStore};
+
+mod times;
+use times::JsonTimesStore;
+
+mod post;
+use post::JsonPostStore;
+
+mod todo;
+use todo::JsonTodoStore;
+
+/*
+ * Storage structure:
+ * /meta.json                    - Root metadata
+ * /{tid}/times.json             - Times
+ * /{tid}/posts.json             - Posts, without their attachments
+ * /{tid}/tags.json              - Tags
+ * /{tid}/todos.json             - Todos
+ * /{tid}/files/{pid}            - The attachment of a post, as is
+ */
+
+/// The directory of a store. Its lock is held while a file is read,
+/// changed and written back.
+type Dir = Arc<Mutex<PathBuf>>;
+
+#[derive(Serialize, Deserialize, Default)]
+struct RootMeta {
+    next_tid: Tid,
+}
+
+/// Reads the JSON file at `path`, `None` if there is none.
+async fn read<T: DeserializeOwned>(
+    path: &Path,
+) -> Result<Option<T>, StoreError> {
+    match tokio::fs::read(path).await {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data).map_err(|e| {
+            StoreError::Corrupt(format!("{}: {e}", path.display()))
+        })?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes `value` to `path` as indented JSON, so that it diffs well.
+async fn write<T: Serialize>(path: &Path, value: &T) -> Result<(), StoreError> {
+    let mut text = serde_json::to_string_pretty(value)?;
+    text.push('\n');
+    write_bytes(path, text.as_bytes()).await
+}
+
+/// The directory of times `tid`, as long as the times exists.
+async fn times_dir(dir: &Path, tid: Tid) -> Result<PathBuf, StoreError> {
+    let tdir = dir.join(tid.to_string());
+    if !tokio::fs::try_exists(tdir.join("times.json")).await? {
+        return Err(StoreError::NotFound(format!("times {tid}")));
+    }
+    Ok(tdir)
+}
+
+/// Keeps each times in a directory of JSON files that can be read, diffed
+/// and kept in git.
+pub struct JsonStore {
+    dir: Dir,
+    events: EventSender,
+}
+
+impl JsonStore {
+    /// Opens the store in the directory at `path`, creating the directory
+    /// if it is missing and `create` is set.
+    pub async fn new(
+        path: impl Into<PathBuf>,
+        create: bool,
+    ) -> Result<Self, StoreError> {
+        let path = path.into();
+
+        if !tokio::fs::try_exists(&path).await? {
+            if !create {
+                return Err(StoreError::NotFound(format!(
+                    "store directory {}",
+                    path.display()
+                )));
+            }
+            tokio::fs::create_dir_all(&path).await?;
+        }
+
+        let meta_path = path.join("meta.json");
+        if read::<RootMeta>(&meta_path).await?.is_none() {
+            write(&meta_path, &RootMeta::default()).await?;
+        }
+
+        Ok(Self {
+            dir: Arc::new(Mutex::new(path)),
+            events: EventSender::new(),
+        })
+    }
+
+    fn new_times_store(
+        &self,
+        tid: Tid,
+    ) -> Arc<Mutex<dyn TimesStore + Send + Sync>> {
+        Arc::new(Mutex::new(JsonTimesStore::new(
+            self.dir.clone(),
+            tid,
+            self.events.clone(),
+        )))
+    }
+}
+
+#[async_trait]
+impl Store for JsonStore {
+    async fn check(&mut self) -> Result<(), StoreError> {
+        let dir = self.dir.lock().await;
+        read::<RootMeta>(&dir.join("meta.json"))
+            .await?
+            .ok_or_else(|| StoreError::NotFound("meta.json".to_string()))?;
+        Ok(())
+    }
+
+    async fn get(
+        &mut self,
+    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, StoreError> {
+        let mut tids = vec![];
+        {
+            let dir = self.dir.lock().await;
+            let mut entries = tokio::fs::read_dir(&*dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name();
+                let Some(tid) = name.to_str().and_then(|n| n.parse().ok())
+                else {
+                    continue;
+                };
+                if tokio::fs::try_exists(entry.path().join("times.json"))
+                    .await?
+                {
+                    tids.push(tid);
+                }
+            }
+        }
+        tids.sort();
+
+        Ok(tids
+            .into_iter()
+            .map(|tid| self.new_times_store(tid))
+            .collect())
+    }
+
+    async fn create(
+        &mut self,
+        title: String,
+        owner: Option<UserId>,
+    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, StoreError> {
+        let times = {
+            let dir = self.dir.lock().await;
+            let meta_path = dir.join("meta.json");
+            let mut meta: RootMeta =
+                read(&meta_path).await?.unwrap_or_default();
+
+            let times = Times {
+                id: meta.next_tid,
+                title,
+                created_at: chrono::Utc::now().naive_local(),
+                updated_at: None,
+                owner,
+            };
+
+            let tdir = dir.join(times.id.to_string());
+            tokio::fs::create_dir_all(&tdir).await?;
+            write(&tdir.join("times.json"), &times).await?;
+
+            meta.next_tid += 1;
+            write(&meta_path, &meta).await?;
+
+            times
+        };
+
+        let tstore = self.new_times_store(times.id);
+        self.events.send(StoreEvent::TimesCreated(times));
+
+        Ok(tstore)
+    }
+
+    async fn delete(&mut self, tid: Tid) -> Result<(), StoreError> {
+        {
+            let dir = self.dir.lock().await;
+            let tdir = times_dir(&dir, tid).await?;
+
+            // Drop the times first, so that a crash in between leaves no
+            // half-deleted times behind.
+            tokio::fs::remove_file(tdir.join("times.json")).await?;
+            tokio::fs::remove_dir_all(&tdir).await?;
+        }
+
+        self.events.send(StoreEvent::TimesDeleted(tid));
+        Ok(())
+    }
+
+    async fn subscribe(&mut self) -> Result<StoreEventStream, StoreError> {
+        Ok(self.events.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use timesman_type::{File, FileType};
+
+    #[tokio::test]
+    async fn test_data_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = File::new(
+            "notes.txt".to_string(),
+            FileType::Text("hello".to_string()),
+        );
+
+        let (tid, post) = {
+            let mut store = JsonStore::new(dir.path(), false).await.unwrap();
+            let tstore =
+                store.create("reopen".to_string(), None).await.unwrap();
+            let mut tstore = tstore.lock().await;
+            let tid = tstore.get().await.unwrap().id;
+            let pstore = tstore.pstore().await.unwrap();
+            let mut pstore = pstore.lock().await;
+            let post = pstore
+                .post("with file".to_string(), Some(file.clone()))
+                .await
+                .unwrap();
+            (tid, post)
+        };
+
+        let mut store = JsonStore::new(dir.path(), false).await.unwrap();
+        let tstores = store.get().await.unwrap();
+        assert_eq!(tstores.len(), 1);
+        let mut tstore = tstores[0].lock().await;
+        assert_eq!(tstore.get().await.unwrap().id, tid);
+
+        let pstore = tstore.pstore().await.unwrap();
+        let stored = pstore.lock().await.get(post.id).await.unwrap();
+        assert_eq!(stored, post);
+        assert_eq!(stored.file, Some(file));
+
+        // The attachment is kept as is, next to readable JSON.
+        let tdir = dir.path().join(tid.to_string());
+        let text = std::fs::read_to_string(tdir.join("posts.json")).unwrap();
+        assert!(text.contains("\"post\": \"with file\""));
+        let attached = tdir.join("files").join(post.id.to_string());
+        assert_eq!(std::fs::read(attached).unwrap(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_update_without_data_keeps_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = JsonStore::new(dir.path(), false).await.unwrap();
+        let tstore = store.create("files".to_string(), None).await.unwrap();
+        let pstore = tstore.lock().await.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+        let empty =
+            File::new("empty.txt".to_string(), FileType::Text(String::new()));
+        let post = pstore
+            .post("empty".to_string(), Some(empty.clone()))
+            .await
+            .unwrap();
+
+        let mut listed = post.clone();
+        listed.file = Some(File {
+            name: "renamed.txt".to_string(),
+            ..empty.info()
+        });
+        pstore.update(listed).await.unwrap();
+
+        // An empty file is still a file with data.
+        let stored = pstore.get(post.id).await.unwrap();
+        assert_eq!(
+            stored.file,
+            Some(File::new(
+                "renamed.txt".to_string(),
+                FileType::Text(String::new())
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store");
+
+        assert!(matches!(
+            JsonStore::new(&path, false).await,
+            Err(StoreError::NotFound(_))
+        ));
+        JsonStore::new(&path, true).await.unwrap();
+        assert!(path.join("meta.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_deleting_times_deletes_its_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = JsonStore::new(dir.path(), false).await.unwrap();
+        let tstore = store.create("doomed".to_string(), None).await.unwrap();
+        let tid = tstore.lock().await.get().await.unwrap().id;
+        let tdstore = tstore.lock().await.tdstore().await.unwrap();
+        tdstore.lock().await.new("gone".to_string()).await.unwrap();
+
+        store.delete(tid).await.unwrap();
+        assert!(!dir.path().join(tid.to_string()).exists());
+        assert!(matches!(
+            tstore.lock().await.get().await,
+            Err(StoreError::NotFound(_))
+        ));
+
+        // Ids are not reused.
+        let tstore = store.create("next".to_string(), None).await.unwrap();
+        assert_eq!(tstore.lock().await.get().await.unwrap().id, tid + 1);
+    }
+}
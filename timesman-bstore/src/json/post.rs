@@ -0,0 +1,442 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::super::attachment::{file_data, file_kind, file_type, omitted_type};
+use super::{async_trait, read, times_dir, write, write_bytes, Dir};
+use super::{EventSender, FileReader, PostStore, StoreError, StoreEvent, Tid};
+use timesman_type::{File, Pid, Post, Tag, TagId};
+
+#[derive(Serialize, Deserialize, Default)]
+struct Posts {
+    next_pid: Pid,
+    posts: Vec<PostRecord>,
+}
+
+/// A post as kept in `posts.json`. The bytes of its attachment are in
+/// `files/{pid}`.
+#[derive(Serialize, Deserialize)]
+struct PostRecord {
+    id: Pid,
+    post: String,
+    created_at: chrono::NaiveDateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<chrono::NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<TagId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file: Option<FileRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileRecord {
+    name: String,
+    kind: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Tags {
+    next_tagid: TagId,
+    tags: Vec<Tag>,
+}
+
+impl PostRecord {
+    fn new(post: &Post) -> Self {
+        Self {
+            id: post.id,
+            post: post.post.clone(),
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+            tag: post.tag,
+            file: post.file.as_ref().map(|file| FileRecord {
+                name: file.name.clone(),
+                kind: file_kind(&file.ftype).to_string(),
+            }),
+        }
+    }
+
+    async fn to_post(&self, tdir: &Path) -> Result<Post, StoreError> {
+        let file = match &self.file {
+            None => None,
+            Some(file) => {
+                let path = tdir.join("files").join(self.id.to_string());
+                let data = tokio::fs::read(&path).await?;
+                Some(File::new(file.name.clone(), file_type(&file.kind, data)?))
+            }
+        };
+
+        Ok(Post {
+            id: self.id,
+            post: self.post.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            file,
+            tag: self.tag,
+        })
+    }
+}
+
+async fn save_file(
+    tdir: &Path,
+    pid: Pid,
+    file: Option<&File>,
+) -> Result<(), StoreError> {
+    let path = tdir.join("files").join(pid.to_string());
+    match file {
+        Some(file) => {
+            tokio::fs::create_dir_all(tdir.join("files")).await?;
+            write_bytes(&path, file_data(&file.ftype)?).await
+        }
+        None => match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        },
+    }
+}
+
+pub struct JsonPostStore {
+    dir: Dir,
+    tid: Tid,
+    events: EventSender,
+}
+
+impl JsonPostStore {
+    pub fn new(dir: Dir, tid: Tid, events: EventSender) -> Self {
+        Self { dir, tid, events }
+    }
+}
+
+// /{tid}/posts.json, /{tid}/tags.json
+#[async_trait]
+impl PostStore for JsonPostStore {
+    async fn get(&mut self, pid: Pid) -> Result<Post, StoreError> {
+        let dir = self.dir.lock().await;
+        let tdir = times_dir(&dir, self.tid).await?;
+        let posts: Posts =
+            read(&tdir.join("posts.json")).await?.unwrap_or_default();
+
+        let Some(record) = posts.posts.iter().find(|p| p.id == pid) else {
+            return Err(StoreError::NotFound(format!("post {pid}")));
+        };
+        record.to_post(&tdir).await
+    }
+
+    async fn open_file(
+        &mut self,
+        pid: Pid,
+    ) -> Result<(File, FileReader), StoreError> {
+        let dir = self.dir.lock().await;
+        let tdir = times_dir(&dir, self.tid).await?;
+        let posts: Posts =
+            read(&tdir.join("posts.json")).await?.unwrap_or_default();
+
+        let Some(record) = posts.posts.iter().find(|p| p.id == pid) else {
+            return Err(StoreError::NotFound(format!("post {pid}")));
+        };
+        let Some(file) = &record.file else {
+            return Err(StoreError::NotFound(format!("file of post {pid}")));
+        };
+
+        let path = tdir.join("files").join(pid.to_string());
+        let data = tokio::fs::File::open(path).await?;
+        let size = data.metadata().await?.len();
+        let info =
+            File::new(file.name.clone(), omitted_type(&file.kind, size)?);
+
+        Ok((info, Box::new(tokio::io::BufReader::new(data))))
+    }
+
+    async fn get_all(&mut self) -> Result<Vec<Post>, StoreError> {
+        let dir = self.dir.lock().await;
+        let tdir = times_dir(&dir, self.tid).await?;
+        let posts: Posts =
+            read(&tdir.join("posts.json")).await?.unwrap_or_default();
+
+        let mut all = Vec::with_capacity(posts.posts.len());
+        for record in &posts.posts {
+            all.push(record.to_post(&tdir).await?);
+        }
+        Ok(all)
+    }
+
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, StoreError> {
+        let dir = self.dir.lock().await;
+        let tdir = times_dir(&dir, self.tid).await?;
+        let tags: Tags =
+            read(&tdir.join("tags.json")).await?.unwrap_or_default();
+        Ok(tags.tags)
+    }
+
+    async fn create_tag(&mut self, name: String) -> Result<Tag, StoreError> {
+        let tag = {
+            let dir = self.dir.lock().await;
+            let path = times_dir(&dir, self.tid).await?.join("tags.json");
+            let mut tags: Tags = read(&path).await?.unwrap_or_default();
+
+            let tag = Tag {
+                id: tags.next_tagid,
+                name,
+            };
+            tags.next_tagid += 1;
+            tags.tags.push(tag.clone());
+            write(&path, &tags).await?;
+
+            tag
+        };
+
+        self.events
+            .send(StoreEvent::TagCreated(self.tid, tag.clone()));
+
+        Ok(tag)
+    }
+
+    async fn rename_tag(
+        &mut self,
+        tagid: TagId,
+        name: String,
+    ) -> Result<Tag, StoreError> {
+        let tag = {
+            let dir = self.dir.lock().await;
+            let path = times_dir(&dir, self.tid).await?.join("tags.json");
+            let mut tags: Tags = read(&path).await?.unwrap_or_default();
+
+            let Some(tag) = tags.tags.iter_mut().find(|t| t.id == tagid) else {
+                return Err(StoreError::NotFound(format!("tag {tagid}")));
+            };
+            tag.name = name;
+            let tag = tag.clone();
+            write(&path, &tags).await?;
+
+            tag
+        };
+
+        self.events
+            .send(StoreEvent::TagUpdated(self.tid, tag.clone()));
+
+        Ok(tag)
+    }
+
+    async fn delete_tag(&mut self, tagid: TagId) -> Result<(), StoreError> {
+        let untagged = {
+            let dir = self.dir.lock().await;
+            let tdir = times_dir(&dir, self.tid).await?;
+
+            let tags_path = tdir.join("tags.json");
+            let mut tags: Tags = read(&tags_path).await?.unwrap_or_default();
+            let len = tags.tags.len();
+            tags.tags.retain(|t| t.id != tagid);
+            if tags.tags.len() == len {
+                return Err(StoreError::NotFound(format!("tag {tagid}")));
+            }
+
+            // Untag the posts before the tag goes, so that no post is left
+            // with a tag that does not exist.
+            let posts_path = tdir.join("posts.json");
+            let mut posts: Posts = read(&posts_path).await?.unwrap_or_default();
+            let mut untagged = vec![];
+            for record in &mut posts.posts {
+                if record.tag == Some(tagid) {
+                    record.tag = None;
+                    untagged.push(record.to_post(&tdir).await?);
+                }
+            }
+            if !untagged.is_empty() {
+                write(&posts_path, &posts).await?;
+            }
+            write(&tags_path, &tags).await?;
+
+            untagged
+        };
+
+        for post in untagged {
+            self.events.send(StoreEvent::PostUpdated(self.tid, post));
+        }
+        self.events.send(StoreEvent::TagDeleted(self.tid, tagid));
+
+        Ok(())
+    }
+
+    async fn assign_tag(
+        &mut self,
+        pid: Pid,
+        tagid: Option<TagId>,
+    ) -> Result<Post, StoreError> {
+        let post = {
+            let dir = self.dir.lock().await;
+            let tdir = times_dir(&dir, self.tid).await?;
+
+            if let Some(tagid) = tagid {
+                let tags: Tags =
+                    read(&tdir.join("tags.json")).await?.unwrap_or_default();
+                if !tags.tags.iter().any(|t| t.id == tagid) {
+                    return Err(StoreError::NotFound(format!("tag {tagid}")));
+                }
+            }
+
+            let path = tdir.join("posts.json");
+            let mut posts: Posts = read(&path).await?.unwrap_or_default();
+            let Some(record) = posts.posts.iter_mut().find(|p| p.id == pid)
+            else {
+                return Err(StoreError::NotFound(format!("post {pid}")));
+            };
+            record.tag = tagid;
+            let post = record.to_post(&tdir).await?;
+            write(&path, &posts).await?;
+
+            post
+        };
+
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+
+    async fn post(
+        &mut self,
+        post: String,
+        file: Option<File>,
+    ) -> Result<Post, StoreError> {
+        let post = {
+            let dir = self.dir.lock().await;
+            let tdir = times_dir(&dir, self.tid).await?;
+            let path = tdir.join("posts.json");
+            let mut posts: Posts = read(&path).await?.unwrap_or_default();
+
+            let post = Post {
+                id: posts.next_pid,
+                post,
+                created_at: chrono::Utc::now().naive_local(),
+                updated_at: None,
+                file,
+                tag: None,
+            };
+
+            // The attachment goes first, so that the post never refers to
+            // a missing file.
+            save_file(&tdir, post.id, post.file.as_ref()).await?;
+            posts.next_pid += 1;
+            posts.posts.push(PostRecord::new(&post));
+            write(&path, &posts).await?;
+
+            post
+        };
+
+        self.events
+            .send(StoreEvent::PostCreated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+
+    async fn delete(&mut self, pid: Pid) -> Result<(), StoreError> {
+        {
+            let dir = self.dir.lock().await;
+            let tdir = times_dir(&dir, self.tid).await?;
+            let path = tdir.join("posts.json");
+            let mut posts: Posts = read(&path).await?.unwrap_or_default();
+
+            let len = posts.posts.len();
+            posts.posts.retain(|p| p.id != pid);
+            if posts.posts.len() == len {
+                return Err(StoreError::NotFound(format!("post {pid}")));
+            }
+            write(&path, &posts).await?;
+            save_file(&tdir, pid, None).await?;
+        }
+
+        self.events.send(StoreEvent::PostDeleted(self.tid, pid));
+
+        Ok(())
+    }
+
+    async fn update(&mut self, post: Post) -> Result<Post, StoreError> {
+        let post = Post {
+            updated_at: Some(chrono::Utc::now().naive_local()),
+            ..post
+        };
+        {
+            let dir = self.dir.lock().await;
+            let tdir = times_dir(&dir, self.tid).await?;
+            let path = tdir.join("posts.json");
+            let mut posts: Posts = read(&path).await?.unwrap_or_default();
+
+            let Some(record) = posts.posts.iter_mut().find(|p| p.id == post.id)
+            else {
+                return Err(StoreError::NotFound(format!("post {}", post.id)));
+            };
+            let kept = match (&post.file, &record.file) {
+                (Some(file), Some(old)) if !file.has_data() => {
+                    Some(FileRecord {
+                        name: file.name.clone(),
+                        kind: old.kind.clone(),
+                    })
+                }
+                (Some(file), None) if !file.has_data() => {
+                    return Err(StoreError::NotFound(format!(
+                        "file of post {}",
+                        post.id
+                    )));
+                }
+                _ => None,
+            };
+            *record = PostRecord::new(&post);
+
+            if kept.is_some() {
+                // Only the name changes; the data stays as it is.
+                record.file = kept;
+                write(&path, &posts).await?;
+            } else if post.file.is_some() {
+                save_file(&tdir, post.id, post.file.as_ref()).await?;
+                write(&path, &posts).await?;
+            } else {
+                write(&path, &posts).await?;
+                save_file(&tdir, post.id, None).await?;
+            }
+        }
+
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+
+    async fn edit(
+        &mut self,
+        pid: Pid,
+        text: String,
+        tag: Option<Option<TagId>>,
+    ) -> Result<Post, StoreError> {
+        let post = {
+            let dir = self.dir.lock().await;
+            let tdir = times_dir(&dir, self.tid).await?;
+
+            if let Some(Some(tagid)) = tag {
+                let tags: Tags =
+                    read(&tdir.join("tags.json")).await?.unwrap_or_default();
+                if !tags.tags.iter().any(|t| t.id == tagid) {
+                    return Err(StoreError::NotFound(format!("tag {tagid}")));
+                }
+            }
+
+            let path = tdir.join("posts.json");
+            let mut posts: Posts = read(&path).await?.unwrap_or_default();
+            let Some(record) = posts.posts.iter_mut().find(|p| p.id == pid)
+            else {
+                return Err(StoreError::NotFound(format!("post {pid}")));
+            };
+            record.post = text;
+            if let Some(tag) = tag {
+                record.tag = tag;
+            }
+            record.updated_at = Some(chrono::Utc::now().naive_local());
+            let post = record.to_post(&tdir).await?;
+            write(&path, &posts).await?;
+
+            post
+        };
+
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+}
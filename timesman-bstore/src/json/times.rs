@@ -0,0 +1,66 @@
+use super::{async_trait, read, times_dir, write, Arc, Dir, Mutex};
+use super::{EventSender, StoreError, StoreEvent};
+use super::{JsonPostStore, JsonTodoStore, Tid, Times};
+use super::{PostStore, TimesStore, TodoStore};
+
+pub struct JsonTimesStore {
+    dir: Dir,
+    tid: Tid,
+    events: EventSender,
+}
+
+impl JsonTimesStore {
+    pub fn new(dir: Dir, tid: Tid, events: EventSender) -> Self {
+        Self { dir, tid, events }
+    }
+}
+
+// /{tid}/times.json
+#[async_trait]
+impl TimesStore for JsonTimesStore {
+    async fn get(&mut self) -> Result<Times, StoreError> {
+        let dir = self.dir.lock().await;
+        let path = dir.join(self.tid.to_string()).join("times.json");
+        read(&path)
+            .await?
+            .ok_or_else(|| StoreError::NotFound(format!("times {}", self.tid)))
+    }
+
+    async fn update(&mut self, times: Times) -> Result<Times, StoreError> {
+        let times = Times {
+            id: self.tid,
+            updated_at: Some(chrono::Utc::now().naive_local()),
+            ..times
+        };
+
+        {
+            let dir = self.dir.lock().await;
+            let tdir = times_dir(&dir, self.tid).await?;
+            write(&tdir.join("times.json"), &times).await?;
+        }
+
+        self.events.send(StoreEvent::TimesUpdated(times.clone()));
+
+        Ok(times)
+    }
+
+    async fn pstore(
+        &mut self,
+    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, StoreError> {
+        Ok(Arc::new(Mutex::new(JsonPostStore::new(
+            self.dir.clone(),
+            self.tid,
+            self.events.clone(),
+        ))))
+    }
+
+    async fn tdstore(
+        &mut self,
+    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, StoreError> {
+        Ok(Arc::new(Mutex::new(JsonTodoStore::new(
+            self.dir.clone(),
+            self.tid,
+            self.events.clone(),
+        ))))
+    }
+}
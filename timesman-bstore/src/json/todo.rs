@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+use super::{async_trait, read, times_dir, write, Dir};
+use super::{EventSender, StoreError, StoreEvent, Tid, TodoStore};
+use timesman_type::{Tdid, Todo};
+
+#[derive(Serialize, Deserialize, Default)]
+struct Todos {
+    next_tdid: Tdid,
+    todos: Vec<Todo>,
+}
+
+pub struct JsonTodoStore {
+    dir: Dir,
+    tid: Tid,
+    events: EventSender,
+}
+
+impl JsonTodoStore {
+    pub fn new(dir: Dir, tid: Tid, events: EventSender) -> Self {
+        Self { dir, tid, events }
+    }
+
+    /// Changes the todo `tdid` with `f` and saves it.
+    async fn change(
+        &self,
+        tdid: Tdid,
+        f: impl FnOnce(&mut Todo) -> Result<(), StoreError>,
+    ) -> Result<Todo, StoreError> {
+        let todo = {
+            let dir = self.dir.lock().await;
+            let path = times_dir(&dir, self.tid).await?.join("todos.json");
+            let mut todos: Todos = read(&path).await?.unwrap_or_default();
+
+            let Some(todo) = todos.todos.iter_mut().find(|t| t.id == tdid)
+            else {
+                return Err(StoreError::NotFound(format!("todo {tdid}")));
+            };
+            f(todo)?;
+            let todo = todo.clone();
+            write(&path, &todos).await?;
+
+            todo
+        };
+
+        self.events
+            .send(StoreEvent::TodoUpdated(self.tid, todo.clone()));
+
+        Ok(todo)
+    }
+}
+
+// /{tid}/todos.json
+#[async_trait]
+impl TodoStore for JsonTodoStore {
+    async fn get(&mut self) -> Result<Vec<Todo>, StoreError> {
+        let dir = self.dir.lock().await;
+        let path = times_dir(&dir, self.tid).await?.join("todos.json");
+        let todos: Todos = read(&path).await?.unwrap_or_default();
+        Ok(todos.todos)
+    }
+
+    async fn new(&mut self, content: String) -> Result<Todo, StoreError> {
+        let todo = {
+            let dir = self.dir.lock().await;
+            let path = times_dir(&dir, self.tid).await?.join("todos.json");
+            let mut todos: Todos = read(&path).await?.unwrap_or_default();
+
+            let todo = Todo {
+                id: todos.next_tdid,
+                content,
+                detail: None,
+                created_at: chrono::Utc::now().naive_local(),
+                done_at: None,
+            };
+            todos.next_tdid += 1;
+            todos.todos.push(todo.clone());
+            write(&path, &todos).await?;
+
+            todo
+        };
+
+        self.events
+            .send(StoreEvent::TodoCreated(self.tid, todo.clone()));
+
+        Ok(todo)
+    }
+
+    async fn done(
+        &mut self,
+        tdid: Tdid,
+        done: bool,
+    ) -> Result<Todo, StoreError> {
+        self.change(tdid, |todo| {
+            if todo.done_at.is_some() == done {
+                let state = if done { "done" } else { "not done" };
+                return Err(StoreError::Conflict(format!(
+                    "todo {tdid} is already {state}"
+                )));
+            }
+            todo.done_at = done.then(|| chrono::Utc::now().naive_local());
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update(&mut self, todo: Todo) -> Result<Todo, StoreError> {
+        self.change(todo.id, |old| {
+            *old = todo;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete(&mut self, tdid: Tdid) -> Result<(), StoreError> {
+        {
+            let dir = self.dir.lock().await;
+            let path = times_dir(&dir, self.tid).await?.join("todos.json");
+            let mut todos: Todos = read(&path).await?.unwrap_or_default();
+
+            let len = todos.todos.len();
+            todos.todos.retain(|t| t.id != tdid);
+            if todos.todos.len() == len {
+                return Err(StoreError::NotFound(format!("todo {tdid}")));
+            }
+            write(&path, &todos).await?;
+        }
+
+        self.events.send(StoreEvent::TodoDeleted(self.tid, tdid));
+
+        Ok(())
+    }
+}
@@ -1,6 +1,12 @@
+mod event;
+use event::EventSender;
+pub use event::{StoreEvent, StoreEventStream};
+
 mod ram;
 use ram::RamStore;
 
+#[cfg(feature = "local")]
+mod fs_file;
 #[cfg(feature = "local")]
 mod local;
 #[cfg(feature = "local")]
@@ -9,7 +15,33 @@ use local::LocalStore;
 #[cfg(feature = "grpc")]
 mod grpc;
 #[cfg(feature = "grpc")]
-use grpc::GrpcStore;
+pub use grpc::GrpcStore;
+
+#[cfg(any(
+    feature = "local",
+    feature = "sqlite",
+    feature = "postgres",
+    feature = "json"
+))]
+mod attachment;
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+mod sql;
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStore;
+
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "json")]
+pub use json::JsonStore;
 
 
 use serde::{Deserialize, Serialize};
@@ -20,13 +52,82 @@ use tokio::sync::Mutex;
 
 use async_trait::async_trait;
 
-use timesman_type::{File, Pid, Post, Tag, Tdid, Tid, Times, Todo};
+use timesman_type::{
+    File, Pid, Post, Tag, TagId, Tdid, Tid, Times, Todo, UserId,
+};
 
-#[derive(Debug)]
+/// Why a store call failed. The messages say which item or operation.
+#[derive(Debug, Clone, PartialEq)]
 pub enum StoreError {
+    /// The times, post, tag or todo does not exist.
+    NotFound(String),
+    AlreadyExists(String),
+    /// The change does not fit the current state, like finishing a todo
+    /// that is already done.
+    Conflict(String),
+    /// Reading or writing the storage failed.
+    Io(String),
+    /// The stored data cannot be encoded or decoded.
+    Corrupt(String),
+    /// The service behind the store failed or cannot be reached.
+    Backend(String),
+    PermissionDenied(String),
     NotSupported,
 }
 
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(what) => write!(f, "Not found: {what}"),
+            Self::AlreadyExists(what) => write!(f, "Already exists: {what}"),
+            Self::Conflict(msg) => write!(f, "Conflict: {msg}"),
+            Self::Io(msg) => write!(f, "I/O error: {msg}"),
+            Self::Corrupt(msg) => write!(f, "Corrupt data: {msg}"),
+            Self::Backend(msg) => write!(f, "Backend error: {msg}"),
+            Self::PermissionDenied(msg) => {
+                write!(f, "Permission denied: {msg}")
+            }
+            Self::NotSupported => write!(f, "Not supported"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+#[cfg(any(feature = "local", feature = "json"))]
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Corrupt(e.to_string())
+    }
+}
+
+#[cfg(any(feature = "local", feature = "json"))]
+impl From<std::io::Error> for StoreError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+/// Replaces the file at `path` by renaming a complete copy over it, so that
+/// a crash leaves either the old or the new content.
+#[cfg(any(feature = "local", feature = "json"))]
+async fn write_bytes(
+    path: &std::path::Path,
+    data: &[u8],
+) -> Result<(), StoreError> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut file = tokio::fs::File::create(&tmp).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    tokio::fs::rename(&tmp, path).await?;
+
+    Ok(())
+}
+
 #[derive(PartialEq, Default, Debug, Clone, Serialize, Deserialize)]
 pub enum StoreType {
     #[default]
@@ -35,6 +136,13 @@ pub enum StoreType {
     Local(String),
     #[cfg(feature = "grpc")]
     Grpc(String),
+    #[cfg(feature = "sqlite")]
+    Sqlite(String),
+    #[cfg(feature = "postgres")]
+    Postgres(String),
+    /// A directory, and whether to create it when it is missing.
+    #[cfg(feature = "json")]
+    Json(std::path::PathBuf, bool),
 }
 
 impl StoreType {
@@ -43,13 +151,39 @@ impl StoreType {
             Self::Memory => Arc::new(Mutex::new(RamStore::new())),
             #[cfg(feature = "local")]
             Self::Local(path) => {
-                Arc::new(Mutex::new(LocalStore::new(&path).await))
+                let local_store =
+                    LocalStore::new(path).await.map_err(|e| format!("{e}"))?;
+                Arc::new(Mutex::new(local_store))
             }
             #[cfg(feature = "grpc")]
             Self::Grpc(server_url) => {
-                let grpc_store = GrpcStore::new(server_url.clone()).await?;
+                let credentials =
+                    timesman_grpc::client::Credentials::from_env();
+                let grpc_store =
+                    GrpcStore::new(server_url.clone(), None, credentials)
+                        .await?;
                 Arc::new(Mutex::new(grpc_store))
             }
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(path) => {
+                let sqlite_store =
+                    SqliteStore::new(path).await.map_err(|e| format!("{e}"))?;
+                Arc::new(Mutex::new(sqlite_store))
+            }
+            #[cfg(feature = "postgres")]
+            Self::Postgres(url) => {
+                let postgres_store = PostgresStore::new(url)
+                    .await
+                    .map_err(|e| format!("{e}"))?;
+                Arc::new(Mutex::new(postgres_store))
+            }
+            #[cfg(feature = "json")]
+            Self::Json(path, create) => {
+                let json_store = JsonStore::new(path, *create)
+                    .await
+                    .map_err(|e| format!("{e}"))?;
+                Arc::new(Mutex::new(json_store))
+            }
         };
 
         Ok(store)
@@ -58,51 +192,154 @@ impl StoreType {
 
 #[async_trait]
 pub trait Store: Send + Sync + 'static {
-    async fn check(&mut self) -> Result<(), String>;
+    async fn check(&mut self) -> Result<(), StoreError>;
     async fn get(
         &mut self,
-    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, String>;
+    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, StoreError>;
+    /// Creates a times owned by `owner`, or by nobody for `None`.
     async fn create(
         &mut self,
         title: String,
-    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, String>;
-    async fn delete(&mut self, tid: Tid) -> Result<(), String>;
+        owner: Option<UserId>,
+    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, StoreError>;
+    async fn delete(&mut self, tid: Tid) -> Result<(), StoreError>;
+    /// Streams the changes made from now on.
+    async fn subscribe(&mut self) -> Result<StoreEventStream, StoreError>;
 }
 
 #[async_trait]
 pub trait TimesStore: Send + Sync + 'static {
-    async fn get(&mut self) -> Result<Times, String>;
-    async fn update(&mut self, times: Times) -> Result<Times, String>;
+    async fn get(&mut self) -> Result<Times, StoreError>;
+    /// Saves `times` with `updated_at` set to now.
+    async fn update(&mut self, times: Times) -> Result<Times, StoreError>;
     async fn pstore(
         &mut self,
-    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, String>;
+    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, StoreError>;
     async fn tdstore(
         &mut self,
-    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, String>;
+    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, StoreError>;
+}
+
+/// Which posts `PostStore::list` returns, ordered by creation time and id.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PostQuery {
+    /// Newest first rather than oldest first.
+    pub newest_first: bool,
+    /// Only posts created at or after this.
+    pub since: Option<chrono::NaiveDateTime>,
+    /// Only posts created before this.
+    pub until: Option<chrono::NaiveDateTime>,
+    /// Only posts that come after this creation time and id in the order.
+    pub after: Option<(chrono::NaiveDateTime, Pid)>,
+    /// At most this many posts.
+    pub limit: Option<usize>,
 }
 
+impl PostQuery {
+    /// Picks the posts the query selects out of all the posts of a times.
+    pub fn select(&self, mut posts: Vec<Post>) -> Vec<Post> {
+        let key = |post: &Post| (post.created_at, post.id);
+        posts.retain(|post| {
+            self.since.is_none_or(|since| post.created_at >= since)
+                && self.until.is_none_or(|until| post.created_at < until)
+                && self.after.is_none_or(|after| {
+                    if self.newest_first {
+                        key(post) < after
+                    } else {
+                        key(post) > after
+                    }
+                })
+        });
+
+        posts.sort_by_key(key);
+        if self.newest_first {
+            posts.reverse();
+        }
+        if let Some(limit) = self.limit {
+            posts.truncate(limit);
+        }
+
+        posts
+    }
+}
+
+/// The data of a file, read as it is needed.
+pub type FileReader = Box<dyn tokio::io::AsyncRead + Send + Unpin>;
+
 #[async_trait]
 pub trait PostStore: Send + Sync + 'static {
-    async fn get(&mut self, pid: Pid) -> Result<Post, String>;
-    async fn get_all(&mut self) -> Result<Vec<Post>, String>;
-    async fn get_tags(&mut self) -> Result<Vec<Tag>, String>;
-    async fn create_tag(&mut self, name: String) -> Result<Tag, String>;
+    async fn get(&mut self, pid: Pid) -> Result<Post, StoreError>;
+    /// Lists the posts. Their files may come without the data, which `get`
+    /// has.
+    async fn get_all(&mut self) -> Result<Vec<Post>, StoreError>;
+    /// Lists the posts `query` selects, as `get_all` does. Stores that can
+    /// ask their backend for a range override this.
+    async fn list(
+        &mut self,
+        query: &PostQuery,
+    ) -> Result<Vec<Post>, StoreError> {
+        Ok(query.select(self.get_all().await?))
+    }
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, StoreError>;
+    async fn create_tag(&mut self, name: String) -> Result<Tag, StoreError>;
+    async fn rename_tag(
+        &mut self,
+        tagid: TagId,
+        name: String,
+    ) -> Result<Tag, StoreError>;
+    /// Deletes the tag and takes it off the posts that have it.
+    async fn delete_tag(&mut self, tagid: TagId) -> Result<(), StoreError>;
+    /// Sets or, with `None`, clears the tag of a post.
+    async fn assign_tag(
+        &mut self,
+        pid: Pid,
+        tagid: Option<TagId>,
+    ) -> Result<Post, StoreError>;
     async fn post(
         &mut self,
         post: String,
         file: Option<File>,
-    ) -> Result<Post, String>;
-    async fn delete(&mut self, pid: Pid) -> Result<(), String>;
-    async fn update(&mut self, post: Post) -> Result<Post, String>;
+    ) -> Result<Post, StoreError>;
+    /// Opens the file of a post, which comes without its data, for
+    /// reading. Stores that keep files apart override this to read them
+    /// as they are needed.
+    async fn open_file(
+        &mut self,
+        pid: Pid,
+    ) -> Result<(File, FileReader), StoreError> {
+        let file = self.get(pid).await?.file.ok_or_else(|| {
+            StoreError::NotFound(format!("file of post {pid}"))
+        })?;
+        let info = file.info();
+        let data = file.into_data().unwrap_or_default();
+
+        Ok((info, Box::new(std::io::Cursor::new(data))))
+    }
+    async fn delete(&mut self, pid: Pid) -> Result<(), StoreError>;
+    /// Saves `post` with `updated_at` set to now.
+    async fn update(&mut self, post: Post) -> Result<Post, StoreError>;
+    /// Changes the text of a post and, unless `tag` is `None`, its tag,
+    /// leaving its file untouched. Its file may come back without the
+    /// data, as in `get_all`.
+    async fn edit(
+        &mut self,
+        pid: Pid,
+        post: String,
+        tag: Option<Option<TagId>>,
+    ) -> Result<Post, StoreError>;
 }
 
 #[async_trait]
 pub trait TodoStore: Send + Sync + 'static {
-    async fn get(&mut self) -> Result<Vec<Todo>, String>;
-    async fn new(&mut self, content: String) -> Result<Todo, String>;
-    async fn done(&mut self, tdid: Tdid, done: bool) -> Result<Todo, String>;
-    async fn update(&mut self, todo: Todo) -> Result<Todo, String>;
-    async fn delete(&mut self, tdid: Tdid) -> Result<(), String>;
+    async fn get(&mut self) -> Result<Vec<Todo>, StoreError>;
+    async fn new(&mut self, content: String) -> Result<Todo, StoreError>;
+    async fn done(
+        &mut self,
+        tdid: Tdid,
+        done: bool,
+    ) -> Result<Todo, StoreError>;
+    async fn update(&mut self, todo: Todo) -> Result<Todo, StoreError>;
+    async fn delete(&mut self, tdid: Tdid) -> Result<(), StoreError>;
 }
 
 #[cfg(test)]
@@ -113,7 +350,7 @@ mod tests {
     async fn test_store(mut store: Box<dyn Store>) {
         // Test creating a TimesStore
         let title = "Test Times".to_string();
-        let _times_store = store.create(title.clone()).await.unwrap();
+        let _times_store = store.create(title.clone(), None).await.unwrap();
 
         // Test retrieving TimesStore
         let times_list = store.get().await.unwrap();
@@ -143,13 +380,46 @@ mod tests {
     fn test_times_local_store() {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
-            let store = Box::new(LocalStore::new(":mem:").await);
+            let store = Box::new(LocalStore::new(":mem:").await.unwrap());
+            test_store(store).await;
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_times_sqlite_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(SqliteStore::new(":memory:").await.unwrap());
+            test_store(store).await;
+        });
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    #[ignore = "needs TIMESMAN_TEST_POSTGRES_URL"]
+    fn test_times_postgres_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(postgres::tests::test_store().await);
+            test_store(store).await;
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_times_json_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let store =
+                Box::new(JsonStore::new(dir.path(), false).await.unwrap());
             test_store(store).await;
         });
     }
 
     async fn test_posts(mut store: Box<dyn Store>) {
-        let tstore = store.create("post test".to_string()).await.unwrap();
+        let tstore = store.create("post test".to_string(), None).await.unwrap();
 
         let mut tstore = tstore.lock().await;
         let pstore = tstore.pstore().await.unwrap();
@@ -180,13 +450,46 @@ mod tests {
     fn test_post_local_store() {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
-            let store = Box::new(LocalStore::new(":mem:").await);
+            let store = Box::new(LocalStore::new(":mem:").await.unwrap());
+            test_posts(store).await;
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_post_sqlite_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(SqliteStore::new(":memory:").await.unwrap());
+            test_posts(store).await;
+        });
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    #[ignore = "needs TIMESMAN_TEST_POSTGRES_URL"]
+    fn test_post_postgres_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(postgres::tests::test_store().await);
+            test_posts(store).await;
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_post_json_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let store =
+                Box::new(JsonStore::new(dir.path(), false).await.unwrap());
             test_posts(store).await;
         });
     }
 
     async fn test_recreate_pstore(mut store: Box<dyn Store>) {
-        let tstore = store.create("post test".to_string()).await.unwrap();
+        let tstore = store.create("post test".to_string(), None).await.unwrap();
 
         let mut tstore = tstore.lock().await;
         {
@@ -218,12 +521,570 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "local")]
     #[test]
     fn test_recreate_local_pstore() {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
-            let store = Box::new(LocalStore::new(":mem:").await);
+            let store = Box::new(LocalStore::new(":mem:").await.unwrap());
+            test_recreate_pstore(store).await;
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_recreate_sqlite_pstore() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(SqliteStore::new(":memory:").await.unwrap());
             test_recreate_pstore(store).await;
         });
     }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    #[ignore = "needs TIMESMAN_TEST_POSTGRES_URL"]
+    fn test_recreate_postgres_pstore() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(postgres::tests::test_store().await);
+            test_recreate_pstore(store).await;
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_recreate_json_pstore() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let store =
+                Box::new(JsonStore::new(dir.path(), false).await.unwrap());
+            test_recreate_pstore(store).await;
+        });
+    }
+
+    async fn test_tags(mut store: Box<dyn Store>) {
+        let tstore = store.create("tag test".to_string(), None).await.unwrap();
+
+        let mut tstore = tstore.lock().await;
+        let pstore = tstore.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+
+        let post = pstore.post("hello".to_string(), None).await.unwrap();
+        let tag = pstore.create_tag("work".to_string()).await.unwrap();
+
+        let renamed = pstore.rename_tag(tag.id, "job".to_string()).await;
+        assert_eq!(renamed.unwrap().name, "job");
+        assert_eq!(pstore.get_tags().await.unwrap()[0].name, "job");
+
+        let tagged = pstore.assign_tag(post.id, Some(tag.id)).await.unwrap();
+        assert_eq!(tagged.tag, Some(tag.id));
+        assert!(pstore.assign_tag(post.id, Some(tag.id + 1)).await.is_err());
+
+        pstore.delete_tag(tag.id).await.unwrap();
+        assert!(pstore.get_tags().await.unwrap().is_empty());
+        assert_eq!(pstore.get_all().await.unwrap()[0].tag, None);
+    }
+
+    #[test]
+    fn test_tags_ram_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(RamStore::new());
+            test_tags(store).await;
+        });
+    }
+
+    #[cfg(feature = "local")]
+    #[test]
+    fn test_tags_local_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(LocalStore::new(":mem:").await.unwrap());
+            test_tags(store).await;
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_tags_sqlite_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(SqliteStore::new(":memory:").await.unwrap());
+            test_tags(store).await;
+        });
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    #[ignore = "needs TIMESMAN_TEST_POSTGRES_URL"]
+    fn test_tags_postgres_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(postgres::tests::test_store().await);
+            test_tags(store).await;
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_tags_json_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let store =
+                Box::new(JsonStore::new(dir.path(), false).await.unwrap());
+            test_tags(store).await;
+        });
+    }
+
+    async fn test_list(mut store: Box<dyn Store>) {
+        let tstore = store.create("list test".to_string(), None).await.unwrap();
+
+        let mut tstore = tstore.lock().await;
+        let pstore = tstore.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+
+        let mut ids = vec![];
+        for i in 0..3 {
+            let post = pstore.post(format!("post {i}"), None).await.unwrap();
+            ids.push(post.id);
+        }
+        let first = pstore.get(ids[0]).await.unwrap();
+        let ids_of = |posts: Vec<Post>| -> Vec<Pid> {
+            posts.into_iter().map(|p| p.id).collect()
+        };
+
+        let all = pstore.list(&PostQuery::default()).await.unwrap();
+        assert_eq!(ids_of(all), ids);
+
+        let query = PostQuery {
+            newest_first: true,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let newest = pstore.list(&query).await.unwrap();
+        assert_eq!(ids_of(newest), vec![ids[2], ids[1]]);
+
+        let query = PostQuery {
+            after: Some((first.created_at, first.id)),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let next = pstore.list(&query).await.unwrap();
+        assert_eq!(ids_of(next), vec![ids[1]]);
+
+        let query = PostQuery {
+            until: Some(first.created_at),
+            ..Default::default()
+        };
+        assert!(pstore.list(&query).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_list_ram_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(RamStore::new());
+            test_list(store).await;
+        });
+    }
+
+    #[cfg(feature = "local")]
+    #[test]
+    fn test_list_local_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(LocalStore::new(":mem:").await.unwrap());
+            test_list(store).await;
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_list_sqlite_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(SqliteStore::new(":memory:").await.unwrap());
+            test_list(store).await;
+        });
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    #[ignore = "needs TIMESMAN_TEST_POSTGRES_URL"]
+    fn test_list_postgres_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(postgres::tests::test_store().await);
+            test_list(store).await;
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_list_json_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let store =
+                Box::new(JsonStore::new(dir.path(), false).await.unwrap());
+            test_list(store).await;
+        });
+    }
+
+    async fn test_edit(mut store: Box<dyn Store>) {
+        let tstore = store.create("edit test".to_string(), None).await.unwrap();
+
+        let mut tstore = tstore.lock().await;
+        let pstore = tstore.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+
+        let file = File::new(
+            "notes.txt".to_string(),
+            timesman_type::FileType::Text("kept".to_string()),
+        );
+        let post = pstore
+            .post("before".to_string(), Some(file.clone()))
+            .await
+            .unwrap();
+        let tag = pstore.create_tag("tag".to_string()).await.unwrap();
+
+        let edited = pstore
+            .edit(post.id, "tagged".to_string(), Some(Some(tag.id)))
+            .await
+            .unwrap();
+        assert_eq!(edited.post, "tagged");
+        assert_eq!(edited.tag, Some(tag.id));
+        assert!(edited.updated_at.is_some());
+
+        // Without a tag, the tag stays.
+        pstore
+            .edit(post.id, "after".to_string(), None)
+            .await
+            .unwrap();
+        let stored = pstore.get(post.id).await.unwrap();
+        assert_eq!(stored.post, "after");
+        assert_eq!(stored.tag, Some(tag.id));
+        assert_eq!(stored.file, Some(file));
+
+        assert!(matches!(
+            pstore
+                .edit(post.id, "x".to_string(), Some(Some(tag.id + 1)))
+                .await,
+            Err(StoreError::NotFound(_))
+        ));
+        assert!(matches!(
+            pstore.edit(post.id + 1, "x".to_string(), None).await,
+            Err(StoreError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_edit_ram_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(RamStore::new());
+            test_edit(store).await;
+        });
+    }
+
+    #[cfg(feature = "local")]
+    #[test]
+    fn test_edit_local_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(LocalStore::new(":mem:").await.unwrap());
+            test_edit(store).await;
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_edit_sqlite_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(SqliteStore::new(":memory:").await.unwrap());
+            test_edit(store).await;
+        });
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    #[ignore = "needs TIMESMAN_TEST_POSTGRES_URL"]
+    fn test_edit_postgres_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(postgres::tests::test_store().await);
+            test_edit(store).await;
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_edit_json_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let store =
+                Box::new(JsonStore::new(dir.path(), false).await.unwrap());
+            test_edit(store).await;
+        });
+    }
+
+    async fn test_open_file(mut store: Box<dyn Store>) {
+        use tokio::io::AsyncReadExt;
+
+        let tstore = store.create("file test".to_string(), None).await.unwrap();
+
+        let mut tstore = tstore.lock().await;
+        let pstore = tstore.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+
+        let file = File::new(
+            "blob".to_string(),
+            timesman_type::FileType::Other(vec![3; 1000]),
+        );
+        let post = pstore
+            .post("with file".to_string(), Some(file.clone()))
+            .await
+            .unwrap();
+        let (info, mut reader) = pstore.open_file(post.id).await.unwrap();
+        assert_eq!(info, file.info());
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, vec![3; 1000]);
+
+        let post = pstore.post("no file".to_string(), None).await.unwrap();
+        assert!(matches!(
+            pstore.open_file(post.id).await,
+            Err(StoreError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_open_file_ram_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(RamStore::new());
+            test_open_file(store).await;
+        });
+    }
+
+    #[cfg(feature = "local")]
+    #[test]
+    fn test_open_file_local_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("local.db");
+            let store = Box::new(
+                LocalStore::new(path.to_str().unwrap()).await.unwrap(),
+            );
+            test_open_file(store).await;
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_open_file_sqlite_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(SqliteStore::new(":memory:").await.unwrap());
+            test_open_file(store).await;
+        });
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    #[ignore = "needs TIMESMAN_TEST_POSTGRES_URL"]
+    fn test_open_file_postgres_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(postgres::tests::test_store().await);
+            test_open_file(store).await;
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_open_file_json_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let store =
+                Box::new(JsonStore::new(dir.path(), false).await.unwrap());
+            test_open_file(store).await;
+        });
+    }
+
+    async fn test_errors(mut store: Box<dyn Store>) {
+        let tstore =
+            store.create("error test".to_string(), None).await.unwrap();
+        let mut tstore = tstore.lock().await;
+
+        let pstore = tstore.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+        assert!(matches!(pstore.get(42).await, Err(StoreError::NotFound(_))));
+        assert!(matches!(
+            pstore.rename_tag(42, "none".to_string()).await,
+            Err(StoreError::NotFound(_))
+        ));
+
+        let tdstore = tstore.tdstore().await.unwrap();
+        let mut tdstore = tdstore.lock().await;
+        let todo = tdstore.new("todo".to_string()).await.unwrap();
+        tdstore.done(todo.id, true).await.unwrap();
+        assert!(matches!(
+            tdstore.done(todo.id, true).await,
+            Err(StoreError::Conflict(_))
+        ));
+        assert!(matches!(
+            tdstore.delete(todo.id + 1).await,
+            Err(StoreError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_errors_ram_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(RamStore::new());
+            test_errors(store).await;
+        });
+    }
+
+    #[cfg(feature = "local")]
+    #[test]
+    fn test_errors_local_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(LocalStore::new(":mem:").await.unwrap());
+            test_errors(store).await;
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_errors_sqlite_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(SqliteStore::new(":memory:").await.unwrap());
+            test_errors(store).await;
+        });
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    #[ignore = "needs TIMESMAN_TEST_POSTGRES_URL"]
+    fn test_errors_postgres_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(postgres::tests::test_store().await);
+            test_errors(store).await;
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_errors_json_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let store =
+                Box::new(JsonStore::new(dir.path(), false).await.unwrap());
+            test_errors(store).await;
+        });
+    }
+
+    async fn test_events(mut store: Box<dyn Store>) {
+        use tokio_stream::StreamExt;
+
+        let mut events = store.subscribe().await.unwrap();
+
+        let owner = Some(uuid::Uuid::new_v4());
+        let tstore =
+            store.create("event test".to_string(), owner).await.unwrap();
+        let mut tstore = tstore.lock().await;
+        let times = tstore.get().await.unwrap();
+        let tid = times.id;
+        assert_eq!(times.owner, owner);
+        assert_eq!(events.next().await, Some(StoreEvent::TimesCreated(times)));
+
+        let pstore = tstore.pstore().await.unwrap();
+        let post = pstore
+            .lock()
+            .await
+            .post("hello".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            events.next().await,
+            Some(StoreEvent::PostCreated(tid, post))
+        );
+
+        let tdstore = tstore.tdstore().await.unwrap();
+        let todo = tdstore.lock().await.new("todo".to_string()).await.unwrap();
+        assert_eq!(
+            events.next().await,
+            Some(StoreEvent::TodoCreated(tid, todo))
+        );
+
+        drop(tstore);
+        store.delete(tid).await.unwrap();
+        assert_eq!(events.next().await, Some(StoreEvent::TimesDeleted(tid)));
+    }
+
+    #[test]
+    fn test_events_ram_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(RamStore::new());
+            test_events(store).await;
+        });
+    }
+
+    #[cfg(feature = "local")]
+    #[test]
+    fn test_events_local_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(LocalStore::new(":mem:").await.unwrap());
+            test_events(store).await;
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_events_sqlite_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(SqliteStore::new(":memory:").await.unwrap());
+            test_events(store).await;
+        });
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    #[ignore = "needs TIMESMAN_TEST_POSTGRES_URL"]
+    fn test_events_postgres_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = Box::new(postgres::tests::test_store().await);
+            test_events(store).await;
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_events_json_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let store =
+                Box::new(JsonStore::new(dir.path(), false).await.unwrap());
+            test_events(store).await;
+        });
+    }
 }
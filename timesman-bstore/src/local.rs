@@ -6,9 +6,13 @@ use tokio::sync::Mutex;
 use async_trait::async_trait;
 use unqlite::{UnQLite, KV};
 
-use timesman_type::{File, Pid, Post, Tag, TagId, Tdid, Tid, Times, Todo};
+use timesman_type::{
+    File, Pid, Post, Tag, TagId, Tdid, Tid, Times, Todo, UserId,
+};
 
-use super::{PostStore, Store, TimesStore, TodoStore};
+use super::fs_file::FsFileStorage;
+use super::{EventSender, StoreError, StoreEvent, StoreEventStream};
+use super::{FileReader, PostStore, Store, TimesStore, TodoStore};
 
 mod times;
 use times::LocalTimesStore;
@@ -18,60 +22,85 @@ use post::LocalPostStore;
 
 mod todo;
 
+impl From<unqlite::Error> for StoreError {
+    fn from(e: unqlite::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct RootMeta {
     ntid: u64,
     tids: Vec<Tid>,
 }
 
+/// Where the attachments of posts are kept, `None` to keep them in the
+/// database itself.
+type Files = Option<Arc<FsFileStorage>>;
+
 pub struct LocalStore {
     store: Arc<Mutex<UnQLite>>,
+    files: Files,
     tids: Vec<Tid>,
     ntid: u64,
     tstores: Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>,
+    events: EventSender,
 }
 
 impl LocalStore {
-    pub async fn new(path: &str) -> Self {
-        let store = UnQLite::create(&path);
+    /// Opens the database at `path`. The attachments of posts go to the
+    /// directory `{path}.files`, but stay in memory for ":mem:".
+    pub async fn new(path: &str) -> Result<Self, StoreError> {
+        let store = UnQLite::create(path);
+        let files = if path == ":mem:" {
+            None
+        } else {
+            let files = FsFileStorage::new(format!("{path}.files")).await?;
+            Some(Arc::new(files))
+        };
 
         let meta = if !store.kv_contains("meta.data") {
             let meta = RootMeta {
                 ntid: 0,
                 tids: vec![],
             };
-            let text = serde_json::to_string(&meta).unwrap();
-            store.kv_store("meta.data", text.into_bytes()).unwrap();
+            let text = serde_json::to_string(&meta)?;
+            store.kv_store("meta.data", text.into_bytes())?;
             meta
         } else {
-            let data = store.kv_fetch("meta.data").unwrap();
-            serde_json::from_slice(&data).unwrap()
+            let data = store.kv_fetch("meta.data")?;
+            serde_json::from_slice(&data)?
         };
 
         let storep = Arc::new(Mutex::new(store));
+        let events = EventSender::new();
 
         let mut tstores = vec![];
 
         for tid in &meta.tids {
             let store = storep.lock().await;
-            let data = store.kv_fetch(format!("{}/meta.data", tid)).unwrap();
-            let tmeta: TimesMeta = serde_json::from_slice(&data).unwrap();
+            let data = store.kv_fetch(format!("{}/meta.data", tid))?;
+            let tmeta: TimesMeta = serde_json::from_slice(&data)?;
 
             let tstore: Arc<Mutex<dyn TimesStore + Send + Sync>> =
                 Arc::new(Mutex::new(LocalTimesStore::new(
                     tmeta.to_times(*tid),
                     storep.clone(),
+                    files.clone(),
+                    events.clone(),
                 )));
 
             tstores.push(tstore);
         }
 
-        Self {
+        Ok(Self {
             store: storep,
+            files,
             tids: meta.tids,
             ntid: meta.ntid,
             tstores,
-        }
+            events,
+        })
     }
 }
 
@@ -85,6 +114,8 @@ impl LocalStore {
  * /{tid}/tags/{tagid}           - Individual tags
  * /{tid}/todos/meta.data        - Todos metadata
  * /{tid}/todos/{tdid}           - Individual todos
+ *
+ * The attachments of posts are kept by FsFileStorage, out of the database.
  */
 
 #[derive(Serialize, Deserialize)]
@@ -92,14 +123,17 @@ struct TimesMeta {
     title: String,
     created_at: chrono::NaiveDateTime,
     updated_at: Option<chrono::NaiveDateTime>,
+    #[serde(default)]
+    owner: Option<UserId>,
 }
 
 impl TimesMeta {
-    pub fn new(title: String) -> Self {
+    pub fn new(title: String, owner: Option<UserId>) -> Self {
         Self {
             title,
             created_at: chrono::Utc::now().naive_local(),
             updated_at: None,
+            owner,
         }
     }
 
@@ -109,6 +143,7 @@ impl TimesMeta {
             title: self.title.clone(),
             created_at: self.created_at,
             updated_at: self.updated_at,
+            owner: self.owner,
         }
     }
 }
@@ -116,36 +151,37 @@ impl TimesMeta {
 // /meta.data
 #[async_trait]
 impl Store for LocalStore {
-    async fn check(&mut self) -> Result<(), String> {
+    async fn check(&mut self) -> Result<(), StoreError> {
         Ok(())
     }
 
     async fn get(
         &mut self,
-    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, String> {
+    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, StoreError> {
         Ok(self.tstores.clone())
     }
 
     async fn create(
         &mut self,
         title: String,
-    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, String> {
+        owner: Option<UserId>,
+    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, StoreError> {
         let store = self.store.lock().await;
         if store.kv_contains(&title) {
-            return Err("Already exists".to_string());
+            return Err(StoreError::AlreadyExists(format!("times {title}")));
         }
 
         let tid = self.ntid;
-        let tmeta = TimesMeta::new(title);
-        let data = serde_json::to_string(&tmeta).map_err(|e| format!("{e}"))?;
+        let tmeta = TimesMeta::new(title, owner);
+        let data = serde_json::to_string(&tmeta)?;
 
-        store
-            .kv_store(format!("{}", tid), data.into_bytes())
-            .map_err(|e| format!("{e}"))?;
+        store.kv_store(format!("{}", tid), data.into_bytes())?;
 
         let tstore = Arc::new(Mutex::new(LocalTimesStore::new(
             tmeta.to_times(tid),
             self.store.clone(),
+            self.files.clone(),
+            self.events.clone(),
         )));
         self.tstores.push(tstore.clone());
 
@@ -156,20 +192,22 @@ impl Store for LocalStore {
             ntid: self.ntid,
             tids: self.tids.clone(),
         };
-        let data = serde_json::to_string(&rmeta).map_err(|e| format!("{e}"))?;
-        store
-            .kv_store("meta.data", data.into_bytes())
-            .map_err(|e| format!("{e}"))?;
+        let data = serde_json::to_string(&rmeta)?;
+        store.kv_store("meta.data", data.into_bytes())?;
+
+        let data = serde_json::to_string(&tmeta)?;
+        store.kv_store(format!("{}/meta.data", tid), data.into_bytes())?;
 
-        let data = serde_json::to_string(&tmeta).unwrap();
-        store
-            .kv_store(format!("{}/meta.data", tid), data.into_bytes())
-            .unwrap();
+        self.events.send(StoreEvent::TimesCreated(tmeta.to_times(tid)));
 
         Ok(tstore)
     }
 
-    async fn delete(&mut self, tid: Tid) -> Result<(), String> {
+    async fn delete(&mut self, tid: Tid) -> Result<(), StoreError> {
+        if !self.tids.contains(&tid) {
+            return Err(StoreError::NotFound(format!("times {tid}")));
+        }
+
         // Remove from tids list
         self.tids.retain(|&x| x != tid);
         
@@ -178,10 +216,10 @@ impl Store for LocalStore {
             ntid: self.ntid,
             tids: self.tids.clone(),
         };
-        let text = serde_json::to_string(&root_meta).map_err(|e| format!("Failed to serialize root meta: {}", e))?;
+        let text = serde_json::to_string(&root_meta)?;
         
         let store = self.store.lock().await;
-        store.kv_store("meta.data", text.into_bytes()).map_err(|e| format!("Failed to store root meta: {}", e))?;
+        store.kv_store("meta.data", text.into_bytes())?;
         
         // Delete times metadata
         let meta_key = format!("{}/meta.data", tid);
@@ -198,6 +236,11 @@ impl Store for LocalStore {
         // Delete todos directory
         let todos_meta_key = format!("{}/todos/meta.data", tid);
         let _ = store.kv_delete(&todos_meta_key); // Ignore error if doesn't exist
+
+        // Delete attachments
+        if let Some(files) = &self.files {
+            files.delete_times(tid).await?;
+        }
         
         // Remove from tstores - collect IDs first, then filter
         let mut indices_to_remove = Vec::new();
@@ -218,7 +261,13 @@ impl Store for LocalStore {
         for &index in indices_to_remove.iter().rev() {
             self.tstores.remove(index);
         }
-        
+
+        self.events.send(StoreEvent::TimesDeleted(tid));
+
         Ok(())
     }
+
+    async fn subscribe(&mut self) -> Result<StoreEventStream, StoreError> {
+        Ok(self.events.subscribe())
+    }
 }
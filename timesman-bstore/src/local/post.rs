@@ -1,14 +1,17 @@
 use super::serde_json;
-use super::PostStore;
 use super::{async_trait, Arc, Mutex, UnQLite, KV};
+use super::{EventSender, Files, StoreError, StoreEvent};
 use super::{File, Pid, Post, Tag, TagId, Tid};
+use super::{FileReader, PostStore};
 use serde::{Deserialize, Serialize};
 
 pub struct LocalPostStore {
     tid: Tid,
     store: Arc<Mutex<UnQLite>>,
+    files: Files,
     pmeta: PostMeta,
     tag_meta: TagMeta,
+    events: EventSender,
 }
 
 fn get_pmeta_path(tid: Tid) -> String {
@@ -31,18 +34,18 @@ impl LocalPostStore {
     async fn load_pmeta(
         tid: Tid,
         store: Arc<Mutex<UnQLite>>,
-    ) -> Result<PostMeta, String> {
+    ) -> Result<PostMeta, StoreError> {
         let store = store.lock().await;
         let meta_path = get_pmeta_path(tid);
 
         let meta = if !store.kv_contains(&meta_path) {
             let meta = PostMeta::default();
-            let data = serde_json::to_string(&meta).unwrap();
-            store.kv_store(&meta_path, data.into_bytes()).unwrap();
+            let data = serde_json::to_string(&meta)?;
+            store.kv_store(&meta_path, data.into_bytes())?;
             meta
         } else {
-            let data = store.kv_fetch(&meta_path).unwrap();
-            serde_json::from_slice(&data).unwrap()
+            let data = store.kv_fetch(&meta_path)?;
+            serde_json::from_slice(&data)?
         };
 
         Ok(meta)
@@ -51,53 +54,205 @@ impl LocalPostStore {
     async fn load_tag_meta(
         tid: Tid,
         store: Arc<Mutex<UnQLite>>,
-    ) -> Result<TagMeta, String> {
+    ) -> Result<TagMeta, StoreError> {
         let meta_path = get_tag_meta_path(tid);
 
         let store = store.lock().await;
 
         let tag_meta = if !store.kv_contains(&meta_path) {
             let meta = TagMeta::default();
-            let data = serde_json::to_string(&meta).unwrap();
-            store.kv_store(&meta_path, data.into_bytes()).unwrap();
+            let data = serde_json::to_string(&meta)?;
+            store.kv_store(&meta_path, data.into_bytes())?;
             meta
         } else {
-            let data = store.kv_fetch(&meta_path).unwrap();
-            serde_json::from_slice(&data).unwrap()
+            let data = store.kv_fetch(&meta_path)?;
+            serde_json::from_slice(&data)?
         };
 
         Ok(tag_meta)
     }
 
-    pub async fn new(tid: Tid, store: Arc<Mutex<UnQLite>>) -> Self {
-        let pmeta = Self::load_pmeta(tid, store.clone()).await.unwrap();
-        let tag_meta = Self::load_tag_meta(tid, store.clone()).await.unwrap();
+    pub async fn new(
+        tid: Tid,
+        store: Arc<Mutex<UnQLite>>,
+        files: Files,
+        events: EventSender,
+    ) -> Result<Self, StoreError> {
+        let pmeta = Self::load_pmeta(tid, store.clone()).await?;
+        let tag_meta = Self::load_tag_meta(tid, store.clone()).await?;
 
-        Self {
+        Ok(Self {
             tid,
             store,
+            files,
             pmeta,
             tag_meta,
-        }
+            events,
+        })
     }
 
-    async fn sync_post_meta(&self) {
-        let data = serde_json::to_string(&self.pmeta).unwrap();
+    async fn sync_post_meta(&self) -> Result<(), StoreError> {
+        let data = serde_json::to_string(&self.pmeta)?;
 
         let store = self.store.lock().await;
-        store
-            .kv_store(get_pmeta_path(self.tid), data.into_bytes())
-            .unwrap();
+        store.kv_store(get_pmeta_path(self.tid), data.into_bytes())?;
+        Ok(())
     }
 
-    async fn sync_tag_meta(&self) {
-        let data = serde_json::to_string(&self.tag_meta).unwrap();
+    async fn sync_tag_meta(&self) -> Result<(), StoreError> {
+        let data = serde_json::to_string(&self.tag_meta)?;
 
         let store = self.store.lock().await;
+        store.kv_store(get_tag_meta_path(self.tid), data.into_bytes())?;
+        Ok(())
+    }
+
+    fn fetch_record(
+        &self,
+        store: &UnQLite,
+        pid: Pid,
+    ) -> Result<PostRecord, StoreError> {
+        let data = store.kv_fetch(get_post_path(self.tid, pid))?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    fn store_record(
+        &self,
+        store: &UnQLite,
+        record: &PostRecord,
+    ) -> Result<(), StoreError> {
+        let text = serde_json::to_string(record)?;
         store
-            .kv_store(get_tag_meta_path(self.tid), data.into_bytes())
-            .unwrap();
+            .kv_store(get_post_path(self.tid, record.id), text.into_bytes())?;
+        Ok(())
     }
+
+    /// Puts the post in `record` back together with its attachment, or
+    /// with only its name, kind and size unless `with_data`.
+    async fn to_post(
+        &self,
+        record: PostRecord,
+        with_data: bool,
+    ) -> Result<Post, StoreError> {
+        let file = match record.file {
+            None => None,
+            Some(Attachment::Inline(file)) if with_data => Some(file),
+            Some(Attachment::Inline(file)) => Some(file.info()),
+            Some(Attachment::Stored { .. }) => {
+                let Some(files) = &self.files else {
+                    return Err(StoreError::Corrupt(format!(
+                        "post {} has a file but there is no file storage",
+                        record.id
+                    )));
+                };
+                if with_data {
+                    Some(files.load_file(self.tid, record.id).await?)
+                } else {
+                    Some(files.load_info(self.tid, record.id).await?)
+                }
+            }
+        };
+
+        Ok(Post {
+            id: record.id,
+            post: record.post,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+            file,
+            tag: record.tag,
+        })
+    }
+
+    /// The stored attachment of post `pid` under a new name, for an update
+    /// that left the data out.
+    async fn rename_attachment(
+        &self,
+        store: &UnQLite,
+        pid: Pid,
+        name: &str,
+    ) -> Result<Attachment, StoreError> {
+        match (self.fetch_record(store, pid)?.file, &self.files) {
+            (Some(Attachment::Inline(file)), _) => {
+                Ok(Attachment::Inline(File {
+                    name: name.to_string(),
+                    ..file
+                }))
+            }
+            (Some(Attachment::Stored { .. }), Some(files)) => {
+                files.rename_file(self.tid, pid, name).await?;
+                Ok(Attachment::Stored {
+                    name: name.to_string(),
+                })
+            }
+            _ => Err(StoreError::NotFound(format!("file of post {pid}"))),
+        }
+    }
+
+    /// Writes `post`, with its attachment in the file storage if there is
+    /// one.
+    async fn store_post(
+        &self,
+        store: &UnQLite,
+        post: &Post,
+    ) -> Result<(), StoreError> {
+        let file = match (&post.file, &self.files) {
+            (None, _) => None,
+            (Some(file), _) if !file.has_data() => {
+                Some(self.rename_attachment(store, post.id, &file.name).await?)
+            }
+            (Some(file), None) => Some(Attachment::Inline(file.clone())),
+            (Some(file), Some(files)) => {
+                // The file goes first, so that the post never refers to a
+                // missing file.
+                files.save_file(self.tid, post.id, file).await?;
+                Some(Attachment::Stored {
+                    name: file.name.clone(),
+                })
+            }
+        };
+
+        self.store_record(store, &PostRecord::new(post, file))?;
+
+        if let (None, Some(files)) = (&post.file, &self.files) {
+            files.delete_file(self.tid, post.id).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A post as kept in the database.
+#[derive(Serialize, Deserialize)]
+struct PostRecord {
+    id: Pid,
+    post: String,
+    created_at: chrono::NaiveDateTime,
+    updated_at: Option<chrono::NaiveDateTime>,
+    file: Option<Attachment>,
+    tag: Option<TagId>,
+}
+
+impl PostRecord {
+    fn new(post: &Post, file: Option<Attachment>) -> Self {
+        Self {
+            id: post.id,
+            post: post.post.clone(),
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+            file,
+            tag: post.tag,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Attachment {
+    /// The file itself, as posts were kept before their attachments moved
+    /// to the file storage, and still are without one.
+    Inline(File),
+    /// A file kept in the file storage.
+    Stored { name: String },
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -129,60 +284,179 @@ impl TagMeta {
 
 #[async_trait]
 impl PostStore for LocalPostStore {
-    async fn get(&mut self, _pid: Pid) -> Result<Post, String> {
-        todo!();
+    async fn get(&mut self, pid: Pid) -> Result<Post, StoreError> {
+        if !self.pmeta.pids.contains(&pid) {
+            return Err(StoreError::NotFound(format!("post {pid}")));
+        }
+
+        let store = self.store.lock().await;
+        let record = self.fetch_record(&store, pid)?;
+        self.to_post(record, true).await
+    }
+
+    async fn open_file(
+        &mut self,
+        pid: Pid,
+    ) -> Result<(File, FileReader), StoreError> {
+        if !self.pmeta.pids.contains(&pid) {
+            return Err(StoreError::NotFound(format!("post {pid}")));
+        }
+
+        let record = {
+            let store = self.store.lock().await;
+            self.fetch_record(&store, pid)?
+        };
+        match (record.file, &self.files) {
+            (Some(Attachment::Inline(file)), _) => {
+                let info = file.info();
+                let data = file.into_data().unwrap_or_default();
+                Ok((info, Box::new(std::io::Cursor::new(data))))
+            }
+            (Some(Attachment::Stored { .. }), Some(files)) => {
+                let (meta, reader) = files.open_file(self.tid, pid).await?;
+                Ok((meta.info()?, Box::new(reader)))
+            }
+            _ => Err(StoreError::NotFound(format!("file of post {pid}"))),
+        }
     }
 
-    async fn get_all(&mut self) -> Result<Vec<Post>, String> {
+    async fn get_all(&mut self) -> Result<Vec<Post>, StoreError> {
         let store = self.store.lock().await;
         let mut posts = vec![];
         for pid in &self.pmeta.pids {
-            let data = store.kv_fetch(get_post_path(self.tid, *pid)).unwrap();
-            let post: Post = serde_json::from_slice(&data).unwrap();
-            posts.push(post);
+            let record = self.fetch_record(&store, *pid)?;
+            posts.push(self.to_post(record, false).await?);
         }
 
         Ok(posts)
     }
 
-    async fn get_tags(&mut self) -> Result<Vec<Tag>, String> {
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, StoreError> {
         let store = self.store.lock().await;
 
         let mut tags = vec![];
         for tagid in &self.tag_meta.tagids {
-            let data = store.kv_fetch(get_tag_path(self.tid, *tagid)).unwrap();
-            let tag: Tag = serde_json::from_slice(&data).unwrap();
+            let data = store.kv_fetch(get_tag_path(self.tid, *tagid))?;
+            let tag: Tag = serde_json::from_slice(&data)?;
             tags.push(tag)
         }
 
         Ok(tags)
     }
 
-    async fn create_tag(&mut self, name: String) -> Result<Tag, String> {
+    async fn create_tag(&mut self, name: String) -> Result<Tag, StoreError> {
         let id = self.tag_meta.ntagid;
 
         let tag = Tag { id, name };
 
         {
-            let text = serde_json::to_string(&tag).unwrap();
+            let text = serde_json::to_string(&tag)?;
             let store = self.store.lock().await;
             store
-                .kv_store(get_tag_path(self.tid, tag.id), text.into_bytes())
-                .unwrap();
+                .kv_store(get_tag_path(self.tid, tag.id), text.into_bytes())?;
         }
 
         self.tag_meta.append(tag.id);
 
-        self.sync_tag_meta().await;
+        self.sync_tag_meta().await?;
+
+        self.events
+            .send(StoreEvent::TagCreated(self.tid, tag.clone()));
 
         Ok(tag)
     }
 
+    async fn rename_tag(
+        &mut self,
+        tagid: TagId,
+        name: String,
+    ) -> Result<Tag, StoreError> {
+        if !self.tag_meta.tagids.contains(&tagid) {
+            return Err(StoreError::NotFound(format!("tag {tagid}")));
+        }
+
+        let tag = Tag { id: tagid, name };
+
+        {
+            let text = serde_json::to_string(&tag)?;
+            let store = self.store.lock().await;
+            store
+                .kv_store(get_tag_path(self.tid, tag.id), text.into_bytes())?;
+        }
+
+        self.events
+            .send(StoreEvent::TagUpdated(self.tid, tag.clone()));
+
+        Ok(tag)
+    }
+
+    async fn delete_tag(&mut self, tagid: TagId) -> Result<(), StoreError> {
+        let Some(pos) = self.tag_meta.tagids.iter().position(|&x| x == tagid)
+        else {
+            return Err(StoreError::NotFound(format!("tag {tagid}")));
+        };
+        self.tag_meta.tagids.remove(pos);
+        self.sync_tag_meta().await?;
+
+        let mut untagged = vec![];
+        {
+            let store = self.store.lock().await;
+            store.kv_delete(get_tag_path(self.tid, tagid))?;
+
+            for pid in &self.pmeta.pids {
+                let mut record = self.fetch_record(&store, *pid)?;
+                if record.tag != Some(tagid) {
+                    continue;
+                }
+
+                record.tag = None;
+                self.store_record(&store, &record)?;
+                untagged.push(self.to_post(record, false).await?);
+            }
+        }
+
+        for post in untagged {
+            self.events.send(StoreEvent::PostUpdated(self.tid, post));
+        }
+        self.events.send(StoreEvent::TagDeleted(self.tid, tagid));
+
+        Ok(())
+    }
+
+    async fn assign_tag(
+        &mut self,
+        pid: Pid,
+        tagid: Option<TagId>,
+    ) -> Result<Post, StoreError> {
+        if let Some(tagid) = tagid {
+            if !self.tag_meta.tagids.contains(&tagid) {
+                return Err(StoreError::NotFound(format!("tag {tagid}")));
+            }
+        }
+        if !self.pmeta.pids.contains(&pid) {
+            return Err(StoreError::NotFound(format!("post {pid}")));
+        }
+
+        let post = {
+            let store = self.store.lock().await;
+            let mut record = self.fetch_record(&store, pid)?;
+
+            record.tag = tagid;
+            self.store_record(&store, &record)?;
+            self.to_post(record, true).await?
+        };
+
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+
     async fn post(
         &mut self,
         post: String,
         file: Option<File>,
-    ) -> Result<Post, String> {
+    ) -> Result<Post, StoreError> {
         // Get next available ID and increment atomically
         let pid = self.pmeta.npid;
         self.pmeta.npid += 1;
@@ -196,37 +470,94 @@ impl PostStore for LocalPostStore {
             tag: None,
         };
 
-        let text = serde_json::to_string(&post)
-            .map_err(|e| format!("Failed to serialize post: {}", e))?;
-        
         // add a scope to avoid deadlock
         {
             let store = self.store.lock().await;
-            store
-                .kv_store(get_post_path(self.tid, pid), text.into_bytes())
-                .map_err(|e| format!("Failed to store post: {}", e))?;
+            self.store_post(&store, &post).await?;
         }
 
         // Add to metadata list
         self.pmeta.pids.push(pid);
 
-        self.sync_post_meta().await;
+        self.sync_post_meta().await?;
+
+        self.events
+            .send(StoreEvent::PostCreated(self.tid, post.clone()));
 
         Ok(post)
     }
 
-    async fn delete(&mut self, _pid: Pid) -> Result<(), String> {
-        todo!();
+    async fn delete(&mut self, pid: Pid) -> Result<(), StoreError> {
+        let Some(pos) = self.pmeta.pids.iter().position(|&x| x == pid) else {
+            return Err(StoreError::NotFound(format!("post {pid}")));
+        };
+        self.pmeta.pids.remove(pos);
+        self.sync_post_meta().await?;
+
+        {
+            let store = self.store.lock().await;
+            store.kv_delete(get_post_path(self.tid, pid))?;
+        }
+
+        if let Some(files) = &self.files {
+            files.delete_file(self.tid, pid).await?;
+        }
+
+        self.events.send(StoreEvent::PostDeleted(self.tid, pid));
+
+        Ok(())
     }
 
-    async fn update(&mut self, post: Post) -> Result<Post, String> {
-        let text = serde_json::to_string(&post).unwrap();
+    async fn update(&mut self, post: Post) -> Result<Post, StoreError> {
+        if !self.pmeta.pids.contains(&post.id) {
+            return Err(StoreError::NotFound(format!("post {}", post.id)));
+        }
+        let post = Post {
+            updated_at: Some(chrono::Utc::now().naive_local()),
+            ..post
+        };
+
         {
             let store = self.store.lock().await;
-            store
-                .kv_store(get_post_path(self.tid, post.id), text.into_bytes())
-                .unwrap();
+            self.store_post(&store, &post).await?;
+        }
+
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+
+    async fn edit(
+        &mut self,
+        pid: Pid,
+        text: String,
+        tag: Option<Option<TagId>>,
+    ) -> Result<Post, StoreError> {
+        if let Some(Some(tagid)) = tag {
+            if !self.tag_meta.tagids.contains(&tagid) {
+                return Err(StoreError::NotFound(format!("tag {tagid}")));
+            }
         }
+        if !self.pmeta.pids.contains(&pid) {
+            return Err(StoreError::NotFound(format!("post {pid}")));
+        }
+
+        let post = {
+            let store = self.store.lock().await;
+            let mut record = self.fetch_record(&store, pid)?;
+
+            record.post = text;
+            if let Some(tag) = tag {
+                record.tag = tag;
+            }
+            record.updated_at = Some(chrono::Utc::now().naive_local());
+            self.store_record(&store, &record)?;
+            self.to_post(record, false).await?
+        };
+
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
 
         Ok(post)
     }
@@ -254,4 +585,199 @@ mod tests {
         assert!(!get_post_path(tid, pid).contains('$'));
         assert!(!get_tag_path(tid, tagid).contains('$'));
     }
+
+    #[tokio::test]
+    async fn test_files_are_kept_out_of_the_database() {
+        use super::super::{LocalStore, Store};
+        use timesman_type::FileType;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("local.db");
+        let path = path.to_str().unwrap();
+        let image = File::new(
+            "photo.png".to_string(),
+            FileType::Image(vec![0x89, 0x50, 0x4e, 0x47]),
+        );
+
+        let (tid, post) = {
+            let mut store = LocalStore::new(path).await.unwrap();
+            let tstore = store.create("files".to_string(), None).await.unwrap();
+            let mut tstore = tstore.lock().await;
+            let tid = tstore.get().await.unwrap().id;
+            let pstore = tstore.pstore().await.unwrap();
+            let post = pstore
+                .lock()
+                .await
+                .post("with file".to_string(), Some(image.clone()))
+                .await
+                .unwrap();
+
+            let kv = store.store.lock().await;
+            let data = kv.kv_fetch(get_post_path(tid, post.id)).unwrap();
+            assert!(!String::from_utf8(data).unwrap().contains("Image"));
+
+            (tid, post)
+        };
+
+        let attached = dir
+            .path()
+            .join("local.db.files")
+            .join(tid.to_string())
+            .join(post.id.to_string());
+        assert_eq!(std::fs::read(&attached).unwrap(), [0x89, 0x50, 0x4e, 0x47]);
+
+        let mut store = LocalStore::new(path).await.unwrap();
+        let tstores = store.get().await.unwrap();
+        let pstore = tstores[0].lock().await.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+        assert_eq!(pstore.get(post.id).await.unwrap(), post);
+
+        // Dropping the file from the post deletes it.
+        let post = pstore.update(Post { file: None, ..post }).await.unwrap();
+        assert_eq!(pstore.get_all().await.unwrap(), vec![post]);
+        assert!(!attached.exists());
+    }
+
+    #[tokio::test]
+    async fn test_listing_does_not_read_files() {
+        use super::super::{LocalStore, Store};
+        use timesman_type::FileType;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("local.db");
+        let mut store = LocalStore::new(path.to_str().unwrap()).await.unwrap();
+        let tstore = store.create("files".to_string(), None).await.unwrap();
+        let mut tstore = tstore.lock().await;
+        let tid = tstore.get().await.unwrap().id;
+        let pstore = tstore.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+        let image =
+            File::new("photo.png".to_string(), FileType::Image(vec![1; 16]));
+        let post = pstore
+            .post("photo".to_string(), Some(image.clone()))
+            .await
+            .unwrap();
+
+        // Saving a listed post keeps the data and takes the new name.
+        let mut listed = pstore.get_all().await.unwrap().remove(0);
+        assert_eq!(listed.file, Some(image.info()));
+        listed.file.as_mut().unwrap().name = "renamed.png".to_string();
+        pstore.update(listed).await.unwrap();
+        let renamed = File {
+            name: "renamed.png".to_string(),
+            ..image
+        };
+        assert_eq!(
+            pstore.get(post.id).await.unwrap().file,
+            Some(renamed.clone())
+        );
+
+        // Without the data, listing still works and only getting fails.
+        let data = dir
+            .path()
+            .join("local.db.files")
+            .join(tid.to_string())
+            .join(post.id.to_string());
+        std::fs::remove_file(data).unwrap();
+        let posts = pstore.get_all().await.unwrap();
+        assert_eq!(posts[0].file, Some(renamed.info()));
+        assert!(pstore.get(post.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_post_and_file() {
+        use super::super::{LocalStore, Store};
+        use timesman_type::FileType;
+        use tokio_stream::StreamExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("local.db");
+        let path = path.to_str().unwrap();
+        let image =
+            File::new("photo.png".to_string(), FileType::Image(vec![2; 16]));
+
+        let mut store = LocalStore::new(path).await.unwrap();
+        let tstore = store.create("delete".to_string(), None).await.unwrap();
+        let mut tstore = tstore.lock().await;
+        let tid = tstore.get().await.unwrap().id;
+        let pstore = tstore.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+        let kept = pstore.post("kept".to_string(), None).await.unwrap();
+        let post = pstore.post("photo".to_string(), Some(image)).await.unwrap();
+        let attached = dir
+            .path()
+            .join("local.db.files")
+            .join(tid.to_string())
+            .join(post.id.to_string());
+        assert!(attached.exists());
+
+        let mut events = store.subscribe().await.unwrap();
+        pstore.delete(post.id).await.unwrap();
+        assert_eq!(
+            events.next().await,
+            Some(StoreEvent::PostDeleted(tid, post.id))
+        );
+        assert!(!attached.exists());
+        assert!(!store
+            .store
+            .lock()
+            .await
+            .kv_contains(get_post_path(tid, post.id)));
+        assert!(matches!(
+            pstore.get(post.id).await,
+            Err(StoreError::NotFound(_))
+        ));
+        assert!(matches!(
+            pstore.delete(post.id).await,
+            Err(StoreError::NotFound(_))
+        ));
+
+        // The post stays gone after reopening.
+        drop(pstore);
+        drop(tstore);
+        drop(store);
+        let mut store = LocalStore::new(path).await.unwrap();
+        let tstores = store.get().await.unwrap();
+        let pstore = tstores[0].lock().await.pstore().await.unwrap();
+        assert_eq!(pstore.lock().await.get_all().await.unwrap(), vec![kept]);
+    }
+
+    #[tokio::test]
+    async fn test_inline_files_are_still_read() {
+        use crate::fs_file::FsFileStorage;
+
+        let store = Arc::new(Mutex::new(UnQLite::create_in_memory()));
+        let dir = tempfile::tempdir().unwrap();
+        let files = FsFileStorage::new(dir.path()).await.unwrap();
+        let mut pstore = LocalPostStore::new(
+            0,
+            store.clone(),
+            Some(Arc::new(files)),
+            EventSender::new(),
+        )
+        .await
+        .unwrap();
+
+        // A post written before attachments moved out of the database.
+        let post = Post {
+            id: 0,
+            post: "inline".to_string(),
+            created_at: chrono::Utc::now().naive_local(),
+            updated_at: None,
+            file: Some(File::new(
+                "notes.txt".to_string(),
+                timesman_type::FileType::Text("hello".to_string()),
+            )),
+            tag: None,
+        };
+        let text = serde_json::to_string(&post).unwrap();
+        store
+            .lock()
+            .await
+            .kv_store(get_post_path(0, 0), text.into_bytes())
+            .unwrap();
+        pstore.pmeta.append(0);
+
+        assert_eq!(pstore.get(0).await.unwrap(), post);
+    }
 }
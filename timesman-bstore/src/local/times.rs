@@ -1,6 +1,7 @@
 use super::async_trait;
 use super::todo::LocalTodoStore;
-use super::{Arc, Mutex, Times, UnQLite};
+use super::{Arc, Files, Mutex, Times, UnQLite};
+use super::{EventSender, StoreError, StoreEvent};
 use super::{PostStore, TimesStore, TodoStore};
 use unqlite::KV;
 
@@ -9,42 +10,64 @@ use super::LocalPostStore;
 pub struct LocalTimesStore {
     times: Times,
     store: Arc<Mutex<UnQLite>>,
+    files: Files,
+    events: EventSender,
 }
 
 impl LocalTimesStore {
-    pub fn new(times: Times, store: Arc<Mutex<UnQLite>>) -> Self {
-        Self { times, store }
+    pub fn new(
+        times: Times,
+        store: Arc<Mutex<UnQLite>>,
+        files: Files,
+        events: EventSender,
+    ) -> Self {
+        Self {
+            times,
+            store,
+            files,
+            events,
+        }
     }
 }
 
 // /{tid}/mata.data
 #[async_trait]
 impl TimesStore for LocalTimesStore {
-    async fn get(&mut self) -> Result<Times, String> {
+    async fn get(&mut self) -> Result<Times, StoreError> {
         Ok(self.times.clone())
     }
 
-    async fn update(&mut self, times: Times) -> Result<Times, String> {
+    async fn update(&mut self, times: Times) -> Result<Times, StoreError> {
+        let times = Times {
+            updated_at: Some(chrono::Utc::now().naive_local()),
+            ..times
+        };
         self.times = times.clone();
         
         // Persist the updated times to storage
         let times_path = format!("{}/meta.data", times.id);
-        let serialized = serde_json::to_string(&times)
-            .map_err(|e| format!("Failed to serialize times: {}", e))?;
+        let serialized = serde_json::to_string(&times)?;
         
         let store = self.store.lock().await;
-        store.kv_store(times_path, serialized.into_bytes())
-            .map_err(|e| format!("Failed to store times: {}", e))?;
-        
+        store.kv_store(times_path, serialized.into_bytes())?;
+
+        self.events.send(StoreEvent::TimesUpdated(times.clone()));
+
         Ok(times)
     }
 
     async fn pstore(
         &mut self,
-    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, String> {
+    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, StoreError> {
         let pstore: Arc<Mutex<dyn PostStore + Send + Sync>> =
             Arc::new(Mutex::new(
-                LocalPostStore::new(self.times.id, self.store.clone()).await,
+                LocalPostStore::new(
+                    self.times.id,
+                    self.store.clone(),
+                    self.files.clone(),
+                    self.events.clone(),
+                )
+                .await?,
             ));
 
         Ok(pstore)
@@ -52,10 +75,15 @@ impl TimesStore for LocalTimesStore {
 
     async fn tdstore(
         &mut self,
-    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, String> {
+    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, StoreError> {
         let tdstore: Arc<Mutex<dyn TodoStore + Send + Sync>> =
             Arc::new(Mutex::new(
-                LocalTodoStore::new(self.times.id, self.store.clone()).await?,
+                LocalTodoStore::new(
+                    self.times.id,
+                    self.store.clone(),
+                    self.events.clone(),
+                )
+                .await?,
             ));
 
         Ok(tdstore)
@@ -1,6 +1,7 @@
 use super::async_trait;
 use super::TodoStore;
 use super::{Arc, Mutex, UnQLite, KV};
+use super::{EventSender, StoreError, StoreEvent};
 use super::{Tdid, Tid, Todo};
 
 use serde::{Deserialize, Serialize};
@@ -9,6 +10,7 @@ pub struct LocalTodoStore {
     tid: Tid,
     meta: TodoMeta,
     store: Arc<Mutex<UnQLite>>,
+    events: EventSender,
 }
 
 // {tid}/todos/meta.data
@@ -30,7 +32,7 @@ struct TodoMeta {
 async fn load_meta(
     tid: Tid,
     store: Arc<Mutex<UnQLite>>,
-) -> Result<TodoMeta, String> {
+) -> Result<TodoMeta, StoreError> {
     let store = store.lock().await;
     let meta_path = get_meta_path(tid);
 
@@ -40,13 +42,13 @@ async fn load_meta(
             tdids: vec![],
         };
 
-        let data = serde_json::to_string(&meta).unwrap();
-        store.kv_store(&meta_path, data.into_bytes()).unwrap();
+        let data = serde_json::to_string(&meta)?;
+        store.kv_store(&meta_path, data.into_bytes())?;
 
         meta
     } else {
-        let data = store.kv_fetch(&meta_path).unwrap();
-        serde_json::from_slice(&data).unwrap()
+        let data = store.kv_fetch(&meta_path)?;
+        serde_json::from_slice(&data)?
     };
 
     Ok(meta)
@@ -56,19 +58,22 @@ impl LocalTodoStore {
     pub async fn new(
         tid: Tid,
         store: Arc<Mutex<UnQLite>>,
-    ) -> Result<Self, String> {
+        events: EventSender,
+    ) -> Result<Self, StoreError> {
         let meta = load_meta(tid, store.clone()).await?;
-        Ok(Self { tid, store, meta })
+        Ok(Self {
+            tid,
+            store,
+            meta,
+            events,
+        })
     }
 
-    async fn sync_meta(&self) -> Result<(), String> {
-        let data = serde_json::to_string(&self.meta)
-            .map_err(|e| format!("Failed to serialize meta: {}", e))?;
+    async fn sync_meta(&self) -> Result<(), StoreError> {
+        let data = serde_json::to_string(&self.meta)?;
 
         let store = self.store.lock().await;
-        store
-            .kv_store(get_meta_path(self.tid), data.into_bytes())
-            .map_err(|e| format!("Failed to store meta: {}", e))?;
+        store.kv_store(get_meta_path(self.tid), data.into_bytes())?;
         
         Ok(())
     }
@@ -76,7 +81,7 @@ impl LocalTodoStore {
 
 #[async_trait]
 impl TodoStore for LocalTodoStore {
-    async fn get(&mut self) -> Result<Vec<Todo>, String> {
+    async fn get(&mut self) -> Result<Vec<Todo>, StoreError> {
         let mut resp = vec![];
         let store = self.store.lock().await;
         for id in &self.meta.tdids {
@@ -101,7 +106,7 @@ impl TodoStore for LocalTodoStore {
         Ok(resp)
     }
 
-    async fn new(&mut self, content: String) -> Result<Todo, String> {
+    async fn new(&mut self, content: String) -> Result<Todo, StoreError> {
         let id = self.meta.ntdid;
 
         let todo = Todo {
@@ -112,14 +117,11 @@ impl TodoStore for LocalTodoStore {
             done_at: None,
         };
 
-        let text = serde_json::to_string(&todo)
-            .map_err(|e| format!("Failed to serialize todo: {}", e))?;
+        let text = serde_json::to_string(&todo)?;
         
         {
             let store = self.store.lock().await;
-            store
-                .kv_store(get_todo_path(self.tid, id), text.into_bytes())
-                .map_err(|e| format!("Failed to store todo: {}", e))?;
+            store.kv_store(get_todo_path(self.tid, id), text.into_bytes())?;
         }
 
         self.meta.ntdid += 1;
@@ -127,20 +129,29 @@ impl TodoStore for LocalTodoStore {
 
         self.sync_meta().await?;
 
+        self.events
+            .send(StoreEvent::TodoCreated(self.tid, todo.clone()));
+
         Ok(todo)
     }
 
-    async fn done(&mut self, tdid: Tdid, done: bool) -> Result<Todo, String> {
+    async fn done(
+        &mut self,
+        tdid: Tdid,
+        done: bool,
+    ) -> Result<Todo, StoreError> {
         let store = self.store.lock().await;
 
         let Ok(data) = store.kv_fetch(get_todo_path(self.tid, tdid)) else {
-            return Err("invalid tid".to_string());
+            return Err(StoreError::NotFound(format!("todo {tdid}")));
         };
-        let mut todo: Todo =
-            serde_json::from_slice(&data).map_err(|e| format!("{e}"))?;
+        let mut todo: Todo = serde_json::from_slice(&data)?;
 
         if todo.done_at.is_some() == done {
-            return Err("invalid state".to_string());
+            let state = if done { "done" } else { "not done" };
+            return Err(StoreError::Conflict(format!(
+                "todo {tdid} is already {state}"
+            )));
         }
 
         todo.done_at = if done {
@@ -149,53 +160,56 @@ impl TodoStore for LocalTodoStore {
             None
         };
 
-        let text = serde_json::to_string(&todo).unwrap();
-        store
-            .kv_store(get_todo_path(self.tid, tdid), text.into_bytes())
-            .map_err(|e| format!("{e}"))?;
+        let text = serde_json::to_string(&todo)?;
+        store.kv_store(get_todo_path(self.tid, tdid), text.into_bytes())?;
+
+        self.events
+            .send(StoreEvent::TodoUpdated(self.tid, todo.clone()));
 
         Ok(todo)
     }
 
-    async fn update(&mut self, todo: Todo) -> Result<Todo, String> {
+    async fn update(&mut self, todo: Todo) -> Result<Todo, StoreError> {
         let store = self.store.lock().await;
         
         // Check if todo exists
         let todo_path = get_todo_path(self.tid, todo.id);
         if !store.kv_contains(&todo_path) {
-            return Err("Todo not found".to_string());
+            return Err(StoreError::NotFound(format!("todo {}", todo.id)));
         }
         
         // Update the todo
-        let text = serde_json::to_string(&todo)
-            .map_err(|e| format!("Failed to serialize todo: {}", e))?;
-        
-        store
-            .kv_store(&todo_path, text.into_bytes())
-            .map_err(|e| format!("Failed to store todo: {}", e))?;
+        let text = serde_json::to_string(&todo)?;
         
+        store.kv_store(&todo_path, text.into_bytes())?;
+
+        self.events
+            .send(StoreEvent::TodoUpdated(self.tid, todo.clone()));
+
         Ok(todo)
     }
 
-    async fn delete(&mut self, tdid: Tdid) -> Result<(), String> {
-        let store = self.store.lock().await;
-        
+    async fn delete(&mut self, tdid: Tdid) -> Result<(), StoreError> {
         // Check if todo exists and remove from metadata
         if let Some(pos) = self.meta.tdids.iter().position(|&x| x == tdid) {
             self.meta.tdids.remove(pos);
         } else {
-            return Err("Todo not found".to_string());
+            return Err(StoreError::NotFound(format!("todo {tdid}")));
         }
         
-        // Delete the todo from storage
-        let todo_path = get_todo_path(self.tid, tdid);
-        store
-            .kv_delete(&todo_path)
-            .map_err(|e| format!("Failed to delete todo: {}", e))?;
+        // Delete the todo from storage. The lock is released before
+        // sync_meta takes it again.
+        {
+            let store = self.store.lock().await;
+            let todo_path = get_todo_path(self.tid, tdid);
+            store.kv_delete(&todo_path)?;
+        }
         
         // Update metadata
         self.sync_meta().await?;
-        
+
+        self.events.send(StoreEvent::TodoDeleted(self.tid, tdid));
+
         Ok(())
     }
 }
@@ -209,7 +223,9 @@ mod tests {
     async fn create_test_store() -> (LocalTodoStore, String) {
         let test_db = format!("{}/test_todos_{}.db", env::temp_dir().display(), uuid::Uuid::new_v4());
         let store = Arc::new(Mutex::new(UnQLite::create(&test_db)));
-        let todo_store = LocalTodoStore::new(1, store).await.unwrap();
+        let todo_store = LocalTodoStore::new(1, store, EventSender::new())
+            .await
+            .unwrap();
         (todo_store, test_db)
     }
 
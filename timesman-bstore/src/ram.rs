@@ -5,32 +5,40 @@ use tokio::sync::Mutex;
 
 use async_trait::async_trait;
 
-use crate::{PostStore, TimesStore, TodoStore};
+use crate::{EventSender, PostStore, StoreEvent, StoreEventStream};
+use crate::{StoreError, TimesStore, TodoStore};
 
 use super::Store;
-use timesman_type::{File, Pid, Post, Tag, TagId, Tdid, Tid, Times, Todo};
+use timesman_type::{
+    File, Pid, Post, Tag, TagId, Tdid, Tid, Times, Todo, UserId,
+};
 
 pub struct RamStore {
     tstores: HashMap<Tid, Arc<Mutex<dyn TimesStore + Send + Sync>>>,
     ntid: Tid,
+    events: EventSender,
 }
 
 impl RamStore {
     pub fn new() -> Self {
         let tstores = HashMap::new();
-        Self { tstores, ntid: 0 }
+        Self {
+            tstores,
+            ntid: 0,
+            events: EventSender::new(),
+        }
     }
 }
 
 #[async_trait]
 impl Store for RamStore {
-    async fn check(&mut self) -> Result<(), String> {
+    async fn check(&mut self) -> Result<(), StoreError> {
         Ok(())
     }
 
     async fn get(
         &mut self,
-    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, String> {
+    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, StoreError> {
         let mut pairs: Vec<(&Tid, &Arc<Mutex<dyn TimesStore + Send + Sync>>)> =
             self.tstores.iter().collect();
 
@@ -43,7 +51,8 @@ impl Store for RamStore {
     async fn create(
         &mut self,
         title: String,
-    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, String> {
+        owner: Option<UserId>,
+    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, StoreError> {
         let tid = self.ntid;
 
         let times = Times {
@@ -51,101 +60,122 @@ impl Store for RamStore {
             title,
             created_at: Utc::now().naive_local(),
             updated_at: None,
+            owner,
         };
 
-        let tstore: Arc<Mutex<dyn TimesStore + Send + Sync>> =
-            Arc::new(Mutex::new(RamTimesStore::new(times.clone())));
+        let tstore: Arc<Mutex<dyn TimesStore + Send + Sync>> = Arc::new(
+            Mutex::new(RamTimesStore::new(times.clone(), self.events.clone())),
+        );
 
         self.ntid += 1;
 
         self.tstores.insert(tid, tstore.clone());
+        self.events.send(StoreEvent::TimesCreated(times));
 
         Ok(tstore)
     }
 
-    async fn delete(&mut self, tid: Tid) -> Result<(), String> {
+    async fn delete(&mut self, tid: Tid) -> Result<(), StoreError> {
         let r = self.tstores.remove(&tid);
         if r.is_some() {
+            self.events.send(StoreEvent::TimesDeleted(tid));
             Ok(())
         } else {
-            Err("invalid tid".to_string())
+            Err(StoreError::NotFound(format!("times {tid}")))
         }
     }
+
+    async fn subscribe(&mut self) -> Result<StoreEventStream, StoreError> {
+        Ok(self.events.subscribe())
+    }
 }
 
 struct RamTimesStore {
     times: Times,
     pstore: Arc<Mutex<dyn PostStore + Send + Sync>>,
     tdstore: Arc<Mutex<dyn TodoStore + Send + Sync>>,
+    events: EventSender,
 }
 
 impl RamTimesStore {
-    pub fn new(times: Times) -> Self {
-        let pstore = Arc::new(Mutex::new(RamPostStore::new()));
-        let tdstore = Arc::new(Mutex::new(RamTodoStore::new()));
+    pub fn new(times: Times, events: EventSender) -> Self {
+        let pstore =
+            Arc::new(Mutex::new(RamPostStore::new(times.id, events.clone())));
+        let tdstore =
+            Arc::new(Mutex::new(RamTodoStore::new(times.id, events.clone())));
         Self {
             times,
             pstore,
             tdstore,
+            events,
         }
     }
 }
 
 #[async_trait]
 impl TimesStore for RamTimesStore {
-    async fn get(&mut self) -> Result<Times, String> {
+    async fn get(&mut self) -> Result<Times, StoreError> {
         Ok(self.times.clone())
     }
 
-    async fn update(&mut self, times: Times) -> Result<Times, String> {
+    async fn update(&mut self, times: Times) -> Result<Times, StoreError> {
+        let times = Times {
+            updated_at: Some(Utc::now().naive_local()),
+            ..times
+        };
         self.times = times.clone();
+        self.events.send(StoreEvent::TimesUpdated(times.clone()));
         Ok(times)
     }
 
     async fn pstore(
         &mut self,
-    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, String> {
+    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, StoreError> {
         Ok(self.pstore.clone())
     }
 
     async fn tdstore(
         &mut self,
-    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, String> {
+    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, StoreError> {
         Ok(self.tdstore.clone())
     }
 }
 
 struct RamPostStore {
+    tid: Tid,
     posts: HashMap<Pid, Post>,
     npid: Pid,
     tags: HashMap<TagId, Tag>,
     ntagid: TagId,
+    events: EventSender,
 }
 
 impl RamPostStore {
-    pub fn new() -> Self {
+    pub fn new(tid: Tid, events: EventSender) -> Self {
         let posts = HashMap::new();
         let tags = HashMap::new();
         Self {
+            tid,
             posts,
             npid: 0,
             tags,
             ntagid: 0,
+            events,
         }
     }
 }
 
 #[async_trait]
 impl PostStore for RamPostStore {
-    async fn get(&mut self, pid: Pid) -> Result<Post, String> {
+    async fn get(&mut self, pid: Pid) -> Result<Post, StoreError> {
         if let Some(post) = self.posts.get(&pid) {
             Ok(post.clone())
         } else {
-            Err("invalid pid".to_string())
+            Err(StoreError::NotFound(format!("post {pid}")))
         }
     }
 
-    async fn get_all(&mut self) -> Result<Vec<Post>, String> {
+    async fn get_all(&mut self) -> Result<Vec<Post>, StoreError> {
         let mut pairs: Vec<(&Tid, &Post)> = self.posts.iter().collect();
 
         pairs.sort_by(|a, b| a.0.cmp(&b.0));
@@ -155,7 +185,7 @@ impl PostStore for RamPostStore {
         Ok(posts)
     }
 
-    async fn get_tags(&mut self) -> Result<Vec<Tag>, String> {
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, StoreError> {
         let mut pairs: Vec<(&TagId, &Tag)> = self.tags.iter().collect();
 
         pairs.sort_by(|a, b| a.0.cmp(&b.0));
@@ -165,20 +195,81 @@ impl PostStore for RamPostStore {
         Ok(tags)
     }
 
-    async fn create_tag(&mut self, name: String) -> Result<Tag, String> {
+    async fn create_tag(&mut self, name: String) -> Result<Tag, StoreError> {
         let id = self.ntagid;
         let tag = Tag { id, name };
         self.tags.insert(id, tag.clone());
         self.ntagid += 1;
+        self.events
+            .send(StoreEvent::TagCreated(self.tid, tag.clone()));
+
+        Ok(tag)
+    }
+
+    async fn rename_tag(
+        &mut self,
+        tagid: TagId,
+        name: String,
+    ) -> Result<Tag, StoreError> {
+        let Some(tag) = self.tags.get_mut(&tagid) else {
+            return Err(StoreError::NotFound(format!("tag {tagid}")));
+        };
+
+        tag.name = name;
+
+        let tag = tag.clone();
+        self.events
+            .send(StoreEvent::TagUpdated(self.tid, tag.clone()));
 
         Ok(tag)
     }
 
+    async fn delete_tag(&mut self, tagid: TagId) -> Result<(), StoreError> {
+        if self.tags.remove(&tagid).is_none() {
+            return Err(StoreError::NotFound(format!("tag {tagid}")));
+        }
+
+        for post in self.posts.values_mut() {
+            if post.tag == Some(tagid) {
+                post.tag = None;
+                self.events
+                    .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+            }
+        }
+        self.events.send(StoreEvent::TagDeleted(self.tid, tagid));
+
+        Ok(())
+    }
+
+    async fn assign_tag(
+        &mut self,
+        pid: Pid,
+        tagid: Option<TagId>,
+    ) -> Result<Post, StoreError> {
+        if let Some(tagid) = tagid {
+            if !self.tags.contains_key(&tagid) {
+                return Err(StoreError::NotFound(format!("tag {tagid}")));
+            }
+        }
+
+        let Some(post) = self.posts.get_mut(&pid) else {
+            return Err(StoreError::NotFound(format!("post {pid}")));
+        };
+
+        post.tag = tagid;
+
+        let post = post.clone();
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+
     async fn post(
         &mut self,
         post: String,
         file: Option<File>,
-    ) -> Result<Post, String> {
+    ) -> Result<Post, StoreError> {
         let id = self.npid;
         self.npid += 1;
 
@@ -192,45 +283,90 @@ impl PostStore for RamPostStore {
         };
 
         self.posts.insert(id, post.clone());
+        self.events
+            .send(StoreEvent::PostCreated(self.tid, post.clone()));
 
         Ok(post)
     }
 
-    async fn delete(&mut self, pid: Pid) -> Result<(), String> {
+    async fn delete(&mut self, pid: Pid) -> Result<(), StoreError> {
         let r = self.posts.remove(&pid);
         if r.is_some() {
+            self.events.send(StoreEvent::PostDeleted(self.tid, pid));
             Ok(())
         } else {
-            Err("invalid pid".to_string())
+            Err(StoreError::NotFound(format!("post {pid}")))
         }
     }
 
-    async fn update(&mut self, post: Post) -> Result<Post, String> {
+    async fn update(&mut self, post: Post) -> Result<Post, StoreError> {
+        let post = Post {
+            updated_at: Some(Utc::now().naive_local()),
+            ..post
+        };
         match self.posts.get_mut(&post.id) {
             Some(val) => {
                 *val = post.clone();
+                self.events
+                    .send(StoreEvent::PostUpdated(self.tid, post.clone()));
                 Ok(post)
             }
-            None => Err("invalid pid".to_string()),
+            None => Err(StoreError::NotFound(format!("post {}", post.id))),
+        }
+    }
+
+    async fn edit(
+        &mut self,
+        pid: Pid,
+        text: String,
+        tag: Option<Option<TagId>>,
+    ) -> Result<Post, StoreError> {
+        if let Some(Some(tagid)) = tag {
+            if !self.tags.contains_key(&tagid) {
+                return Err(StoreError::NotFound(format!("tag {tagid}")));
+            }
         }
+
+        let Some(post) = self.posts.get_mut(&pid) else {
+            return Err(StoreError::NotFound(format!("post {pid}")));
+        };
+
+        post.post = text;
+        if let Some(tag) = tag {
+            post.tag = tag;
+        }
+        post.updated_at = Some(Utc::now().naive_local());
+
+        let post = post.clone();
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
     }
 }
 
 struct RamTodoStore {
+    tid: Tid,
     todos: HashMap<Tdid, Todo>,
     ntdid: Tdid,
+    events: EventSender,
 }
 
 impl RamTodoStore {
-    pub fn new() -> Self {
+    pub fn new(tid: Tid, events: EventSender) -> Self {
         let todos = HashMap::new();
-        Self { todos, ntdid: 0 }
+        Self {
+            tid,
+            todos,
+            ntdid: 0,
+            events,
+        }
     }
 }
 
 #[async_trait]
 impl TodoStore for RamTodoStore {
-    async fn get(&mut self) -> Result<Vec<Todo>, String> {
+    async fn get(&mut self) -> Result<Vec<Todo>, StoreError> {
         let mut pairs: Vec<(&Tdid, &Todo)> = self.todos.iter().collect();
 
         pairs.sort_by(|a, b| a.0.cmp(&b.0));
@@ -240,7 +376,7 @@ impl TodoStore for RamTodoStore {
         Ok(todos)
     }
 
-    async fn new(&mut self, content: String) -> Result<Todo, String> {
+    async fn new(&mut self, content: String) -> Result<Todo, StoreError> {
         let id = self.ntdid;
         self.ntdid += 1;
 
@@ -253,27 +389,38 @@ impl TodoStore for RamTodoStore {
         };
 
         self.todos.insert(id, todo.clone());
+        self.events
+            .send(StoreEvent::TodoCreated(self.tid, todo.clone()));
 
         Ok(todo)
     }
 
-    async fn update(&mut self, todo: Todo) -> Result<Todo, String> {
+    async fn update(&mut self, todo: Todo) -> Result<Todo, StoreError> {
         match self.todos.get_mut(&todo.id) {
             Some(val) => {
                 *val = todo.clone();
+                self.events
+                    .send(StoreEvent::TodoUpdated(self.tid, todo.clone()));
                 Ok(todo)
             }
-            None => Err("invalid pid".to_string()),
+            None => Err(StoreError::NotFound(format!("todo {}", todo.id))),
         }
     }
 
-    async fn done(&mut self, tdid: Tdid, done: bool) -> Result<Todo, String> {
+    async fn done(
+        &mut self,
+        tdid: Tdid,
+        done: bool,
+    ) -> Result<Todo, StoreError> {
         let Some(todo) = self.todos.get_mut(&tdid) else {
-            return Err("invalid tdid".to_string());
+            return Err(StoreError::NotFound(format!("todo {tdid}")));
         };
 
         if todo.done_at.is_some() == done {
-            return Err("invalid state".to_string());
+            let state = if done { "done" } else { "not done" };
+            return Err(StoreError::Conflict(format!(
+                "todo {tdid} is already {state}"
+            )));
         }
 
         todo.done_at = if done {
@@ -283,15 +430,18 @@ impl TodoStore for RamTodoStore {
         };
 
         let new = todo.clone();
+        self.events
+            .send(StoreEvent::TodoUpdated(self.tid, new.clone()));
 
         Ok(new)
     }
 
-    async fn delete(&mut self, tdid: Tdid) -> Result<(), String> {
+    async fn delete(&mut self, tdid: Tdid) -> Result<(), StoreError> {
         if let Some(_) = self.todos.remove(&tdid) {
+            self.events.send(StoreEvent::TodoDeleted(self.tid, tdid));
             Ok(())
         } else {
-            Err("invalid tdid".to_string())
+            Err(StoreError::NotFound(format!("todo {tdid}")))
         }
     }
 }
@@ -0,0 +1,309 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use sqlx::query::Query;
+use sqlx::{
+    ColumnIndex, Database, Decode, Encode, Executor, IntoArguments, Pool, Row,
+    Type,
+};
+use tokio::sync::Mutex;
+
+use timesman_type::{File, Post, Tag, Tid, Times, Todo, UserId};
+
+use super::attachment::{file_data, file_kind, file_type, omitted_type};
+use super::{EventSender, StoreError, StoreEvent, StoreEventStream};
+use super::{PostStore, Store, TimesStore, TodoStore};
+
+mod times;
+use times::SqlTimesStore;
+
+mod post;
+use post::SqlPostStore;
+
+mod todo;
+use todo::SqlTodoStore;
+
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                Self::AlreadyExists(db.message().to_string())
+            }
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                Self::Conflict(db.message().to_string())
+            }
+            sqlx::Error::Io(_) => Self::Io(e.to_string()),
+            sqlx::Error::ColumnDecode { .. } | sqlx::Error::Decode(_) => {
+                Self::Corrupt(e.to_string())
+            }
+            _ => Self::Backend(e.to_string()),
+        }
+    }
+}
+
+/// What differs between the databases the SQL stores keep their data in.
+/// The statements are shared: both SQLite and PostgreSQL take parameters
+/// numbered as `$1`, `RETURNING` and `ON CONFLICT`.
+pub trait Dialect: Database {
+    /// Reads the owner of a times, which only PostgreSQL has a type for.
+    fn owner(row: &Self::Row) -> Result<Option<UserId>, StoreError>;
+
+    /// Binds `owner` as the next parameter of `query`.
+    fn bind_owner<'q>(
+        query: Query<'q, Self, Self::Arguments<'q>>,
+        owner: Option<UserId>,
+    ) -> Query<'q, Self, Self::Arguments<'q>>;
+}
+
+/// A type that goes in and out of the columns of `DB` as it is.
+pub trait Column<DB: Database>:
+    Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>
+{
+}
+
+impl<DB: Database, T> Column<DB> for T where
+    T: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>
+{
+}
+
+/// Keeps everything in relational tables, with the same schema on every
+/// database. `SqliteStore` and `PostgresStore` make it.
+pub struct SqlStore<DB: Database> {
+    pub(crate) pool: Pool<DB>,
+    events: EventSender,
+}
+
+impl<DB: Database> SqlStore<DB> {
+    pub(crate) fn with_pool(pool: Pool<DB>) -> Self {
+        Self {
+            pool,
+            events: EventSender::new(),
+        }
+    }
+}
+
+fn id<R: Row>(row: &R, column: &str) -> Result<u64, StoreError>
+where
+    for<'a> &'a str: ColumnIndex<R>,
+    i64: Column<R::Database>,
+{
+    let id: i64 = row.try_get(column)?;
+    Ok(id as u64)
+}
+
+fn row_to_times<DB: Dialect>(row: DB::Row) -> Result<Times, StoreError>
+where
+    for<'a> &'a str: ColumnIndex<DB::Row>,
+    i64: Column<DB>,
+    String: Column<DB>,
+    NaiveDateTime: Column<DB>,
+{
+    Ok(Times {
+        id: id(&row, "id")?,
+        title: row.try_get("title")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        owner: DB::owner(&row)?,
+    })
+}
+
+fn row_to_tag<R: Row>(row: R) -> Result<Tag, StoreError>
+where
+    for<'a> &'a str: ColumnIndex<R>,
+    i64: Column<R::Database>,
+    String: Column<R::Database>,
+{
+    Ok(Tag {
+        id: id(&row, "id")?,
+        name: row.try_get("name")?,
+    })
+}
+
+/// Reads a post joined with its attachment and its data, if it has one.
+fn row_to_post<R: Row>(row: R) -> Result<Post, StoreError>
+where
+    for<'a> &'a str: ColumnIndex<R>,
+    i64: Column<R::Database>,
+    String: Column<R::Database>,
+    NaiveDateTime: Column<R::Database>,
+    Vec<u8>: Column<R::Database>,
+{
+    let name: Option<String> = row.try_get("file_name")?;
+
+    let file = match name {
+        None => None,
+        Some(name) => {
+            let kind: String = row.try_get("file_kind")?;
+            let data: Vec<u8> = row.try_get("file_data")?;
+            Some(File::new(name, file_type(&kind, data)?))
+        }
+    };
+
+    post_with_file(&row, file)
+}
+
+/// Reads a post joined with the name, kind and size of its attachment, if
+/// it has one, leaving the data out.
+fn row_to_post_info<R: Row>(row: R) -> Result<Post, StoreError>
+where
+    for<'a> &'a str: ColumnIndex<R>,
+    i64: Column<R::Database>,
+    String: Column<R::Database>,
+    NaiveDateTime: Column<R::Database>,
+{
+    let name: Option<String> = row.try_get("file_name")?;
+
+    let file = match name {
+        None => None,
+        Some(name) => {
+            let kind: String = row.try_get("file_kind")?;
+            let size: i64 = row.try_get("file_size")?;
+            Some(File::new(name, omitted_type(&kind, size as u64)?))
+        }
+    };
+
+    post_with_file(&row, file)
+}
+
+fn post_with_file<R: Row>(
+    row: &R,
+    file: Option<File>,
+) -> Result<Post, StoreError>
+where
+    for<'a> &'a str: ColumnIndex<R>,
+    i64: Column<R::Database>,
+    String: Column<R::Database>,
+    NaiveDateTime: Column<R::Database>,
+{
+    let tagid: Option<i64> = row.try_get("tagid")?;
+
+    Ok(Post {
+        id: id(row, "id")?,
+        post: row.try_get("post")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        file,
+        tag: tagid.map(|tagid| tagid as u64),
+    })
+}
+
+fn row_to_todo<R: Row>(row: R) -> Result<Todo, StoreError>
+where
+    for<'a> &'a str: ColumnIndex<R>,
+    i64: Column<R::Database>,
+    String: Column<R::Database>,
+    NaiveDateTime: Column<R::Database>,
+{
+    Ok(Todo {
+        id: id(&row, "id")?,
+        content: row.try_get("content")?,
+        detail: row.try_get("detail")?,
+        created_at: row.try_get("created_at")?,
+        done_at: row.try_get("done_at")?,
+    })
+}
+
+impl<DB> SqlStore<DB>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'a> &'a str: ColumnIndex<DB::Row>,
+    for<'q> &'q str: Encode<'q, DB>,
+    for<'q> &'q [u8]: Encode<'q, DB>,
+    str: Type<DB>,
+    [u8]: Type<DB>,
+    bool: Column<DB>,
+    i64: Column<DB>,
+    String: Column<DB>,
+    NaiveDateTime: Column<DB>,
+    Vec<u8>: Column<DB>,
+    Option<i64>: Column<DB>,
+    Option<String>: Column<DB>,
+    Option<NaiveDateTime>: Column<DB>,
+{
+    fn new_times_store(
+        &self,
+        tid: Tid,
+    ) -> Arc<Mutex<dyn TimesStore + Send + Sync>> {
+        Arc::new(Mutex::new(SqlTimesStore::new(
+            self.pool.clone(),
+            tid,
+            self.events.clone(),
+        )))
+    }
+}
+
+#[async_trait]
+impl<DB> Store for SqlStore<DB>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'a> &'a str: ColumnIndex<DB::Row>,
+    for<'q> &'q str: Encode<'q, DB>,
+    for<'q> &'q [u8]: Encode<'q, DB>,
+    str: Type<DB>,
+    [u8]: Type<DB>,
+    bool: Column<DB>,
+    i64: Column<DB>,
+    String: Column<DB>,
+    NaiveDateTime: Column<DB>,
+    Vec<u8>: Column<DB>,
+    Option<i64>: Column<DB>,
+    Option<String>: Column<DB>,
+    Option<NaiveDateTime>: Column<DB>,
+{
+    async fn check(&mut self) -> Result<(), StoreError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn get(
+        &mut self,
+    ) -> Result<Vec<Arc<Mutex<dyn TimesStore + Send + Sync>>>, StoreError> {
+        let rows = sqlx::query("SELECT id FROM times ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter()
+            .map(|row| Ok(self.new_times_store(id(row, "id")?)))
+            .collect()
+    }
+
+    async fn create(
+        &mut self,
+        title: String,
+        owner: Option<UserId>,
+    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, StoreError> {
+        let query = sqlx::query(
+            "INSERT INTO times (title, created_at, owner) VALUES ($1, $2, $3)
+            RETURNING *",
+        )
+        .bind(&title)
+        .bind(chrono::Utc::now().naive_local());
+        let row = DB::bind_owner(query, owner).fetch_one(&self.pool).await?;
+        let times = row_to_times::<DB>(row)?;
+
+        let tstore = self.new_times_store(times.id);
+        self.events.send(StoreEvent::TimesCreated(times));
+
+        Ok(tstore)
+    }
+
+    async fn delete(&mut self, tid: Tid) -> Result<(), StoreError> {
+        sqlx::query("DELETE FROM times WHERE id = $1 RETURNING id")
+            .bind(tid as i64)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| StoreError::NotFound(format!("times {tid}")))?;
+
+        self.events.send(StoreEvent::TimesDeleted(tid));
+        Ok(())
+    }
+
+    async fn subscribe(&mut self) -> Result<StoreEventStream, StoreError> {
+        Ok(self.events.subscribe())
+    }
+}
@@ -0,0 +1,429 @@
+use super::{async_trait, id, row_to_post, row_to_post_info, row_to_tag};
+use super::{file_data, file_kind, PostStore};
+use super::{Column, ColumnIndex, Dialect, Encode, Executor, IntoArguments};
+use super::{EventSender, StoreError, StoreEvent};
+use super::{File, Post, Tag, Tid};
+use super::{NaiveDateTime, Type};
+use super::{Pool, Row};
+use crate::PostQuery;
+use timesman_type::{Pid, TagId};
+
+/// Posts with the columns of their attachment, `NULL` for those without.
+const SELECT_POSTS: &str = "SELECT posts.*, attachments.name AS file_name,
+        attachments.kind AS file_kind, attachments.data AS file_data
+    FROM posts LEFT JOIN attachments ON attachments.pid = posts.id";
+
+/// Like `SELECT_POSTS`, with the size of the attachments instead of their
+/// data, for listing many posts.
+const SELECT_POST_INFOS: &str = "SELECT posts.*,
+        attachments.name AS file_name, attachments.kind AS file_kind,
+        CAST(LENGTH(attachments.data) AS BIGINT) AS file_size
+    FROM posts LEFT JOIN attachments ON attachments.pid = posts.id";
+
+pub struct SqlPostStore<DB: Dialect> {
+    pool: Pool<DB>,
+    tid: Tid,
+    events: EventSender,
+}
+
+impl<DB: Dialect> SqlPostStore<DB> {
+    pub fn new(pool: Pool<DB>, tid: Tid, events: EventSender) -> Self {
+        Self { pool, tid, events }
+    }
+}
+
+impl<DB> SqlPostStore<DB>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    i64: Column<DB>,
+{
+    async fn has_tag(&self, tagid: TagId) -> Result<bool, StoreError> {
+        let row = sqlx::query("SELECT 1 FROM tags WHERE tid = $1 AND id = $2")
+            .bind(self.tid as i64)
+            .bind(tagid as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+}
+
+#[async_trait]
+impl<DB> PostStore for SqlPostStore<DB>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'a> &'a str: ColumnIndex<DB::Row>,
+    for<'q> &'q str: Encode<'q, DB>,
+    for<'q> &'q [u8]: Encode<'q, DB>,
+    str: Type<DB>,
+    [u8]: Type<DB>,
+    bool: Column<DB>,
+    i64: Column<DB>,
+    String: Column<DB>,
+    NaiveDateTime: Column<DB>,
+    Vec<u8>: Column<DB>,
+    Option<i64>: Column<DB>,
+    Option<String>: Column<DB>,
+    Option<NaiveDateTime>: Column<DB>,
+{
+    async fn get(&mut self, pid: Pid) -> Result<Post, StoreError> {
+        let row = sqlx::query(&format!(
+            "{SELECT_POSTS} WHERE posts.tid = $1 AND posts.id = $2"
+        ))
+        .bind(self.tid as i64)
+        .bind(pid as i64)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| StoreError::NotFound(format!("post {pid}")))?;
+
+        row_to_post(row)
+    }
+
+    async fn get_all(&mut self) -> Result<Vec<Post>, StoreError> {
+        self.list(&PostQuery::default()).await
+    }
+
+    async fn list(
+        &mut self,
+        query: &PostQuery,
+    ) -> Result<Vec<Post>, StoreError> {
+        let mut sql = format!("{SELECT_POST_INFOS} WHERE posts.tid = $1");
+        let mut n = 1;
+        if query.since.is_some() {
+            n += 1;
+            sql += &format!(" AND posts.created_at >= ${n}");
+        }
+        if query.until.is_some() {
+            n += 1;
+            sql += &format!(" AND posts.created_at < ${n}");
+        }
+        if query.after.is_some() {
+            let op = if query.newest_first { "<" } else { ">" };
+            sql += &format!(
+                " AND (posts.created_at, posts.id) {op} (${}, ${})",
+                n + 1,
+                n + 2
+            );
+            n += 2;
+        }
+        let order = if query.newest_first { "DESC" } else { "ASC" };
+        sql += &format!(" ORDER BY posts.created_at {order}, posts.id {order}");
+        if query.limit.is_some() {
+            n += 1;
+            sql += &format!(" LIMIT ${n}");
+        }
+
+        let mut select = sqlx::query(&sql).bind(self.tid as i64);
+        if let Some(since) = query.since {
+            select = select.bind(since);
+        }
+        if let Some(until) = query.until {
+            select = select.bind(until);
+        }
+        if let Some((created_at, pid)) = query.after {
+            select = select.bind(created_at).bind(pid as i64);
+        }
+        if let Some(limit) = query.limit {
+            select = select.bind(limit as i64);
+        }
+
+        select
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(row_to_post_info)
+            .collect()
+    }
+
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, StoreError> {
+        sqlx::query("SELECT * FROM tags WHERE tid = $1 ORDER BY id")
+            .bind(self.tid as i64)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(row_to_tag)
+            .collect()
+    }
+
+    async fn create_tag(&mut self, name: String) -> Result<Tag, StoreError> {
+        let row = sqlx::query(
+            "INSERT INTO tags (tid, name) VALUES ($1, $2) RETURNING *",
+        )
+        .bind(self.tid as i64)
+        .bind(&name)
+        .fetch_one(&self.pool)
+        .await?;
+        let tag = row_to_tag(row)?;
+
+        self.events
+            .send(StoreEvent::TagCreated(self.tid, tag.clone()));
+
+        Ok(tag)
+    }
+
+    async fn rename_tag(
+        &mut self,
+        tagid: TagId,
+        name: String,
+    ) -> Result<Tag, StoreError> {
+        let row = sqlx::query(
+            "UPDATE tags SET name = $1 WHERE tid = $2 AND id = $3 RETURNING *",
+        )
+        .bind(&name)
+        .bind(self.tid as i64)
+        .bind(tagid as i64)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| StoreError::NotFound(format!("tag {tagid}")))?;
+        let tag = row_to_tag(row)?;
+
+        self.events
+            .send(StoreEvent::TagUpdated(self.tid, tag.clone()));
+
+        Ok(tag)
+    }
+
+    async fn delete_tag(&mut self, tagid: TagId) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        // The posts lose the tag through the foreign key.
+        let pids =
+            sqlx::query("SELECT id FROM posts WHERE tid = $1 AND tagid = $2")
+                .bind(self.tid as i64)
+                .bind(tagid as i64)
+                .fetch_all(&mut *tx)
+                .await?
+                .iter()
+                .map(|row| id(row, "id"))
+                .collect::<Result<Vec<_>, _>>()?;
+
+        sqlx::query("DELETE FROM tags WHERE tid = $1 AND id = $2 RETURNING id")
+            .bind(self.tid as i64)
+            .bind(tagid as i64)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| StoreError::NotFound(format!("tag {tagid}")))?;
+
+        tx.commit().await?;
+
+        for pid in pids {
+            let post = self.get(pid).await?;
+            self.events.send(StoreEvent::PostUpdated(self.tid, post));
+        }
+        self.events.send(StoreEvent::TagDeleted(self.tid, tagid));
+
+        Ok(())
+    }
+
+    async fn assign_tag(
+        &mut self,
+        pid: Pid,
+        tagid: Option<TagId>,
+    ) -> Result<Post, StoreError> {
+        if let Some(tagid) = tagid {
+            if !self.has_tag(tagid).await? {
+                return Err(StoreError::NotFound(format!("tag {tagid}")));
+            }
+        }
+
+        sqlx::query(
+            "UPDATE posts SET tagid = $1 WHERE tid = $2 AND id = $3
+            RETURNING id",
+        )
+        .bind(tagid.map(|tagid| tagid as i64))
+        .bind(self.tid as i64)
+        .bind(pid as i64)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| StoreError::NotFound(format!("post {pid}")))?;
+
+        let post = self.get(pid).await?;
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+
+    async fn post(
+        &mut self,
+        post: String,
+        file: Option<File>,
+    ) -> Result<Post, StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        // PostgreSQL keeps microseconds, so take the time it stored.
+        let row = sqlx::query(
+            "INSERT INTO posts (tid, post, created_at) VALUES ($1, $2, $3)
+            RETURNING id, created_at",
+        )
+        .bind(self.tid as i64)
+        .bind(&post)
+        .bind(chrono::Utc::now().naive_local())
+        .fetch_one(&mut *tx)
+        .await?;
+        let pid = id(&row, "id")?;
+        let created_at = row.try_get("created_at")?;
+
+        if let Some(file) = &file {
+            sqlx::query(
+                "INSERT INTO attachments (pid, name, kind, data)
+                VALUES ($1, $2, $3, $4)",
+            )
+            .bind(pid as i64)
+            .bind(&file.name)
+            .bind(file_kind(&file.ftype))
+            .bind(file_data(&file.ftype)?)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        let post = Post {
+            id: pid,
+            post,
+            created_at,
+            updated_at: None,
+            file,
+            tag: None,
+        };
+        self.events
+            .send(StoreEvent::PostCreated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+
+    async fn delete(&mut self, pid: Pid) -> Result<(), StoreError> {
+        sqlx::query(
+            "DELETE FROM posts WHERE tid = $1 AND id = $2 RETURNING id",
+        )
+        .bind(self.tid as i64)
+        .bind(pid as i64)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| StoreError::NotFound(format!("post {pid}")))?;
+
+        self.events.send(StoreEvent::PostDeleted(self.tid, pid));
+
+        Ok(())
+    }
+
+    async fn update(&mut self, mut post: Post) -> Result<Post, StoreError> {
+        if let Some(tagid) = post.tag {
+            if !self.has_tag(tagid).await? {
+                return Err(StoreError::NotFound(format!("tag {tagid}")));
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query(
+            "UPDATE posts SET post = $1, created_at = $2, updated_at = $3,
+                tagid = $4
+            WHERE tid = $5 AND id = $6
+            RETURNING created_at, updated_at",
+        )
+        .bind(&post.post)
+        .bind(post.created_at)
+        .bind(chrono::Utc::now().naive_local())
+        .bind(post.tag.map(|tagid| tagid as i64))
+        .bind(self.tid as i64)
+        .bind(post.id as i64)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| StoreError::NotFound(format!("post {}", post.id)))?;
+        post.created_at = row.try_get("created_at")?;
+        post.updated_at = row.try_get("updated_at")?;
+
+        match &post.file {
+            // Only the name changes when the data was left out.
+            Some(file) if !file.has_data() => {
+                sqlx::query("UPDATE attachments SET name = $1 WHERE pid = $2")
+                    .bind(&file.name)
+                    .bind(post.id as i64)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            Some(file) => {
+                sqlx::query(
+                    "INSERT INTO attachments (pid, name, kind, data)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (pid) DO UPDATE SET name = EXCLUDED.name,
+                        kind = EXCLUDED.kind, data = EXCLUDED.data",
+                )
+                .bind(post.id as i64)
+                .bind(&file.name)
+                .bind(file_kind(&file.ftype))
+                .bind(file_data(&file.ftype)?)
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM attachments WHERE pid = $1")
+                    .bind(post.id as i64)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        tx.commit().await?;
+
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+
+    async fn edit(
+        &mut self,
+        pid: Pid,
+        text: String,
+        tag: Option<Option<TagId>>,
+    ) -> Result<Post, StoreError> {
+        if let Some(Some(tagid)) = tag {
+            if !self.has_tag(tagid).await? {
+                return Err(StoreError::NotFound(format!("tag {tagid}")));
+            }
+        }
+
+        let mut sql = "UPDATE posts SET post = $1, updated_at = $2".to_string();
+        let mut n = 2;
+        if tag.is_some() {
+            n += 1;
+            sql.push_str(&format!(", tagid = ${n}"));
+        }
+        sql.push_str(&format!(
+            " WHERE tid = ${} AND id = ${} RETURNING id",
+            n + 1,
+            n + 2
+        ));
+
+        let mut query = sqlx::query(&sql)
+            .bind(&text)
+            .bind(chrono::Utc::now().naive_local());
+        if let Some(tag) = tag {
+            query = query.bind(tag.map(|tagid| tagid as i64));
+        }
+        query
+            .bind(self.tid as i64)
+            .bind(pid as i64)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| StoreError::NotFound(format!("post {pid}")))?;
+
+        let row = sqlx::query(&format!(
+            "{SELECT_POST_INFOS} WHERE posts.tid = $1 AND posts.id = $2"
+        ))
+        .bind(self.tid as i64)
+        .bind(pid as i64)
+        .fetch_one(&self.pool)
+        .await?;
+        let post = row_to_post_info(row)?;
+
+        self.events
+            .send(StoreEvent::PostUpdated(self.tid, post.clone()));
+
+        Ok(post)
+    }
+}
@@ -0,0 +1,95 @@
+use super::{async_trait, row_to_times, Arc, Mutex, Pool};
+use super::{Column, ColumnIndex, Dialect, Encode, Executor, IntoArguments};
+use super::{EventSender, StoreError, StoreEvent};
+use super::{NaiveDateTime, Type};
+use super::{PostStore, TimesStore, TodoStore};
+use super::{SqlPostStore, SqlTodoStore, Tid, Times};
+
+pub struct SqlTimesStore<DB: Dialect> {
+    pool: Pool<DB>,
+    tid: Tid,
+    events: EventSender,
+}
+
+impl<DB: Dialect> SqlTimesStore<DB> {
+    pub fn new(pool: Pool<DB>, tid: Tid, events: EventSender) -> Self {
+        Self { pool, tid, events }
+    }
+}
+
+#[async_trait]
+impl<DB> TimesStore for SqlTimesStore<DB>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'a> &'a str: ColumnIndex<DB::Row>,
+    for<'q> &'q str: Encode<'q, DB>,
+    for<'q> &'q [u8]: Encode<'q, DB>,
+    str: Type<DB>,
+    [u8]: Type<DB>,
+    bool: Column<DB>,
+    i64: Column<DB>,
+    String: Column<DB>,
+    NaiveDateTime: Column<DB>,
+    Vec<u8>: Column<DB>,
+    Option<i64>: Column<DB>,
+    Option<String>: Column<DB>,
+    Option<NaiveDateTime>: Column<DB>,
+{
+    async fn get(&mut self) -> Result<Times, StoreError> {
+        let row = sqlx::query("SELECT * FROM times WHERE id = $1")
+            .bind(self.tid as i64)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| {
+                StoreError::NotFound(format!("times {}", self.tid))
+            })?;
+
+        row_to_times::<DB>(row)
+    }
+
+    async fn update(&mut self, times: Times) -> Result<Times, StoreError> {
+        let query = sqlx::query(
+            "UPDATE times SET title = $1, created_at = $2, updated_at = $3,
+                owner = $4
+            WHERE id = $5
+            RETURNING *",
+        )
+        .bind(&times.title)
+        .bind(times.created_at)
+        .bind(chrono::Utc::now().naive_local());
+        let row = DB::bind_owner(query, times.owner)
+            .bind(self.tid as i64)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| {
+                StoreError::NotFound(format!("times {}", self.tid))
+            })?;
+        let times = row_to_times::<DB>(row)?;
+
+        self.events.send(StoreEvent::TimesUpdated(times.clone()));
+
+        Ok(times)
+    }
+
+    async fn pstore(
+        &mut self,
+    ) -> Result<Arc<Mutex<dyn PostStore + Send + Sync>>, StoreError> {
+        Ok(Arc::new(Mutex::new(SqlPostStore::new(
+            self.pool.clone(),
+            self.tid,
+            self.events.clone(),
+        ))))
+    }
+
+    async fn tdstore(
+        &mut self,
+    ) -> Result<Arc<Mutex<dyn TodoStore + Send + Sync>>, StoreError> {
+        Ok(Arc::new(Mutex::new(SqlTodoStore::new(
+            self.pool.clone(),
+            self.tid,
+            self.events.clone(),
+        ))))
+    }
+}
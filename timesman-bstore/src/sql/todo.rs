@@ -0,0 +1,149 @@
+use super::{async_trait, row_to_todo, Pool, TodoStore};
+use super::{Column, ColumnIndex, Dialect, Encode, Executor, IntoArguments};
+use super::{EventSender, StoreError, StoreEvent};
+use super::{NaiveDateTime, Type};
+use super::{Tid, Todo};
+use timesman_type::Tdid;
+
+pub struct SqlTodoStore<DB: Dialect> {
+    pool: Pool<DB>,
+    tid: Tid,
+    events: EventSender,
+}
+
+impl<DB: Dialect> SqlTodoStore<DB> {
+    pub fn new(pool: Pool<DB>, tid: Tid, events: EventSender) -> Self {
+        Self { pool, tid, events }
+    }
+}
+
+#[async_trait]
+impl<DB> TodoStore for SqlTodoStore<DB>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'a> &'a str: ColumnIndex<DB::Row>,
+    for<'q> &'q str: Encode<'q, DB>,
+    for<'q> &'q [u8]: Encode<'q, DB>,
+    str: Type<DB>,
+    [u8]: Type<DB>,
+    bool: Column<DB>,
+    i64: Column<DB>,
+    String: Column<DB>,
+    NaiveDateTime: Column<DB>,
+    Vec<u8>: Column<DB>,
+    Option<i64>: Column<DB>,
+    Option<String>: Column<DB>,
+    Option<NaiveDateTime>: Column<DB>,
+{
+    async fn get(&mut self) -> Result<Vec<Todo>, StoreError> {
+        sqlx::query("SELECT * FROM todos WHERE tid = $1 ORDER BY id")
+            .bind(self.tid as i64)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(row_to_todo)
+            .collect()
+    }
+
+    async fn new(&mut self, content: String) -> Result<Todo, StoreError> {
+        let row = sqlx::query(
+            "INSERT INTO todos (tid, content, created_at) VALUES ($1, $2, $3)
+            RETURNING *",
+        )
+        .bind(self.tid as i64)
+        .bind(&content)
+        .bind(chrono::Utc::now().naive_local())
+        .fetch_one(&self.pool)
+        .await?;
+        let todo = row_to_todo(row)?;
+
+        self.events
+            .send(StoreEvent::TodoCreated(self.tid, todo.clone()));
+
+        Ok(todo)
+    }
+
+    async fn done(
+        &mut self,
+        tdid: Tdid,
+        done: bool,
+    ) -> Result<Todo, StoreError> {
+        let done_at = done.then(|| chrono::Utc::now().naive_local());
+
+        // Only a todo in the other state changes.
+        let row = sqlx::query(
+            "UPDATE todos SET done_at = $1
+            WHERE tid = $2 AND id = $3 AND (done_at IS NULL) = $4
+            RETURNING *",
+        )
+        .bind(done_at)
+        .bind(self.tid as i64)
+        .bind(tdid as i64)
+        .bind(done)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            let exists =
+                sqlx::query("SELECT 1 FROM todos WHERE tid = $1 AND id = $2")
+                    .bind(self.tid as i64)
+                    .bind(tdid as i64)
+                    .fetch_optional(&self.pool)
+                    .await?;
+            if exists.is_none() {
+                return Err(StoreError::NotFound(format!("todo {tdid}")));
+            }
+            let state = if done { "done" } else { "not done" };
+            return Err(StoreError::Conflict(format!(
+                "todo {tdid} is already {state}"
+            )));
+        };
+        let todo = row_to_todo(row)?;
+
+        self.events
+            .send(StoreEvent::TodoUpdated(self.tid, todo.clone()));
+
+        Ok(todo)
+    }
+
+    async fn update(&mut self, todo: Todo) -> Result<Todo, StoreError> {
+        let row = sqlx::query(
+            "UPDATE todos SET content = $1, detail = $2, created_at = $3,
+                done_at = $4
+            WHERE tid = $5 AND id = $6
+            RETURNING *",
+        )
+        .bind(&todo.content)
+        .bind(todo.detail.clone())
+        .bind(todo.created_at)
+        .bind(todo.done_at)
+        .bind(self.tid as i64)
+        .bind(todo.id as i64)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| StoreError::NotFound(format!("todo {}", todo.id)))?;
+        let todo = row_to_todo(row)?;
+
+        self.events
+            .send(StoreEvent::TodoUpdated(self.tid, todo.clone()));
+
+        Ok(todo)
+    }
+
+    async fn delete(&mut self, tdid: Tdid) -> Result<(), StoreError> {
+        sqlx::query(
+            "DELETE FROM todos WHERE tid = $1 AND id = $2 RETURNING id",
+        )
+        .bind(self.tid as i64)
+        .bind(tdid as i64)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| StoreError::NotFound(format!("todo {tdid}")))?;
+
+        self.events.send(StoreEvent::TodoDeleted(self.tid, tdid));
+
+        Ok(())
+    }
+}
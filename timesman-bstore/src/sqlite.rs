@@ -0,0 +1,229 @@
+use std::str::FromStr;
+
+use sqlx::query::Query;
+use sqlx::sqlite::{
+    Sqlite, SqliteArguments, SqliteConnectOptions, SqliteJournalMode,
+    SqlitePoolOptions, SqliteRow,
+};
+use sqlx::Row;
+
+use timesman_type::UserId;
+
+use super::sql::{Dialect, SqlStore};
+use super::StoreError;
+
+/// Posts and todos belong to a times and go with it. Attachments go with
+/// their post.
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS times (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        title TEXT NOT NULL,
+        created_at DATETIME NOT NULL,
+        updated_at DATETIME,
+        owner TEXT
+    )",
+    "CREATE TABLE IF NOT EXISTS tags (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        tid INTEGER NOT NULL REFERENCES times(id) ON DELETE CASCADE,
+        name TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS tags_tid ON tags(tid)",
+    "CREATE TABLE IF NOT EXISTS posts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        tid INTEGER NOT NULL REFERENCES times(id) ON DELETE CASCADE,
+        post TEXT NOT NULL,
+        created_at DATETIME NOT NULL,
+        updated_at DATETIME,
+        tagid INTEGER REFERENCES tags(id) ON DELETE SET NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS posts_tid_created_at
+        ON posts(tid, created_at)",
+    "CREATE INDEX IF NOT EXISTS posts_tagid ON posts(tagid)",
+    "CREATE TABLE IF NOT EXISTS attachments (
+        pid INTEGER PRIMARY KEY REFERENCES posts(id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        data BLOB NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS todos (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        tid INTEGER NOT NULL REFERENCES times(id) ON DELETE CASCADE,
+        content TEXT NOT NULL,
+        detail TEXT,
+        created_at DATETIME NOT NULL,
+        done_at DATETIME
+    )",
+    "CREATE INDEX IF NOT EXISTS todos_tid ON todos(tid)",
+];
+
+/// Keeps everything in relational tables of a SQLite database, so that
+/// several connections can share it.
+pub type SqliteStore = SqlStore<Sqlite>;
+
+impl SqliteStore {
+    /// Opens (and creates if needed) the database at `path`. `:memory:`
+    /// gives a private in-memory database.
+    pub async fn new(path: &str) -> Result<Self, StoreError> {
+        let options = SqliteConnectOptions::from_str(path)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal);
+
+        // Every connection to an in-memory database sees a different
+        // database, so keep a single one.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(if path == ":memory:" { 1 } else { 4 })
+            .connect_with(options)
+            .await?;
+
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+
+        Ok(Self::with_pool(pool))
+    }
+}
+
+/// Owners are kept as text.
+impl Dialect for Sqlite {
+    fn owner(row: &SqliteRow) -> Result<Option<UserId>, StoreError> {
+        let owner: Option<String> = row.try_get("owner")?;
+        owner
+            .map(|owner| {
+                owner.parse().map_err(|e| {
+                    StoreError::Corrupt(format!("Invalid owner {owner}: {e}"))
+                })
+            })
+            .transpose()
+    }
+
+    fn bind_owner<'q>(
+        query: Query<'q, Self, SqliteArguments<'q>>,
+        owner: Option<UserId>,
+    ) -> Query<'q, Self, SqliteArguments<'q>> {
+        query.bind(owner.map(|owner| owner.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Store;
+    use timesman_type::{File, FileType};
+
+    #[tokio::test]
+    async fn test_data_survives_reopen() {
+        let path = std::env::temp_dir()
+            .join(format!("timesman-store-{}.db", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        let file = File::new(
+            "notes.txt".to_string(),
+            FileType::Text("hello".to_string()),
+        );
+
+        let (tid, post) = {
+            let mut store = SqliteStore::new(path).await.unwrap();
+            let tstore =
+                store.create("reopen".to_string(), None).await.unwrap();
+            let mut tstore = tstore.lock().await;
+            let tid = tstore.get().await.unwrap().id;
+            let pstore = tstore.pstore().await.unwrap();
+            let mut pstore = pstore.lock().await;
+            let post = pstore
+                .post("with file".to_string(), Some(file.clone()))
+                .await
+                .unwrap();
+            store.pool.close().await;
+            (tid, post)
+        };
+
+        let mut store = SqliteStore::new(path).await.unwrap();
+        let tstores = store.get().await.unwrap();
+        assert_eq!(tstores.len(), 1);
+        let mut tstore = tstores[0].lock().await;
+        assert_eq!(tstore.get().await.unwrap().id, tid);
+
+        let pstore = tstore.pstore().await.unwrap();
+        let stored = pstore.lock().await.get(post.id).await.unwrap();
+        assert_eq!(stored, post);
+        assert_eq!(stored.file, Some(file));
+
+        store.pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_deleting_times_deletes_its_items() {
+        let mut store = SqliteStore::new(":memory:").await.unwrap();
+        let tstore = store.create("doomed".to_string(), None).await.unwrap();
+        let mut tstore = tstore.lock().await;
+        let tid = tstore.get().await.unwrap().id;
+
+        let pstore = tstore.pstore().await.unwrap();
+        let post = pstore
+            .lock()
+            .await
+            .post("gone".to_string(), None)
+            .await
+            .unwrap();
+        let tdstore = tstore.tdstore().await.unwrap();
+        tdstore.lock().await.new("gone".to_string()).await.unwrap();
+
+        store.delete(tid).await.unwrap();
+        assert!(matches!(tstore.get().await, Err(StoreError::NotFound(_))));
+        assert!(matches!(
+            pstore.lock().await.get(post.id).await,
+            Err(StoreError::NotFound(_))
+        ));
+        assert!(tdstore.lock().await.get().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_listing_leaves_file_data_out() {
+        let mut store = SqliteStore::new(":memory:").await.unwrap();
+        let tstore = store.create("files".to_string(), None).await.unwrap();
+        let pstore = tstore.lock().await.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+        let file =
+            File::new("photo.png".to_string(), FileType::Image(vec![7; 16]));
+        let post = pstore
+            .post("photo".to_string(), Some(file.clone()))
+            .await
+            .unwrap();
+
+        let posts = pstore.get_all().await.unwrap();
+        assert_eq!(posts[0].file, Some(file.info()));
+
+        // Saving the listed post keeps the data.
+        let mut listed = posts[0].clone();
+        listed.post = "renamed".to_string();
+        pstore.update(listed).await.unwrap();
+        let stored = pstore.get(post.id).await.unwrap();
+        assert_eq!(stored.post, "renamed");
+        assert_eq!(stored.file, Some(file));
+    }
+
+    #[tokio::test]
+    async fn test_update_refuses_tag_of_other_times() {
+        let mut store = SqliteStore::new(":memory:").await.unwrap();
+        let other = store.create("other".to_string(), None).await.unwrap();
+        let other = other.lock().await.pstore().await.unwrap();
+        let tag = other
+            .lock()
+            .await
+            .create_tag("theirs".to_string())
+            .await
+            .unwrap();
+
+        let tstore = store.create("mine".to_string(), None).await.unwrap();
+        let pstore = tstore.lock().await.pstore().await.unwrap();
+        let mut pstore = pstore.lock().await;
+        let mut post = pstore.post("post".to_string(), None).await.unwrap();
+        post.tag = Some(tag.id);
+
+        assert!(matches!(
+            pstore.update(post.clone()).await,
+            Err(StoreError::NotFound(_))
+        ));
+        assert_eq!(pstore.get(post.id).await.unwrap().tag, None);
+    }
+}
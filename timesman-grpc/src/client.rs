@@ -0,0 +1,471 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::Channel;
+use tonic::{Code, Response, Status};
+
+use crate::grpc;
+use crate::grpc::times_man_client::TimesManClient;
+use crate::tls::{self, ClientTls};
+
+/// Sessions are renewed when less than this is left before they expire.
+const RENEW_MARGIN: Duration = Duration::from_secs(30);
+
+/// How often `call_idempotent` tries before giving up.
+const MAX_ATTEMPTS: u32 = 4;
+
+/// The wait before the first retry. It doubles with every retry.
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// The environment variable `Credentials::from_env` reads a token from.
+pub const TOKEN_ENV: &str = "TIMESMAN_TOKEN";
+
+/// How a client proves who it is.
+#[derive(Clone)]
+pub enum Credentials {
+    /// An access token or personal access token, sent as it is.
+    Token(String),
+    /// Logged in with, and again whenever the session can no longer be
+    /// refreshed. Accounts with TOTP need `Client::login` and
+    /// `Client::verify_totp` instead.
+    Password { username: String, password: String },
+}
+
+impl Credentials {
+    /// The token in `TIMESMAN_TOKEN`, if it is set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var(TOKEN_ENV).ok().map(Self::Token)
+    }
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Token(_) => write!(f, "Token(..)"),
+            Self::Password { username, .. } => {
+                write!(f, "Password {{ username: {username:?}, .. }}")
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    header: Option<MetadataValue<Ascii>>,
+    refresh_token: Option<String>,
+    expires_at: Option<Instant>,
+    password: Option<(String, String)>,
+    /// Bumped whenever the tokens change, so that concurrent calls that
+    /// failed with the same token renew the session only once.
+    generation: u64,
+}
+
+impl State {
+    fn can_renew(&self) -> bool {
+        self.refresh_token.is_some() || self.password.is_some()
+    }
+}
+
+#[derive(Default)]
+struct Session {
+    state: std::sync::Mutex<State>,
+    renewing: tokio::sync::Mutex<()>,
+}
+
+impl Session {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn set_token(&self, header: MetadataValue<Ascii>) {
+        let mut state = self.state();
+        *state = State {
+            header: Some(header),
+            generation: state.generation + 1,
+            ..Default::default()
+        };
+    }
+
+    fn signed_in(
+        &self,
+        header: MetadataValue<Ascii>,
+        response: &grpc::AuthResponse,
+    ) {
+        let mut state = self.state();
+        state.header = Some(header);
+        state.refresh_token = Some(response.refresh_token.clone())
+            .filter(|token| !token.is_empty());
+        state.expires_at = (response.expires_in > 0)
+            .then(|| Instant::now() + Duration::from_secs(response.expires_in));
+        state.generation += 1;
+    }
+}
+
+/// Adds `authorization: Bearer <token>` to every request, with the token
+/// the client currently holds.
+#[derive(Clone)]
+pub struct AuthInterceptor {
+    session: Arc<Session>,
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, Status> {
+        if let Some(header) = &self.session.state().header {
+            request
+                .metadata_mut()
+                .insert("authorization", header.clone());
+        }
+        Ok(request)
+    }
+}
+
+pub type AuthedClient =
+    TimesManClient<InterceptedService<Channel, AuthInterceptor>>;
+
+fn bearer(token: &str) -> Result<MetadataValue<Ascii>, String> {
+    format!("Bearer {token}")
+        .parse()
+        .map_err(|_| "The token is not a valid header value".to_string())
+}
+
+/// The codes worth trying an idempotent call again for.
+fn retryable(code: Code) -> bool {
+    matches!(code, Code::Unavailable | Code::DeadlineExceeded)
+}
+
+/// A TimesMan client that keeps a session. It attaches the token to each
+/// call, and refreshes the session, or logs in again, when the token runs
+/// out. Clones share the session.
+#[derive(Clone)]
+pub struct Client {
+    channel: Channel,
+    session: Arc<Session>,
+}
+
+impl Client {
+    /// Connects to `url`, without a session yet. `tls` is as for
+    /// `tls::connect`.
+    pub async fn connect(
+        url: String,
+        tls: Option<&ClientTls>,
+    ) -> Result<Self, String> {
+        let channel = tls::connect(url, tls).await?;
+        Ok(Self::from_channel(channel))
+    }
+
+    pub fn from_channel(channel: Channel) -> Self {
+        Self {
+            channel,
+            session: Arc::default(),
+        }
+    }
+
+    /// Starts a session with `credentials`.
+    pub async fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Result<(), String> {
+        match credentials {
+            Credentials::Token(token) => self.set_token(&token),
+            Credentials::Password { username, password } => {
+                let response = self
+                    .login(username, password)
+                    .await
+                    .map_err(|e| e.message().to_string())?;
+                if response.totp_challenge.is_empty() {
+                    Ok(())
+                } else {
+                    Err("The account needs a TOTP code".to_string())
+                }
+            }
+        }
+    }
+
+    /// Uses `token` from now on. It cannot be renewed.
+    pub fn set_token(&self, token: &str) -> Result<(), String> {
+        self.session.set_token(bearer(token)?);
+        Ok(())
+    }
+
+    /// Logs in. If the response has a `totp_challenge`, finish with
+    /// `verify_totp`; otherwise the password is kept to log in again when
+    /// the session cannot be refreshed.
+    pub async fn login(
+        &self,
+        username: String,
+        password: String,
+    ) -> Result<grpc::AuthResponse, Status> {
+        let request = grpc::LoginRequest {
+            username: username.clone(),
+            password: password.clone(),
+        };
+        let response = self.unauthed().login(request).await?.into_inner();
+
+        if response.totp_challenge.is_empty() {
+            self.signed_in(&response).await?;
+            self.session.state().password = Some((username, password));
+        }
+        Ok(response)
+    }
+
+    /// Completes a login that returned a `totp_challenge`.
+    pub async fn verify_totp(
+        &self,
+        challenge: String,
+        code: String,
+    ) -> Result<grpc::AuthResponse, Status> {
+        let request = grpc::VerifyTotpRequest { challenge, code };
+        let response = self.unauthed().verify_totp(request).await?.into_inner();
+        self.signed_in(&response).await?;
+        Ok(response)
+    }
+
+    /// Creates an account and keeps it logged in.
+    pub async fn register(
+        &self,
+        username: String,
+        email: String,
+        password: String,
+    ) -> Result<grpc::AuthResponse, Status> {
+        let request = grpc::RegisterRequest {
+            username: username.clone(),
+            email,
+            password: password.clone(),
+        };
+        let response = self.unauthed().register(request).await?.into_inner();
+
+        self.signed_in(&response).await?;
+        self.session.state().password = Some((username, password));
+        Ok(response)
+    }
+
+    /// Ends the session on the server and forgets it.
+    pub async fn logout(&self) -> Result<(), Status> {
+        let refresh_token = self.session.state().refresh_token.clone();
+        let request = grpc::LogoutRequest { refresh_token };
+        self.times_man().logout(request).await?;
+
+        let mut state = self.session.state();
+        *state = State {
+            generation: state.generation + 1,
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    /// A client that sends the current token. Calls made with it are not
+    /// renewed or retried.
+    pub fn times_man(&self) -> AuthedClient {
+        let interceptor = AuthInterceptor {
+            session: self.session.clone(),
+        };
+        TimesManClient::with_interceptor(self.channel.clone(), interceptor)
+    }
+
+    /// Makes a call with `f`. If the session ran out, it is renewed and
+    /// the call made once more.
+    ///
+    /// ```ignore
+    /// let times = client
+    ///     .call((), |mut c, r| async move { c.get_times(r).await })
+    ///     .await?;
+    /// ```
+    pub async fn call<Req, T, F, Fut>(
+        &self,
+        request: Req,
+        f: F,
+    ) -> Result<T, Status>
+    where
+        Req: Clone,
+        F: Fn(AuthedClient, Req) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        let generation = self.renew_if_expiring().await;
+
+        let result = match f(self.times_man(), request.clone()).await {
+            Err(status)
+                if status.code() == Code::Unauthenticated
+                    && self.session.state().can_renew() =>
+            {
+                self.renew(generation).await?;
+                f(self.times_man(), request).await
+            }
+            result => result,
+        };
+
+        result.map(Response::into_inner)
+    }
+
+    /// Like `call`, but also tries again with backoff while the server is
+    /// unreachable. Only for calls that can safely be made twice.
+    pub async fn call_idempotent<Req, T, F, Fut>(
+        &self,
+        request: Req,
+        f: F,
+    ) -> Result<T, Status>
+    where
+        Req: Clone,
+        F: Fn(AuthedClient, Req) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        let mut attempt = 1;
+        let mut delay = FIRST_RETRY_DELAY;
+
+        loop {
+            match self.call(request.clone(), &f).await {
+                Err(status)
+                    if attempt < MAX_ATTEMPTS && retryable(status.code()) =>
+                {
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn unauthed(&self) -> TimesManClient<Channel> {
+        TimesManClient::new(self.channel.clone())
+    }
+
+    async fn signed_in(
+        &self,
+        response: &grpc::AuthResponse,
+    ) -> Result<(), Status> {
+        let header = bearer(&response.access_token)
+            .map_err(|e| Status::new(Code::Internal, e))?;
+        self.session.signed_in(header, response);
+        Ok(())
+    }
+
+    /// Renews the session ahead of time if it is about to expire, and
+    /// returns the generation of the tokens to make the call with.
+    async fn renew_if_expiring(&self) -> u64 {
+        let (generation, expiring) = {
+            let state = self.session.state();
+            let expiring = state.can_renew()
+                && state.expires_at.is_some_and(|expires_at| {
+                    expires_at.saturating_duration_since(Instant::now())
+                        < RENEW_MARGIN
+                });
+            (state.generation, expiring)
+        };
+
+        // If it fails, the token may still do; the call will tell.
+        if expiring && self.renew(generation).await.is_err() {
+            return generation;
+        }
+        self.session.state().generation
+    }
+
+    /// Gets new tokens with the refresh token or else the password, unless
+    /// another call already did since `generation`.
+    async fn renew(&self, generation: u64) -> Result<(), Status> {
+        let _renewing = self.session.renewing.lock().await;
+
+        let (refresh_token, password) = {
+            let state = self.session.state();
+            if state.generation != generation {
+                return Ok(());
+            }
+            (state.refresh_token.clone(), state.password.clone())
+        };
+
+        if let Some(refresh_token) = refresh_token {
+            let request = grpc::RefreshTokenRequest { refresh_token };
+            if let Ok(response) = self.unauthed().refresh_token(request).await {
+                return self.signed_in(&response.into_inner()).await;
+            }
+        }
+
+        let Some((username, password)) = password else {
+            return Err(Status::new(
+                Code::Unauthenticated,
+                "The session expired and cannot be renewed",
+            ));
+        };
+
+        let request = grpc::LoginRequest { username, password };
+        let response = self.unauthed().login(request).await?.into_inner();
+        if !response.totp_challenge.is_empty() {
+            return Err(Status::new(
+                Code::Unauthenticated,
+                "Logging in again needs a TOTP code",
+            ));
+        }
+        self.signed_in(&response).await
+    }
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Client")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth_response(expires_in: u64) -> grpc::AuthResponse {
+        grpc::AuthResponse {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            expires_in,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_interceptor_sends_current_token() {
+        let session = Arc::new(Session::default());
+        let mut interceptor = AuthInterceptor {
+            session: session.clone(),
+        };
+
+        let request = interceptor.call(tonic::Request::new(())).unwrap();
+        assert!(request.metadata().get("authorization").is_none());
+
+        session.set_token(bearer("first").unwrap());
+        session.signed_in(bearer("second").unwrap(), &auth_response(60));
+        let request = interceptor.call(tonic::Request::new(())).unwrap();
+        assert_eq!(
+            request.metadata().get("authorization").unwrap(),
+            "Bearer second"
+        );
+        assert_eq!(session.state().generation, 2);
+    }
+
+    #[test]
+    fn test_only_sessions_can_renew() {
+        let session = Session::default();
+
+        session.set_token(bearer("token").unwrap());
+        assert!(!session.state().can_renew());
+
+        session.signed_in(bearer("access").unwrap(), &auth_response(0));
+        let state = session.state();
+        assert!(state.can_renew());
+        assert!(state.expires_at.is_none());
+    }
+
+    #[test]
+    fn test_credentials_hide_secrets() {
+        let password = Credentials::Password {
+            username: "user".to_string(),
+            password: "secret".to_string(),
+        };
+        let token = Credentials::Token("secret".to_string());
+
+        assert!(!format!("{password:?}").contains("secret"));
+        assert!(!format!("{token:?}").contains("secret"));
+    }
+}
@@ -0,0 +1,198 @@
+use std::path::Path;
+
+use timesman_type::{File, FileKind, FileType};
+use tokio_stream::Stream;
+
+use crate::grpc;
+use crate::grpc::create_post_with_file_chunk;
+
+/// How much data each message of an upload or download carries.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+fn mime_type(file: &File) -> String {
+    match file.ftype.kind() {
+        FileKind::Text => "text/plain".to_string(),
+        FileKind::Other => "application/octet-stream".to_string(),
+        FileKind::Image => {
+            let ext = Path::new(&file.name)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(str::to_ascii_lowercase);
+            match ext.as_deref() {
+                Some("jpg") => "image/jpeg".to_string(),
+                Some("svg") => "image/svg+xml".to_string(),
+                Some(ext) => format!("image/{ext}"),
+                None => "image/unknown".to_string(),
+            }
+        }
+    }
+}
+
+fn kind(info: &grpc::FileInfo) -> FileKind {
+    if info.mime_type.starts_with("image/") {
+        FileKind::Image
+    } else if info.mime_type.starts_with("text/") {
+        FileKind::Text
+    } else {
+        FileKind::Other
+    }
+}
+
+/// The metadata sent ahead of the data of `file`.
+pub fn info(file: &File) -> grpc::FileInfo {
+    grpc::FileInfo {
+        name: file.name.clone(),
+        mime_type: mime_type(file),
+        size: file.size(),
+    }
+}
+
+/// Puts a file back together from its info and data. Text that is not
+/// valid UTF-8 is kept as `Other`.
+pub fn from_parts(info: &grpc::FileInfo, data: Vec<u8>) -> File {
+    let ftype = match kind(info) {
+        FileKind::Image => FileType::Image(data),
+        FileKind::Text => match String::from_utf8(data) {
+            Ok(text) => FileType::Text(text),
+            Err(e) => FileType::Other(e.into_bytes()),
+        },
+        FileKind::Other => FileType::Other(data),
+    };
+
+    File::new(info.name.clone(), ftype)
+}
+
+/// The file `info` describes, without its data.
+pub fn from_info(info: &grpc::FileInfo) -> File {
+    let ftype = FileType::Omitted {
+        kind: kind(info),
+        size: info.size,
+    };
+    File::new(info.name.clone(), ftype)
+}
+
+fn upload_messages(
+    tid: u64,
+    text: String,
+    file: File,
+) -> Vec<grpc::CreatePostWithFileChunk> {
+    let header = grpc::CreatePostWithFileHeader {
+        tid,
+        text,
+        file: Some(info(&file)),
+    };
+    let data = file.into_data().unwrap_or_default();
+
+    let mut messages = vec![create_post_with_file_chunk::Chunk::Header(header)];
+    messages.extend(
+        data.chunks(CHUNK_SIZE).map(|chunk| {
+            create_post_with_file_chunk::Chunk::Data(chunk.to_vec())
+        }),
+    );
+
+    messages
+        .into_iter()
+        .map(|chunk| grpc::CreatePostWithFileChunk { chunk: Some(chunk) })
+        .collect()
+}
+
+/// The messages of a `CreatePostWithFile` call. A file whose data is left
+/// out sends none, which the server refuses as short.
+pub fn upload(
+    tid: u64,
+    text: String,
+    file: File,
+) -> impl Stream<Item = grpc::CreatePostWithFileChunk> + Send + 'static {
+    tokio_stream::iter(upload_messages(tid, text, file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_roundtrip() {
+        let files = [
+            File::new(
+                "photo.JPG".to_string(),
+                FileType::Image(vec![0xff, 0xd8, 0xff]),
+            ),
+            File::new(
+                "notes.txt".to_string(),
+                FileType::Text("hello".to_string()),
+            ),
+            File::new(
+                "blob".to_string(),
+                FileType::Other(vec![0; CHUNK_SIZE * 2 + 1]),
+            ),
+        ];
+
+        for file in files {
+            let messages = upload_messages(1, String::new(), file.clone());
+            let Some(create_post_with_file_chunk::Chunk::Header(header)) =
+                &messages[0].chunk
+            else {
+                panic!("The first message is not the header");
+            };
+            let info = header.file.as_ref().unwrap();
+
+            let mut data = vec![];
+            for message in &messages[1..] {
+                let Some(create_post_with_file_chunk::Chunk::Data(chunk)) =
+                    &message.chunk
+                else {
+                    panic!("Only data follows the header");
+                };
+                assert!(chunk.len() <= CHUNK_SIZE);
+                data.extend_from_slice(chunk);
+            }
+
+            assert_eq!(info.size, data.len() as u64);
+            assert_eq!(from_parts(info, data), file);
+        }
+    }
+
+    #[test]
+    fn test_mime_type() {
+        let image = File::new("photo.JPG".to_string(), FileType::Image(vec![]));
+        assert_eq!(info(&image).mime_type, "image/jpeg");
+
+        let text = grpc::FileInfo {
+            name: "bad.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            size: 1,
+        };
+        assert_eq!(
+            from_parts(&text, vec![0xff]).ftype,
+            FileType::Other(vec![0xff])
+        );
+    }
+
+    #[test]
+    fn test_info_only() {
+        let image =
+            File::new("photo.png".to_string(), FileType::Image(vec![1, 2, 3]));
+
+        let listed = from_info(&info(&image));
+        assert_eq!(listed, image.info());
+        assert_eq!(info(&listed), info(&image));
+    }
+
+    #[test]
+    fn test_upload_starts_with_header() {
+        let file = File::new(
+            "blob".to_string(),
+            FileType::Other(vec![1; CHUNK_SIZE + 1]),
+        );
+        let messages = upload_messages(3, "text".to_string(), file);
+
+        assert_eq!(messages.len(), 3);
+        let Some(create_post_with_file_chunk::Chunk::Header(header)) =
+            &messages[0].chunk
+        else {
+            panic!("The first message is not the header");
+        };
+        assert_eq!(header.tid, 3);
+        assert_eq!(header.file.as_ref().unwrap().size, CHUNK_SIZE as u64 + 1);
+    }
+}
@@ -2,31 +2,80 @@ pub mod grpc {
     tonic::include_proto!("timesman");
 }
 
-impl Into<timesman_type::Times> for grpc::Times {
-    fn into(self) -> timesman_type::Times {
-        let c = self.created_at.unwrap();
-        let ctime = chrono::DateTime::from_timestamp(c.seconds, c.nanos as u32)
-            .unwrap();
-
-        let utime = if let Some(u) = self.updated_at {
-            Some(
-                chrono::DateTime::from_timestamp(u.seconds, u.nanos as u32)
-                    .unwrap()
-                    .naive_local(),
-            )
-        } else {
-            None
-        };
+pub mod client;
+pub mod file;
+pub mod tls;
+
+/// Why a message does not make a valid domain value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    /// A required field is not set.
+    MissingField(&'static str),
+    /// A timestamp is outside the dates that can be represented.
+    InvalidTimestamp(&'static str),
+}
 
-        timesman_type::Times {
-            id: self.id,
-            title: self.title,
-            created_at: ctime.naive_local(),
-            updated_at: utime,
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::MissingField(field) => {
+                write!(f, "Missing field {field}")
+            }
+            ConversionError::InvalidTimestamp(field) => {
+                write!(f, "Invalid timestamp in {field}")
+            }
         }
     }
 }
 
+impl std::error::Error for ConversionError {}
+
+/// A peer sent a malformed message.
+impl From<ConversionError> for tonic::Status {
+    fn from(e: ConversionError) -> Self {
+        tonic::Status::new(tonic::Code::InvalidArgument, e.to_string())
+    }
+}
+
+fn to_datetime(
+    t: prost_types::Timestamp,
+    field: &'static str,
+) -> Result<NaiveDateTime, ConversionError> {
+    u32::try_from(t.nanos)
+        .ok()
+        .and_then(|nanos| chrono::DateTime::from_timestamp(t.seconds, nanos))
+        .map(|t| t.naive_utc())
+        .ok_or(ConversionError::InvalidTimestamp(field))
+}
+
+fn required_datetime(
+    t: Option<prost_types::Timestamp>,
+    field: &'static str,
+) -> Result<NaiveDateTime, ConversionError> {
+    to_datetime(t.ok_or(ConversionError::MissingField(field))?, field)
+}
+
+fn optional_datetime(
+    t: Option<prost_types::Timestamp>,
+    field: &'static str,
+) -> Result<Option<NaiveDateTime>, ConversionError> {
+    t.map(|t| to_datetime(t, field)).transpose()
+}
+
+impl TryFrom<grpc::Times> for timesman_type::Times {
+    type Error = ConversionError;
+
+    fn try_from(value: grpc::Times) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            title: value.title,
+            created_at: required_datetime(value.created_at, "created_at")?,
+            updated_at: optional_datetime(value.updated_at, "updated_at")?,
+            owner: value.owner.and_then(|o| o.parse().ok()),
+        })
+    }
+}
+
 use chrono::{Datelike, NaiveDateTime, Timelike};
 
 fn to_timestamp(c: NaiveDateTime) -> prost_types::Timestamp {
@@ -76,35 +125,24 @@ impl From<timesman_type::Times> for grpc::Times {
             title: value.title,
             created_at: Some(ctime),
             updated_at: utime,
+            owner: value.owner.map(|o| o.to_string()),
         }
     }
 }
 
-impl Into<timesman_type::Post> for grpc::Post {
-    fn into(self) -> timesman_type::Post {
-        let c = self.created_at.unwrap();
-        let ctime = chrono::DateTime::from_timestamp(c.seconds, c.nanos as u32)
-            .unwrap()
-            .naive_local();
-
-        let utime = if let Some(u) = self.updated_at {
-            Some(
-                chrono::DateTime::from_timestamp(u.seconds, u.nanos as u32)
-                    .unwrap()
-                    .naive_local(),
-            )
-        } else {
-            None
-        };
+/// The file is left out; only its info travels with the post.
+impl TryFrom<grpc::Post> for timesman_type::Post {
+    type Error = ConversionError;
 
-        timesman_type::Post {
-            id: self.id,
-            post: self.post,
-            created_at: ctime,
-            updated_at: utime,
-            file: None,
-            tag: self.tagid,
-        }
+    fn try_from(value: grpc::Post) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            post: value.post,
+            created_at: required_datetime(value.created_at, "created_at")?,
+            updated_at: optional_datetime(value.updated_at, "updated_at")?,
+            file: value.file.as_ref().map(file::from_info),
+            tag: value.tagid,
+        })
     }
 }
 
@@ -144,6 +182,25 @@ impl From<timesman_type::Post> for grpc::Post {
             created_at: Some(ctime),
             updated_at: utime,
             tagid: value.tag,
+            file: value.file.as_ref().map(file::info),
+        }
+    }
+}
+
+impl From<timesman_type::Tag> for grpc::Tag {
+    fn from(value: timesman_type::Tag) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+        }
+    }
+}
+
+impl From<grpc::Tag> for timesman_type::Tag {
+    fn from(value: grpc::Tag) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
         }
     }
 }
@@ -167,34 +224,17 @@ impl From<timesman_type::Todo> for grpc::Todo {
     }
 }
 
-impl Into<timesman_type::Todo> for grpc::Todo {
-    fn into(self) -> timesman_type::Todo {
-        let created_at = if let Some(c) = self.created_at {
-            chrono::DateTime::from_timestamp(c.seconds, c.nanos as u32)
-                .unwrap_or_else(|| chrono::DateTime::UNIX_EPOCH)
-                .naive_local()
-        } else {
-            // Use current time as fallback instead of panicking
-            chrono::Utc::now().naive_local()
-        };
-
-        let done_at = if let Some(d) = self.done_at {
-            Some(
-                chrono::DateTime::from_timestamp(d.seconds, d.nanos as u32)
-                    .unwrap_or_else(|| chrono::DateTime::UNIX_EPOCH)
-                    .naive_local(),
-            )
-        } else {
-            None
-        };
+impl TryFrom<grpc::Todo> for timesman_type::Todo {
+    type Error = ConversionError;
 
-        timesman_type::Todo {
-            id: self.id,
-            content: self.content,
-            detail: self.detail,
-            created_at,
-            done_at,
-        }
+    fn try_from(value: grpc::Todo) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            content: value.content,
+            detail: value.detail,
+            created_at: required_datetime(value.created_at, "created_at")?,
+            done_at: optional_datetime(value.done_at, "done_at")?,
+        })
     }
 }
 
@@ -288,6 +328,8 @@ impl From<timesman_type::AuthResponse> for grpc::AuthResponse {
             token_type: resp.token_type,
             expires_in: resp.expires_in as u64,
             user: Some(grpc::UserInfo::from(resp.user)),
+            refresh_token: resp.refresh_token,
+            totp_challenge: String::new(),
         }
     }
 }
@@ -305,11 +347,78 @@ impl Into<timesman_type::AuthResponse> for grpc::AuthResponse {
             access_token: self.access_token,
             token_type: self.token_type,
             expires_in: self.expires_in as usize,
+            refresh_token: self.refresh_token,
             user: self.user.unwrap_or(default_user).into(),
         }
     }
 }
 
+/// Everything but the password hash.
+impl From<timesman_type::User> for grpc::User {
+    fn from(user: timesman_type::User) -> Self {
+        Self {
+            id: user.id.to_string(),
+            username: user.username,
+            email: user.email,
+            role: grpc::UserRole::from(user.role) as i32,
+            is_active: user.is_active,
+            created_at: Some(to_timestamp(user.created_at)),
+            updated_at: user.updated_at.map(to_timestamp),
+        }
+    }
+}
+
+impl From<timesman_type::TokenScope> for grpc::TokenScope {
+    fn from(scope: timesman_type::TokenScope) -> Self {
+        Self {
+            read_only: scope.read_only,
+            tid: scope.tid,
+        }
+    }
+}
+
+impl From<grpc::TokenScope> for timesman_type::TokenScope {
+    fn from(scope: grpc::TokenScope) -> Self {
+        Self {
+            read_only: scope.read_only,
+            tid: scope.tid,
+        }
+    }
+}
+
+fn from_timestamp(t: prost_types::Timestamp) -> NaiveDateTime {
+    chrono::DateTime::from_timestamp(t.seconds, t.nanos as u32)
+        .unwrap_or_default()
+        .naive_utc()
+}
+
+impl From<timesman_type::PersonalAccessToken> for grpc::PersonalAccessToken {
+    fn from(token: timesman_type::PersonalAccessToken) -> Self {
+        Self {
+            id: token.id.to_string(),
+            name: token.name,
+            scope: Some(token.scope.into()),
+            created_at: Some(to_timestamp(token.created_at)),
+            expires_at: token.expires_at.map(to_timestamp),
+        }
+    }
+}
+
+impl From<grpc::PersonalAccessToken> for timesman_type::PersonalAccessToken {
+    fn from(token: grpc::PersonalAccessToken) -> Self {
+        Self {
+            id: token.id.parse().unwrap_or_default(),
+            name: token.name,
+            scope: token.scope.map(Into::into).unwrap_or_default(),
+            created_at: token
+                .created_at
+                .map(from_timestamp)
+                .unwrap_or_default(),
+            expires_at: token.expires_at.map(from_timestamp),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -337,7 +446,7 @@ mod tests {
         assert!(grpc_todo.done_at.is_some());
         
         // Test grpc::Todo -> timesman_type::Todo roundtrip
-        let roundtrip_todo: timesman_type::Todo = grpc_todo.into();
+        let roundtrip_todo: timesman_type::Todo = grpc_todo.try_into().unwrap();
         assert_eq!(roundtrip_todo.id, original_todo.id);
         assert_eq!(roundtrip_todo.content, original_todo.content);
         assert_eq!(roundtrip_todo.detail, original_todo.detail);
@@ -366,7 +475,7 @@ mod tests {
         assert!(grpc_todo.done_at.is_none());
         
         // Test roundtrip
-        let roundtrip_todo: timesman_type::Todo = grpc_todo.into();
+        let roundtrip_todo: timesman_type::Todo = grpc_todo.try_into().unwrap();
         assert_eq!(roundtrip_todo, original_todo);
     }
 
@@ -436,6 +545,7 @@ mod tests {
             title: "Test Times".to_string(),
             created_at: created,
             updated_at: Some(updated),
+            owner: None,
         };
         
         let grpc_times: grpc::Times = original_times.clone().into();
@@ -444,7 +554,7 @@ mod tests {
         assert!(grpc_times.created_at.is_some());
         assert!(grpc_times.updated_at.is_some());
         
-        let roundtrip_times: timesman_type::Times = grpc_times.into();
+        let roundtrip_times: timesman_type::Times = grpc_times.try_into().unwrap();
         assert_eq!(roundtrip_times.id, original_times.id);
         assert_eq!(roundtrip_times.title, original_times.title);
         assert_eq!(roundtrip_times.created_at, original_times.created_at);
@@ -472,7 +582,7 @@ mod tests {
         assert!(grpc_post.updated_at.is_none());
         assert_eq!(grpc_post.tagid, Some(42));
         
-        let roundtrip_post: timesman_type::Post = grpc_post.into();
+        let roundtrip_post: timesman_type::Post = grpc_post.try_into().unwrap();
         assert_eq!(roundtrip_post.id, original_post.id);
         assert_eq!(roundtrip_post.post, original_post.post);
         assert_eq!(roundtrip_post.created_at, original_post.created_at);
@@ -480,6 +590,21 @@ mod tests {
         assert_eq!(roundtrip_post.tag, original_post.tag);
     }
 
+    #[test]
+    fn test_tag_grpc_conversion() {
+        let original_tag = timesman_type::Tag {
+            id: 3,
+            name: "work".to_string(),
+        };
+
+        let grpc_tag: grpc::Tag = original_tag.clone().into();
+        assert_eq!(grpc_tag.id, 3);
+        assert_eq!(grpc_tag.name, "work");
+
+        let roundtrip_tag: timesman_type::Tag = grpc_tag.into();
+        assert_eq!(roundtrip_tag, original_tag);
+    }
+
     #[test]
     fn test_timestamp_conversion_edge_cases() {
         // Test with minimum and maximum valid dates
@@ -495,7 +620,7 @@ mod tests {
         };
         
         let grpc_todo: grpc::Todo = todo_min.clone().into();
-        let roundtrip_todo: timesman_type::Todo = grpc_todo.into();
+        let roundtrip_todo: timesman_type::Todo = grpc_todo.try_into().unwrap();
         assert_eq!(roundtrip_todo.created_at, todo_min.created_at);
         
         let todo_max = timesman_type::Todo {
@@ -507,8 +632,96 @@ mod tests {
         };
         
         let grpc_todo: grpc::Todo = todo_max.clone().into();
-        let roundtrip_todo: timesman_type::Todo = grpc_todo.into();
+        let roundtrip_todo: timesman_type::Todo = grpc_todo.try_into().unwrap();
         assert_eq!(roundtrip_todo.created_at, todo_max.created_at);
         assert_eq!(roundtrip_todo.done_at, todo_max.done_at);
     }
+
+    #[test]
+    fn test_bad_timestamps_are_rejected() {
+        let valid = || prost_types::Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 0,
+        };
+        let out_of_range = prost_types::Timestamp {
+            seconds: i64::MAX,
+            nanos: 0,
+        };
+        let negative_nanos = prost_types::Timestamp {
+            seconds: 0,
+            nanos: -1,
+        };
+
+        let times = grpc::Times {
+            id: 1,
+            title: "Times".to_string(),
+            created_at: None,
+            updated_at: None,
+            owner: None,
+        };
+        assert_eq!(
+            timesman_type::Times::try_from(times.clone()),
+            Err(ConversionError::MissingField("created_at"))
+        );
+        let times = grpc::Times {
+            created_at: Some(valid()),
+            updated_at: Some(out_of_range),
+            ..times
+        };
+        assert_eq!(
+            timesman_type::Times::try_from(times),
+            Err(ConversionError::InvalidTimestamp("updated_at"))
+        );
+
+        let post = grpc::Post {
+            id: 1,
+            post: "Post".to_string(),
+            created_at: Some(out_of_range),
+            updated_at: None,
+            tagid: None,
+            file: None,
+        };
+        assert_eq!(
+            timesman_type::Post::try_from(post),
+            Err(ConversionError::InvalidTimestamp("created_at"))
+        );
+
+        let todo = grpc::Todo {
+            id: 1,
+            content: "Todo".to_string(),
+            detail: None,
+            created_at: Some(valid()),
+            done_at: Some(negative_nanos),
+        };
+        let error = timesman_type::Todo::try_from(todo).unwrap_err();
+        assert_eq!(error, ConversionError::InvalidTimestamp("done_at"));
+
+        let status = tonic::Status::from(error);
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(status.message(), "Invalid timestamp in done_at");
+    }
+
+    #[test]
+    fn test_personal_access_token_grpc_conversion() {
+        let created = NaiveDateTime::parse_from_str("2023-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let expires = NaiveDateTime::parse_from_str("2023-02-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        let original_token = timesman_type::PersonalAccessToken {
+            id: uuid::Uuid::new_v4(),
+            name: "ci".to_string(),
+            scope: timesman_type::TokenScope {
+                read_only: false,
+                tid: Some(3),
+            },
+            created_at: created,
+            expires_at: Some(expires),
+        };
+
+        let grpc_token: grpc::PersonalAccessToken = original_token.clone().into();
+        assert_eq!(grpc_token.id, original_token.id.to_string());
+        assert_eq!(grpc_token.scope.as_ref().unwrap().tid, Some(3));
+
+        let roundtrip_token: timesman_type::PersonalAccessToken = grpc_token.into();
+        assert_eq!(roundtrip_token, original_token);
+    }
 }
@@ -0,0 +1,93 @@
+use tonic::transport::{
+    Certificate, Channel, ClientTlsConfig, Endpoint, Identity,
+};
+
+/// TLS settings for connecting to the server. Without a CA bundle the
+/// system's root certificates are trusted.
+#[derive(Clone, Debug, Default)]
+pub struct ClientTls {
+    /// PEM certificates to trust instead of the system roots.
+    pub ca_cert: Option<Vec<u8>>,
+    /// PEM certificate and key, for servers that require client
+    /// certificates.
+    pub identity: Option<(Vec<u8>, Vec<u8>)>,
+    /// Name to check the server certificate for, if it is not the host in
+    /// the URL.
+    pub domain_name: Option<String>,
+}
+
+impl ClientTls {
+    /// Reads the PEM files. A client certificate needs its key.
+    pub fn from_files(
+        ca_file: Option<&str>,
+        cert_file: Option<&str>,
+        key_file: Option<&str>,
+    ) -> Result<Self, String> {
+        let read = |path: &str| {
+            std::fs::read(path)
+                .map_err(|e| format!("Failed to read {path}: {e}"))
+        };
+
+        let identity = match (cert_file, key_file) {
+            (Some(cert), Some(key)) => Some((read(cert)?, read(key)?)),
+            (None, None) => None,
+            _ => {
+                return Err("Client certificate and key must be given together"
+                    .to_string())
+            }
+        };
+
+        Ok(Self {
+            ca_cert: ca_file.map(read).transpose()?,
+            identity,
+            domain_name: None,
+        })
+    }
+
+    fn to_config(&self) -> ClientTlsConfig {
+        let mut config = ClientTlsConfig::new();
+
+        config = match &self.ca_cert {
+            Some(ca) => config.ca_certificate(Certificate::from_pem(ca)),
+            None => config.with_native_roots(),
+        };
+        if let Some((cert, key)) = &self.identity {
+            config = config.identity(Identity::from_pem(cert, key));
+        }
+        if let Some(domain) = &self.domain_name {
+            config = config.domain_name(domain);
+        }
+        config
+    }
+}
+
+/// Connects to `url`. `https://` URLs use TLS, with the system roots if
+/// `tls` is not given; `tls` needs an `https://` URL.
+pub async fn connect(
+    url: String,
+    tls: Option<&ClientTls>,
+) -> Result<Channel, String> {
+    let mut endpoint = Endpoint::from_shared(url)
+        .map_err(|e| format!("Invalid server URL: {e}"))?;
+
+    let https = endpoint.uri().scheme_str() == Some("https");
+    let default_tls = ClientTls::default();
+    let tls = match tls {
+        Some(_) if !https => {
+            return Err("TLS settings need an https:// URL".to_string())
+        }
+        Some(tls) => Some(tls),
+        None if https => Some(&default_tls),
+        None => None,
+    };
+    if let Some(tls) = tls {
+        endpoint = endpoint
+            .tls_config(tls.to_config())
+            .map_err(|e| format!("Invalid TLS settings: {e}"))?;
+    }
+
+    endpoint
+        .connect()
+        .await
+        .map_err(|e| format!("Failed to connect to gRPC server: {e}"))
+}
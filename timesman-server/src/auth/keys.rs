@@ -0,0 +1,100 @@
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+
+use super::AuthError;
+
+/// A key for signing or verifying tokens. The `kid` is written into the
+/// token header so that the matching key can still be found after the
+/// signing key has been rotated.
+#[derive(Clone)]
+pub struct JwtKey {
+    pub kid: Option<String>,
+    pub algorithm: Algorithm,
+    encoding: Option<EncodingKey>,
+    decoding: DecodingKey,
+}
+
+impl JwtKey {
+    /// A shared secret for the HS* algorithms.
+    pub fn from_secret(
+        kid: Option<String>,
+        algorithm: Algorithm,
+        secret: &[u8],
+    ) -> Result<Self, AuthError> {
+        if !matches!(
+            algorithm,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(AuthError::InternalError(format!(
+                "{algorithm:?} needs a key pair, not a secret"
+            )));
+        }
+
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding: Some(EncodingKey::from_secret(secret)),
+            decoding: DecodingKey::from_secret(secret),
+        })
+    }
+
+    /// A PEM key pair for the RSA, EC and EdDSA algorithms. Without the
+    /// private key the result can only verify tokens.
+    pub fn from_pem(
+        kid: Option<String>,
+        algorithm: Algorithm,
+        private_pem: Option<&[u8]>,
+        public_pem: &[u8],
+    ) -> Result<Self, AuthError> {
+        let invalid = |e: jsonwebtoken::errors::Error| {
+            AuthError::InternalError(format!("Invalid {algorithm:?} key: {e}"))
+        };
+
+        let (encoding, decoding) = match algorithm {
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => (
+                private_pem.map(EncodingKey::from_rsa_pem).transpose(),
+                DecodingKey::from_rsa_pem(public_pem),
+            ),
+            Algorithm::ES256 | Algorithm::ES384 => (
+                private_pem.map(EncodingKey::from_ec_pem).transpose(),
+                DecodingKey::from_ec_pem(public_pem),
+            ),
+            Algorithm::EdDSA => (
+                private_pem.map(EncodingKey::from_ed_pem).transpose(),
+                DecodingKey::from_ed_pem(public_pem),
+            ),
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                return Err(AuthError::InternalError(format!(
+                    "{algorithm:?} needs a secret, not a key pair"
+                )));
+            }
+        };
+
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding: encoding.map_err(invalid)?,
+            decoding: decoding.map_err(invalid)?,
+        })
+    }
+
+    pub fn can_sign(&self) -> bool {
+        self.encoding.is_some()
+    }
+
+    pub(super) fn encoding_key(&self) -> Result<&EncodingKey, AuthError> {
+        self.encoding.as_ref().ok_or_else(|| {
+            AuthError::InternalError(
+                "Signing key has no private key".to_string(),
+            )
+        })
+    }
+
+    pub(super) fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::AuthError;
+
+/// Stop tracking this many keys before dropping the ones that no longer
+/// matter.
+const MAX_TRACKED: usize = 10_000;
+
+/// When failed logins lock out a username or a peer address. Once a key
+/// has `max_attempts` failures, each further failure locks it for twice as
+/// long as the last, starting at `base_delay` and capped at `max_delay`.
+/// Failures are forgotten after `max_delay` without a new one.
+#[derive(Clone, Debug)]
+pub struct LockoutPolicy {
+    pub max_attempts: u32,
+    pub max_peer_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl LockoutPolicy {
+    /// Never locks anyone out.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: u32::MAX,
+            max_peer_attempts: u32::MAX,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    /// How long a key with `failures` failures is locked.
+    fn delay(&self, failures: u32, max_attempts: u32) -> Duration {
+        if failures < max_attempts {
+            return Duration::ZERO;
+        }
+
+        let doublings = (failures - max_attempts).min(31);
+        self.base_delay
+            .saturating_mul(1 << doublings)
+            .min(self.max_delay)
+    }
+}
+
+impl Default for LockoutPolicy {
+    /// 5 failures per username and 20 per peer, locked for 30 seconds up to
+    /// an hour.
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            max_peer_attempts: 20,
+            base_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Username(String),
+    Peer(IpAddr),
+}
+
+/// Counts failed logins in memory. The counts are lost on restart.
+pub(super) struct LoginThrottle {
+    policy: LockoutPolicy,
+    failures: Mutex<HashMap<Key, Failures>>,
+}
+
+impl LoginThrottle {
+    pub(super) fn new(policy: LockoutPolicy) -> Self {
+        Self {
+            policy,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn keys(username: &str, peer: Option<IpAddr>) -> Vec<Key> {
+        let mut keys = vec![Key::Username(username.to_lowercase())];
+        keys.extend(peer.map(Key::Peer));
+        keys
+    }
+
+    /// Fails with `TooManyAttempts` while the username or the peer is
+    /// locked.
+    pub(super) fn check(
+        &self,
+        username: &str,
+        peer: Option<IpAddr>,
+    ) -> Result<(), AuthError> {
+        let now = Instant::now();
+        let failures = self.failures.lock().unwrap();
+
+        let retry_after = Self::keys(username, peer)
+            .iter()
+            .filter_map(|key| failures.get(key)?.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+            .max();
+
+        match retry_after {
+            Some(wait) => Err(AuthError::TooManyAttempts {
+                // Round up so that clients do not retry a moment too early
+                retry_after_secs: wait.as_secs() + 1,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub(super) fn record_failure(&self, username: &str, peer: Option<IpAddr>) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+
+        if failures.len() >= MAX_TRACKED {
+            let max_delay = self.policy.max_delay;
+            failures.retain(|_, f| {
+                f.locked_until.is_some_and(|until| until > now)
+                    || now - f.last < max_delay
+            });
+        }
+
+        for key in Self::keys(username, peer) {
+            let max_attempts = match key {
+                Key::Username(_) => self.policy.max_attempts,
+                Key::Peer(_) => self.policy.max_peer_attempts,
+            };
+
+            let entry = failures.entry(key).or_insert(Failures {
+                count: 0,
+                last: now,
+                locked_until: None,
+            });
+            if now - entry.last > self.policy.max_delay {
+                entry.count = 0;
+            }
+
+            entry.count = entry.count.saturating_add(1);
+            entry.last = now;
+            let delay = self.policy.delay(entry.count, max_attempts);
+            if !delay.is_zero() {
+                entry.locked_until = Some(now + delay);
+            }
+        }
+    }
+
+    /// Forgets the username's failures. The peer's are kept, so that one
+    /// good account does not unlock guessing at others.
+    pub(super) fn record_success(&self, username: &str) {
+        self.failures
+            .lock()
+            .unwrap()
+            .remove(&Key::Username(username.to_lowercase()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_doubles_up_to_max() {
+        let policy = LockoutPolicy {
+            max_attempts: 3,
+            max_peer_attempts: 10,
+            base_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(100),
+        };
+
+        assert_eq!(policy.delay(2, 3), Duration::ZERO);
+        assert_eq!(policy.delay(3, 3), Duration::from_secs(30));
+        assert_eq!(policy.delay(4, 3), Duration::from_secs(60));
+        assert_eq!(policy.delay(5, 3), Duration::from_secs(100));
+        assert_eq!(policy.delay(u32::MAX, 3), Duration::from_secs(100));
+    }
+
+    #[test]
+    fn test_peer_lockout_spans_usernames() {
+        let throttle = LoginThrottle::new(LockoutPolicy {
+            max_attempts: 5,
+            max_peer_attempts: 3,
+            base_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(60),
+        });
+        let peer = Some("192.0.2.1".parse().unwrap());
+        let other = Some("192.0.2.2".parse().unwrap());
+
+        for name in ["a", "b", "c"] {
+            assert!(throttle.check(name, peer).is_ok());
+            throttle.record_failure(name, peer);
+        }
+
+        assert!(matches!(
+            throttle.check("d", peer),
+            Err(AuthError::TooManyAttempts { .. })
+        ));
+        assert!(throttle.check("d", other).is_ok());
+        assert!(throttle.check("d", None).is_ok());
+    }
+}
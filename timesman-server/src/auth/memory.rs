@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::sync::RwLock;
+
+use timesman_type::{PersonalAccessToken, User, UserId};
+use uuid::Uuid;
+
+use super::{
+    AuthError, PersonalTokenRecord, RefreshToken, TotpRecord, UserRepository,
+};
+
+/// Keeps users in memory. Everything is lost when the server stops.
+#[derive(Default)]
+pub struct MemoryUserRepository {
+    users: RwLock<HashMap<String, User>>,
+    refresh_tokens: RwLock<HashMap<String, RefreshToken>>,
+    revoked: RwLock<HashMap<String, usize>>,
+    epochs: RwLock<HashMap<UserId, u64>>,
+    personal_tokens: RwLock<HashMap<String, PersonalTokenRecord>>,
+    totp: RwLock<HashMap<UserId, TotpRecord>>,
+}
+
+impl MemoryUserRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryUserRepository {
+    async fn get_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, AuthError> {
+        Ok(self.users.read().await.get(username).cloned())
+    }
+
+    async fn get_by_id(&self, id: &UserId) -> Result<Option<User>, AuthError> {
+        let users = self.users.read().await;
+        Ok(users.values().find(|u| u.id == *id).cloned())
+    }
+
+    async fn insert(&self, user: User) -> Result<(), AuthError> {
+        let mut users = self.users.write().await;
+
+        if users.contains_key(&user.username) {
+            return Err(AuthError::UserAlreadyExists);
+        }
+
+        users.insert(user.username.clone(), user);
+        Ok(())
+    }
+
+    async fn update(&self, user: User) -> Result<(), AuthError> {
+        let mut users = self.users.write().await;
+
+        let stored = users
+            .get_mut(&user.username)
+            .ok_or(AuthError::UserNotFound)?;
+        *stored = user;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<User>, AuthError> {
+        let mut users: Vec<User> =
+            self.users.read().await.values().cloned().collect();
+        users.sort_by_key(|u| u.created_at);
+        Ok(users)
+    }
+
+    async fn delete(&self, username: &str) -> Result<bool, AuthError> {
+        let Some(user) = self.users.write().await.remove(username) else {
+            return Ok(false);
+        };
+
+        self.delete_refresh_tokens(&user.id).await?;
+        self.personal_tokens
+            .write()
+            .await
+            .retain(|_, r| r.user_id != user.id);
+        self.delete_totp(&user.id).await?;
+        self.epochs.write().await.remove(&user.id);
+        Ok(true)
+    }
+
+    async fn insert_refresh_token(
+        &self,
+        token: RefreshToken,
+    ) -> Result<(), AuthError> {
+        let mut tokens = self.refresh_tokens.write().await;
+
+        let now = Utc::now().naive_utc();
+        tokens.retain(|_, t| t.expires_at >= now);
+        tokens.insert(token.hash.clone(), token);
+        Ok(())
+    }
+
+    async fn take_refresh_token(
+        &self,
+        hash: &str,
+    ) -> Result<Option<RefreshToken>, AuthError> {
+        Ok(self.refresh_tokens.write().await.remove(hash))
+    }
+
+    async fn delete_refresh_token(
+        &self,
+        hash: &str,
+        user_id: &UserId,
+    ) -> Result<bool, AuthError> {
+        let mut tokens = self.refresh_tokens.write().await;
+        if tokens.get(hash).is_some_and(|t| t.user_id == *user_id) {
+            tokens.remove(hash);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    async fn delete_refresh_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<(), AuthError> {
+        let mut tokens = self.refresh_tokens.write().await;
+        tokens.retain(|_, t| t.user_id != *user_id);
+        Ok(())
+    }
+
+    async fn revoke_token(
+        &self,
+        jti: &str,
+        expires_at: usize,
+    ) -> Result<(), AuthError> {
+        let mut revoked = self.revoked.write().await;
+
+        let now = Utc::now().timestamp() as usize;
+        revoked.retain(|_, exp| *exp >= now);
+        revoked.insert(jti.to_string(), expires_at);
+        Ok(())
+    }
+
+    async fn is_token_revoked(&self, jti: &str) -> Result<bool, AuthError> {
+        Ok(self.revoked.read().await.contains_key(jti))
+    }
+
+    async fn token_epoch(&self, user_id: &UserId) -> Result<u64, AuthError> {
+        Ok(self.epochs.read().await.get(user_id).copied().unwrap_or(0))
+    }
+
+    async fn bump_token_epoch(
+        &self,
+        user_id: &UserId,
+    ) -> Result<(), AuthError> {
+        *self.epochs.write().await.entry(*user_id).or_default() += 1;
+        Ok(())
+    }
+
+    async fn insert_personal_token(
+        &self,
+        record: PersonalTokenRecord,
+    ) -> Result<(), AuthError> {
+        let mut tokens = self.personal_tokens.write().await;
+        tokens.insert(record.hash.clone(), record);
+        Ok(())
+    }
+
+    async fn get_personal_token(
+        &self,
+        hash: &str,
+    ) -> Result<Option<PersonalTokenRecord>, AuthError> {
+        Ok(self.personal_tokens.read().await.get(hash).cloned())
+    }
+
+    async fn list_personal_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<PersonalAccessToken>, AuthError> {
+        let tokens = self.personal_tokens.read().await;
+
+        let mut list: Vec<PersonalAccessToken> = tokens
+            .values()
+            .filter(|r| r.user_id == *user_id)
+            .map(|r| r.token.clone())
+            .collect();
+        list.sort_by_key(|t| t.created_at);
+        Ok(list)
+    }
+
+    async fn delete_personal_token(
+        &self,
+        user_id: &UserId,
+        id: &Uuid,
+    ) -> Result<bool, AuthError> {
+        let mut tokens = self.personal_tokens.write().await;
+
+        let before = tokens.len();
+        tokens.retain(|_, r| !(r.user_id == *user_id && r.token.id == *id));
+        Ok(tokens.len() != before)
+    }
+
+    async fn get_totp(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Option<TotpRecord>, AuthError> {
+        Ok(self.totp.read().await.get(user_id).cloned())
+    }
+
+    async fn save_totp(&self, record: TotpRecord) -> Result<(), AuthError> {
+        self.totp.write().await.insert(record.user_id, record);
+        Ok(())
+    }
+
+    async fn delete_totp(&self, user_id: &UserId) -> Result<bool, AuthError> {
+        Ok(self.totp.write().await.remove(user_id).is_some())
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashSet;
+
+use super::AuthError;
+
+/// Passwords rejected by the default policy.
+const COMMON_PASSWORDS: &[&str] = &[
+    "password",
+    "password1",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "11111111",
+    "00000000",
+    "qwerty123",
+    "qwertyuiop",
+    "iloveyou",
+    "letmein1",
+    "abc12345",
+    "baseball",
+    "football",
+    "sunshine",
+    "trustno1",
+];
+
+/// Rules a new password has to follow. Banned passwords are compared
+/// without regard to case.
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    banned: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn new(
+        min_length: usize,
+        banned: impl IntoIterator<Item = String>,
+    ) -> Self {
+        Self {
+            min_length,
+            banned: banned.into_iter().map(|p| p.to_lowercase()).collect(),
+        }
+    }
+
+    /// Adds to the banned passwords.
+    pub fn ban(&mut self, passwords: impl IntoIterator<Item = String>) {
+        self.banned
+            .extend(passwords.into_iter().map(|p| p.to_lowercase()));
+    }
+
+    /// Accepts any password.
+    pub fn none() -> Self {
+        Self::new(0, [])
+    }
+
+    pub fn check(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<(), AuthError> {
+        if password.chars().count() < self.min_length {
+            return Err(AuthError::WeakPassword(format!(
+                "must be at least {} characters",
+                self.min_length
+            )));
+        }
+
+        let lower = password.to_lowercase();
+        if self.banned.contains(&lower) {
+            return Err(AuthError::WeakPassword("is too common".to_string()));
+        }
+        if !username.is_empty() && lower == username.to_lowercase() {
+            return Err(AuthError::WeakPassword(
+                "must differ from the username".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for PasswordPolicy {
+    /// At least 8 characters and none of a short list of common passwords.
+    fn default() -> Self {
+        Self::new(8, COMMON_PASSWORDS.iter().map(|p| p.to_string()))
+    }
+}
@@ -0,0 +1,805 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow,
+};
+use sqlx::Row;
+
+use timesman_type::{PersonalAccessToken, TokenScope, User, UserId, UserRole};
+use uuid::Uuid;
+
+use super::{
+    AuthError, PersonalTokenRecord, RefreshToken, TotpRecord, UserRepository,
+};
+
+/// Stores users in a SQLite database so accounts survive restarts.
+pub struct SqliteUserRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteUserRepository {
+    /// Opens (and creates if needed) the database at `path`. `:memory:`
+    /// gives a private in-memory database.
+    pub async fn new(path: &str) -> Result<Self, AuthError> {
+        let options = SqliteConnectOptions::from_str(path)
+            .map_err(internal)?
+            .create_if_missing(true);
+
+        // Every connection to an in-memory database sees a different
+        // database, so keep a single one.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(if path == ":memory:" { 1 } else { 4 })
+            .connect_with(options)
+            .await
+            .map_err(internal)?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS users (
+                id TEXT PRIMARY KEY NOT NULL,
+                username TEXT UNIQUE NOT NULL,
+                email TEXT NOT NULL,
+                password_hash TEXT NOT NULL,
+                role TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                updated_at DATETIME,
+                is_active BOOLEAN NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(internal)?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS refresh_tokens (
+                hash TEXT PRIMARY KEY NOT NULL,
+                user_id TEXT NOT NULL,
+                expires_at DATETIME NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(internal)?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS revoked_tokens (
+                jti TEXT PRIMARY KEY NOT NULL,
+                expires_at INTEGER NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(internal)?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS token_epochs (
+                user_id TEXT PRIMARY KEY NOT NULL,
+                epoch INTEGER NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(internal)?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS personal_tokens (
+                id TEXT PRIMARY KEY NOT NULL,
+                user_id TEXT NOT NULL,
+                hash TEXT UNIQUE NOT NULL,
+                name TEXT NOT NULL,
+                read_only BOOLEAN NOT NULL,
+                tid INTEGER,
+                created_at DATETIME NOT NULL,
+                expires_at DATETIME
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(internal)?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS totp (
+                user_id TEXT PRIMARY KEY NOT NULL,
+                secret BLOB NOT NULL,
+                confirmed BOOLEAN NOT NULL,
+                backup_codes TEXT NOT NULL,
+                last_step INTEGER
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(internal)?;
+
+        Ok(Self { pool })
+    }
+}
+
+fn internal(e: sqlx::Error) -> AuthError {
+    AuthError::InternalError(format!("User database error: {e}"))
+}
+
+fn role_to_str(role: &UserRole) -> &'static str {
+    match role {
+        UserRole::Admin => "Admin",
+        UserRole::User => "User",
+        UserRole::ReadOnly => "ReadOnly",
+    }
+}
+
+fn role_from_str(role: &str) -> Result<UserRole, AuthError> {
+    match role {
+        "Admin" => Ok(UserRole::Admin),
+        "User" => Ok(UserRole::User),
+        "ReadOnly" => Ok(UserRole::ReadOnly),
+        _ => Err(AuthError::InternalError(format!("Unknown role: {role}"))),
+    }
+}
+
+fn parse_uuid(id: &str) -> Result<Uuid, AuthError> {
+    id.parse()
+        .map_err(|e| AuthError::InternalError(format!("Invalid id {id}: {e}")))
+}
+
+fn row_to_personal_token(
+    row: SqliteRow,
+) -> Result<PersonalTokenRecord, AuthError> {
+    let id: String = row.try_get("id").map_err(internal)?;
+    let user_id: String = row.try_get("user_id").map_err(internal)?;
+    let tid: Option<i64> = row.try_get("tid").map_err(internal)?;
+
+    Ok(PersonalTokenRecord {
+        user_id: parse_uuid(&user_id)?,
+        hash: row.try_get("hash").map_err(internal)?,
+        token: PersonalAccessToken {
+            id: parse_uuid(&id)?,
+            name: row.try_get("name").map_err(internal)?,
+            scope: TokenScope {
+                read_only: row.try_get("read_only").map_err(internal)?,
+                tid: tid.map(|tid| tid as u64),
+            },
+            created_at: row.try_get("created_at").map_err(internal)?,
+            expires_at: row.try_get("expires_at").map_err(internal)?,
+        },
+    })
+}
+
+fn row_to_user(row: SqliteRow) -> Result<User, AuthError> {
+    let id: String = row.try_get("id").map_err(internal)?;
+    let role: String = row.try_get("role").map_err(internal)?;
+
+    Ok(User {
+        id: id.parse().map_err(|e| {
+            AuthError::InternalError(format!("Invalid user id {id}: {e}"))
+        })?,
+        username: row.try_get("username").map_err(internal)?,
+        email: row.try_get("email").map_err(internal)?,
+        password_hash: row.try_get("password_hash").map_err(internal)?,
+        role: role_from_str(&role)?,
+        created_at: row.try_get("created_at").map_err(internal)?,
+        updated_at: row.try_get("updated_at").map_err(internal)?,
+        is_active: row.try_get("is_active").map_err(internal)?,
+    })
+}
+
+#[async_trait]
+impl UserRepository for SqliteUserRepository {
+    async fn get_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, AuthError> {
+        sqlx::query("SELECT * FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(internal)?
+            .map(row_to_user)
+            .transpose()
+    }
+
+    async fn get_by_id(&self, id: &UserId) -> Result<Option<User>, AuthError> {
+        sqlx::query("SELECT * FROM users WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(internal)?
+            .map(row_to_user)
+            .transpose()
+    }
+
+    async fn insert(&self, user: User) -> Result<(), AuthError> {
+        let result = sqlx::query(
+            "INSERT INTO users (id, username, email, password_hash, role,
+                created_at, updated_at, is_active)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(user.id.to_string())
+        .bind(&user.username)
+        .bind(&user.email)
+        .bind(&user.password_hash)
+        .bind(role_to_str(&user.role))
+        .bind(user.created_at)
+        .bind(user.updated_at)
+        .bind(user.is_active)
+        .execute(&self.pool)
+        .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(AuthError::UserAlreadyExists)
+            }
+            Err(e) => Err(internal(e)),
+        }
+    }
+
+    async fn update(&self, user: User) -> Result<(), AuthError> {
+        let result = sqlx::query(
+            "UPDATE users SET email = ?, password_hash = ?, role = ?,
+                updated_at = ?, is_active = ?
+            WHERE username = ?",
+        )
+        .bind(&user.email)
+        .bind(&user.password_hash)
+        .bind(role_to_str(&user.role))
+        .bind(user.updated_at)
+        .bind(user.is_active)
+        .bind(&user.username)
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+
+        if result.rows_affected() == 0 {
+            return Err(AuthError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<User>, AuthError> {
+        sqlx::query("SELECT * FROM users ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .map(row_to_user)
+            .collect()
+    }
+
+    async fn delete(&self, username: &str) -> Result<bool, AuthError> {
+        let mut tx = self.pool.begin().await.map_err(internal)?;
+
+        let row =
+            sqlx::query("DELETE FROM users WHERE username = ? RETURNING id")
+                .bind(username)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(internal)?;
+        let Some(row) = row else {
+            return Ok(false);
+        };
+        let id: String = row.try_get("id").map_err(internal)?;
+
+        sqlx::query("DELETE FROM refresh_tokens WHERE user_id = ?")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        sqlx::query("DELETE FROM personal_tokens WHERE user_id = ?")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        sqlx::query("DELETE FROM totp WHERE user_id = ?")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        sqlx::query("DELETE FROM token_epochs WHERE user_id = ?")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+
+        tx.commit().await.map_err(internal)?;
+        Ok(true)
+    }
+
+    async fn insert_refresh_token(
+        &self,
+        token: RefreshToken,
+    ) -> Result<(), AuthError> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE expires_at < ?")
+            .bind(chrono::Utc::now().naive_utc())
+            .execute(&self.pool)
+            .await
+            .map_err(internal)?;
+
+        sqlx::query(
+            "INSERT INTO refresh_tokens (hash, user_id, expires_at)
+            VALUES (?, ?, ?)",
+        )
+        .bind(&token.hash)
+        .bind(token.user_id.to_string())
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+
+        Ok(())
+    }
+
+    async fn take_refresh_token(
+        &self,
+        hash: &str,
+    ) -> Result<Option<RefreshToken>, AuthError> {
+        let row = sqlx::query(
+            "DELETE FROM refresh_tokens WHERE hash = ?
+            RETURNING hash, user_id, expires_at",
+        )
+        .bind(hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(internal)?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let user_id: String = row.try_get("user_id").map_err(internal)?;
+        Ok(Some(RefreshToken {
+            hash: row.try_get("hash").map_err(internal)?,
+            user_id: user_id.parse().map_err(|e| {
+                AuthError::InternalError(format!(
+                    "Invalid user id {user_id}: {e}"
+                ))
+            })?,
+            expires_at: row.try_get("expires_at").map_err(internal)?,
+        }))
+    }
+
+    async fn delete_refresh_token(
+        &self,
+        hash: &str,
+        user_id: &UserId,
+    ) -> Result<bool, AuthError> {
+        let result = sqlx::query(
+            "DELETE FROM refresh_tokens WHERE hash = ? AND user_id = ?",
+        )
+        .bind(hash)
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_refresh_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<(), AuthError> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(internal)?;
+        Ok(())
+    }
+
+    async fn revoke_token(
+        &self,
+        jti: &str,
+        expires_at: usize,
+    ) -> Result<(), AuthError> {
+        sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < ?")
+            .bind(chrono::Utc::now().timestamp())
+            .execute(&self.pool)
+            .await
+            .map_err(internal)?;
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO revoked_tokens (jti, expires_at)
+            VALUES (?, ?)",
+        )
+        .bind(jti)
+        .bind(expires_at as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+
+        Ok(())
+    }
+
+    async fn is_token_revoked(&self, jti: &str) -> Result<bool, AuthError> {
+        let row = sqlx::query("SELECT 1 FROM revoked_tokens WHERE jti = ?")
+            .bind(jti)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(internal)?;
+
+        Ok(row.is_some())
+    }
+
+    async fn token_epoch(&self, user_id: &UserId) -> Result<u64, AuthError> {
+        let row =
+            sqlx::query("SELECT epoch FROM token_epochs WHERE user_id = ?")
+                .bind(user_id.to_string())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(internal)?;
+
+        let Some(row) = row else {
+            return Ok(0);
+        };
+        let epoch: i64 = row.try_get("epoch").map_err(internal)?;
+        Ok(epoch as u64)
+    }
+
+    async fn bump_token_epoch(
+        &self,
+        user_id: &UserId,
+    ) -> Result<(), AuthError> {
+        sqlx::query(
+            "INSERT INTO token_epochs (user_id, epoch) VALUES (?, 1)
+            ON CONFLICT (user_id) DO UPDATE SET epoch = epoch + 1",
+        )
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+
+        Ok(())
+    }
+
+    async fn insert_personal_token(
+        &self,
+        record: PersonalTokenRecord,
+    ) -> Result<(), AuthError> {
+        let token = record.token;
+
+        sqlx::query(
+            "INSERT INTO personal_tokens (id, user_id, hash, name, read_only,
+                tid, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(token.id.to_string())
+        .bind(record.user_id.to_string())
+        .bind(&record.hash)
+        .bind(&token.name)
+        .bind(token.scope.read_only)
+        .bind(token.scope.tid.map(|tid| tid as i64))
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+
+        Ok(())
+    }
+
+    async fn get_personal_token(
+        &self,
+        hash: &str,
+    ) -> Result<Option<PersonalTokenRecord>, AuthError> {
+        sqlx::query("SELECT * FROM personal_tokens WHERE hash = ?")
+            .bind(hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(internal)?
+            .map(row_to_personal_token)
+            .transpose()
+    }
+
+    async fn list_personal_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<PersonalAccessToken>, AuthError> {
+        sqlx::query(
+            "SELECT * FROM personal_tokens WHERE user_id = ?
+            ORDER BY created_at",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(internal)?
+        .into_iter()
+        .map(|row| row_to_personal_token(row).map(|r| r.token))
+        .collect()
+    }
+
+    async fn delete_personal_token(
+        &self,
+        user_id: &UserId,
+        id: &Uuid,
+    ) -> Result<bool, AuthError> {
+        let result = sqlx::query(
+            "DELETE FROM personal_tokens WHERE user_id = ? AND id = ?",
+        )
+        .bind(user_id.to_string())
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_totp(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Option<TotpRecord>, AuthError> {
+        let row = sqlx::query("SELECT * FROM totp WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(internal)?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let backup_codes: String =
+            row.try_get("backup_codes").map_err(internal)?;
+        let last_step: Option<i64> =
+            row.try_get("last_step").map_err(internal)?;
+        Ok(Some(TotpRecord {
+            user_id: *user_id,
+            secret: row.try_get("secret").map_err(internal)?,
+            confirmed: row.try_get("confirmed").map_err(internal)?,
+            backup_codes: backup_codes
+                .split(',')
+                .filter(|h| !h.is_empty())
+                .map(str::to_string)
+                .collect(),
+            last_step: last_step.map(|step| step as u64),
+        }))
+    }
+
+    async fn save_totp(&self, record: TotpRecord) -> Result<(), AuthError> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO totp (user_id, secret, confirmed,
+                backup_codes, last_step)
+            VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(record.user_id.to_string())
+        .bind(&record.secret)
+        .bind(record.confirmed)
+        .bind(record.backup_codes.join(","))
+        .bind(record.last_step.map(|step| step as i64))
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+
+        Ok(())
+    }
+
+    async fn delete_totp(&self, user_id: &UserId) -> Result<bool, AuthError> {
+        let result = sqlx::query("DELETE FROM totp WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(internal)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn user(username: &str) -> User {
+        User {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            email: format!("{username}@example.com"),
+            password_hash: "hash".to_string(),
+            role: UserRole::User,
+            created_at: Utc::now().naive_utc(),
+            updated_at: None,
+            is_active: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_get() {
+        let repo = SqliteUserRepository::new(":memory:").await.unwrap();
+        let user = user("alice");
+
+        repo.insert(user.clone()).await.unwrap();
+
+        let by_name = repo.get_by_username("alice").await.unwrap();
+        assert_eq!(by_name, Some(user.clone()));
+        let by_id = repo.get_by_id(&user.id).await.unwrap();
+        assert_eq!(by_id, Some(user));
+        assert_eq!(repo.get_by_username("bob").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_username() {
+        let repo = SqliteUserRepository::new(":memory:").await.unwrap();
+
+        repo.insert(user("alice")).await.unwrap();
+        let result = repo.insert(user("alice")).await;
+        assert!(matches!(result, Err(AuthError::UserAlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let repo = SqliteUserRepository::new(":memory:").await.unwrap();
+        let mut user = user("alice");
+        repo.insert(user.clone()).await.unwrap();
+
+        user.role = UserRole::ReadOnly;
+        user.updated_at = Some(Utc::now().naive_utc());
+        repo.update(user.clone()).await.unwrap();
+        assert_eq!(repo.get_by_id(&user.id).await.unwrap(), Some(user));
+
+        let result = repo.update(self::user("bob")).await;
+        assert!(matches!(result, Err(AuthError::UserNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_tokens() {
+        let repo = SqliteUserRepository::new(":memory:").await.unwrap();
+        let token = RefreshToken {
+            hash: "hash".to_string(),
+            user_id: Uuid::new_v4(),
+            expires_at: Utc::now().naive_utc() + chrono::Duration::days(1),
+        };
+
+        repo.insert_refresh_token(token.clone()).await.unwrap();
+        let taken = repo.take_refresh_token("hash").await.unwrap();
+        assert_eq!(taken, Some(token.clone()));
+        assert_eq!(repo.take_refresh_token("hash").await.unwrap(), None);
+
+        // Only the owner can delete a token.
+        repo.insert_refresh_token(token.clone()).await.unwrap();
+        let (owner, other) = (token.user_id, Uuid::new_v4());
+        assert!(!repo.delete_refresh_token("hash", &other).await.unwrap());
+        assert!(repo.delete_refresh_token("hash", &owner).await.unwrap());
+        assert!(!repo.delete_refresh_token("hash", &owner).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_token_epochs() {
+        let repo = SqliteUserRepository::new(":memory:").await.unwrap();
+        let user_id = Uuid::new_v4();
+
+        assert_eq!(repo.token_epoch(&user_id).await.unwrap(), 0);
+        repo.bump_token_epoch(&user_id).await.unwrap();
+        repo.bump_token_epoch(&user_id).await.unwrap();
+        assert_eq!(repo.token_epoch(&user_id).await.unwrap(), 2);
+        assert_eq!(repo.token_epoch(&Uuid::new_v4()).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_tokens() {
+        let repo = SqliteUserRepository::new(":memory:").await.unwrap();
+        let exp = (Utc::now().timestamp() + 60) as usize;
+
+        assert!(!repo.is_token_revoked("jti").await.unwrap());
+        repo.revoke_token("jti", exp).await.unwrap();
+        assert!(repo.is_token_revoked("jti").await.unwrap());
+
+        // Entries past their expiry are dropped on the next revocation
+        repo.revoke_token("old", 0).await.unwrap();
+        repo.revoke_token("other", exp).await.unwrap();
+        assert!(!repo.is_token_revoked("old").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_personal_tokens() {
+        let repo = SqliteUserRepository::new(":memory:").await.unwrap();
+        let user_id = Uuid::new_v4();
+        let record = PersonalTokenRecord {
+            user_id,
+            hash: "hash".to_string(),
+            token: PersonalAccessToken {
+                id: Uuid::new_v4(),
+                name: "ci".to_string(),
+                scope: TokenScope {
+                    read_only: true,
+                    tid: Some(1),
+                },
+                created_at: Utc::now().naive_utc(),
+                expires_at: None,
+            },
+        };
+
+        repo.insert_personal_token(record.clone()).await.unwrap();
+        let stored = repo.get_personal_token("hash").await.unwrap();
+        assert_eq!(stored, Some(record.clone()));
+
+        let tokens = repo.list_personal_tokens(&user_id).await.unwrap();
+        assert_eq!(tokens, vec![record.token.clone()]);
+        let others = repo.list_personal_tokens(&Uuid::new_v4()).await.unwrap();
+        assert!(others.is_empty());
+
+        let id = record.token.id;
+        assert!(!repo
+            .delete_personal_token(&Uuid::new_v4(), &id)
+            .await
+            .unwrap());
+        assert!(repo.delete_personal_token(&user_id, &id).await.unwrap());
+        assert_eq!(repo.get_personal_token("hash").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_list_and_delete() {
+        let repo = SqliteUserRepository::new(":memory:").await.unwrap();
+        let alice = user("alice");
+        repo.insert(alice.clone()).await.unwrap();
+        repo.insert(user("bob")).await.unwrap();
+        repo.insert_refresh_token(RefreshToken {
+            hash: "hash".to_string(),
+            user_id: alice.id,
+            expires_at: Utc::now().naive_utc() + chrono::Duration::days(1),
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(repo.list().await.unwrap().len(), 2);
+
+        assert!(repo.delete("alice").await.unwrap());
+        assert!(!repo.delete("alice").await.unwrap());
+        assert_eq!(repo.get_by_id(&alice.id).await.unwrap(), None);
+        assert_eq!(repo.take_refresh_token("hash").await.unwrap(), None);
+
+        let users = repo.list().await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].username, "bob");
+    }
+
+    #[tokio::test]
+    async fn test_totp() {
+        let repo = SqliteUserRepository::new(":memory:").await.unwrap();
+        let alice = user("alice");
+        repo.insert(alice.clone()).await.unwrap();
+        let mut record = TotpRecord {
+            user_id: alice.id,
+            secret: vec![1, 2, 3],
+            confirmed: false,
+            backup_codes: vec![],
+            last_step: None,
+        };
+
+        assert_eq!(repo.get_totp(&alice.id).await.unwrap(), None);
+        repo.save_totp(record.clone()).await.unwrap();
+        assert_eq!(
+            repo.get_totp(&alice.id).await.unwrap(),
+            Some(record.clone())
+        );
+
+        record.confirmed = true;
+        record.backup_codes = vec!["a".to_string(), "b".to_string()];
+        record.last_step = Some(42);
+        repo.save_totp(record.clone()).await.unwrap();
+        assert_eq!(repo.get_totp(&alice.id).await.unwrap(), Some(record));
+
+        assert!(repo.delete("alice").await.unwrap());
+        assert_eq!(repo.get_totp(&alice.id).await.unwrap(), None);
+        assert!(!repo.delete_totp(&alice.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_users_survive_reopen() {
+        let path = std::env::temp_dir()
+            .join(format!("timesman-users-{}.db", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        let user = user("alice");
+
+        {
+            let repo = SqliteUserRepository::new(path).await.unwrap();
+            repo.insert(user.clone()).await.unwrap();
+            repo.pool.close().await;
+        }
+
+        let repo = SqliteUserRepository::new(path).await.unwrap();
+        assert_eq!(repo.get_by_username("alice").await.unwrap(), Some(user));
+
+        repo.pool.close().await;
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use timesman_type::UserId;
+
+use super::{hash_token, hex, random_token};
+
+/// Length of a time step in seconds.
+pub const STEP_SECS: u64 = 30;
+pub const DIGITS: u32 = 6;
+/// Codes from this many steps before or after the current one are accepted,
+/// to allow for clocks that are a little off.
+const SKEW_STEPS: u64 = 1;
+const SECRET_LEN: usize = 20;
+const BACKUP_CODES: usize = 10;
+
+/// How long a login challenge waits for its code.
+const CHALLENGE_MINUTES: i64 = 5;
+/// Wrong codes before a login challenge is dropped.
+const CHALLENGE_ATTEMPTS: u32 = 5;
+
+/// What an authenticator app needs to generate codes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TotpEnrollment {
+    /// The secret in base32, for typing in by hand.
+    pub secret: String,
+    /// The `otpauth://` URI, usually shown as a QR code.
+    pub uri: String,
+}
+
+pub(super) fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LEN];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// The RFC 4226 HOTP code of `secret` for `counter`, which RFC 6238 sets
+/// to the number of time steps since the epoch.
+pub fn code(secret: &[u8], counter: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret)
+        .expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+pub(super) fn step_at(time: DateTime<Utc>) -> u64 {
+    time.timestamp().max(0) as u64 / STEP_SECS
+}
+
+/// Returns the time step `input` was generated for, if it is valid at
+/// `time`. Steps up to `last_step` have been used already and are refused
+/// so that a code cannot be replayed.
+pub(super) fn verify(
+    secret: &[u8],
+    input: &str,
+    time: DateTime<Utc>,
+    last_step: Option<u64>,
+) -> Option<u64> {
+    let input = normalize(input);
+    if input.len() != DIGITS as usize {
+        return None;
+    }
+
+    let now = step_at(time);
+    (now.saturating_sub(SKEW_STEPS)..=now + SKEW_STEPS)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| constant_time_eq(&code(secret, *step), &input))
+}
+
+/// Fresh backup codes, returned as shown to the user and as stored.
+pub(super) fn generate_backup_codes() -> (Vec<String>, Vec<String>) {
+    (0..BACKUP_CODES)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            OsRng.fill_bytes(&mut bytes);
+            let raw = hex(&bytes);
+            let shown = format!("{}-{}", &raw[..5], &raw[5..]);
+            (shown, hash_backup_code(&raw))
+        })
+        .unzip()
+}
+
+pub(super) fn hash_backup_code(input: &str) -> String {
+    hash_token(&normalize(input).to_lowercase())
+}
+
+/// Codes are often typed with spaces or dashes.
+fn normalize(input: &str) -> String {
+    input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect()
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// RFC 4648 base32 without padding, as authenticator apps expect.
+pub(super) fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut out = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+pub(super) fn otpauth_uri(
+    issuer: &str,
+    username: &str,
+    secret: &[u8],
+) -> String {
+    let issuer = percent_encode(issuer);
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECS}",
+        percent_encode(username),
+        base32(secret),
+    )
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The current code for a base32 secret, as an authenticator app would
+/// show it.
+#[cfg(test)]
+pub(crate) fn code_from_base32(secret: &str, time: DateTime<Utc>) -> String {
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in secret.chars() {
+        buffer = (buffer << 5) | ALPHABET.find(c).unwrap() as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    code(&bytes, step_at(time))
+}
+
+struct PendingLogin {
+    user_id: UserId,
+    expires_at: DateTime<Utc>,
+    attempts: u32,
+}
+
+/// Logins whose password was right and that wait for a TOTP code. Kept in
+/// memory; a restart means logging in again.
+#[derive(Default)]
+pub(super) struct PendingLogins {
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl PendingLogins {
+    /// Returns the challenge the client has to send back with the code.
+    pub(super) fn create(&self, user_id: UserId, now: DateTime<Utc>) -> String {
+        let challenge = random_token();
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.expires_at > now);
+        pending.insert(
+            hash_token(&challenge),
+            PendingLogin {
+                user_id,
+                expires_at: now + Duration::minutes(CHALLENGE_MINUTES),
+                attempts: 0,
+            },
+        );
+        challenge
+    }
+
+    pub(super) fn user(
+        &self,
+        challenge: &str,
+        now: DateTime<Utc>,
+    ) -> Option<UserId> {
+        let pending = self.pending.lock().unwrap();
+        pending
+            .get(&hash_token(challenge))
+            .filter(|p| p.expires_at > now)
+            .map(|p| p.user_id)
+    }
+
+    /// Counts a wrong code, dropping the challenge after too many.
+    pub(super) fn fail(&self, challenge: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        let hash = hash_token(challenge);
+        if let Some(p) = pending.get_mut(&hash) {
+            p.attempts += 1;
+            if p.attempts >= CHALLENGE_ATTEMPTS {
+                pending.remove(&hash);
+            }
+        }
+    }
+
+    pub(super) fn remove(&self, challenge: &str) {
+        self.pending.lock().unwrap().remove(&hash_token(challenge));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(secs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_rfc_vectors() {
+        // RFC 4226 appendix D
+        assert_eq!(code(RFC_SECRET, 0), "755224");
+        assert_eq!(code(RFC_SECRET, 1), "287082");
+        assert_eq!(code(RFC_SECRET, 9), "520489");
+
+        // RFC 6238 appendix B, SHA1, last six digits
+        assert_eq!(code(RFC_SECRET, step_at(at(59))), "287082");
+        assert_eq!(code(RFC_SECRET, step_at(at(1111111109))), "081804");
+        assert_eq!(code(RFC_SECRET, step_at(at(1234567890))), "005924");
+        assert_eq!(code(RFC_SECRET, step_at(at(2000000000))), "279037");
+    }
+
+    #[test]
+    fn test_verify_window_and_replay() {
+        let now = at(1111111109);
+        let step = step_at(now);
+
+        assert_eq!(verify(RFC_SECRET, "081804", now, None), Some(step));
+        assert_eq!(verify(RFC_SECRET, "081 804", now, None), Some(step));
+
+        // One step either side is accepted, two are not
+        let previous = code(RFC_SECRET, step - 1);
+        assert_eq!(verify(RFC_SECRET, &previous, now, None), Some(step - 1));
+        let old = code(RFC_SECRET, step - 2);
+        assert_eq!(verify(RFC_SECRET, &old, now, None), None);
+
+        // Used steps are refused
+        assert_eq!(verify(RFC_SECRET, "081804", now, Some(step)), None);
+        assert_eq!(verify(RFC_SECRET, "12345", now, None), None);
+    }
+
+    #[test]
+    fn test_base32_and_uri() {
+        assert_eq!(base32(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(
+            code_from_base32(&base32(RFC_SECRET), at(59)),
+            code(RFC_SECRET, 1)
+        );
+        assert_eq!(base32(b"f"), "MY");
+        assert_eq!(base32(b"foobar"), "MZXW6YTBOI");
+
+        assert_eq!(
+            otpauth_uri("Times Man", "alice@example.com", b"foobar"),
+            "otpauth://totp/Times%20Man:alice%40example.com?secret=MZXW6YTBOI\
+             &issuer=Times%20Man&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_pending_logins() {
+        let pending = PendingLogins::default();
+        let user_id = UserId::new_v4();
+        let now = at(1000);
+
+        let challenge = pending.create(user_id, now);
+        assert_eq!(pending.user(&challenge, now), Some(user_id));
+        assert_eq!(pending.user("other", now), None);
+        assert_eq!(pending.user(&challenge, now + Duration::minutes(6)), None);
+
+        for _ in 0..CHALLENGE_ATTEMPTS {
+            assert_eq!(pending.user(&challenge, now), Some(user_id));
+            pending.fail(&challenge);
+        }
+        assert_eq!(pending.user(&challenge, now), None);
+    }
+}
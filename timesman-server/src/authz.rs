@@ -0,0 +1,235 @@
+use timesman_type::{Claims, Tid, Times, TokenScope, UserId, UserRole};
+
+/// Kind of access a request needs on the data it touches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthzError {
+    InvalidSubject,
+    ReadOnly,
+    ReadOnlyToken,
+    NotOwner,
+    OutOfScope,
+    SessionRequired,
+    NotAdmin,
+}
+
+impl std::fmt::Display for AuthzError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthzError::InvalidSubject => write!(f, "Invalid subject in token"),
+            AuthzError::ReadOnly => {
+                write!(f, "Read-only users cannot modify data")
+            }
+            AuthzError::ReadOnlyToken => {
+                write!(f, "Token is limited to reading data")
+            }
+            AuthzError::NotOwner => {
+                write!(f, "Not allowed to access another user's data")
+            }
+            AuthzError::OutOfScope => {
+                write!(f, "Token is limited to a single times")
+            }
+            AuthzError::NotAdmin => write!(f, "Requires the admin role"),
+            AuthzError::SessionRequired => {
+                write!(
+                    f,
+                    "Requires a login session, not a personal access token"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthzError {}
+
+/// The authenticated user behind a request.
+#[derive(Clone, Debug)]
+pub struct Caller {
+    pub id: UserId,
+    pub role: UserRole,
+    /// Set when the request is made with a personal access token.
+    pub scope: Option<TokenScope>,
+}
+
+impl Caller {
+    pub fn from_claims(claims: &Claims) -> Result<Self, AuthzError> {
+        let id = claims.sub.parse().map_err(|_| AuthzError::InvalidSubject)?;
+
+        Ok(Self {
+            id,
+            role: claims.role.clone(),
+            scope: claims.scope.clone(),
+        })
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == UserRole::Admin
+    }
+
+    /// Checks that the caller's role and token allow `access` at all.
+    pub fn authorize(&self, access: Access) -> Result<(), AuthzError> {
+        if access == Access::Read {
+            return Ok(());
+        }
+
+        if self.role == UserRole::ReadOnly {
+            return Err(AuthzError::ReadOnly);
+        }
+        if self.scope.as_ref().is_some_and(|s| s.read_only) {
+            return Err(AuthzError::ReadOnlyToken);
+        }
+        Ok(())
+    }
+
+    /// Admins can reach every times, everyone else only their own. Tokens
+    /// scoped to one times reach nothing else.
+    pub fn can_access(&self, times: &Times) -> bool {
+        self.can_access_times(times.id, times.owner)
+    }
+
+    /// Like `can_access`, for a times known only by its id and owner.
+    pub fn can_access_times(&self, tid: Tid, owner: Option<UserId>) -> bool {
+        if let Some(scope_tid) = self.scope.as_ref().and_then(|s| s.tid) {
+            if tid != scope_tid {
+                return false;
+            }
+        }
+
+        self.is_admin() || owner == Some(self.id)
+    }
+
+    /// New times are outside the reach of a token scoped to one times.
+    pub fn can_create_times(&self) -> Result<(), AuthzError> {
+        match self.scope.as_ref().and_then(|s| s.tid) {
+            Some(_) => Err(AuthzError::OutOfScope),
+            None => Ok(()),
+        }
+    }
+
+    /// User management is for admins with a login session.
+    pub fn require_admin(&self) -> Result<(), AuthzError> {
+        self.require_session()?;
+        match self.is_admin() {
+            true => Ok(()),
+            false => Err(AuthzError::NotAdmin),
+        }
+    }
+
+    /// Managing personal access tokens needs a login session, so that a
+    /// leaked token cannot mint more.
+    pub fn require_session(&self) -> Result<(), AuthzError> {
+        match self.scope {
+            Some(_) => Err(AuthzError::SessionRequired),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caller(role: UserRole) -> Caller {
+        Caller {
+            id: UserId::new_v4(),
+            role,
+            scope: None,
+        }
+    }
+
+    fn scoped(read_only: bool, tid: Option<u64>) -> Caller {
+        Caller {
+            scope: Some(TokenScope { read_only, tid }),
+            ..caller(UserRole::User)
+        }
+    }
+
+    fn times(owner: Option<UserId>) -> Times {
+        Times {
+            id: 1,
+            title: "test".to_string(),
+            created_at: chrono::Utc::now().naive_local(),
+            updated_at: None,
+            owner,
+        }
+    }
+
+    #[test]
+    fn test_role_access_matrix() {
+        let admin = caller(UserRole::Admin);
+        let user = caller(UserRole::User);
+        let reader = caller(UserRole::ReadOnly);
+
+        assert!(admin.authorize(Access::Read).is_ok());
+        assert!(admin.authorize(Access::Write).is_ok());
+        assert!(user.authorize(Access::Read).is_ok());
+        assert!(user.authorize(Access::Write).is_ok());
+        assert!(reader.authorize(Access::Read).is_ok());
+        assert_eq!(reader.authorize(Access::Write), Err(AuthzError::ReadOnly));
+    }
+
+    #[test]
+    fn test_can_access_owned_times() {
+        let admin = caller(UserRole::Admin);
+        let user = caller(UserRole::User);
+        let other = caller(UserRole::User);
+
+        let owned = times(Some(user.id));
+        assert!(user.can_access(&owned));
+        assert!(!other.can_access(&owned));
+        assert!(admin.can_access(&owned));
+
+        let orphan = times(None);
+        assert!(!user.can_access(&orphan));
+        assert!(admin.can_access(&orphan));
+    }
+
+    #[test]
+    fn test_from_claims_rejects_bad_subject() {
+        let claims = Claims {
+            sub: "not-a-uuid".to_string(),
+            username: "test".to_string(),
+            role: UserRole::User,
+            exp: 0,
+            iat: 0,
+            iss: "test".to_string(),
+            jti: "test".to_string(),
+            scope: None,
+            epoch: 0,
+        };
+
+        assert_eq!(
+            Caller::from_claims(&claims).unwrap_err(),
+            AuthzError::InvalidSubject
+        );
+    }
+
+    #[test]
+    fn test_token_scope() {
+        let reader = scoped(true, None);
+        assert!(reader.authorize(Access::Read).is_ok());
+        assert_eq!(
+            reader.authorize(Access::Write),
+            Err(AuthzError::ReadOnlyToken)
+        );
+        assert_eq!(reader.require_session(), Err(AuthzError::SessionRequired));
+
+        let single = scoped(false, Some(1));
+        assert!(single.authorize(Access::Write).is_ok());
+        assert!(single.can_access(&times(Some(single.id))));
+        assert!(!single.can_access(&Times {
+            id: 2,
+            ..times(Some(single.id))
+        }));
+        assert_eq!(single.can_create_times(), Err(AuthzError::OutOfScope));
+
+        let user = caller(UserRole::User);
+        assert!(user.can_create_times().is_ok());
+        assert!(user.require_session().is_ok());
+    }
+}
@@ -1,11 +1,19 @@
+use jsonwebtoken::Algorithm;
 use serde::{Deserialize, Serialize};
 use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
 use std::{default::Default, fs::File, path::PathBuf};
 use timesman_bstore::StoreType;
+use timesman_server::auth::{
+    AuthSettings, JwtKey, LockoutPolicy, MemoryUserRepository, PasswordPolicy,
+    SqliteUserRepository, UserRepository,
+};
+use timesman_server::TlsSettings;
 
 use toml;
 
-#[derive(Deserialize, Serialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub enum FrontType {
     Http,
     Grpc,
@@ -17,25 +25,130 @@ pub struct StoreConfig {
     pub store_type: String,
     pub path: Option<String>,
     pub create: Option<bool>,
+    /// Where the database server is, for the Postgres store.
+    pub url: Option<String>,
 }
 
+/// Where registered users are kept. Defaults to memory when the `[users]`
+/// section is missing.
 #[derive(Deserialize, Serialize, Clone)]
-pub struct Config {
-    pub listen: String,
+pub struct UsersConfig {
+    #[serde(rename = "type")]
+    pub store_type: String,
+    pub path: Option<String>,
+}
+
+/// A token signing or verification key. HS* algorithms take a secret from
+/// `secret_file` or the `secret_env` variable; the others take PEM files.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct KeyConfig {
+    pub kid: Option<String>,
+    pub algorithm: Option<String>,
+    pub secret_file: Option<String>,
+    pub secret_env: Option<String>,
+    pub private_key_file: Option<String>,
+    pub public_key_file: Option<String>,
+}
+
+/// Token settings. Without a `signing_key` the HS256 secret is read from
+/// `TIMESMAN_JWT_SECRET`, and without `token_expiry_hours` the expiry is read
+/// from `TIMESMAN_TOKEN_EXPIRY_HOURS`, then defaults to 24.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct AuthConfig {
+    pub issuer: Option<String>,
+    pub token_expiry_hours: Option<i64>,
+    pub refresh_token_expiry_days: Option<i64>,
+    pub signing_key: Option<KeyConfig>,
+    #[serde(default)]
+    pub verification_keys: Vec<KeyConfig>,
+    pub password: Option<PasswordConfig>,
+    pub lockout: Option<LockoutConfig>,
+}
+
+/// Password rules for new accounts. `banned` and the lines of
+/// `banned_file` are added to a built-in list of common passwords.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct PasswordConfig {
+    pub min_length: Option<usize>,
+    #[serde(default)]
+    pub banned: Vec<String>,
+    pub banned_file: Option<String>,
+}
+
+/// Lockout after failed logins. Unset fields keep their defaults.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct LockoutConfig {
+    pub max_attempts: Option<u32>,
+    pub max_peer_attempts: Option<u32>,
+    pub base_delay_secs: Option<u64>,
+    pub max_delay_secs: Option<u64>,
+}
+
+/// Serve TLS with `cert_file` and `key_file`. With `client_ca_file`, clients
+/// have to present a certificate signed by that CA, unless
+/// `client_auth_optional` is true.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TlsConfig {
+    pub cert_file: String,
+    pub key_file: String,
+    pub client_ca_file: Option<String>,
+    pub client_auth_optional: Option<bool>,
+}
+
+/// A front to serve. `listen` is a TCP address, or `unix:<path>` for a Unix
+/// socket. Only gRPC fronts serve TLS.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct FrontConfig {
+    #[serde(rename = "type")]
     pub front_type: FrontType,
+    pub listen: String,
+    pub tls: Option<TlsConfig>,
+}
+
+/// The first admin, created at startup unless that admin exists. The
+/// password comes from `password_file` or the `password_env` variable.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AdminConfig {
+    pub username: String,
+    pub email: String,
+    pub password_file: Option<String>,
+    pub password_env: Option<String>,
+}
+
+/// Either a single front from `listen`, `front_type` and `tls`, or any
+/// number of `[[fronts]]`.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Config {
+    pub listen: Option<String>,
+    pub front_type: Option<FrontType>,
+    #[serde(default)]
+    pub fronts: Vec<FrontConfig>,
     pub store: StoreConfig,
+    pub users: Option<UsersConfig>,
+    pub auth: Option<AuthConfig>,
+    pub admin: Option<AdminConfig>,
+    pub tls: Option<TlsConfig>,
+    /// Largest attachment the fronts accept, in bytes.
+    pub max_file_size: Option<u64>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            listen: "localhost:8080".to_string(),
-            front_type: FrontType::Grpc,
+            listen: Some("localhost:8080".to_string()),
+            front_type: Some(FrontType::Grpc),
+            fronts: Vec::new(),
             store: StoreConfig {
                 store_type: "Memory".to_string(),
                 path: None,
                 create: None,
+                url: None,
             },
+            users: None,
+            auth: None,
+            admin: None,
+            tls: None,
+            max_file_size: None,
         }
     }
 }
@@ -63,6 +176,19 @@ impl StoreConfig {
                 let expanded_path = Self::expand_path(path);
                 Ok(StoreType::Local(expanded_path))
             }
+            #[cfg(feature = "sqlite")]
+            "Sqlite" => {
+                let path = self.path.as_ref()
+                    .ok_or_else(|| "Sqlite store requires path parameter".to_string())?;
+                let expanded_path = Self::expand_path(path);
+                Ok(StoreType::Sqlite(expanded_path))
+            }
+            #[cfg(feature = "postgres")]
+            "Postgres" => {
+                let url = self.url.as_ref()
+                    .ok_or_else(|| "Postgres store requires url parameter".to_string())?;
+                Ok(StoreType::Postgres(url.clone()))
+            }
             #[cfg(feature = "json")]
             "Json" => {
                 let path = self.path.as_ref()
@@ -76,6 +202,236 @@ impl StoreConfig {
     }
 }
 
+impl UsersConfig {
+    pub async fn to_repository(
+        &self,
+    ) -> Result<Arc<dyn UserRepository>, String> {
+        match self.store_type.as_str() {
+            "Memory" => Ok(Arc::new(MemoryUserRepository::new())),
+            "Sqlite" => {
+                let path = self.path.as_ref().ok_or_else(|| {
+                    "Sqlite user store requires path parameter".to_string()
+                })?;
+                let expanded_path = StoreConfig::expand_path(path);
+                let repo = SqliteUserRepository::new(&expanded_path)
+                    .await
+                    .map_err(|e| format!("{e}"))?;
+                Ok(Arc::new(repo))
+            }
+            _ => Err(format!("Unknown user store type: {}", self.store_type)),
+        }
+    }
+}
+
+/// Looks up an environment variable. Tests pass their own lookup rather
+/// than change the environment the other tests share.
+type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn process_env(var: &str) -> Option<String> {
+    std::env::var(var).ok()
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let path = StoreConfig::expand_path(path);
+    std::fs::read(&path).map_err(|e| format!("{path}: {e}"))
+}
+
+impl KeyConfig {
+    fn to_key(&self, env: Env) -> Result<JwtKey, String> {
+        let algorithm: Algorithm = self
+            .algorithm
+            .as_deref()
+            .unwrap_or("HS256")
+            .parse()
+            .map_err(|e| format!("Unknown key algorithm: {e}"))?;
+
+        let key = match algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                let secret = self.secret(env)?;
+                JwtKey::from_secret(self.kid.clone(), algorithm, &secret)
+            }
+            _ => {
+                let public = self.public_key_file.as_ref().ok_or_else(|| {
+                    format!("{algorithm:?} key requires public_key_file")
+                })?;
+                let private = match &self.private_key_file {
+                    Some(path) => Some(read_file(path)?),
+                    None => None,
+                };
+                JwtKey::from_pem(
+                    self.kid.clone(),
+                    algorithm,
+                    private.as_deref(),
+                    &read_file(public)?,
+                )
+            }
+        };
+
+        key.map_err(|e| format!("{e}"))
+    }
+
+    fn secret(&self, env: Env) -> Result<Vec<u8>, String> {
+        read_secret(&self.secret_file, &self.secret_env, env)
+            .map_err(|e| format!("Key {e}"))
+    }
+}
+
+/// Reads a secret from `file`, or else from the environment variable `var`.
+fn read_secret(
+    file: &Option<String>,
+    var: &Option<String>,
+    env: Env,
+) -> Result<Vec<u8>, String> {
+    let secret = if let Some(path) = file {
+        let mut secret = read_file(path)?;
+        // Files usually end with a newline that is not part of the secret
+        while secret.last().is_some_and(|b| b.is_ascii_whitespace()) {
+            secret.pop();
+        }
+        secret
+    } else if let Some(var) = var {
+        env(var)
+            .ok_or_else(|| format!("environment variable {var} is not set"))?
+            .into_bytes()
+    } else {
+        return Err("requires a file or an environment variable".to_string());
+    };
+
+    if secret.is_empty() {
+        return Err("secret is empty".to_string());
+    }
+    Ok(secret)
+}
+
+impl TlsConfig {
+    pub fn to_settings(&self) -> Result<TlsSettings, String> {
+        Ok(TlsSettings {
+            cert: read_file(&self.cert_file)?,
+            key: read_file(&self.key_file)?,
+            client_ca: self
+                .client_ca_file
+                .as_deref()
+                .map(read_file)
+                .transpose()?,
+            client_auth_optional: self.client_auth_optional.unwrap_or(false),
+        })
+    }
+}
+
+impl AdminConfig {
+    pub fn password(&self) -> Result<String, String> {
+        let password =
+            read_secret(&self.password_file, &self.password_env, &process_env)
+                .map_err(|e| format!("Admin password {e}"))?;
+        String::from_utf8(password)
+            .map_err(|_| "Admin password is not valid UTF-8".to_string())
+    }
+}
+
+impl PasswordConfig {
+    fn to_policy(&self) -> Result<PasswordPolicy, String> {
+        let mut policy = PasswordPolicy::default();
+        if let Some(min_length) = self.min_length {
+            policy.min_length = min_length;
+        }
+        policy.ban(self.banned.iter().cloned());
+
+        if let Some(path) = &self.banned_file {
+            let banned = String::from_utf8(read_file(path)?)
+                .map_err(|_| format!("{path} is not valid UTF-8"))?;
+            policy.ban(
+                banned
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string),
+            );
+        }
+        Ok(policy)
+    }
+}
+
+impl LockoutConfig {
+    fn to_policy(&self) -> LockoutPolicy {
+        let default = LockoutPolicy::default();
+        LockoutPolicy {
+            max_attempts: self.max_attempts.unwrap_or(default.max_attempts),
+            max_peer_attempts: self
+                .max_peer_attempts
+                .unwrap_or(default.max_peer_attempts),
+            base_delay: self
+                .base_delay_secs
+                .map_or(default.base_delay, Duration::from_secs),
+            max_delay: self
+                .max_delay_secs
+                .map_or(default.max_delay, Duration::from_secs),
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn to_settings(&self) -> Result<AuthSettings, String> {
+        self.to_settings_in(&process_env)
+    }
+
+    fn to_settings_in(&self, env: Env) -> Result<AuthSettings, String> {
+        let token_expiry_hours = match self.token_expiry_hours {
+            Some(hours) => hours,
+            None => match env("TIMESMAN_TOKEN_EXPIRY_HOURS") {
+                Some(hours) => hours.parse().map_err(|e| {
+                    format!("Invalid TIMESMAN_TOKEN_EXPIRY_HOURS: {e}")
+                })?,
+                None => 24,
+            },
+        };
+        if token_expiry_hours <= 0 {
+            return Err("token_expiry_hours must be positive".to_string());
+        }
+        let refresh_token_expiry_days =
+            self.refresh_token_expiry_days.unwrap_or(30);
+        if refresh_token_expiry_days <= 0 {
+            return Err(
+                "refresh_token_expiry_days must be positive".to_string()
+            );
+        }
+
+        let signing_key = match &self.signing_key {
+            Some(key) => key.to_key(env)?,
+            None => KeyConfig {
+                secret_env: Some("TIMESMAN_JWT_SECRET".to_string()),
+                ..Default::default()
+            }
+            .to_key(env)?,
+        };
+        if !signing_key.can_sign() {
+            return Err("Signing key requires private_key_file".to_string());
+        }
+
+        let verification_keys = self
+            .verification_keys
+            .iter()
+            .map(|key| key.to_key(env))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(AuthSettings {
+            issuer: self
+                .issuer
+                .clone()
+                .unwrap_or_else(|| "timesman-server".to_string()),
+            token_expiry_hours,
+            refresh_token_expiry_days,
+            signing_key,
+            verification_keys,
+            password_policy: self
+                .password
+                .clone()
+                .unwrap_or_default()
+                .to_policy()?,
+            lockout: self.lockout.clone().unwrap_or_default().to_policy(),
+        })
+    }
+}
+
 impl Config {
     pub fn load(path: PathBuf) -> Result<Self, String> {
         if !path.exists() {
@@ -90,4 +446,271 @@ impl Config {
             toml::from_str(&buf).map_err(|e| format!("{e}"))?;
         Ok(config)
     }
+
+    /// The fronts to serve, checked for mistakes.
+    pub fn fronts(&self) -> Result<Vec<FrontConfig>, String> {
+        let fronts = if self.fronts.is_empty() {
+            let (Some(listen), Some(front_type)) =
+                (&self.listen, &self.front_type)
+            else {
+                return Err(
+                    "Set listen and front_type, or add [[fronts]]".to_string()
+                );
+            };
+            vec![FrontConfig {
+                front_type: front_type.clone(),
+                listen: listen.clone(),
+                tls: self.tls.clone(),
+            }]
+        } else {
+            if self.listen.is_some()
+                || self.front_type.is_some()
+                || self.tls.is_some()
+            {
+                return Err("With [[fronts]], set listen, type and tls for \
+                            each front"
+                    .to_string());
+            }
+            self.fronts.clone()
+        };
+
+        for (i, front) in fronts.iter().enumerate() {
+            if matches!(front.front_type, FrontType::Http)
+                && front.tls.is_some()
+            {
+                return Err("TLS is only supported by gRPC fronts".to_string());
+            }
+            if fronts[..i].iter().any(|f| f.listen == front.listen) {
+                return Err(format!("{} is used by two fronts", front.listen));
+            }
+        }
+        Ok(fronts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testdata(name: &str) -> String {
+        format!("{}/testdata/auth/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn auth(text: &str) -> AuthConfig {
+        toml::from_str(text).unwrap()
+    }
+
+    /// An environment with only `var` set to `value`.
+    fn env_with<'a>(
+        var: &'a str,
+        value: &'a str,
+    ) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| (name == var).then(|| value.to_string())
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_secret_file_comes_before_env() {
+        let env = env_with("TIMESMAN_JWT_SECRET", "from-env");
+        let key = KeyConfig {
+            secret_file: Some(testdata("secret")),
+            secret_env: Some("TIMESMAN_TEST_UNSET_SECRET".to_string()),
+            ..Default::default()
+        };
+        assert!(key.to_key(&env).unwrap().can_sign());
+
+        let key = KeyConfig {
+            secret_env: Some("TIMESMAN_JWT_SECRET".to_string()),
+            ..Default::default()
+        };
+        assert!(key.to_key(&env).is_ok());
+        assert!(key.to_key(&env_with("TIMESMAN_JWT_SECRET", "")).is_err());
+
+        let key = KeyConfig {
+            secret_env: Some("TIMESMAN_TEST_UNSET_SECRET".to_string()),
+            ..Default::default()
+        };
+        assert!(key.to_key(&env).is_err());
+        assert!(KeyConfig::default().to_key(&env).is_err());
+    }
+
+    #[test]
+    fn test_key_pairs() {
+        let key = KeyConfig {
+            kid: Some("rsa".to_string()),
+            algorithm: Some("RS256".to_string()),
+            private_key_file: Some(testdata("rsa.key")),
+            public_key_file: Some(testdata("rsa.pub")),
+            ..Default::default()
+        };
+        let signing = key.to_key(&no_env).unwrap();
+        assert_eq!(signing.algorithm, Algorithm::RS256);
+        assert!(signing.can_sign());
+
+        // Without the private key the key only verifies.
+        let key = KeyConfig {
+            private_key_file: None,
+            ..key
+        };
+        assert!(!key.to_key(&no_env).unwrap().can_sign());
+
+        // The PEM files have to match the algorithm.
+        let key = KeyConfig {
+            algorithm: Some("EdDSA".to_string()),
+            ..key
+        };
+        assert!(key.to_key(&no_env).is_err());
+        let key = KeyConfig {
+            algorithm: Some("RS256".to_string()),
+            secret_file: Some(testdata("secret")),
+            ..Default::default()
+        };
+        assert!(key.to_key(&no_env).is_err());
+        let key = KeyConfig {
+            algorithm: Some("XX256".to_string()),
+            ..key
+        };
+        assert!(key.to_key(&no_env).is_err());
+    }
+
+    #[test]
+    fn test_auth_settings() {
+        let config = auth(&format!(
+            r#"
+            issuer = "test"
+            token_expiry_hours = 2
+
+            [signing_key]
+            kid = "new"
+            secret_file = "{}"
+
+            [[verification_keys]]
+            kid = "old"
+            algorithm = "RS256"
+            public_key_file = "{}"
+            "#,
+            testdata("secret"),
+            testdata("rsa.pub"),
+        ));
+        let settings = config.to_settings_in(&no_env).unwrap();
+        assert_eq!(settings.issuer, "test");
+        assert_eq!(settings.token_expiry_hours, 2);
+        assert_eq!(settings.refresh_token_expiry_days, 30);
+        assert_eq!(settings.signing_key.kid.as_deref(), Some("new"));
+        assert_eq!(settings.verification_keys.len(), 1);
+
+        // A verification-only key cannot sign.
+        let config = auth(&format!(
+            r#"
+            token_expiry_hours = 2
+
+            [signing_key]
+            algorithm = "RS256"
+            public_key_file = "{}"
+            "#,
+            testdata("rsa.pub"),
+        ));
+        assert!(config.to_settings_in(&no_env).is_err());
+    }
+
+    #[test]
+    fn test_invalid_expiry() {
+        let signing_key = Some(KeyConfig {
+            secret_file: Some(testdata("secret")),
+            ..Default::default()
+        });
+
+        for (hours, days) in [(0, 30), (-1, 30), (24, 0)] {
+            let config = AuthConfig {
+                token_expiry_hours: Some(hours),
+                refresh_token_expiry_days: Some(days),
+                signing_key: signing_key.clone(),
+                ..Default::default()
+            };
+            assert!(config.to_settings_in(&no_env).is_err());
+        }
+
+        let config = AuthConfig {
+            signing_key,
+            ..Default::default()
+        };
+        let env = env_with("TIMESMAN_TOKEN_EXPIRY_HOURS", "12");
+        let settings = config.to_settings_in(&env).unwrap();
+        assert_eq!(settings.token_expiry_hours, 12);
+        let env = env_with("TIMESMAN_TOKEN_EXPIRY_HOURS", "twelve");
+        assert!(config.to_settings_in(&env).is_err());
+        assert_eq!(
+            config.to_settings_in(&no_env).unwrap().token_expiry_hours,
+            24
+        );
+    }
+
+    fn config(text: &str) -> Config {
+        toml::from_str(&format!("{text}\n[store]\ntype = \"Memory\"\n"))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_fronts() {
+        let fronts = config(
+            r#"
+            listen = "localhost:8080"
+            front_type = "Grpc"
+            "#,
+        )
+        .fronts()
+        .unwrap();
+        assert_eq!(fronts.len(), 1);
+        assert_eq!(fronts[0].listen, "localhost:8080");
+
+        let fronts = config(
+            r#"
+            [[fronts]]
+            type = "Grpc"
+            listen = "localhost:8080"
+
+            [[fronts]]
+            type = "Http"
+            listen = "unix:/tmp/timesman.sock"
+            "#,
+        )
+        .fronts()
+        .unwrap();
+        assert_eq!(fronts.len(), 2);
+        assert!(matches!(fronts[1].front_type, FrontType::Http));
+
+        let invalid = [
+            // Nothing to serve.
+            "",
+            // Both ways at once.
+            r#"
+            listen = "localhost:8080"
+            [[fronts]]
+            type = "Grpc"
+            listen = "localhost:8081"
+            "#,
+            // The same address twice.
+            r#"
+            [[fronts]]
+            type = "Grpc"
+            listen = "localhost:8080"
+            [[fronts]]
+            type = "Http"
+            listen = "localhost:8080"
+            "#,
+            // TLS on HTTP.
+            r#"
+            [[fronts]]
+            type = "Http"
+            listen = "localhost:8080"
+            tls = { cert_file = "cert.pem", key_file = "key.pem" }
+            "#,
+        ];
+        for text in invalid {
+            assert!(config(text).fronts().is_err(), "{text}");
+        }
+    }
 }
@@ -0,0 +1,78 @@
+use chrono::NaiveDateTime;
+use timesman_type::{Pid, Post};
+
+/// Where a page of posts ended. Clients get it as an opaque token.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Cursor {
+    pub newest_first: bool,
+    pub created_at: NaiveDateTime,
+    pub pid: Pid,
+}
+
+impl Cursor {
+    pub fn after(post: &Post, newest_first: bool) -> Self {
+        Self {
+            newest_first,
+            created_at: post.created_at,
+            pid: post.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let created_at = self.created_at.and_utc();
+        format!(
+            "{}.{}.{}.{}",
+            self.newest_first as i32,
+            created_at.timestamp(),
+            created_at.timestamp_subsec_nanos(),
+            self.pid
+        )
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        let mut parts = token.split('.');
+        let newest_first = match parts.next()? {
+            "0" => false,
+            "1" => true,
+            _ => return None,
+        };
+        let secs = parts.next()?.parse().ok()?;
+        let nanos = parts.next()?.parse().ok()?;
+        let created_at =
+            chrono::DateTime::from_timestamp(secs, nanos)?.naive_utc();
+        let pid = parts.next()?.parse().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            newest_first,
+            created_at,
+            pid,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_keeps_nanoseconds() {
+        let post = Post {
+            id: 3,
+            post: "post".to_string(),
+            created_at: chrono::DateTime::from_timestamp(1_700_000_000, 1_500)
+                .unwrap()
+                .naive_utc(),
+            updated_at: None,
+            file: None,
+            tag: None,
+        };
+        let cursor = Cursor::after(&post, true);
+
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(Cursor::decode("2.0.0.3"), None);
+        assert_eq!(Cursor::decode("1.0.0.3.4"), None);
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Weak};
+
+use chrono::Utc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, Mutex};
+use tokio_stream::StreamExt;
+
+use timesman_bstore::{Store, StoreError, StoreEvent, StoreEventStream};
+use timesman_type::{Pid, Post, Tag, TagId, Tdid, Tid, Times, Todo, UserId};
+
+/// How many of the latest events are kept for watchers that resume.
+const RECENT_EVENTS: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// What changed. The item is `None` for deletions.
+#[derive(Clone, Debug)]
+pub enum Change {
+    Times(Option<Times>),
+    Post { pid: Pid, post: Option<Post> },
+    Tag { tagid: TagId, tag: Option<Tag> },
+    Todo { tdid: Tdid, todo: Option<Todo> },
+}
+
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub seq: u64,
+    pub kind: EventKind,
+    pub tid: Tid,
+    /// Owner of the times, to decide who may see the event.
+    pub owner: Option<UserId>,
+    pub change: Change,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WatchError {
+    /// The sequence number was never handed out by this server.
+    UnknownSeq(u64),
+    /// Some events after the sequence number are no longer kept.
+    Expired(u64),
+    /// The watcher fell too far behind and missed events.
+    Lagged,
+    /// The server lost track of the store's changes.
+    Missed,
+}
+
+impl std::fmt::Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchError::UnknownSeq(seq) => {
+                write!(f, "Unknown sequence number {seq}")
+            }
+            WatchError::Expired(seq) => {
+                write!(f, "Events after sequence number {seq} have expired")
+            }
+            WatchError::Lagged => write!(f, "Watcher fell behind"),
+            WatchError::Missed => {
+                write!(f, "Some changes to the store were missed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WatchError {}
+
+struct Inner {
+    next_seq: u64,
+    recent: VecDeque<Arc<Event>>,
+}
+
+/// Hands the changes made to the store to the watchers, numbered so that
+/// they can resume.
+///
+/// Sequence numbers start from the clock in microseconds, so that they keep
+/// growing across restarts and a watcher resuming from before a restart is
+/// told it missed events.
+pub struct EventHub {
+    inner: std::sync::Mutex<Inner>,
+    /// `None` tells the watchers that events were missed.
+    sender: broadcast::Sender<Option<Arc<Event>>>,
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(RECENT_EVENTS);
+        let start = Utc::now().timestamp_micros().max(1) as u64;
+
+        Self {
+            inner: std::sync::Mutex::new(Inner {
+                next_seq: start,
+                recent: VecDeque::with_capacity(RECENT_EVENTS),
+            }),
+            sender,
+        }
+    }
+
+    /// Records a change and returns its sequence number.
+    pub fn publish(
+        &self,
+        kind: EventKind,
+        tid: Tid,
+        owner: Option<UserId>,
+        change: Change,
+    ) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+
+        let event = Arc::new(Event {
+            seq: inner.next_seq,
+            kind,
+            tid,
+            owner,
+            change,
+        });
+        inner.next_seq += 1;
+
+        if inner.recent.len() == RECENT_EVENTS {
+            inner.recent.pop_front();
+        }
+        inner.recent.push_back(event.clone());
+
+        // Sent under the lock, so that subscribers see events in order and
+        // none twice. Having no watchers is fine.
+        let _ = self.sender.send(Some(event.clone()));
+
+        event.seq
+    }
+
+    /// Forgets the kept events and ends the current watches, after events
+    /// were lost. A number is skipped, so that resuming from any earlier
+    /// one fails.
+    fn miss(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.recent.clear();
+        inner.next_seq += 1;
+        let _ = self.sender.send(None);
+    }
+
+    /// Starts a hub fed with the changes made to `store`.
+    pub async fn follow(
+        store: Arc<Mutex<dyn Store>>,
+    ) -> Result<Arc<Self>, StoreError> {
+        let events = store.lock().await.subscribe().await?;
+        let owners = load_owners(&store).await?;
+
+        let hub = Arc::new(Self::new());
+        tokio::spawn(feed(Arc::downgrade(&hub), store, events, owners));
+        Ok(hub)
+    }
+
+    /// Starts watching. With `after`, the events following that sequence
+    /// number are replayed first.
+    pub fn subscribe(
+        &self,
+        after: Option<u64>,
+    ) -> Result<Subscription, WatchError> {
+        let inner = self.inner.lock().unwrap();
+        let receiver = self.sender.subscribe();
+
+        let backlog = match after {
+            None => VecDeque::new(),
+            Some(seq) => {
+                if seq >= inner.next_seq {
+                    return Err(WatchError::UnknownSeq(seq));
+                }
+                let oldest =
+                    inner.recent.front().map_or(inner.next_seq, |e| e.seq);
+                if seq + 1 < oldest {
+                    return Err(WatchError::Expired(seq));
+                }
+
+                inner
+                    .recent
+                    .iter()
+                    .filter(|e| e.seq > seq)
+                    .cloned()
+                    .collect()
+            }
+        };
+
+        Ok(Subscription { backlog, receiver })
+    }
+}
+
+pub struct Subscription {
+    backlog: VecDeque<Arc<Event>>,
+    receiver: broadcast::Receiver<Option<Arc<Event>>>,
+}
+
+impl Subscription {
+    /// Waits for the next event. Returns `None` once the hub is gone.
+    pub async fn next(&mut self) -> Result<Option<Arc<Event>>, WatchError> {
+        if let Some(event) = self.backlog.pop_front() {
+            return Ok(Some(event));
+        }
+
+        match self.receiver.recv().await {
+            Ok(Some(event)) => Ok(Some(event)),
+            Ok(None) => Err(WatchError::Missed),
+            Err(RecvError::Closed) => Ok(None),
+            Err(RecvError::Lagged(_)) => Err(WatchError::Lagged),
+        }
+    }
+}
+
+type Owners = HashMap<Tid, Option<UserId>>;
+
+async fn load_owners(
+    store: &Arc<Mutex<dyn Store>>,
+) -> Result<Owners, StoreError> {
+    let mut owners = HashMap::new();
+    for tstore in store.lock().await.get().await? {
+        let times = tstore.lock().await.get().await?;
+        owners.insert(times.id, times.owner);
+    }
+    Ok(owners)
+}
+
+/// Owner of times `tid`, read from the store if it is not known yet.
+async fn owner(
+    owners: &mut Owners,
+    store: &Arc<Mutex<dyn Store>>,
+    tid: Tid,
+) -> Option<UserId> {
+    if !owners.contains_key(&tid) {
+        if let Ok(loaded) = load_owners(store).await {
+            *owners = loaded;
+        }
+    }
+    owners.get(&tid).copied().flatten()
+}
+
+/// Publishes the events of `store` on `hub` until either goes away.
+async fn feed(
+    hub: Weak<EventHub>,
+    store: Arc<Mutex<dyn Store>>,
+    mut events: StoreEventStream,
+    mut owners: Owners,
+) {
+    use EventKind::{Created, Deleted, Updated};
+
+    while let Some(event) = events.next().await {
+        let Some(hub) = hub.upgrade() else {
+            return;
+        };
+
+        let (kind, tid, change) = match event {
+            StoreEvent::TimesCreated(times) => {
+                (Created, times.id, Change::Times(Some(times)))
+            }
+            StoreEvent::TimesUpdated(times) => {
+                (Updated, times.id, Change::Times(Some(times)))
+            }
+            StoreEvent::TimesDeleted(tid) => {
+                (Deleted, tid, Change::Times(None))
+            }
+            StoreEvent::PostCreated(tid, post) => {
+                let pid = post.id;
+                (
+                    Created,
+                    tid,
+                    Change::Post {
+                        pid,
+                        post: Some(post),
+                    },
+                )
+            }
+            StoreEvent::PostUpdated(tid, post) => {
+                let pid = post.id;
+                (
+                    Updated,
+                    tid,
+                    Change::Post {
+                        pid,
+                        post: Some(post),
+                    },
+                )
+            }
+            StoreEvent::PostDeleted(tid, pid) => {
+                (Deleted, tid, Change::Post { pid, post: None })
+            }
+            StoreEvent::TagCreated(tid, tag) => {
+                let tagid = tag.id;
+                (
+                    Created,
+                    tid,
+                    Change::Tag {
+                        tagid,
+                        tag: Some(tag),
+                    },
+                )
+            }
+            StoreEvent::TagUpdated(tid, tag) => {
+                let tagid = tag.id;
+                (
+                    Updated,
+                    tid,
+                    Change::Tag {
+                        tagid,
+                        tag: Some(tag),
+                    },
+                )
+            }
+            StoreEvent::TagDeleted(tid, tagid) => {
+                (Deleted, tid, Change::Tag { tagid, tag: None })
+            }
+            StoreEvent::TodoCreated(tid, todo) => {
+                let tdid = todo.id;
+                (
+                    Created,
+                    tid,
+                    Change::Todo {
+                        tdid,
+                        todo: Some(todo),
+                    },
+                )
+            }
+            StoreEvent::TodoUpdated(tid, todo) => {
+                let tdid = todo.id;
+                (
+                    Updated,
+                    tid,
+                    Change::Todo {
+                        tdid,
+                        todo: Some(todo),
+                    },
+                )
+            }
+            StoreEvent::TodoDeleted(tid, tdid) => {
+                (Deleted, tid, Change::Todo { tdid, todo: None })
+            }
+            StoreEvent::Missed => {
+                hub.miss();
+                if let Ok(loaded) = load_owners(&store).await {
+                    owners = loaded;
+                }
+                continue;
+            }
+        };
+
+        let owner = match &change {
+            Change::Times(Some(times)) => {
+                owners.insert(tid, times.owner);
+                times.owner
+            }
+            Change::Times(None) => owners.remove(&tid).flatten(),
+            _ => owner(&mut owners, &store, tid).await,
+        };
+        hub.publish(kind, tid, owner, change);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publish_times(hub: &EventHub, tid: Tid) -> u64 {
+        hub.publish(EventKind::Deleted, tid, None, Change::Times(None))
+    }
+
+    async fn next_tid(subscription: &mut Subscription) -> Tid {
+        subscription.next().await.unwrap().unwrap().tid
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_sees_later_events() {
+        let hub = EventHub::new();
+        let first = publish_times(&hub, 1);
+
+        let mut subscription = hub.subscribe(None).unwrap();
+        let second = publish_times(&hub, 2);
+
+        assert!(second > first);
+        assert_eq!(next_tid(&mut subscription).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_resume_replays_missed_events() {
+        let hub = EventHub::new();
+        let first = publish_times(&hub, 1);
+        publish_times(&hub, 2);
+        publish_times(&hub, 3);
+
+        let mut subscription = hub.subscribe(Some(first)).unwrap();
+        publish_times(&hub, 4);
+
+        assert_eq!(next_tid(&mut subscription).await, 2);
+        assert_eq!(next_tid(&mut subscription).await, 3);
+        assert_eq!(next_tid(&mut subscription).await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_unknown_or_expired_seq() {
+        let hub = EventHub::new();
+        let first = publish_times(&hub, 1);
+
+        assert_eq!(
+            hub.subscribe(Some(first + 1)).err(),
+            Some(WatchError::UnknownSeq(first + 1))
+        );
+
+        for tid in 0..=RECENT_EVENTS as u64 {
+            publish_times(&hub, tid);
+        }
+        assert_eq!(
+            hub.subscribe(Some(first)).err(),
+            Some(WatchError::Expired(first))
+        );
+        assert!(hub.subscribe(Some(first + 1)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_follow_publishes_store_changes() {
+        let store =
+            timesman_bstore::StoreType::Memory.to_store().await.unwrap();
+        let hub = EventHub::follow(store.clone()).await.unwrap();
+        let mut subscription = hub.subscribe(None).unwrap();
+
+        let owner = Some(uuid::Uuid::new_v4());
+        let tstore = store
+            .lock()
+            .await
+            .create("followed".to_string(), owner)
+            .await
+            .unwrap();
+        let pstore = tstore.lock().await.pstore().await.unwrap();
+        let post = pstore
+            .lock()
+            .await
+            .post("hello".to_string(), None)
+            .await
+            .unwrap();
+        let tid = tstore.lock().await.get().await.unwrap().id;
+        store.lock().await.delete(tid).await.unwrap();
+
+        let event = subscription.next().await.unwrap().unwrap();
+        assert_eq!((event.kind, event.tid), (EventKind::Created, tid));
+        assert_eq!(event.owner, owner);
+        let event = subscription.next().await.unwrap().unwrap();
+        assert_eq!(event.kind, EventKind::Created);
+        assert_eq!(event.owner, owner);
+        assert!(matches!(
+            event.change,
+            Change::Post { pid, post: Some(_) } if pid == post.id
+        ));
+        let event = subscription.next().await.unwrap().unwrap();
+        assert_eq!((event.kind, event.tid), (EventKind::Deleted, tid));
+        assert_eq!(event.owner, owner);
+        assert!(matches!(event.change, Change::Times(None)));
+    }
+
+    #[tokio::test]
+    async fn test_missed_events_end_watches() {
+        let hub = EventHub::new();
+        let seq = publish_times(&hub, 1);
+        let mut subscription = hub.subscribe(None).unwrap();
+
+        hub.miss();
+        assert_eq!(subscription.next().await.err(), Some(WatchError::Missed));
+        assert_eq!(
+            hub.subscribe(Some(seq)).err(),
+            Some(WatchError::Expired(seq))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_restart() {
+        let old = EventHub::new();
+        let seq = publish_times(&old, 1);
+        drop(old);
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+
+        let hub = EventHub::new();
+        assert_eq!(
+            hub.subscribe(Some(seq)).err(),
+            Some(WatchError::Expired(seq))
+        );
+    }
+}
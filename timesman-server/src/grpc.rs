@@ -1,48 +1,260 @@
+mod admin;
+mod page;
+
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{mpsc, Mutex};
 
-use super::{AuthService, TimesManServer};
+use super::authz::{Access, AuthzError, Caller};
+use super::auth::AuthError;
+use super::events::{Change, Event, EventKind, Subscription, WatchError};
+use super::{AuthService, EventHub, Listen, TimesManServer, TlsSettings};
 
-use timesman_bstore::Store;
+use timesman_bstore::{FileReader, Store, StoreError, TimesStore};
+use timesman_type::{File, Tid, Times};
 
 use async_trait::async_trait;
 
+use timesman_grpc::file;
 use timesman_grpc::grpc;
-use timesman_grpc::grpc::times_man_server;
+use timesman_grpc::grpc::{
+    create_post_with_file_chunk, file_chunk, times_man_admin_server,
+    times_man_server,
+};
+
+#[cfg(unix)]
+use tokio_stream::wrappers::UnixListenerStream;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::transport::server::{Server, ServerTlsConfig};
+use tonic::transport::{Certificate, Identity};
+
+pub struct GrpcServer {
+    pub auth_service: Arc<AuthService>,
+    pub events: Arc<EventHub>,
+    pub tls: Option<TlsSettings>,
+    /// Largest attachment accepted, in bytes.
+    pub max_file_size: u64,
+}
 
-use tonic::transport::server::Server;
+fn tls_config(tls: &TlsSettings) -> ServerTlsConfig {
+    let mut config =
+        ServerTlsConfig::new().identity(Identity::from_pem(&tls.cert, &tls.key));
 
-pub struct GrpcServer {}
+    if let Some(ca) = &tls.client_ca {
+        config = config
+            .client_ca_root(Certificate::from_pem(ca))
+            .client_auth_optional(tls.client_auth_optional);
+    }
+    config
+}
 
 #[async_trait]
 impl TimesManServer for GrpcServer {
     async fn run(
         &self,
-        listen: &str,
+        listen: &Listen,
         store: Arc<Mutex<dyn Store>>,
-    ) {
-        let addr = listen.parse().unwrap();
+    ) -> std::io::Result<()> {
+        let server = Arc::new(TMServer {
+            store,
+            auth_service: self.auth_service.clone(),
+            events: self.events.clone(),
+            max_file_size: self.max_file_size,
+        });
 
-        let auth_service = AuthService::new("your-secret-key-here"); // TODO: Make this configurable
-        
-        Server::builder()
-            .add_service(times_man_server::TimesManServer::new(TMServer {
-                store,
-                auth_service,
-            }))
-            .serve(addr)
-            .await
-            .unwrap();
+        let mut builder = Server::builder();
+        if let Some(tls) = &self.tls {
+            builder = builder
+                .tls_config(tls_config(tls))
+                .map_err(std::io::Error::other)?;
+        }
+
+        let router = builder
+            .add_service(times_man_server::TimesManServer::from_arc(
+                server.clone(),
+            ))
+            .add_service(times_man_admin_server::TimesManAdminServer::from_arc(
+                server,
+            ));
+
+        match listen {
+            Listen::Tcp(addr) => {
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                router
+                    .serve_with_incoming(TcpListenerStream::new(listener))
+                    .await
+                    .map_err(std::io::Error::other)
+            }
+            #[cfg(unix)]
+            Listen::Unix(path) => {
+                super::remove_stale_socket(path)?;
+                let listener = tokio::net::UnixListener::bind(path)?;
+                router
+                    .serve_with_incoming(UnixListenerStream::new(listener))
+                    .await
+                    .map_err(std::io::Error::other)
+            }
+        }
     }
 }
 
 pub struct TMServer {
     store: Arc<Mutex<dyn Store>>,
-    auth_service: AuthService,
+    auth_service: Arc<AuthService>,
+    events: Arc<EventHub>,
+    max_file_size: u64,
+}
+
+/// Errors of the login RPCs. Lockouts carry a `retry-after` entry with the
+/// seconds to wait.
+fn login_status(e: AuthError) -> tonic::Status {
+    match e {
+        AuthError::TooManyAttempts { retry_after_secs } => {
+            let mut status = tonic::Status::new(
+                tonic::Code::ResourceExhausted,
+                e.to_string(),
+            );
+            status
+                .metadata_mut()
+                .insert("retry-after", retry_after_secs.into());
+            status
+        }
+        _ => tonic::Status::new(tonic::Code::Unauthenticated, e.to_string()),
+    }
+}
+
+fn totp_status(e: AuthError) -> tonic::Status {
+    let code = match e {
+        AuthError::InvalidTotpCode => tonic::Code::InvalidArgument,
+        AuthError::TotpAlreadyEnabled | AuthError::TotpNotEnabled => {
+            tonic::Code::FailedPrecondition
+        }
+        _ => tonic::Code::Internal,
+    };
+    tonic::Status::new(code, e.to_string())
+}
+
+/// Errors of the store, by what went wrong.
+fn store_status(e: StoreError) -> tonic::Status {
+    let code = match e {
+        StoreError::NotFound(_) => tonic::Code::NotFound,
+        StoreError::AlreadyExists(_) => tonic::Code::AlreadyExists,
+        StoreError::Conflict(_) => tonic::Code::FailedPrecondition,
+        StoreError::Io(_) => tonic::Code::Internal,
+        StoreError::Corrupt(_) => tonic::Code::DataLoss,
+        StoreError::Backend(_) => tonic::Code::Unavailable,
+        StoreError::PermissionDenied(_) => tonic::Code::PermissionDenied,
+        StoreError::NotSupported => tonic::Code::Unimplemented,
+    };
+    tonic::Status::new(code, e.to_string())
+}
+
+fn event_kind(kind: EventKind) -> grpc::EventKind {
+    match kind {
+        EventKind::Created => grpc::EventKind::Created,
+        EventKind::Updated => grpc::EventKind::Updated,
+        EventKind::Deleted => grpc::EventKind::Deleted,
+    }
+}
+
+fn watch_status(e: WatchError) -> tonic::Status {
+    tonic::Status::new(tonic::Code::OutOfRange, e.to_string())
+}
+
+fn times_deleted(tid: Tid) -> tonic::Status {
+    tonic::Status::new(
+        tonic::Code::NotFound,
+        format!("Times with id {} was deleted", tid),
+    )
+}
+
+type WatchStream<T> = ReceiverStream<Result<T, tonic::Status>>;
+
+type FileStream = ReceiverStream<Result<grpc::FileChunk, tonic::Status>>;
+
+/// Streams the file `info` describes: the info first, then the data read
+/// from `reader` as it is sent.
+fn download(info: File, mut reader: FileReader) -> FileStream {
+    let (tx, rx) = mpsc::channel(4);
+
+    tokio::spawn(async move {
+        let info = grpc::FileChunk {
+            chunk: Some(file_chunk::Chunk::Info(file::info(&info))),
+        };
+        if tx.send(Ok(info)).await.is_err() {
+            return;
+        }
+
+        loop {
+            let mut data = Vec::with_capacity(file::CHUNK_SIZE);
+            let read = (&mut reader)
+                .take(file::CHUNK_SIZE as u64)
+                .read_to_end(&mut data)
+                .await;
+            let item = match read {
+                Ok(0) => return,
+                Ok(_) => Ok(grpc::FileChunk {
+                    chunk: Some(file_chunk::Chunk::Data(data)),
+                }),
+                Err(e) => Err(tonic::Status::new(
+                    tonic::Code::Internal,
+                    format!("Reading the file failed: {e}"),
+                )),
+            };
+
+            let last = item.is_err();
+            if tx.send(item).await.is_err() || last {
+                return;
+            }
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
+
+/// Streams what `select` makes of each event, skipping `None`. When
+/// watching inside the times `within`, the stream ends with NOT_FOUND once
+/// that times is deleted.
+fn watch<T: Send + 'static>(
+    mut subscription: Subscription,
+    within: Option<Tid>,
+    mut select: impl FnMut(&Event) -> Option<T> + Send + 'static,
+) -> WatchStream<T> {
+    let (tx, rx) = mpsc::channel(16);
+
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                _ = tx.closed() => return,
+                event = subscription.next() => event,
+            };
+
+            let item = match event {
+                Ok(Some(event)) => match (&event.change, within) {
+                    (Change::Times(None), Some(tid)) if event.tid == tid => {
+                        Err(times_deleted(tid))
+                    }
+                    _ => match select(&event) {
+                        Some(item) => Ok(item),
+                        None => continue,
+                    },
+                },
+                Ok(None) => return,
+                Err(e) => Err(watch_status(e)),
+            };
+
+            let last = item.is_err();
+            if tx.send(item).await.is_err() || last {
+                return;
+            }
+        }
+    });
+
+    ReceiverStream::new(rx)
 }
 
 impl TMServer {
-    fn validate_token(&self, request: &tonic::Request<impl std::fmt::Debug>) -> Result<timesman_type::Claims, tonic::Status> {
+    async fn validate_token(&self, request: &tonic::Request<impl std::fmt::Debug>) -> Result<timesman_type::Claims, tonic::Status> {
         let metadata = request.metadata();
         
         // Try to get the authorization header
@@ -64,8 +276,70 @@ impl TMServer {
         // Validate token
         self.auth_service
             .validate_token(token)
+            .await
             .map_err(|e| tonic::Status::new(tonic::Code::Unauthenticated, e.to_string()))
     }
+
+    /// Validates the request's token and checks that the caller's role
+    /// allows `access`.
+    async fn authenticate(
+        &self,
+        request: &tonic::Request<impl std::fmt::Debug>,
+        access: Access,
+    ) -> Result<Caller, tonic::Status> {
+        let claims = self.validate_token(request).await?;
+
+        let caller = Caller::from_claims(&claims).map_err(|e| {
+            tonic::Status::new(tonic::Code::Unauthenticated, e.to_string())
+        })?;
+        caller.authorize(access).map_err(|e| {
+            tonic::Status::new(tonic::Code::PermissionDenied, e.to_string())
+        })?;
+
+        Ok(caller)
+    }
+
+    /// Looks up the times `tid` and checks that `caller` may access it.
+    async fn times_store(
+        &self,
+        caller: &Caller,
+        tid: Tid,
+    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, tonic::Status> {
+        let (_, times_store) = self.find_times(caller, tid).await?;
+        Ok(times_store)
+    }
+
+    /// Like `times_store`, also returning the times itself.
+    async fn find_times(
+        &self,
+        caller: &Caller,
+        tid: Tid,
+    ) -> Result<(Times, Arc<Mutex<dyn TimesStore + Send + Sync>>), tonic::Status>
+    {
+        let mut store = self.store.lock().await;
+
+        let times_stores = store.get().await.map_err(store_status)?;
+
+        for times_store in times_stores {
+            let times =
+                times_store.lock().await.get().await.map_err(store_status)?;
+
+            if times.id == tid {
+                if !caller.can_access(&times) {
+                    return Err(tonic::Status::new(
+                        tonic::Code::PermissionDenied,
+                        AuthzError::NotOwner.to_string(),
+                    ));
+                }
+                return Ok((times, times_store));
+            }
+        }
+
+        Err(tonic::Status::new(
+            tonic::Code::NotFound,
+            format!("Times with id {} not found", tid),
+        ))
+    }
 }
 
 #[async_trait]
@@ -78,6 +352,9 @@ impl times_man_server::TimesMan for TMServer {
         
         match self.auth_service.register(register_req).await {
             Ok(auth_response) => Ok(tonic::Response::new(grpc::AuthResponse::from(auth_response))),
+            Err(e @ AuthError::UserAlreadyExists) => {
+                Err(tonic::Status::new(tonic::Code::AlreadyExists, e.to_string()))
+            }
             Err(e) => Err(tonic::Status::new(tonic::Code::InvalidArgument, e.to_string())),
         }
     }
@@ -86,32 +363,217 @@ impl times_man_server::TimesMan for TMServer {
         &self,
         request: tonic::Request<grpc::LoginRequest>,
     ) -> Result<tonic::Response<grpc::AuthResponse>, tonic::Status> {
+        let peer = request.remote_addr().map(|addr| addr.ip());
         let login_req: timesman_type::LoginRequest = request.into_inner().into();
         
-        match self.auth_service.login(login_req).await {
+        match self.auth_service.login_from(login_req, peer).await {
+            Ok(auth_response) => Ok(tonic::Response::new(grpc::AuthResponse::from(auth_response))),
+            Err(AuthError::SecondFactorRequired { challenge }) => {
+                Ok(tonic::Response::new(grpc::AuthResponse {
+                    totp_challenge: challenge,
+                    ..Default::default()
+                }))
+            }
+            Err(e) => Err(login_status(e)),
+        }
+    }
+
+    async fn verify_totp(
+        &self,
+        request: tonic::Request<grpc::VerifyTotpRequest>,
+    ) -> Result<tonic::Response<grpc::AuthResponse>, tonic::Status> {
+        let peer = request.remote_addr().map(|addr| addr.ip());
+        let params = request.into_inner();
+
+        self.auth_service
+            .verify_totp_from(&params.challenge, &params.code, peer)
+            .await
+            .map(|auth_response| tonic::Response::new(auth_response.into()))
+            .map_err(login_status)
+    }
+
+    async fn enroll_totp(
+        &self,
+        request: tonic::Request<()>,
+    ) -> Result<tonic::Response<grpc::TotpEnrollment>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        caller.require_session().map_err(|e| {
+            tonic::Status::new(tonic::Code::PermissionDenied, e.to_string())
+        })?;
+
+        let enrollment = self
+            .auth_service
+            .enroll_totp(&caller.id)
+            .await
+            .map_err(totp_status)?;
+
+        Ok(tonic::Response::new(grpc::TotpEnrollment {
+            secret: enrollment.secret,
+            otpauth_uri: enrollment.uri,
+        }))
+    }
+
+    async fn confirm_totp(
+        &self,
+        request: tonic::Request<grpc::TotpCode>,
+    ) -> Result<tonic::Response<grpc::TotpBackupCodes>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        caller.require_session().map_err(|e| {
+            tonic::Status::new(tonic::Code::PermissionDenied, e.to_string())
+        })?;
+
+        let codes = self
+            .auth_service
+            .confirm_totp(&caller.id, &request.into_inner().code)
+            .await
+            .map_err(totp_status)?;
+
+        Ok(tonic::Response::new(grpc::TotpBackupCodes { codes }))
+    }
+
+    async fn disable_totp(
+        &self,
+        request: tonic::Request<grpc::TotpCode>,
+    ) -> Result<tonic::Response<()>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        caller.require_session().map_err(|e| {
+            tonic::Status::new(tonic::Code::PermissionDenied, e.to_string())
+        })?;
+
+        self.auth_service
+            .disable_totp(&caller.id, &request.into_inner().code)
+            .await
+            .map_err(totp_status)?;
+
+        Ok(tonic::Response::new(()))
+    }
+    async fn refresh_token(
+        &self,
+        request: tonic::Request<grpc::RefreshTokenRequest>,
+    ) -> Result<tonic::Response<grpc::AuthResponse>, tonic::Status> {
+        let refresh_token = request.into_inner().refresh_token;
+
+        match self.auth_service.refresh(&refresh_token).await {
             Ok(auth_response) => Ok(tonic::Response::new(grpc::AuthResponse::from(auth_response))),
             Err(e) => Err(tonic::Status::new(tonic::Code::Unauthenticated, e.to_string())),
         }
     }
+
+    async fn logout(
+        &self,
+        request: tonic::Request<grpc::LogoutRequest>,
+    ) -> Result<tonic::Response<()>, tonic::Status> {
+        let claims = self.validate_token(&request).await?;
+        let refresh_token = request.into_inner().refresh_token;
+
+        self.auth_service
+            .logout(&claims, refresh_token.as_deref())
+            .await
+            .map_err(|e| tonic::Status::new(tonic::Code::InvalidArgument, e.to_string()))?;
+
+        Ok(tonic::Response::new(()))
+    }
+
+    async fn create_personal_access_token(
+        &self,
+        request: tonic::Request<grpc::CreatePersonalAccessTokenRequest>,
+    ) -> Result<tonic::Response<grpc::CreatedPersonalAccessToken>, tonic::Status>
+    {
+        let caller = self.authenticate(&request, Access::Write).await?;
+        caller.require_session().map_err(|e| {
+            tonic::Status::new(tonic::Code::PermissionDenied, e.to_string())
+        })?;
+        let params = request.into_inner();
+        let scope: timesman_type::TokenScope =
+            params.scope.map(|s| s.into()).unwrap_or_default();
+
+        if let Some(tid) = scope.tid {
+            self.times_store(&caller, tid).await?;
+        }
+
+        let (info, token) = self
+            .auth_service
+            .create_personal_token(
+                &caller.id,
+                params.name,
+                scope,
+                params.expires_in_days,
+            )
+            .await
+            .map_err(|e| {
+                tonic::Status::new(tonic::Code::Internal, e.to_string())
+            })?;
+
+        Ok(tonic::Response::new(grpc::CreatedPersonalAccessToken {
+            info: Some(info.into()),
+            token,
+        }))
+    }
+
+    async fn list_personal_access_tokens(
+        &self,
+        request: tonic::Request<()>,
+    ) -> Result<tonic::Response<grpc::PersonalAccessTokenArray>, tonic::Status>
+    {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        caller.require_session().map_err(|e| {
+            tonic::Status::new(tonic::Code::PermissionDenied, e.to_string())
+        })?;
+
+        let tokens = self
+            .auth_service
+            .list_personal_tokens(&caller.id)
+            .await
+            .map_err(|e| {
+                tonic::Status::new(tonic::Code::Internal, e.to_string())
+            })?;
+
+        Ok(tonic::Response::new(grpc::PersonalAccessTokenArray {
+            tokens: tokens.into_iter().map(|t| t.into()).collect(),
+        }))
+    }
+
+    async fn revoke_personal_access_token(
+        &self,
+        request: tonic::Request<grpc::PersonalAccessTokenId>,
+    ) -> Result<tonic::Response<()>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        caller.require_session().map_err(|e| {
+            tonic::Status::new(tonic::Code::PermissionDenied, e.to_string())
+        })?;
+        let id = request.into_inner().id.parse().map_err(|_| {
+            tonic::Status::new(tonic::Code::InvalidArgument, "Invalid token id")
+        })?;
+
+        self.auth_service
+            .revoke_personal_token(&caller.id, &id)
+            .await
+            .map_err(|e| match e {
+                AuthError::TokenNotFound => {
+                    tonic::Status::new(tonic::Code::NotFound, e.to_string())
+                }
+                _ => tonic::Status::new(tonic::Code::Internal, e.to_string()),
+            })?;
+
+        Ok(tonic::Response::new(()))
+    }
+
     async fn get_times(
         &self,
         request: tonic::Request<()>,
     ) -> Result<tonic::Response<grpc::TimesArray>, tonic::Status> {
-        // Validate authentication
-        let _claims = self.validate_token(&request)?;
+        let caller = self.authenticate(&request, Access::Read).await?;
         let mut store = self.store.lock().await;
 
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_stores = store.get().await.map_err(store_status)?;
 
         let mut timeses = Vec::new();
         for times_store in times_stores {
             let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            timeses.push(grpc::Times::from(times));
+            let times = ts.get().await.map_err(store_status)?;
+            if caller.can_access(&times) {
+                timeses.push(grpc::Times::from(times));
+            }
         }
 
         Ok(tonic::Response::new(grpc::TimesArray { timeses }))
@@ -121,17 +583,19 @@ impl times_man_server::TimesMan for TMServer {
         &self,
         request: tonic::Request<grpc::TimesTitle>,
     ) -> Result<tonic::Response<grpc::Times>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Write).await?;
+        caller.can_create_times().map_err(|e| {
+            tonic::Status::new(tonic::Code::PermissionDenied, e.to_string())
+        })?;
         let mut store = self.store.lock().await;
         let title = request.into_inner().title;
 
-        let times_store = store.create(title).await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
-
-        let mut ts = times_store.lock().await;
-        let times = ts.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = store
+            .create(title, Some(caller.id))
+            .await
+            .map_err(store_status)?;
+        let times =
+            times_store.lock().await.get().await.map_err(store_status)?;
 
         Ok(tonic::Response::new(grpc::Times::from(times)))
     }
@@ -140,12 +604,13 @@ impl times_man_server::TimesMan for TMServer {
         &self,
         request: tonic::Request<grpc::TimesId>,
     ) -> Result<tonic::Response<()>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Write).await?;
         let tid = request.into_inner().id;
 
-        store.delete(tid).await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        self.times_store(&caller, tid).await?;
+
+        let mut store = self.store.lock().await;
+        store.delete(tid).await.map_err(store_status)?;
 
         Ok(tonic::Response::new(()))
     }
@@ -154,373 +619,417 @@ impl times_man_server::TimesMan for TMServer {
         &self,
         request: tonic::Request<grpc::Times>,
     ) -> Result<tonic::Response<grpc::Times>, tonic::Status> {
-        let mut store = self.store.lock().await;
-        let times_data: timesman_type::Times = request.into_inner().into();
-        let tid = times_data.id;
+        let caller = self.authenticate(&request, Access::Write).await?;
+        // Malformed timestamps are an InvalidArgument.
+        let mut times_data = Times::try_from(request.into_inner())?;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = self.times_store(&caller, times_data.id).await?;
+        let mut ts = times_store.lock().await;
 
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let updated_times = ts.update(times_data).await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
+        // Ownership is decided by the server, never by the client.
+        let times = ts.get().await.map_err(store_status)?;
+        times_data.owner = times.owner;
 
-                return Ok(tonic::Response::new(grpc::Times::from(updated_times)));
-            }
-        }
+        let updated_times =
+            ts.update(times_data).await.map_err(store_status)?;
 
-        Err(tonic::Status::new(
-            tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
-        ))
+        Ok(tonic::Response::new(grpc::Times::from(updated_times)))
     }
 
     async fn get_posts(
         &self,
         request: tonic::Request<grpc::TimesId>,
     ) -> Result<tonic::Response<grpc::PostArray>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Read).await?;
         let tid = request.into_inner().id;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = self.times_store(&caller, tid).await?;
+        let mut ts = times_store.lock().await;
 
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let post_store = ts.pstore().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-                let mut ps = post_store.lock().await;
-                let posts = ps.get_all().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-
-                let posts = posts
-                    .iter()
-                    .map(|p| grpc::Post::from(p.clone()))
-                    .collect::<Vec<grpc::Post>>();
-
-                return Ok(tonic::Response::new(grpc::PostArray { posts }));
-            }
-        }
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let posts = ps.get_all().await.map_err(store_status)?;
 
-        Err(tonic::Status::new(
-            tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
-        ))
+        let posts = posts
+            .iter()
+            .map(|p| grpc::Post::from(p.clone()))
+            .collect::<Vec<grpc::Post>>();
+
+        Ok(tonic::Response::new(grpc::PostArray { posts }))
+    }
+
+    async fn get_post(
+        &self,
+        request: tonic::Request<grpc::GetPostParams>,
+    ) -> Result<tonic::Response<grpc::Post>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        let params = request.into_inner();
+
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let post = ps.get(params.pid).await.map_err(store_status)?;
+
+        Ok(tonic::Response::new(grpc::Post::from(post)))
+    }
+
+    async fn list_posts(
+        &self,
+        request: tonic::Request<grpc::ListPostsRequest>,
+    ) -> Result<tonic::Response<grpc::ListPostsResponse>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        let params = request.into_inner();
+
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let query = page::query(&params)
+            .map_err(|e| tonic::Status::new(tonic::Code::InvalidArgument, e))?;
+        let posts = ps.list(&query).await.map_err(store_status)?;
+
+        Ok(tonic::Response::new(page::page(posts, &params)))
     }
 
     async fn create_post(
         &self,
         request: tonic::Request<grpc::CreatePostPrams>,
     ) -> Result<tonic::Response<grpc::Post>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Write).await?;
         let params = request.into_inner();
-        let tid = params.id;
-        let text = params.text;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = self.times_store(&caller, params.id).await?;
+        let mut ts = times_store.lock().await;
 
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let post_store = ts.pstore().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-                let mut ps = post_store.lock().await;
-                let post = ps.post(text, None).await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-
-                return Ok(tonic::Response::new(grpc::Post::from(post)));
-            }
-        }
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let post = ps.post(params.text, None).await.map_err(store_status)?;
 
-        Err(tonic::Status::new(
-            tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
-        ))
+        Ok(tonic::Response::new(grpc::Post::from(post)))
     }
 
     async fn delete_post(
         &self,
         request: tonic::Request<grpc::DeletePostParam>,
     ) -> Result<tonic::Response<()>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Write).await?;
         let params = request.into_inner();
-        let tid = params.tid;
-        let pid = params.pid;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
 
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let post_store = ts.pstore().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-                let mut ps = post_store.lock().await;
-                ps.delete(pid).await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-
-                return Ok(tonic::Response::new(()));
-            }
-        }
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        ps.delete(params.pid).await.map_err(store_status)?;
 
-        Err(tonic::Status::new(
-            tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
-        ))
+        Ok(tonic::Response::new(()))
     }
 
     async fn update_post(
         &self,
         request: tonic::Request<grpc::UpdatePostParam>,
     ) -> Result<tonic::Response<grpc::Post>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Write).await?;
         let params = request.into_inner();
-        let tid = params.tid;
         let post_data = params.post.ok_or_else(|| {
             tonic::Status::new(tonic::Code::InvalidArgument, "Post data is required")
         })?;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
 
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let post_store = ts.pstore().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-                let mut ps = post_store.lock().await;
-                let updated_post = ps.update(post_data.into()).await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-
-                return Ok(tonic::Response::new(grpc::Post::from(updated_post)));
-            }
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+
+        let updated_post = ps
+            .edit(post_data.id, post_data.post, Some(post_data.tagid))
+            .await
+            .map_err(store_status)?;
+
+        Ok(tonic::Response::new(grpc::Post::from(updated_post)))
+    }
+
+    async fn create_post_with_file(
+        &self,
+        request: tonic::Request<
+            tonic::Streaming<grpc::CreatePostWithFileChunk>,
+        >,
+    ) -> Result<tonic::Response<grpc::Post>, tonic::Status> {
+        // The stream is not Sync, so it is kept out of the awaits.
+        let (metadata, extensions, mut stream) = request.into_parts();
+        let request = tonic::Request::from_parts(metadata, extensions, ());
+        let caller = self.authenticate(&request, Access::Write).await?;
+
+        let header = match stream.message().await? {
+            Some(grpc::CreatePostWithFileChunk {
+                chunk: Some(create_post_with_file_chunk::Chunk::Header(header)),
+            }) => header,
+            _ => {
+                return Err(tonic::Status::new(
+                    tonic::Code::InvalidArgument,
+                    "The first message must be the header",
+                ))
+            }
+        };
+        let info = header.file.ok_or_else(|| {
+            tonic::Status::new(
+                tonic::Code::InvalidArgument,
+                "File info is required",
+            )
+        })?;
+        if info.size > self.max_file_size {
+            return Err(tonic::Status::new(
+                tonic::Code::ResourceExhausted,
+                format!("Files are limited to {} bytes", self.max_file_size),
+            ));
         }
 
-        Err(tonic::Status::new(
-            tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
-        ))
+        // Checked before taking the data.
+        let times_store = self.times_store(&caller, header.tid).await?;
+
+        let mut data = Vec::with_capacity(info.size as usize);
+        while let Some(message) = stream.message().await? {
+            let Some(create_post_with_file_chunk::Chunk::Data(chunk)) =
+                message.chunk
+            else {
+                return Err(tonic::Status::new(
+                    tonic::Code::InvalidArgument,
+                    "Only data may follow the header",
+                ));
+            };
+            if (data.len() + chunk.len()) as u64 > info.size {
+                return Err(tonic::Status::new(
+                    tonic::Code::InvalidArgument,
+                    "More data than the file size",
+                ));
+            }
+            data.extend_from_slice(&chunk);
+        }
+        if data.len() as u64 != info.size {
+            return Err(tonic::Status::new(
+                tonic::Code::InvalidArgument,
+                "Less data than the file size",
+            ));
+        }
+
+        let mut ts = times_store.lock().await;
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let file = file::from_parts(&info, data);
+        let post = ps
+            .post(header.text, Some(file))
+            .await
+            .map_err(store_status)?;
+
+        Ok(tonic::Response::new(grpc::Post::from(post)))
+    }
+
+    type DownloadFileStream = FileStream;
+
+    async fn download_file(
+        &self,
+        request: tonic::Request<grpc::DownloadFileParams>,
+    ) -> Result<tonic::Response<Self::DownloadFileStream>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        let params = request.into_inner();
+
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let (info, reader) =
+            ps.open_file(params.pid).await.map_err(store_status)?;
+
+        Ok(tonic::Response::new(download(info, reader)))
+    }
+
+    async fn get_tags(
+        &self,
+        request: tonic::Request<grpc::TimesId>,
+    ) -> Result<tonic::Response<grpc::TagArray>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        let tid = request.into_inner().id;
+
+        let times_store = self.times_store(&caller, tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let tags = ps.get_tags().await.map_err(store_status)?;
+
+        let tags = tags.into_iter().map(grpc::Tag::from).collect();
+
+        Ok(tonic::Response::new(grpc::TagArray { tags }))
+    }
+
+    async fn create_tag(
+        &self,
+        request: tonic::Request<grpc::CreateTagParams>,
+    ) -> Result<tonic::Response<grpc::Tag>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Write).await?;
+        let params = request.into_inner();
+
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let tag = ps.create_tag(params.name).await.map_err(store_status)?;
+
+        Ok(tonic::Response::new(grpc::Tag::from(tag)))
+    }
+
+    async fn rename_tag(
+        &self,
+        request: tonic::Request<grpc::RenameTagParams>,
+    ) -> Result<tonic::Response<grpc::Tag>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Write).await?;
+        let params = request.into_inner();
+
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let tag = ps
+            .rename_tag(params.tagid, params.name)
+            .await
+            .map_err(store_status)?;
+
+        Ok(tonic::Response::new(grpc::Tag::from(tag)))
+    }
+
+    async fn delete_tag(
+        &self,
+        request: tonic::Request<grpc::DeleteTagParams>,
+    ) -> Result<tonic::Response<()>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Write).await?;
+        let params = request.into_inner();
+
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        ps.delete_tag(params.tagid).await.map_err(store_status)?;
+
+        Ok(tonic::Response::new(()))
+    }
+
+    async fn assign_tag(
+        &self,
+        request: tonic::Request<grpc::AssignTagParams>,
+    ) -> Result<tonic::Response<grpc::Post>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Write).await?;
+        let params = request.into_inner();
+
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let post_store = ts.pstore().await.map_err(store_status)?;
+        let mut ps = post_store.lock().await;
+        let post = ps
+            .assign_tag(params.pid, params.tagid)
+            .await
+            .map_err(store_status)?;
+
+        Ok(tonic::Response::new(grpc::Post::from(post)))
     }
 
     async fn get_todos(
         &self,
         request: tonic::Request<grpc::TimesId>,
     ) -> Result<tonic::Response<grpc::TodoArray>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Read).await?;
         let tid = request.into_inner().id;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = self.times_store(&caller, tid).await?;
+        let mut ts = times_store.lock().await;
 
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let todo_store = ts.tdstore().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-                let mut tds = todo_store.lock().await;
-                let todos = tds.get().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-
-                let todos = todos
-                    .iter()
-                    .map(|t| grpc::Todo::from(t.clone()))
-                    .collect::<Vec<grpc::Todo>>();
-
-                return Ok(tonic::Response::new(grpc::TodoArray { todos }));
-            }
-        }
+        let todo_store = ts.tdstore().await.map_err(store_status)?;
+        let mut tds = todo_store.lock().await;
+        let todos = tds.get().await.map_err(store_status)?;
 
-        Err(tonic::Status::new(
-            tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
-        ))
+        let todos = todos
+            .iter()
+            .map(|t| grpc::Todo::from(t.clone()))
+            .collect::<Vec<grpc::Todo>>();
+
+        Ok(tonic::Response::new(grpc::TodoArray { todos }))
     }
 
     async fn create_todo(
         &self,
         request: tonic::Request<grpc::CreateTodoParams>,
     ) -> Result<tonic::Response<grpc::Todo>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Write).await?;
         let params = request.into_inner();
-        let tid = params.tid;
-        let content = params.content;
-        let detail = params.detail;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
 
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let todo_store = ts.tdstore().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-                let mut tds = todo_store.lock().await;
-                let mut todo = tds.new(content).await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-
-                // If detail is provided, update the todo with the detail
-                if let Some(detail_text) = detail {
-                    todo.detail = Some(detail_text);
-                    todo = tds.update(todo).await.map_err(|e| {
-                        tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                    })?;
-                }
+        let todo_store = ts.tdstore().await.map_err(store_status)?;
+        let mut tds = todo_store.lock().await;
+        let mut todo = tds.new(params.content).await.map_err(store_status)?;
 
-                return Ok(tonic::Response::new(grpc::Todo::from(todo)));
-            }
+        // If detail is provided, update the todo with the detail
+        if let Some(detail_text) = params.detail {
+            todo.detail = Some(detail_text);
+            todo = tds.update(todo).await.map_err(store_status)?;
         }
 
-        Err(tonic::Status::new(
-            tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
-        ))
+        Ok(tonic::Response::new(grpc::Todo::from(todo)))
     }
 
     async fn done_todo(
         &self,
         request: tonic::Request<grpc::DoneTodoParams>,
     ) -> Result<tonic::Response<grpc::Todo>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Write).await?;
         let params = request.into_inner();
-        let tid = params.tid;
-        let tdid = params.tdid;
-        let done = params.done;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
-
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let todo_store = ts.tdstore().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-                let mut tds = todo_store.lock().await;
-                let todo = tds.done(tdid, done).await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
 
-                return Ok(tonic::Response::new(grpc::Todo::from(todo)));
-            }
-        }
+        let todo_store = ts.tdstore().await.map_err(store_status)?;
+        let mut tds = todo_store.lock().await;
+        let todo = tds
+            .done(params.tdid, params.done)
+            .await
+            .map_err(store_status)?;
 
-        Err(tonic::Status::new(
-            tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
-        ))
+        Ok(tonic::Response::new(grpc::Todo::from(todo)))
     }
 
     async fn get_todo_detail(
         &self,
         request: tonic::Request<grpc::TodoDetailParams>,
     ) -> Result<tonic::Response<grpc::Todo>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Read).await?;
         let params = request.into_inner();
-        let tid = params.tid;
         let tdid = params.tdid;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
 
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let todo_store = ts.tdstore().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-                let mut tds = todo_store.lock().await;
-                let todos = tds.get().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-
-                // Find the specific todo by ID
-                for todo in todos {
-                    if todo.id == tdid {
-                        return Ok(tonic::Response::new(grpc::Todo::from(todo)));
-                    }
-                }
+        let todo_store = ts.tdstore().await.map_err(store_status)?;
+        let mut tds = todo_store.lock().await;
+        let todos = tds.get().await.map_err(store_status)?;
 
-                return Err(tonic::Status::new(
-                    tonic::Code::NotFound,
-                    format!("Todo with id {} not found", tdid),
-                ));
+        // Find the specific todo by ID
+        for todo in todos {
+            if todo.id == tdid {
+                return Ok(tonic::Response::new(grpc::Todo::from(todo)));
             }
         }
 
         Err(tonic::Status::new(
             tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
+            format!("Todo with id {} not found", tdid),
         ))
     }
 
@@ -528,85 +1037,277 @@ impl times_man_server::TimesMan for TMServer {
         &self,
         request: tonic::Request<grpc::UpdateTodoDetailParams>,
     ) -> Result<tonic::Response<grpc::Todo>, tonic::Status> {
-        let mut store = self.store.lock().await;
+        let caller = self.authenticate(&request, Access::Write).await?;
         let params = request.into_inner();
-        let tid = params.tid;
         let tdid = params.tdid;
-        let new_detail = params.detail;
 
-        // Find the times store by ID
-        let times_stores = store.get().await.map_err(|e| {
-            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-        })?;
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
 
-        for times_store in times_stores {
-            let mut ts = times_store.lock().await;
-            let times = ts.get().await.map_err(|e| {
-                tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-            })?;
-            
-            if times.id == tid {
-                let todo_store = ts.tdstore().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-                let mut tds = todo_store.lock().await;
-                let todos = tds.get().await.map_err(|e| {
-                    tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                })?;
-
-                // Find and update the specific todo by ID
-                for mut todo in todos {
-                    if todo.id == tdid {
-                        todo.detail = Some(new_detail);
-                        let updated_todo = tds.update(todo).await.map_err(|e| {
-                            tonic::Status::new(tonic::Code::Aborted, format!("{e}"))
-                        })?;
-
-                        return Ok(tonic::Response::new(grpc::Todo::from(updated_todo)));
-                    }
-                }
+        let todo_store = ts.tdstore().await.map_err(store_status)?;
+        let mut tds = todo_store.lock().await;
+        let todos = tds.get().await.map_err(store_status)?;
 
-                return Err(tonic::Status::new(
-                    tonic::Code::NotFound,
-                    format!("Todo with id {} not found", tdid),
-                ));
+        // Find and update the specific todo by ID
+        for mut todo in todos {
+            if todo.id == tdid {
+                todo.detail = Some(params.detail);
+                let updated_todo =
+                    tds.update(todo).await.map_err(store_status)?;
+
+                return Ok(tonic::Response::new(grpc::Todo::from(updated_todo)));
             }
         }
 
         Err(tonic::Status::new(
             tonic::Code::NotFound,
-            format!("Times with id {} not found", tid),
+            format!("Todo with id {} not found", tdid),
         ))
     }
+
+    async fn update_todo(
+        &self,
+        request: tonic::Request<grpc::UpdateTodoParams>,
+    ) -> Result<tonic::Response<grpc::Todo>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Write).await?;
+        let params = request.into_inner();
+        let tdid = params.tdid;
+
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let todo_store = ts.tdstore().await.map_err(store_status)?;
+        let mut tds = todo_store.lock().await;
+        let todos = tds.get().await.map_err(store_status)?;
+
+        let Some(mut todo) = todos.into_iter().find(|t| t.id == tdid) else {
+            return Err(tonic::Status::new(
+                tonic::Code::NotFound,
+                format!("Todo with id {} not found", tdid),
+            ));
+        };
+
+        todo.content = params.content;
+        todo.detail = params.detail;
+        todo.done_at = match (todo.done_at, params.done) {
+            (_, false) => None,
+            (Some(done_at), true) => Some(done_at),
+            (None, true) => Some(chrono::Utc::now().naive_local()),
+        };
+
+        let todo = tds.update(todo).await.map_err(store_status)?;
+
+        Ok(tonic::Response::new(grpc::Todo::from(todo)))
+    }
+
+    async fn delete_todo(
+        &self,
+        request: tonic::Request<grpc::DeleteTodoParams>,
+    ) -> Result<tonic::Response<()>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Write).await?;
+        let params = request.into_inner();
+
+        let times_store = self.times_store(&caller, params.tid).await?;
+        let mut ts = times_store.lock().await;
+
+        let todo_store = ts.tdstore().await.map_err(store_status)?;
+        let mut tds = todo_store.lock().await;
+        tds.delete(params.tdid).await.map_err(store_status)?;
+
+        Ok(tonic::Response::new(()))
+    }
+
+    type WatchTimesStream = WatchStream<grpc::TimesEvent>;
+
+    async fn watch_times(
+        &self,
+        request: tonic::Request<grpc::WatchTimesRequest>,
+    ) -> Result<tonic::Response<Self::WatchTimesStream>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        let subscription = self
+            .events
+            .subscribe(request.into_inner().after_seq)
+            .map_err(watch_status)?;
+
+        Ok(tonic::Response::new(watch(subscription, None, move |event| {
+            let Change::Times(times) = &event.change else {
+                return None;
+            };
+            if !caller.can_access_times(event.tid, event.owner) {
+                return None;
+            }
+
+            Some(grpc::TimesEvent {
+                seq: event.seq,
+                kind: event_kind(event.kind) as i32,
+                tid: event.tid,
+                times: times.clone().map(grpc::Times::from),
+            })
+        })))
+    }
+
+    type WatchPostsStream = WatchStream<grpc::PostEvent>;
+
+    async fn watch_posts(
+        &self,
+        request: tonic::Request<grpc::WatchPostsRequest>,
+    ) -> Result<tonic::Response<Self::WatchPostsStream>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        let params = request.into_inner();
+        let tid = params.tid;
+
+        self.times_store(&caller, tid).await?;
+        let subscription =
+            self.events.subscribe(params.after_seq).map_err(watch_status)?;
+
+        Ok(tonic::Response::new(watch(
+            subscription,
+            Some(tid),
+            move |event| match &event.change {
+                Change::Post { pid, post } if event.tid == tid => {
+                    Some(grpc::PostEvent {
+                        seq: event.seq,
+                        kind: event_kind(event.kind) as i32,
+                        tid,
+                        pid: *pid,
+                        post: post.clone().map(grpc::Post::from),
+                    })
+                }
+                _ => None,
+            },
+        )))
+    }
+
+    type WatchTodosStream = WatchStream<grpc::TodoEvent>;
+
+    async fn watch_todos(
+        &self,
+        request: tonic::Request<grpc::WatchTodosRequest>,
+    ) -> Result<tonic::Response<Self::WatchTodosStream>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        let params = request.into_inner();
+        let tid = params.tid;
+
+        self.times_store(&caller, tid).await?;
+        let subscription =
+            self.events.subscribe(params.after_seq).map_err(watch_status)?;
+
+        Ok(tonic::Response::new(watch(
+            subscription,
+            Some(tid),
+            move |event| match &event.change {
+                Change::Todo { tdid, todo } if event.tid == tid => {
+                    Some(grpc::TodoEvent {
+                        seq: event.seq,
+                        kind: event_kind(event.kind) as i32,
+                        tid,
+                        tdid: *tdid,
+                        todo: todo.clone().map(grpc::Todo::from),
+                    })
+                }
+                _ => None,
+            },
+        )))
+    }
+
+    type WatchTagsStream = WatchStream<grpc::TagEvent>;
+
+    async fn watch_tags(
+        &self,
+        request: tonic::Request<grpc::WatchTagsRequest>,
+    ) -> Result<tonic::Response<Self::WatchTagsStream>, tonic::Status> {
+        let caller = self.authenticate(&request, Access::Read).await?;
+        let params = request.into_inner();
+        let tid = params.tid;
+
+        self.times_store(&caller, tid).await?;
+        let subscription =
+            self.events.subscribe(params.after_seq).map_err(watch_status)?;
+
+        Ok(tonic::Response::new(watch(
+            subscription,
+            Some(tid),
+            move |event| match &event.change {
+                Change::Tag { tagid, tag } if event.tid == tid => {
+                    Some(grpc::TagEvent {
+                        seq: event.seq,
+                        kind: event_kind(event.kind) as i32,
+                        tid,
+                        tagid: *tagid,
+                        tag: tag.clone().map(grpc::Tag::from),
+                    })
+                }
+                _ => None,
+            },
+        )))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::DEFAULT_MAX_FILE_SIZE;
     use tonic::Request;
+    use timesman_grpc::grpc::times_man_admin_server::TimesManAdmin;
     use timesman_grpc::grpc::times_man_server::TimesMan;
+    use timesman_type::UserRole;
 
-    async fn setup_test_server() -> TMServer {
+    async fn setup_test_server() -> (TMServer, String) {
         let store_type = timesman_bstore::StoreType::Memory;
         let store = store_type.to_store().await.unwrap();
-        TMServer { store }
+        let auth_service = Arc::new(AuthService::new("test-secret-key"));
+        let events = EventHub::follow(store.clone()).await.unwrap();
+        let server = TMServer {
+            store,
+            auth_service,
+            events,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        };
+        let token = register_user(&server, "testuser").await;
+        (server, token)
     }
 
-    async fn create_test_times(server: &TMServer) -> u64 {
-        let request = Request::new(grpc::TimesTitle {
-            title: "Test Times".to_string(),
+    async fn register_user(server: &TMServer, username: &str) -> String {
+        let request = Request::new(grpc::RegisterRequest {
+            username: username.to_string(),
+            email: format!("{username}@example.com"),
+            password: "testpassword".to_string(),
         });
+        let response = server.register(request).await.unwrap();
+        response.into_inner().access_token
+    }
+
+    fn denied<T: std::fmt::Debug>(result: Result<T, tonic::Status>) {
+        assert_eq!(result.unwrap_err().code(), tonic::Code::PermissionDenied);
+    }
+
+    fn authed<T>(token: &str, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert(
+            "authorization",
+            format!("Bearer {token}").parse().unwrap(),
+        );
+        request
+    }
+
+    async fn create_test_times(server: &TMServer, token: &str) -> u64 {
+        let request = authed(
+            token,
+            grpc::TimesTitle {
+                title: "Test Times".to_string(),
+            },
+        );
         let response = server.create_times(request).await.unwrap();
         response.into_inner().id
     }
 
     #[tokio::test]
     async fn test_get_todo_detail_endpoint() {
-        let server = setup_test_server().await;
-        let tid = create_test_times(&server).await;
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
 
         // Create a todo with detail
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Test todo".to_string(),
             detail: Some("This is a detailed description\nwith multiple lines".to_string()),
@@ -616,7 +1317,7 @@ mod tests {
         let tdid = todo.id;
 
         // Test get_todo_detail endpoint
-        let get_request = Request::new(grpc::TodoDetailParams { tid, tdid });
+        let get_request = authed(&token, grpc::TodoDetailParams { tid, tdid });
         let get_response = server.get_todo_detail(get_request).await.unwrap();
         let retrieved_todo = get_response.into_inner();
 
@@ -629,11 +1330,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_update_todo_detail_endpoint() {
-        let server = setup_test_server().await;
-        let tid = create_test_times(&server).await;
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
 
         // Create a todo without detail
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Test todo".to_string(),
             detail: None,
@@ -643,7 +1344,7 @@ mod tests {
         let tdid = todo.id;
 
         // Update the todo with detail
-        let update_request = Request::new(grpc::UpdateTodoDetailParams {
+        let update_request = authed(&token, grpc::UpdateTodoDetailParams {
             tid,
             tdid,
             detail: "This is a new detailed description with Unicode: ñáéíóú 🚀".to_string(),
@@ -656,7 +1357,7 @@ mod tests {
         assert_eq!(updated_todo.detail, Some("This is a new detailed description with Unicode: ñáéíóú 🚀".to_string()));
 
         // Verify the update persisted by getting the todo again
-        let get_request = Request::new(grpc::TodoDetailParams { tid, tdid });
+        let get_request = authed(&token, grpc::TodoDetailParams { tid, tdid });
         let get_response = server.get_todo_detail(get_request).await.unwrap();
         let retrieved_todo = get_response.into_inner();
         assert_eq!(retrieved_todo.detail, Some("This is a new detailed description with Unicode: ñáéíóú 🚀".to_string()));
@@ -664,11 +1365,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_create_todo_with_detail() {
-        let server = setup_test_server().await;
-        let tid = create_test_times(&server).await;
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
 
         // Test creating todo with detail
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Todo with detail".to_string(),
             detail: Some("Initial detail text\nwith newlines and special chars: \"quotes\" & symbols".to_string()),
@@ -682,7 +1383,7 @@ mod tests {
         assert!(todo.done_at.is_none());
 
         // Test creating todo without detail
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Simple todo".to_string(),
             detail: None,
@@ -694,7 +1395,7 @@ mod tests {
         assert_eq!(todo.detail, None);
 
         // Test creating todo with empty detail
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Empty detail todo".to_string(),
             detail: Some("".to_string()),
@@ -708,11 +1409,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_todo_detail_error_handling() {
-        let server = setup_test_server().await;
-        let tid = create_test_times(&server).await;
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
 
         // Test get_todo_detail with non-existent times ID
-        let get_request = Request::new(grpc::TodoDetailParams {
+        let get_request = authed(&token, grpc::TodoDetailParams {
             tid: 99999,
             tdid: 1,
         });
@@ -721,7 +1422,7 @@ mod tests {
         assert_eq!(get_result.unwrap_err().code(), tonic::Code::NotFound);
 
         // Test get_todo_detail with non-existent todo ID
-        let get_request = Request::new(grpc::TodoDetailParams {
+        let get_request = authed(&token, grpc::TodoDetailParams {
             tid,
             tdid: 99999,
         });
@@ -730,7 +1431,7 @@ mod tests {
         assert_eq!(get_result.unwrap_err().code(), tonic::Code::NotFound);
 
         // Test update_todo_detail with non-existent times ID
-        let update_request = Request::new(grpc::UpdateTodoDetailParams {
+        let update_request = authed(&token, grpc::UpdateTodoDetailParams {
             tid: 99999,
             tdid: 1,
             detail: "New detail".to_string(),
@@ -740,7 +1441,7 @@ mod tests {
         assert_eq!(update_result.unwrap_err().code(), tonic::Code::NotFound);
 
         // Test update_todo_detail with non-existent todo ID
-        let update_request = Request::new(grpc::UpdateTodoDetailParams {
+        let update_request = authed(&token, grpc::UpdateTodoDetailParams {
             tid,
             tdid: 99999,
             detail: "New detail".to_string(),
@@ -750,7 +1451,7 @@ mod tests {
         assert_eq!(update_result.unwrap_err().code(), tonic::Code::NotFound);
 
         // Test create_todo with non-existent times ID
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid: 99999,
             content: "Test".to_string(),
             detail: None,
@@ -762,8 +1463,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_todos_includes_details() {
-        let server = setup_test_server().await;
-        let tid = create_test_times(&server).await;
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
 
         // Create several todos with and without details
         let todos_data = vec![
@@ -775,7 +1476,7 @@ mod tests {
 
         let mut created_todo_ids = Vec::new();
         for (content, detail) in todos_data.iter() {
-            let create_request = Request::new(grpc::CreateTodoParams {
+            let create_request = authed(&token, grpc::CreateTodoParams {
                 tid,
                 content: content.to_string(),
                 detail: detail.map(|d| d.to_string()),
@@ -785,7 +1486,7 @@ mod tests {
         }
 
         // Get all todos and verify details are included
-        let get_request = Request::new(grpc::TimesId { id: tid });
+        let get_request = authed(&token, grpc::TimesId { id: tid });
         let get_response = server.get_todos(get_request).await.unwrap();
         let todos = get_response.into_inner().todos;
 
@@ -801,11 +1502,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_todo_detail_persistence() {
-        let server = setup_test_server().await;
-        let tid = create_test_times(&server).await;
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
 
         // Create a todo with detail
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Persistent todo".to_string(),
             detail: Some("Original detail".to_string()),
@@ -822,7 +1523,7 @@ mod tests {
         ];
 
         for detail in details.iter() {
-            let update_request = Request::new(grpc::UpdateTodoDetailParams {
+            let update_request = authed(&token, grpc::UpdateTodoDetailParams {
                 tid,
                 tdid,
                 detail: detail.to_string(),
@@ -832,7 +1533,7 @@ mod tests {
             assert_eq!(updated_todo.detail, Some(detail.to_string()));
 
             // Verify persistence by getting the todo
-            let get_request = Request::new(grpc::TodoDetailParams { tid, tdid });
+            let get_request = authed(&token, grpc::TodoDetailParams { tid, tdid });
             let get_response = server.get_todo_detail(get_request).await.unwrap();
             let retrieved_todo = get_response.into_inner();
             assert_eq!(retrieved_todo.detail, Some(detail.to_string()));
@@ -841,11 +1542,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_todo_detail_with_done_status() {
-        let server = setup_test_server().await;
-        let tid = create_test_times(&server).await;
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
 
         // Create a todo with detail
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Todo to be done".to_string(),
             detail: Some("This todo will be marked as done".to_string()),
@@ -855,7 +1556,7 @@ mod tests {
         let tdid = todo.id;
 
         // Mark todo as done
-        let done_request = Request::new(grpc::DoneTodoParams {
+        let done_request = authed(&token, grpc::DoneTodoParams {
             tid,
             tdid,
             done: true,
@@ -867,7 +1568,7 @@ mod tests {
         assert!(done_todo.done_at.is_some());
 
         // Update detail of done todo
-        let update_request = Request::new(grpc::UpdateTodoDetailParams {
+        let update_request = authed(&token, grpc::UpdateTodoDetailParams {
             tid,
             tdid,
             detail: "Updated detail for done todo".to_string(),
@@ -879,7 +1580,7 @@ mod tests {
         assert!(updated_todo.done_at.is_some());
 
         // Mark todo as not done
-        let undone_request = Request::new(grpc::DoneTodoParams {
+        let undone_request = authed(&token, grpc::DoneTodoParams {
             tid,
             tdid,
             done: false,
@@ -893,12 +1594,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_todo_detail_edge_cases() {
-        let server = setup_test_server().await;
-        let tid = create_test_times(&server).await;
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
 
         // Test with very long detail
         let long_detail = "x".repeat(10000);
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Long detail todo".to_string(),
             detail: Some(long_detail.clone()),
@@ -909,7 +1610,7 @@ mod tests {
 
         // Test with detail containing only whitespace
         let whitespace_detail = "   \n\t\r\n   ".to_string();
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Whitespace detail todo".to_string(),
             detail: Some(whitespace_detail.clone()),
@@ -920,7 +1621,7 @@ mod tests {
 
         // Test with detail containing control characters
         let control_detail = "Detail with control chars: \u{0001}\u{0002}\u{001F}".to_string();
-        let create_request = Request::new(grpc::CreateTodoParams {
+        let create_request = authed(&token, grpc::CreateTodoParams {
             tid,
             content: "Control chars todo".to_string(),
             detail: Some(control_detail.clone()),
@@ -929,4 +1630,1748 @@ mod tests {
         let todo = create_response.into_inner();
         assert_eq!(todo.detail, Some(control_detail));
     }
+
+    #[tokio::test]
+    async fn test_update_todo_and_delete_todo() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+
+        let create_request = authed(&token, grpc::CreateTodoParams {
+            tid,
+            content: "Test todo".to_string(),
+            detail: Some("Old detail".to_string()),
+        });
+        let todo = server.create_todo(create_request).await.unwrap().into_inner();
+        let tdid = todo.id;
+
+        let update = |content: &str, detail: Option<&str>, done| {
+            authed(&token, grpc::UpdateTodoParams {
+                tid,
+                tdid,
+                content: content.to_string(),
+                detail: detail.map(str::to_string),
+                done,
+            })
+        };
+
+        let updated = server
+            .update_todo(update("New content", Some("New detail"), true))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(updated.content, "New content");
+        assert_eq!(updated.detail, Some("New detail".to_string()));
+        assert!(updated.done_at.is_some());
+
+        // Staying done keeps when it was done; the detail can be cleared.
+        let again = server
+            .update_todo(update("New content", None, true))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(again.done_at, updated.done_at);
+        assert_eq!(again.detail, None);
+
+        let undone = server
+            .update_todo(update("New content", None, false))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(undone.done_at.is_none());
+
+        let delete_request =
+            authed(&token, grpc::DeleteTodoParams { tid, tdid });
+        server.delete_todo(delete_request).await.unwrap();
+
+        let todos = server
+            .get_todos(authed(&token, grpc::TimesId { id: tid }))
+            .await
+            .unwrap()
+            .into_inner()
+            .todos;
+        assert!(todos.is_empty());
+
+        let result = server.update_todo(update("Gone", None, false)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::NotFound);
+        let delete_request =
+            authed(&token, grpc::DeleteTodoParams { tid, tdid });
+        assert!(server.delete_todo(delete_request).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_token_are_rejected() {
+        let (server, _token) = setup_test_server().await;
+
+        let result = server.get_times(Request::new(())).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        let request = Request::new(grpc::TimesTitle {
+            title: "Anonymous".to_string(),
+        });
+        let result = server.create_times(request).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        let result = server.get_posts(Request::new(grpc::TimesId { id: 0 })).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_and_logout() {
+        let (server, _token) = setup_test_server().await;
+
+        let request = Request::new(grpc::LoginRequest {
+            username: "testuser".to_string(),
+            password: "testpassword".to_string(),
+        });
+        let login = server.login(request).await.unwrap().into_inner();
+        assert!(!login.refresh_token.is_empty());
+
+        let request = Request::new(grpc::RefreshTokenRequest {
+            refresh_token: login.refresh_token.clone(),
+        });
+        let refreshed = server.refresh_token(request).await.unwrap().into_inner();
+        let token = refreshed.access_token;
+        assert!(server.get_times(authed(&token, ())).await.is_ok());
+
+        let request = authed(
+            &token,
+            grpc::LogoutRequest {
+                refresh_token: Some(refreshed.refresh_token.clone()),
+            },
+        );
+        server.logout(request).await.unwrap();
+
+        let result = server.get_times(authed(&token, ())).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        let request = Request::new(grpc::RefreshTokenRequest {
+            refresh_token: refreshed.refresh_token,
+        });
+        let result = server.refresh_token(request).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+    }
+
+    async fn create_personal_token(
+        server: &TMServer,
+        token: &str,
+        read_only: bool,
+        tid: Option<u64>,
+    ) -> String {
+        let request = authed(
+            token,
+            grpc::CreatePersonalAccessTokenRequest {
+                name: "ci".to_string(),
+                scope: Some(grpc::TokenScope { read_only, tid }),
+                expires_in_days: None,
+            },
+        );
+        let response = server.create_personal_access_token(request).await;
+        response.unwrap().into_inner().token
+    }
+
+    #[tokio::test]
+    async fn test_read_only_personal_token() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+        let pat = create_personal_token(&server, &token, true, None).await;
+
+        let id = grpc::TimesId { id: tid };
+        assert!(server.get_posts(authed(&pat, id)).await.is_ok());
+
+        let params = grpc::CreatePostPrams {
+            id: tid,
+            text: "build passed".to_string(),
+        };
+        denied(server.create_post(authed(&pat, params)).await);
+    }
+
+    #[tokio::test]
+    async fn test_single_times_personal_token() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+        let other_tid = create_test_times(&server, &token).await;
+        let pat = create_personal_token(&server, &token, false, Some(tid)).await;
+
+        let timeses = server.get_times(authed(&pat, ())).await.unwrap();
+        let ids: Vec<u64> =
+            timeses.into_inner().timeses.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![tid]);
+
+        let params = grpc::CreatePostPrams {
+            id: tid,
+            text: "build passed".to_string(),
+        };
+        assert!(server.create_post(authed(&pat, params)).await.is_ok());
+
+        let params = grpc::CreatePostPrams {
+            id: other_tid,
+            text: "build passed".to_string(),
+        };
+        denied(server.create_post(authed(&pat, params)).await);
+
+        let title = grpc::TimesTitle {
+            title: "new".to_string(),
+        };
+        denied(server.create_times(authed(&pat, title)).await);
+    }
+
+    #[tokio::test]
+    async fn test_personal_token_management() {
+        let (server, token) = setup_test_server().await;
+        let pat = create_personal_token(&server, &token, false, None).await;
+
+        // A personal access token cannot be used to manage tokens
+        let request = grpc::CreatePersonalAccessTokenRequest {
+            name: "more".to_string(),
+            scope: None,
+            expires_in_days: None,
+        };
+        denied(server.create_personal_access_token(authed(&pat, request)).await);
+        denied(server.list_personal_access_tokens(authed(&pat, ())).await);
+
+        // Nor can it be scoped to someone else's times
+        let bob = register_user(&server, "bob").await;
+        let bob_tid = create_test_times(&server, &bob).await;
+        let request = grpc::CreatePersonalAccessTokenRequest {
+            name: "steal".to_string(),
+            scope: Some(grpc::TokenScope {
+                read_only: true,
+                tid: Some(bob_tid),
+            }),
+            expires_in_days: None,
+        };
+        denied(server.create_personal_access_token(authed(&token, request)).await);
+
+        let tokens = server
+            .list_personal_access_tokens(authed(&token, ()))
+            .await
+            .unwrap()
+            .into_inner()
+            .tokens;
+        assert_eq!(tokens.len(), 1);
+
+        let id = grpc::PersonalAccessTokenId {
+            id: tokens[0].id.clone(),
+        };
+        server
+            .revoke_personal_access_token(authed(&token, id.clone()))
+            .await
+            .unwrap();
+        let result = server.get_times(authed(&pat, ())).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        let result = server.revoke_personal_access_token(authed(&token, id)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_times_are_isolated_per_user() {
+        let (server, alice) = setup_test_server().await;
+        let bob = register_user(&server, "bob").await;
+
+        let alice_tid = create_test_times(&server, &alice).await;
+        let bob_tid = create_test_times(&server, &bob).await;
+
+        let alice_times = server
+            .get_times(authed(&alice, ()))
+            .await
+            .unwrap()
+            .into_inner()
+            .timeses;
+        assert_eq!(alice_times.len(), 1);
+        assert_eq!(alice_times[0].id, alice_tid);
+
+        let bob_times = server
+            .get_times(authed(&bob, ()))
+            .await
+            .unwrap()
+            .into_inner()
+            .timeses;
+        assert_eq!(bob_times.len(), 1);
+        assert_eq!(bob_times[0].id, bob_tid);
+    }
+
+    #[tokio::test]
+    async fn test_foreign_times_are_denied() {
+        let (server, alice) = setup_test_server().await;
+        let bob = register_user(&server, "bob").await;
+        let tid = create_test_times(&server, &alice).await;
+
+        let post = server
+            .create_post(authed(
+                &alice,
+                grpc::CreatePostPrams {
+                    id: tid,
+                    text: "private".to_string(),
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+
+        denied(server.get_posts(authed(&bob, grpc::TimesId { id: tid })).await);
+        denied(server.get_todos(authed(&bob, grpc::TimesId { id: tid })).await);
+        denied(
+            server
+                .create_post(authed(
+                    &bob,
+                    grpc::CreatePostPrams {
+                        id: tid,
+                        text: "intrusion".to_string(),
+                    },
+                ))
+                .await,
+        );
+        denied(
+            server
+                .delete_post(authed(
+                    &bob,
+                    grpc::DeletePostParam { tid, pid: post.id },
+                ))
+                .await,
+        );
+        denied(
+            server
+                .create_todo(authed(
+                    &bob,
+                    grpc::CreateTodoParams {
+                        tid,
+                        content: "intrusion".to_string(),
+                        detail: None,
+                    },
+                ))
+                .await,
+        );
+        denied(
+            server
+                .delete_times(authed(&bob, grpc::TimesId { id: tid }))
+                .await,
+        );
+
+        // Alice's data is untouched
+        let posts = server
+            .get_posts(authed(&alice, grpc::TimesId { id: tid }))
+            .await
+            .unwrap()
+            .into_inner()
+            .posts;
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].post, "private");
+    }
+
+    #[tokio::test]
+    async fn test_update_times_keeps_owner() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+
+        let updated = server
+            .update_times(authed(
+                &token,
+                grpc::Times {
+                    id: tid,
+                    title: "Renamed".to_string(),
+                    created_at: Some(Default::default()),
+                    updated_at: None,
+                    owner: Some(uuid::Uuid::new_v4().to_string()),
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(updated.title, "Renamed");
+
+        let timeses = server
+            .get_times(authed(&token, ()))
+            .await
+            .unwrap()
+            .into_inner()
+            .timeses;
+        assert_eq!(timeses.len(), 1);
+        assert_eq!(timeses[0].owner, updated.owner);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_timestamps_are_invalid_arguments() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+
+        let mut times = grpc::Times {
+            id: tid,
+            title: "Renamed".to_string(),
+            created_at: Some(Default::default()),
+            updated_at: None,
+            owner: None,
+        };
+        times.created_at.as_mut().unwrap().seconds = i64::MAX;
+        let result = server.update_times(authed(&token, times)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+    }
+
+    #[tokio::test]
+    async fn test_update_post_keeps_its_timestamps() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+        let params = grpc::CreatePostPrams {
+            id: tid,
+            text: "Original".to_string(),
+        };
+        let created = server
+            .create_post(authed(&token, params))
+            .await
+            .unwrap()
+            .into_inner();
+
+        // Only the text and tag are taken from the request.
+        let post = grpc::Post {
+            id: created.id,
+            post: "No creation time".to_string(),
+            created_at: None,
+            updated_at: None,
+            tagid: None,
+            file: None,
+        };
+        let params = grpc::UpdatePostParam {
+            tid,
+            post: Some(post),
+        };
+        let updated = server
+            .update_post(authed(&token, params))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(updated.post, "No creation time");
+        assert_eq!(updated.created_at, created.created_at);
+        assert!(updated.updated_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_store_errors_keep_their_codes() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+
+        let params = grpc::GetPostParams { tid, pid: 42 };
+        let result = server.get_post(authed(&token, params)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::NotFound);
+
+        let params = grpc::CreateTodoParams {
+            tid,
+            content: "Twice".to_string(),
+            detail: None,
+        };
+        let todo = server
+            .create_todo(authed(&token, params))
+            .await
+            .unwrap()
+            .into_inner();
+        let done = || grpc::DoneTodoParams {
+            tid,
+            tdid: todo.id,
+            done: true,
+        };
+        server.done_todo(authed(&token, done())).await.unwrap();
+        let result = server.done_todo(authed(&token, done())).await;
+        assert_eq!(
+            result.unwrap_err().code(),
+            tonic::Code::FailedPrecondition
+        );
+
+        let status = store_status(StoreError::Corrupt("bad".to_string()));
+        assert_eq!(status.code(), tonic::Code::DataLoss);
+        let status = store_status(StoreError::NotSupported);
+        assert_eq!(status.code(), tonic::Code::Unimplemented);
+    }
+
+    #[tokio::test]
+    async fn test_get_post_and_list_posts() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+        let mut pids = vec![];
+        for text in ["first", "second", "third"] {
+            let params = grpc::CreatePostPrams {
+                id: tid,
+                text: text.to_string(),
+            };
+            let post = server
+                .create_post(authed(&token, params))
+                .await
+                .unwrap()
+                .into_inner();
+            pids.push(post.id);
+        }
+
+        let params = grpc::GetPostParams { tid, pid: pids[1] };
+        let post = server
+            .get_post(authed(&token, params))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(post.post, "second");
+        let params = grpc::GetPostParams { tid, pid: 100 };
+        let status = server.get_post(authed(&token, params)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        let mut request = grpc::ListPostsRequest {
+            tid,
+            page_size: 2,
+            order: grpc::PostOrder::NewestFirst.into(),
+            ..Default::default()
+        };
+        let first = server
+            .list_posts(authed(&token, request.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        request.page_token = first.next_page_token;
+        let second = server
+            .list_posts(authed(&token, request))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let listed: Vec<u64> = first
+            .posts
+            .iter()
+            .chain(&second.posts)
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(listed, vec![pids[2], pids[1], pids[0]]);
+        assert!(second.next_page_token.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tags() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+        let post = server
+            .create_post(authed(
+                &token,
+                grpc::CreatePostPrams {
+                    id: tid,
+                    text: "tagged".to_string(),
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let tag = server
+            .create_tag(authed(
+                &token,
+                grpc::CreateTagParams {
+                    tid,
+                    name: "work".to_string(),
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        let renamed = server
+            .rename_tag(authed(
+                &token,
+                grpc::RenameTagParams {
+                    tid,
+                    tagid: tag.id,
+                    name: "job".to_string(),
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(renamed.id, tag.id);
+        assert_eq!(renamed.name, "job");
+
+        let tags = server
+            .get_tags(authed(&token, grpc::TimesId { id: tid }))
+            .await
+            .unwrap()
+            .into_inner()
+            .tags;
+        assert_eq!(tags, vec![renamed.clone()]);
+
+        let assign = |tagid| grpc::AssignTagParams {
+            tid,
+            pid: post.id,
+            tagid,
+        };
+        let tagged = server
+            .assign_tag(authed(&token, assign(Some(tag.id))))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(tagged.tagid, Some(tag.id));
+        assert!(server
+            .assign_tag(authed(&token, assign(Some(tag.id + 1))))
+            .await
+            .is_err());
+
+        // Deleting the tag takes it off the post.
+        server
+            .delete_tag(authed(
+                &token,
+                grpc::DeleteTagParams { tid, tagid: tag.id },
+            ))
+            .await
+            .unwrap();
+        let tags = server
+            .get_tags(authed(&token, grpc::TimesId { id: tid }))
+            .await
+            .unwrap()
+            .into_inner()
+            .tags;
+        assert!(tags.is_empty());
+        let posts = server
+            .get_posts(authed(&token, grpc::TimesId { id: tid }))
+            .await
+            .unwrap()
+            .into_inner()
+            .posts;
+        assert_eq!(posts[0].tagid, None);
+    }
+
+    async fn next_event<T>(stream: &mut WatchStream<T>) -> T {
+        use tokio_stream::StreamExt;
+
+        let next = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            stream.next(),
+        );
+        next.await.unwrap().unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_watch_posts() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+        let watch = grpc::WatchPostsRequest {
+            tid,
+            after_seq: None,
+        };
+        let mut stream = server
+            .watch_posts(authed(&token, watch))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut post = server
+            .create_post(authed(
+                &token,
+                grpc::CreatePostPrams {
+                    id: tid,
+                    text: "first".to_string(),
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        post.post = "edited".to_string();
+        server
+            .update_post(authed(
+                &token,
+                grpc::UpdatePostParam {
+                    tid,
+                    post: Some(post.clone()),
+                },
+            ))
+            .await
+            .unwrap();
+        server
+            .delete_post(authed(
+                &token,
+                grpc::DeletePostParam { tid, pid: post.id },
+            ))
+            .await
+            .unwrap();
+
+        let created = next_event(&mut stream).await;
+        assert_eq!(created.kind(), grpc::EventKind::Created);
+        assert_eq!(created.post.unwrap().post, "first");
+        let updated = next_event(&mut stream).await;
+        assert_eq!(updated.kind(), grpc::EventKind::Updated);
+        assert_eq!(updated.post.unwrap().post, "edited");
+        let deleted = next_event(&mut stream).await;
+        assert_eq!(deleted.kind(), grpc::EventKind::Deleted);
+        assert_eq!(deleted.pid, post.id);
+        assert!(deleted.post.is_none());
+        assert!(created.seq < updated.seq && updated.seq < deleted.seq);
+
+        // Resuming replays what came after the given event.
+        let watch = grpc::WatchPostsRequest {
+            tid,
+            after_seq: Some(created.seq),
+        };
+        let mut stream = server
+            .watch_posts(authed(&token, watch))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(next_event(&mut stream).await.seq, updated.seq);
+        assert_eq!(next_event(&mut stream).await.seq, deleted.seq);
+    }
+
+    #[tokio::test]
+    async fn test_watch_tags() {
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+        let watch = grpc::WatchTagsRequest {
+            tid,
+            after_seq: None,
+        };
+        let mut stream = server
+            .watch_tags(authed(&token, watch))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let tag = server
+            .create_tag(authed(
+                &token,
+                grpc::CreateTagParams {
+                    tid,
+                    name: "work".to_string(),
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        server
+            .rename_tag(authed(
+                &token,
+                grpc::RenameTagParams {
+                    tid,
+                    tagid: tag.id,
+                    name: "job".to_string(),
+                },
+            ))
+            .await
+            .unwrap();
+        server
+            .delete_tag(authed(
+                &token,
+                grpc::DeleteTagParams { tid, tagid: tag.id },
+            ))
+            .await
+            .unwrap();
+
+        let created = next_event(&mut stream).await;
+        assert_eq!(created.kind(), grpc::EventKind::Created);
+        assert_eq!(created.tag.unwrap().name, "work");
+        let updated = next_event(&mut stream).await;
+        assert_eq!(updated.kind(), grpc::EventKind::Updated);
+        assert_eq!(updated.tag.unwrap().name, "job");
+        let deleted = next_event(&mut stream).await;
+        assert_eq!(deleted.kind(), grpc::EventKind::Deleted);
+        assert_eq!(deleted.tagid, tag.id);
+        assert!(deleted.tag.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_watch_times_only_shows_accessible_times() {
+        let (server, alice) = setup_test_server().await;
+        let bob = register_user(&server, "bob").await;
+        let watch = grpc::WatchTimesRequest { after_seq: None };
+        let mut stream = server
+            .watch_times(authed(&bob, watch))
+            .await
+            .unwrap()
+            .into_inner();
+
+        create_test_times(&server, &alice).await;
+        let tid = create_test_times(&server, &bob).await;
+        server
+            .delete_times(authed(&bob, grpc::TimesId { id: tid }))
+            .await
+            .unwrap();
+
+        let created = next_event(&mut stream).await;
+        assert_eq!(created.kind(), grpc::EventKind::Created);
+        assert_eq!(created.tid, tid);
+        assert_eq!(created.times.unwrap().id, tid);
+        let deleted = next_event(&mut stream).await;
+        assert_eq!(deleted.kind(), grpc::EventKind::Deleted);
+        assert_eq!(deleted.tid, tid);
+    }
+
+    #[tokio::test]
+    async fn test_watch_todos_ends_when_times_is_deleted() {
+        use tokio_stream::StreamExt;
+
+        let (server, token) = setup_test_server().await;
+        let tid = create_test_times(&server, &token).await;
+        let watch = grpc::WatchTodosRequest {
+            tid,
+            after_seq: None,
+        };
+        let mut stream = server
+            .watch_todos(authed(&token, watch))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let todo = server
+            .create_todo(authed(
+                &token,
+                grpc::CreateTodoParams {
+                    tid,
+                    content: "todo".to_string(),
+                    detail: None,
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        server
+            .done_todo(authed(
+                &token,
+                grpc::DoneTodoParams {
+                    tid,
+                    tdid: todo.id,
+                    done: true,
+                },
+            ))
+            .await
+            .unwrap();
+        server
+            .delete_times(authed(&token, grpc::TimesId { id: tid }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            next_event(&mut stream).await.kind(),
+            grpc::EventKind::Created
+        );
+        let done = next_event(&mut stream).await;
+        assert_eq!(done.kind(), grpc::EventKind::Updated);
+        assert!(done.todo.unwrap().done_at.is_some());
+
+        let end = stream.next().await.unwrap();
+        assert_eq!(end.unwrap_err().code(), tonic::Code::NotFound);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_watch_rejects_unknown_seq_and_foreign_times() {
+        let (server, alice) = setup_test_server().await;
+        let bob = register_user(&server, "bob").await;
+        let tid = create_test_times(&server, &alice).await;
+
+        let watch = grpc::WatchTimesRequest {
+            after_seq: Some(u64::MAX),
+        };
+        let result = server.watch_times(authed(&alice, watch)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::OutOfRange);
+
+        let watch = grpc::WatchPostsRequest {
+            tid,
+            after_seq: None,
+        };
+        denied(server.watch_posts(authed(&bob, watch)).await);
+    }
+
+    /// A server with three users: `user` owns `tid` (with one post and one
+    /// todo), `reader` is read-only and owns `reader_tid`, and `admin` owns
+    /// nothing.
+    struct AuthzFixture {
+        server: TMServer,
+        user: String,
+        reader: String,
+        admin: String,
+        tid: u64,
+        reader_tid: u64,
+        pid: u64,
+        tdid: u64,
+    }
+
+    async fn login(server: &TMServer, username: &str, password: &str) -> String {
+        let request = Request::new(grpc::LoginRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+        });
+        let response = server.login(request).await.unwrap();
+        response.into_inner().access_token
+    }
+
+    async fn setup_authz_fixture() -> AuthzFixture {
+        let (server, user) = setup_test_server().await;
+        let tid = create_test_times(&server, &user).await;
+
+        let pid = server
+            .create_post(authed(
+                &user,
+                grpc::CreatePostPrams {
+                    id: tid,
+                    text: "post".to_string(),
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+        let tdid = server
+            .create_todo(authed(
+                &user,
+                grpc::CreateTodoParams {
+                    tid,
+                    content: "todo".to_string(),
+                    detail: None,
+                },
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+
+        let reader = register_user(&server, "reader").await;
+        let reader_tid = create_test_times(&server, &reader).await;
+        server
+            .auth_service
+            .set_user_role("reader", UserRole::ReadOnly)
+            .await
+            .unwrap();
+        let reader = login(&server, "reader", "testpassword").await;
+
+        server
+            .auth_service
+            .create_admin_user("admin", "admin@example.com", "adminpass")
+            .await
+            .unwrap();
+        let admin = login(&server, "admin", "adminpass").await;
+
+        AuthzFixture {
+            server,
+            user,
+            reader,
+            admin,
+            tid,
+            reader_tid,
+            pid,
+            tdid,
+        }
+    }
+
+    fn times_params(tid: u64, title: &str) -> grpc::Times {
+        grpc::Times {
+            id: tid,
+            title: title.to_string(),
+            created_at: Some(Default::default()),
+            updated_at: None,
+            owner: None,
+        }
+    }
+
+    fn post_params(tid: u64, pid: u64, text: &str) -> grpc::UpdatePostParam {
+        grpc::UpdatePostParam {
+            tid,
+            post: Some(grpc::Post {
+                id: pid,
+                post: text.to_string(),
+                created_at: Some(Default::default()),
+                updated_at: None,
+                tagid: None,
+                file: None,
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authz_get_times() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+
+        let timeses = s.get_times(authed(&f.user, ())).await.unwrap();
+        let ids: Vec<u64> =
+            timeses.into_inner().timeses.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![f.tid]);
+
+        let timeses = s.get_times(authed(&f.reader, ())).await.unwrap();
+        let ids: Vec<u64> =
+            timeses.into_inner().timeses.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![f.reader_tid]);
+
+        let timeses = s.get_times(authed(&f.admin, ())).await.unwrap();
+        let ids: Vec<u64> =
+            timeses.into_inner().timeses.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![f.tid, f.reader_tid]);
+    }
+
+    #[tokio::test]
+    async fn test_authz_create_times() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let title = || grpc::TimesTitle {
+            title: "new".to_string(),
+        };
+
+        denied(s.create_times(authed(&f.reader, title())).await);
+        assert!(s.create_times(authed(&f.admin, title())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_update_times() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+
+        let params = times_params(f.reader_tid, "renamed");
+        denied(s.update_times(authed(&f.reader, params)).await);
+
+        let params = times_params(f.tid, "renamed by admin");
+        let times = s.update_times(authed(&f.admin, params)).await.unwrap();
+        assert_eq!(times.into_inner().title, "renamed by admin");
+    }
+
+    #[tokio::test]
+    async fn test_authz_delete_times() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+
+        let id = grpc::TimesId { id: f.reader_tid };
+        denied(s.delete_times(authed(&f.reader, id)).await);
+
+        let id = grpc::TimesId { id: f.tid };
+        assert!(s.delete_times(authed(&f.admin, id)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_get_posts() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+
+        let id = grpc::TimesId { id: f.reader_tid };
+        assert!(s.get_posts(authed(&f.reader, id)).await.is_ok());
+
+        let id = grpc::TimesId { id: f.tid };
+        denied(s.get_posts(authed(&f.reader, id)).await);
+
+        let id = grpc::TimesId { id: f.tid };
+        let posts = s.get_posts(authed(&f.admin, id)).await.unwrap();
+        assert_eq!(posts.into_inner().posts.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_authz_create_post() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let params = |id| grpc::CreatePostPrams {
+            id,
+            text: "text".to_string(),
+        };
+
+        denied(s.create_post(authed(&f.reader, params(f.reader_tid))).await);
+        assert!(s.create_post(authed(&f.admin, params(f.tid))).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_update_post() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+
+        let params = post_params(f.tid, f.pid, "edited");
+        denied(s.update_post(authed(&f.reader, params)).await);
+
+        let params = post_params(f.tid, f.pid, "edited by admin");
+        let post = s.update_post(authed(&f.admin, params)).await.unwrap();
+        assert_eq!(post.into_inner().post, "edited by admin");
+    }
+
+    #[tokio::test]
+    async fn test_authz_delete_post() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let params = || grpc::DeletePostParam {
+            tid: f.tid,
+            pid: f.pid,
+        };
+
+        denied(s.delete_post(authed(&f.reader, params())).await);
+        assert!(s.delete_post(authed(&f.admin, params())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_tags() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let create = |tid| grpc::CreateTagParams {
+            tid,
+            name: "work".to_string(),
+        };
+
+        let id = grpc::TimesId { id: f.reader_tid };
+        assert!(s.get_tags(authed(&f.reader, id)).await.is_ok());
+        let id = grpc::TimesId { id: f.tid };
+        denied(s.get_tags(authed(&f.reader, id)).await);
+        denied(s.create_tag(authed(&f.reader, create(f.reader_tid))).await);
+
+        let tag = s
+            .create_tag(authed(&f.user, create(f.tid)))
+            .await
+            .unwrap()
+            .into_inner();
+        let assign = || grpc::AssignTagParams {
+            tid: f.tid,
+            pid: f.pid,
+            tagid: Some(tag.id),
+        };
+        denied(s.assign_tag(authed(&f.reader, assign())).await);
+        assert!(s.assign_tag(authed(&f.admin, assign())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_get_todos() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+
+        let id = grpc::TimesId { id: f.reader_tid };
+        assert!(s.get_todos(authed(&f.reader, id)).await.is_ok());
+
+        let id = grpc::TimesId { id: f.tid };
+        let todos = s.get_todos(authed(&f.admin, id)).await.unwrap();
+        assert_eq!(todos.into_inner().todos.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_authz_create_todo() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let params = |tid| grpc::CreateTodoParams {
+            tid,
+            content: "todo".to_string(),
+            detail: None,
+        };
+
+        denied(s.create_todo(authed(&f.reader, params(f.reader_tid))).await);
+        assert!(s.create_todo(authed(&f.admin, params(f.tid))).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_done_todo() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let params = || grpc::DoneTodoParams {
+            tid: f.tid,
+            tdid: f.tdid,
+            done: true,
+        };
+
+        denied(s.done_todo(authed(&f.reader, params())).await);
+        assert!(s.done_todo(authed(&f.admin, params())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_get_todo_detail() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let params = || grpc::TodoDetailParams {
+            tid: f.tid,
+            tdid: f.tdid,
+        };
+
+        denied(s.get_todo_detail(authed(&f.reader, params())).await);
+        assert!(s.get_todo_detail(authed(&f.admin, params())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_update_todo_detail() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let params = || grpc::UpdateTodoDetailParams {
+            tid: f.tid,
+            tdid: f.tdid,
+            detail: "detail".to_string(),
+        };
+
+        denied(s.update_todo_detail(authed(&f.reader, params())).await);
+        assert!(s.update_todo_detail(authed(&f.admin, params())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_update_todo() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let params = || grpc::UpdateTodoParams {
+            tid: f.tid,
+            tdid: f.tdid,
+            content: "content".to_string(),
+            detail: None,
+            done: false,
+        };
+
+        denied(s.update_todo(authed(&f.reader, params())).await);
+        assert!(s.update_todo(authed(&f.admin, params())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_authz_delete_todo() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let params = || grpc::DeleteTodoParams {
+            tid: f.tid,
+            tdid: f.tdid,
+        };
+
+        denied(s.delete_todo(authed(&f.reader, params())).await);
+        assert!(s.delete_todo(authed(&f.admin, params())).await.is_ok());
+    }
+
+    fn username(name: &str) -> grpc::Username {
+        grpc::Username {
+            username: name.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_rpcs_require_admin() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+
+        for token in [&f.user, &f.reader] {
+            denied(s.list_users(authed(token, ())).await);
+            denied(s.delete_user(authed(token, username("admin"))).await);
+            let params = grpc::SetUserRoleRequest {
+                username: "testuser".to_string(),
+                role: grpc::UserRole::Admin as i32,
+            };
+            denied(s.set_user_role(authed(token, params)).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_list_users() {
+        let f = setup_authz_fixture().await;
+
+        let users = f.server.list_users(authed(&f.admin, ())).await.unwrap();
+        let mut names: Vec<String> = users
+            .into_inner()
+            .users
+            .into_iter()
+            .map(|u| u.username)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["admin", "reader", "testuser"]);
+    }
+
+    #[tokio::test]
+    async fn test_admin_set_user_active() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let params = |is_active| grpc::SetUserActiveRequest {
+            username: "testuser".to_string(),
+            is_active,
+        };
+
+        let user = s.set_user_active(authed(&f.admin, params(false))).await;
+        assert!(!user.unwrap().into_inner().is_active);
+        let result = s.get_times(authed(&f.user, ())).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        s.set_user_active(authed(&f.admin, params(true))).await.unwrap();
+        assert!(s.get_times(authed(&f.user, ())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_admin_set_user_role() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let title = || grpc::TimesTitle {
+            title: "new".to_string(),
+        };
+        denied(s.create_times(authed(&f.reader, title())).await);
+
+        let params = grpc::SetUserRoleRequest {
+            username: "reader".to_string(),
+            role: grpc::UserRole::User as i32,
+        };
+        let user = s.set_user_role(authed(&f.admin, params)).await.unwrap();
+        assert_eq!(user.into_inner().role, grpc::UserRole::User as i32);
+
+        assert!(s.create_times(authed(&f.reader, title())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_admin_reset_password() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+
+        let params = grpc::ResetPasswordRequest {
+            username: "testuser".to_string(),
+            password: "newpassword".to_string(),
+        };
+        s.reset_password(authed(&f.admin, params)).await.unwrap();
+
+        login(s, "testuser", "newpassword").await;
+
+        let params = grpc::ResetPasswordRequest {
+            username: "nobody".to_string(),
+            password: "newpassword".to_string(),
+        };
+        let result = s.reset_password(authed(&f.admin, params)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_admin_delete_user_and_data() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+
+        s.delete_user(authed(&f.admin, username("testuser"))).await.unwrap();
+
+        let result = s.get_times(authed(&f.user, ())).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        let timeses = s.get_times(authed(&f.admin, ())).await.unwrap();
+        let ids: Vec<u64> =
+            timeses.into_inner().timeses.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![f.reader_tid]);
+    }
+
+    #[tokio::test]
+    async fn test_admin_cannot_lock_self_out() {
+        let f = setup_authz_fixture().await;
+        let s = &f.server;
+        let result = s.delete_user(authed(&f.admin, username("admin"))).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::FailedPrecondition);
+
+        let params = grpc::SetUserActiveRequest {
+            username: "admin".to_string(),
+            is_active: false,
+        };
+        let result = s.set_user_active(authed(&f.admin, params)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::FailedPrecondition);
+
+        let params = grpc::SetUserRoleRequest {
+            username: "admin".to_string(),
+            role: grpc::UserRole::User as i32,
+        };
+        let result = s.set_user_role(authed(&f.admin, params)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn test_register_and_login_errors() {
+        let (server, _) = setup_test_server().await;
+
+        let register = |password: &str| {
+            Request::new(grpc::RegisterRequest {
+                username: "testuser".to_string(),
+                email: "other@example.com".to_string(),
+                password: password.to_string(),
+            })
+        };
+        let result = server.register(register("testpassword")).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::AlreadyExists);
+
+        let weak = Request::new(grpc::RegisterRequest {
+            username: "newuser".to_string(),
+            email: "new@example.com".to_string(),
+            password: "short".to_string(),
+        });
+        let result = server.register(weak).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let login = || {
+            Request::new(grpc::LoginRequest {
+                username: "testuser".to_string(),
+                password: "wrongpassword".to_string(),
+            })
+        };
+        for _ in 0..5 {
+            let result = server.login(login()).await;
+            assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+        }
+
+        let status = server.login(login()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(status.metadata().get("retry-after").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_totp_login() {
+        let (server, token) = setup_test_server().await;
+        let code = |secret: &str| {
+            crate::auth::code_from_base32(secret, chrono::Utc::now())
+        };
+
+        let enrollment = server
+            .enroll_totp(authed(&token, ()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+
+        let wrong = grpc::TotpCode {
+            code: "abc".to_string(),
+        };
+        let result = server.confirm_totp(authed(&token, wrong)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let params = grpc::TotpCode {
+            code: code(&enrollment.secret),
+        };
+        let backup_codes = server
+            .confirm_totp(authed(&token, params))
+            .await
+            .unwrap()
+            .into_inner()
+            .codes;
+        let result = server.enroll_totp(authed(&token, ())).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::FailedPrecondition);
+
+        let login = Request::new(grpc::LoginRequest {
+            username: "testuser".to_string(),
+            password: "testpassword".to_string(),
+        });
+        let challenge = server.login(login).await.unwrap().into_inner();
+        assert!(challenge.access_token.is_empty());
+        assert!(challenge.user.is_none());
+        assert!(!challenge.totp_challenge.is_empty());
+
+        let params = grpc::VerifyTotpRequest {
+            challenge: "unknown".to_string(),
+            code: backup_codes[0].clone(),
+        };
+        let result = server.verify_totp(Request::new(params)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        let params = grpc::VerifyTotpRequest {
+            challenge: challenge.totp_challenge,
+            code: backup_codes[0].clone(),
+        };
+        let response = server
+            .verify_totp(Request::new(params))
+            .await
+            .unwrap()
+            .into_inner();
+        server
+            .get_times(authed(&response.access_token, ()))
+            .await
+            .unwrap();
+
+        let pat = create_personal_token(&server, &token, false, None).await;
+        let params = grpc::TotpCode {
+            code: backup_codes[1].clone(),
+        };
+        denied(server.disable_totp(authed(&pat, params)).await);
+    }
+
+    fn server_tls(client_ca: bool) -> TlsSettings {
+        TlsSettings {
+            cert: include_bytes!("../testdata/tls/server.pem").to_vec(),
+            key: include_bytes!("../testdata/tls/server.key").to_vec(),
+            client_ca: client_ca
+                .then(|| include_bytes!("../testdata/tls/ca.pem").to_vec()),
+            client_auth_optional: false,
+        }
+    }
+
+    fn client_tls(identity: bool) -> timesman_grpc::tls::ClientTls {
+        timesman_grpc::tls::ClientTls {
+            ca_cert: Some(include_bytes!("../testdata/tls/ca.pem").to_vec()),
+            identity: identity.then(|| {
+                (
+                    include_bytes!("../testdata/tls/client.pem").to_vec(),
+                    include_bytes!("../testdata/tls/client.key").to_vec(),
+                )
+            }),
+            domain_name: None,
+        }
+    }
+
+    /// Runs `server` on a free port and returns the port once it listens.
+    async fn run_on_free_port(server: GrpcServer) -> u16 {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let listen = format!("127.0.0.1:{port}");
+        let store =
+            timesman_bstore::StoreType::Memory.to_store().await.unwrap();
+
+        let addr = Listen::Tcp(listen.clone());
+        tokio::spawn(async move { server.run(&addr, store).await });
+        while tokio::net::TcpStream::connect(&listen).await.is_err() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        port
+    }
+
+    /// Runs a server on a free port and returns its URL once it listens.
+    async fn serve(tls: TlsSettings) -> String {
+        let port = run_on_free_port(GrpcServer {
+            auth_service: Arc::new(AuthService::new("test-secret-key")),
+            events: Arc::new(EventHub::new()),
+            tls: Some(tls),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        })
+        .await;
+        format!("https://localhost:{port}")
+    }
+
+    async fn register_over(
+        url: &str,
+        tls: Option<&timesman_grpc::tls::ClientTls>,
+    ) -> Result<(), String> {
+        let channel = timesman_grpc::tls::connect(url.to_string(), tls).await?;
+        let mut client =
+            timesman_grpc::grpc::times_man_client::TimesManClient::new(channel);
+
+        let request = grpc::RegisterRequest {
+            username: "testuser".to_string(),
+            email: "test@example.com".to_string(),
+            password: "testpassword".to_string(),
+        };
+        client
+            .register(request)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_tls() {
+        let url = serve(server_tls(false)).await;
+
+        register_over(&url, Some(&client_tls(false))).await.unwrap();
+
+        // The test CA is not among the system roots
+        assert!(register_over(&url, None).await.is_err());
+        let plain = url.replace("https://", "http://");
+        assert!(register_over(&plain, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls() {
+        let url = serve(server_tls(true)).await;
+
+        assert!(register_over(&url, Some(&client_tls(false))).await.is_err());
+        register_over(&url, Some(&client_tls(true))).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_post_with_file() {
+        use timesman_type::{File, FileType};
+
+        let port = run_on_free_port(GrpcServer {
+            auth_service: Arc::new(AuthService::new("test-secret-key")),
+            events: Arc::new(EventHub::new()),
+            tls: None,
+            max_file_size: 2 * file::CHUNK_SIZE as u64,
+        })
+        .await;
+        let url = format!("http://127.0.0.1:{port}");
+        let channel = timesman_grpc::tls::connect(url, None).await.unwrap();
+        let mut client =
+            timesman_grpc::grpc::times_man_client::TimesManClient::new(channel);
+
+        let token = client
+            .register(grpc::RegisterRequest {
+                username: "testuser".to_string(),
+                email: "test@example.com".to_string(),
+                password: "testpassword".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .access_token;
+        let title = grpc::TimesTitle {
+            title: "Files".to_string(),
+        };
+        let tid = client
+            .create_times(authed(&token, title))
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+
+        // Spans two chunks.
+        let image = File::new(
+            "photo.png".to_string(),
+            FileType::Image(vec![7; file::CHUNK_SIZE + 1]),
+        );
+        let upload = file::upload(tid, "with file".to_string(), image.clone());
+        let mut post = client
+            .create_post_with_file(authed(&token, upload))
+            .await
+            .unwrap()
+            .into_inner();
+        let info = post.file.clone().unwrap();
+        assert_eq!(info.mime_type, "image/png");
+        assert_eq!(info.size, file::CHUNK_SIZE as u64 + 1);
+
+        // Editing the text keeps the file.
+        post.post = "edited".to_string();
+        let update = grpc::UpdatePostParam {
+            tid,
+            post: Some(post.clone()),
+        };
+        client.update_post(authed(&token, update)).await.unwrap();
+
+        let params = grpc::DownloadFileParams { tid, pid: post.id };
+        let mut stream = client
+            .download_file(authed(&token, params))
+            .await
+            .unwrap()
+            .into_inner();
+        let mut info = None;
+        let mut data = vec![];
+        while let Some(message) = stream.message().await.unwrap() {
+            match message.chunk.unwrap() {
+                grpc::file_chunk::Chunk::Info(i) => info = Some(i),
+                grpc::file_chunk::Chunk::Data(chunk) => data.extend(chunk),
+            }
+        }
+        assert_eq!(file::from_parts(&info.unwrap(), data), image);
+
+        let large = File::new(
+            "large".to_string(),
+            FileType::Other(vec![0; 2 * file::CHUNK_SIZE + 1]),
+        );
+        let upload = file::upload(tid, "too large".to_string(), large);
+        let status = client
+            .create_post_with_file(authed(&token, upload))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+        let params = grpc::CreatePostPrams {
+            id: tid,
+            text: "no file".to_string(),
+        };
+        let plain = client
+            .create_post(authed(&token, params))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(plain.file.is_none());
+        let params = grpc::DownloadFileParams { tid, pid: plain.id };
+        let status = client
+            .download_file(authed(&token, params))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_store_lists_file_info() {
+        use timesman_bstore::{GrpcStore, Store};
+        use timesman_grpc::client::Credentials;
+        use timesman_type::{File, FileType};
+
+        let port = run_on_free_port(GrpcServer {
+            auth_service: Arc::new(AuthService::new("test-secret-key")),
+            events: Arc::new(EventHub::new()),
+            tls: None,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        })
+        .await;
+        let url = format!("http://127.0.0.1:{port}");
+        let channel = timesman_grpc::tls::connect(url.clone(), None)
+            .await
+            .unwrap();
+        let token = grpc::times_man_client::TimesManClient::new(channel)
+            .register(grpc::RegisterRequest {
+                username: "testuser".to_string(),
+                email: "test@example.com".to_string(),
+                password: "testpassword".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .access_token;
+
+        let mut store =
+            GrpcStore::new(url, None, Some(Credentials::Token(token)))
+                .await
+                .unwrap();
+        let times_store =
+            store.create("Files".to_string(), None).await.unwrap();
+        let post_store = times_store.lock().await.pstore().await.unwrap();
+        let mut ps = post_store.lock().await;
+
+        let image = File::new(
+            "photo.png".to_string(),
+            FileType::Image(vec![7; file::CHUNK_SIZE + 1]),
+        );
+        let post = ps
+            .post("with file".to_string(), Some(image.clone()))
+            .await
+            .unwrap();
+
+        let posts = ps.get_all().await.unwrap();
+        assert_eq!(posts[0].file, Some(image.info()));
+        assert_eq!(ps.get(post.id).await.unwrap().file, Some(image.clone()));
+
+        // Editing the text leaves the file as it is.
+        let edited =
+            ps.edit(post.id, "edited".to_string(), None).await.unwrap();
+        assert_eq!(edited.post, "edited");
+        assert_eq!(edited.file, Some(image.info()));
+        assert_eq!(ps.get(post.id).await.unwrap().file, Some(image));
+    }
+
+    #[tokio::test]
+    async fn test_client_renews_session() {
+        use timesman_grpc::client::{Client, Credentials};
+
+        let port = run_on_free_port(GrpcServer {
+            auth_service: Arc::new(AuthService::new("test-secret-key")),
+            events: Arc::new(EventHub::new()),
+            tls: None,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        })
+        .await;
+        let url = format!("http://127.0.0.1:{port}");
+
+        let client = Client::connect(url.clone(), None).await.unwrap();
+        client
+            .register(
+                "testuser".to_string(),
+                "test@example.com".to_string(),
+                "testpassword".to_string(),
+            )
+            .await
+            .unwrap();
+        let title = grpc::TimesTitle {
+            title: "Renewed".to_string(),
+        };
+        client
+            .call(title, |mut c, r| async move { c.create_times(r).await })
+            .await
+            .unwrap();
+
+        // Revoke the access token behind the client's back.
+        let request = grpc::LogoutRequest {
+            refresh_token: None,
+        };
+        client.times_man().logout(request).await.unwrap();
+        let result = client.times_man().get_times(()).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        let timeses = client
+            .call_idempotent((), |mut c, r| async move { c.get_times(r).await })
+            .await
+            .unwrap()
+            .timeses;
+        assert_eq!(timeses.len(), 1);
+
+        // Tokens given as they are cannot be renewed.
+        let other = Client::connect(url, None).await.unwrap();
+        other
+            .authenticate(Credentials::Token("invalid".to_string()))
+            .await
+            .unwrap();
+        let result = other
+            .call((), |mut c, r| async move { c.get_times(r).await })
+            .await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        let password = Credentials::Password {
+            username: "testuser".to_string(),
+            password: "testpassword".to_string(),
+        };
+        other.authenticate(password).await.unwrap();
+        let result = other
+            .call((), |mut c, r| async move { c.get_times(r).await })
+            .await;
+        assert!(result.is_ok());
+    }
 }
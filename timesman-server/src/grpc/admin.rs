@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+
+use timesman_grpc::grpc;
+use timesman_grpc::grpc::times_man_admin_server;
+
+use super::super::auth::AuthError;
+use super::super::authz::{Access, Caller};
+use super::{store_status, TMServer};
+
+fn auth_status(e: AuthError) -> tonic::Status {
+    let code = match e {
+        AuthError::UserNotFound => tonic::Code::NotFound,
+        AuthError::WeakPassword(_) => tonic::Code::InvalidArgument,
+        _ => tonic::Code::Internal,
+    };
+    tonic::Status::new(code, e.to_string())
+}
+
+impl TMServer {
+    async fn authenticate_admin(
+        &self,
+        request: &tonic::Request<impl std::fmt::Debug>,
+    ) -> Result<Caller, tonic::Status> {
+        let caller = self.authenticate(request, Access::Write).await?;
+        caller.require_admin().map_err(|e| {
+            tonic::Status::new(tonic::Code::PermissionDenied, e.to_string())
+        })?;
+        Ok(caller)
+    }
+
+    /// Keeps admins from locking themselves out.
+    async fn not_self(
+        &self,
+        caller: &Caller,
+        username: &str,
+    ) -> Result<(), tonic::Status> {
+        let user = self
+            .auth_service
+            .get_user_by_id(&caller.id)
+            .await
+            .map_err(auth_status)?;
+
+        if user.username == username {
+            return Err(tonic::Status::new(
+                tonic::Code::FailedPrecondition,
+                "Cannot do this to your own account",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl times_man_admin_server::TimesManAdmin for TMServer {
+    async fn list_users(
+        &self,
+        request: tonic::Request<()>,
+    ) -> Result<tonic::Response<grpc::UserArray>, tonic::Status> {
+        self.authenticate_admin(&request).await?;
+
+        let users =
+            self.auth_service.list_users().await.map_err(auth_status)?;
+
+        Ok(tonic::Response::new(grpc::UserArray {
+            users: users.into_iter().map(|u| u.into()).collect(),
+        }))
+    }
+
+    async fn set_user_active(
+        &self,
+        request: tonic::Request<grpc::SetUserActiveRequest>,
+    ) -> Result<tonic::Response<grpc::User>, tonic::Status> {
+        let caller = self.authenticate_admin(&request).await?;
+        let params = request.into_inner();
+
+        if !params.is_active {
+            self.not_self(&caller, &params.username).await?;
+        }
+
+        let user = self
+            .auth_service
+            .set_user_active(&params.username, params.is_active)
+            .await
+            .map_err(auth_status)?;
+
+        Ok(tonic::Response::new(user.into()))
+    }
+
+    async fn set_user_role(
+        &self,
+        request: tonic::Request<grpc::SetUserRoleRequest>,
+    ) -> Result<tonic::Response<grpc::User>, tonic::Status> {
+        let caller = self.authenticate_admin(&request).await?;
+        let params = request.into_inner();
+
+        let role = grpc::UserRole::try_from(params.role).map_err(|_| {
+            tonic::Status::new(tonic::Code::InvalidArgument, "Unknown role")
+        })?;
+        if role != grpc::UserRole::Admin {
+            self.not_self(&caller, &params.username).await?;
+        }
+
+        let user = self
+            .auth_service
+            .set_user_role(&params.username, role.into())
+            .await
+            .map_err(auth_status)?;
+
+        Ok(tonic::Response::new(user.into()))
+    }
+
+    async fn reset_password(
+        &self,
+        request: tonic::Request<grpc::ResetPasswordRequest>,
+    ) -> Result<tonic::Response<()>, tonic::Status> {
+        self.authenticate_admin(&request).await?;
+        let params = request.into_inner();
+
+        self.auth_service
+            .reset_password(&params.username, &params.password)
+            .await
+            .map_err(auth_status)?;
+
+        Ok(tonic::Response::new(()))
+    }
+
+    async fn delete_user(
+        &self,
+        request: tonic::Request<grpc::Username>,
+    ) -> Result<tonic::Response<()>, tonic::Status> {
+        let caller = self.authenticate_admin(&request).await?;
+        let username = request.into_inner().username;
+        self.not_self(&caller, &username).await?;
+
+        // The account is locked first and removed last, so that its times
+        // are never left without an owner. If deleting them fails, the
+        // account stays for another try.
+        let user = self
+            .auth_service
+            .set_user_active(&username, false)
+            .await
+            .map_err(auth_status)?;
+
+        let mut store = self.store.lock().await;
+        let times_stores = store
+            .get()
+            .await
+            .map_err(store_status)?;
+
+        let mut owned = Vec::new();
+        for times_store in times_stores {
+            let times = times_store
+                .lock()
+                .await
+                .get()
+                .await
+                .map_err(store_status)?;
+            if times.owner == Some(user.id) {
+                owned.push(times.id);
+            }
+        }
+
+        for tid in owned {
+            store
+                .delete(tid)
+                .await
+                .map_err(store_status)?;
+        }
+
+        self.auth_service
+            .delete_user(&username)
+            .await
+            .map_err(auth_status)?;
+
+        Ok(tonic::Response::new(()))
+    }
+
+    async fn reset_totp(
+        &self,
+        request: tonic::Request<grpc::Username>,
+    ) -> Result<tonic::Response<()>, tonic::Status> {
+        self.authenticate_admin(&request).await?;
+
+        self.auth_service
+            .reset_totp(&request.into_inner().username)
+            .await
+            .map_err(auth_status)?;
+
+        Ok(tonic::Response::new(()))
+    }
+}
@@ -0,0 +1,169 @@
+use timesman_bstore::PostQuery;
+use timesman_grpc::grpc;
+use timesman_type::Post;
+
+use super::super::cursor::Cursor;
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+fn page_size(request: &grpc::ListPostsRequest) -> usize {
+    match request.page_size as usize {
+        0 => DEFAULT_PAGE_SIZE,
+        size => size.min(MAX_PAGE_SIZE),
+    }
+}
+
+/// The posts to ask the store for the page `request` asks for. One more
+/// than fits on the page tells whether there is a next page.
+pub(super) fn query(
+    request: &grpc::ListPostsRequest,
+) -> Result<PostQuery, String> {
+    let newest_first = request.order() == grpc::PostOrder::NewestFirst;
+
+    let after = if request.page_token.is_empty() {
+        None
+    } else {
+        let cursor = Cursor::decode(&request.page_token)
+            .ok_or_else(|| "Invalid page token".to_string())?;
+        if cursor.newest_first != newest_first {
+            return Err("The page token is for another order".to_string());
+        }
+        Some((cursor.created_at, cursor.pid))
+    };
+
+    // Seconds and nanoseconds out of range make the time invalid.
+    let since = request
+        .since
+        .as_ref()
+        .map(|t| {
+            chrono::DateTime::from_timestamp(t.seconds, t.nanos as u32)
+                .map(|t| t.naive_utc())
+                .ok_or_else(|| "Invalid since".to_string())
+        })
+        .transpose()?;
+    let until = request
+        .until
+        .as_ref()
+        .map(|t| {
+            chrono::DateTime::from_timestamp(t.seconds, t.nanos as u32)
+                .map(|t| t.naive_utc())
+                .ok_or_else(|| "Invalid until".to_string())
+        })
+        .transpose()?;
+
+    Ok(PostQuery {
+        newest_first,
+        since,
+        until,
+        after,
+        limit: Some(page_size(request) + 1),
+    })
+}
+
+/// Makes the page out of the posts the `query` of `request` selected.
+pub(super) fn page(
+    mut posts: Vec<Post>,
+    request: &grpc::ListPostsRequest,
+) -> grpc::ListPostsResponse {
+    let page_size = page_size(request);
+
+    let next_page_token = if posts.len() > page_size {
+        posts.truncate(page_size);
+        let newest_first = request.order() == grpc::PostOrder::NewestFirst;
+        Cursor::after(&posts[page_size - 1], newest_first).encode()
+    } else {
+        String::new()
+    };
+
+    grpc::ListPostsResponse {
+        posts: posts.into_iter().map(grpc::Post::from).collect(),
+        next_page_token,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use timesman_type::Pid;
+
+    fn posts() -> Vec<Post> {
+        let start = chrono::DateTime::from_timestamp(1_700_000_000, 0)
+            .unwrap()
+            .naive_utc();
+        (0..5)
+            .map(|i| Post {
+                id: i,
+                post: format!("post {i}"),
+                created_at: start + chrono::Duration::seconds(i as i64),
+                updated_at: None,
+                file: None,
+                tag: None,
+            })
+            .collect()
+    }
+
+    fn list_posts(
+        posts: Vec<Post>,
+        request: &grpc::ListPostsRequest,
+    ) -> Result<grpc::ListPostsResponse, String> {
+        let query = query(request)?;
+        Ok(page(query.select(posts), request))
+    }
+
+    fn ids(response: &grpc::ListPostsResponse) -> Vec<Pid> {
+        response.posts.iter().map(|p| p.id).collect()
+    }
+
+    #[test]
+    fn test_pages() {
+        let mut request = grpc::ListPostsRequest {
+            page_size: 2,
+            ..Default::default()
+        };
+
+        let mut pages = vec![];
+        loop {
+            let page = list_posts(posts(), &request).unwrap();
+            pages.push(ids(&page));
+            if page.next_page_token.is_empty() {
+                break;
+            }
+            request.page_token = page.next_page_token;
+        }
+
+        assert_eq!(pages, vec![vec![0, 1], vec![2, 3], vec![4]]);
+    }
+
+    #[test]
+    fn test_newest_first_within_range() {
+        let all = posts();
+        let created_at = |i: usize| grpc::Post::from(all[i].clone()).created_at;
+        let request = grpc::ListPostsRequest {
+            since: created_at(1),
+            until: created_at(4),
+            order: grpc::PostOrder::NewestFirst.into(),
+            ..Default::default()
+        };
+
+        let page = list_posts(posts(), &request).unwrap();
+        assert_eq!(ids(&page), vec![3, 2, 1]);
+        assert!(page.next_page_token.is_empty());
+    }
+
+    #[test]
+    fn test_bad_page_tokens() {
+        let mut request = grpc::ListPostsRequest {
+            page_size: 1,
+            ..Default::default()
+        };
+        let token = list_posts(posts(), &request).unwrap().next_page_token;
+
+        request.page_token = "garbage".to_string();
+        assert!(list_posts(posts(), &request).is_err());
+
+        request.page_token = token;
+        request.order = grpc::PostOrder::NewestFirst.into();
+        assert!(list_posts(posts(), &request).is_err());
+    }
+}
@@ -1,241 +1,1799 @@
-use std::sync::mpsc;
+mod admin;
+mod auth;
+mod watch;
+
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{mpsc, Mutex};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
 
-use timesman_bstore::Store;
-use timesman_type::{Post, Times};
+use super::auth::AuthError;
+use super::authz::{Access, AuthzError, Caller};
+use super::cursor::Cursor;
+use super::{AuthService, EventHub, Listen, TimesManServer};
 
-use super::TimesManServer;
+use timesman_bstore::{
+    FileReader, PostQuery, PostStore, Store, StoreError, TimesStore,
+};
+use timesman_type::{
+    File, FileKind, FileType, Pid, Post, Tag, TagId, Tdid, Tid, Times, Todo,
+};
 
-use actix_web::{web, App, HttpResponse, Responder};
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::{web, App, HttpRequest, HttpResponse, ResponseError};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use tracing_actix_web::TracingLogger;
 
-#[derive(Clone)]
-struct Context {
-    store: Arc<Mutex<Box<dyn Store + Send + Sync + 'static>>>,
-}
+/// The OpenAPI description of the routes in `routes`.
+const OPENAPI: &str = include_str!("http/openapi.json");
 
-pub struct HttpServer {}
+pub struct HttpServer {
+    pub auth_service: Arc<AuthService>,
+    pub events: Arc<EventHub>,
+    /// Largest attachment accepted, in bytes.
+    pub max_file_size: u64,
+}
 
 #[async_trait]
-impl<T> TimesManServer<T> for HttpServer {
+impl TimesManServer for HttpServer {
     async fn run(
         &self,
-        listen: &str,
-        store: Arc<Mutex<Box<dyn Store + Send + Sync + 'static>>>,
-        tx: Option<mpsc::Sender<T>>,
-    ) {
-        actix_web::HttpServer::new(move || {
+        listen: &Listen,
+        store: Arc<Mutex<dyn Store>>,
+    ) -> std::io::Result<()> {
+        let ctx = web::Data::new(Context {
+            store,
+            auth_service: self.auth_service.clone(),
+            events: self.events.clone(),
+            max_file_size: self.max_file_size,
+        });
+
+        let server = actix_web::HttpServer::new(move || {
             App::new()
-                .app_data(web::Data::new(Context {
-                    store: store.clone(),
-                }))
-                .route("/times", web::get().to(get_times))
-                .route("/times", web::post().to(create_times))
-                .route("/times/{tid}", web::delete().to(delete_times))
-                .route("/times/{tid}", web::get().to(get_posts))
-                .route("/times/{tid}", web::post().to(post_post))
-        })
-        .bind(listen)
-        .unwrap()
-        .run()
-        .await
-        .unwrap();
+                .app_data(ctx.clone())
+                .wrap(TracingLogger::default())
+                .configure(routes)
+        });
+        let server = match listen {
+            Listen::Tcp(addr) => server.bind(addr)?,
+            #[cfg(unix)]
+            Listen::Unix(path) => {
+                super::remove_stale_socket(path)?;
+                server.bind_uds(path)?
+            }
+        };
+
+        server.run().await
     }
 }
 
-#[derive(Serialize)]
-struct ResponseBase {
-    status: u64,
-    text: String,
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::JsonConfig::default().error_handler(|e, _| {
+        ApiError::new(StatusCode::BAD_REQUEST, e).into()
+    }))
+    .app_data(
+        web::PathConfig::default().error_handler(|e, _| {
+            ApiError::new(StatusCode::NOT_FOUND, e).into()
+        }),
+    )
+    .app_data(
+        web::QueryConfig::default().error_handler(|e, _| {
+            ApiError::new(StatusCode::BAD_REQUEST, e).into()
+        }),
+    )
+    .route("/openapi.json", web::get().to(openapi))
+    .configure(auth::routes)
+    .configure(admin::routes)
+    .configure(watch::routes)
+    .route("/times", web::get().to(get_times))
+    .route("/times", web::post().to(create_times))
+    .route("/times/{tid}", web::get().to(get_times_by_id))
+    .route("/times/{tid}", web::put().to(update_times))
+    .route("/times/{tid}", web::delete().to(delete_times))
+    .route("/times/{tid}/posts", web::get().to(get_posts))
+    .route("/times/{tid}/posts", web::post().to(create_post))
+    .route("/times/{tid}/posts/{pid}", web::get().to(get_post))
+    .route("/times/{tid}/posts/{pid}", web::put().to(update_post))
+    .route("/times/{tid}/posts/{pid}", web::delete().to(delete_post))
+    .route("/times/{tid}/posts/{pid}/tag", web::put().to(assign_tag))
+    .route("/times/{tid}/posts/{pid}/file", web::get().to(get_file))
+    .route("/times/{tid}/posts/{pid}/file", web::put().to(put_file))
+    .route("/times/{tid}/tags", web::get().to(get_tags))
+    .route("/times/{tid}/tags", web::post().to(create_tag))
+    .route("/times/{tid}/tags/{tagid}", web::put().to(rename_tag))
+    .route("/times/{tid}/tags/{tagid}", web::delete().to(delete_tag))
+    .route("/times/{tid}/todos", web::get().to(get_todos))
+    .route("/times/{tid}/todos", web::post().to(create_todo))
+    .route("/times/{tid}/todos/{tdid}", web::get().to(get_todo))
+    .route("/times/{tid}/todos/{tdid}", web::patch().to(update_todo))
+    .route("/times/{tid}/todos/{tdid}", web::delete().to(delete_todo));
 }
 
-#[derive(Serialize)]
-struct ResponseTimes {
-    base: ResponseBase,
-    times: Vec<Times>,
+async fn openapi() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(OPENAPI)
 }
 
-async fn get_times(ctx: web::Data<Context>) -> impl Responder {
-    let mut store = ctx.store.lock().await; //.unwrap();
-    let times = match store.get_times().await {
-        Ok(times) => times,
-        Err(e) => {
-            tracing::info!("failed to get times from store {e}");
-            let resp = ResponseBase { status: 1, text: e };
+/// An error response. The body is `{"error": "<message>"}`.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+    retry_after: Option<u64>,
+}
 
-            return HttpResponse::Ok()
-                .body(serde_json::to_string(&resp).unwrap());
+impl ApiError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+            retry_after: None,
         }
-    };
+    }
+
+    fn unauthorized(message: impl ToString) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, message)
+    }
 
-    tracing::info!("get times. num: {}", times.iter().len());
+    fn forbidden(e: AuthzError) -> Self {
+        Self::new(StatusCode::FORBIDDEN, e)
+    }
 
-    let resp = ResponseTimes {
-        base: ResponseBase {
-            status: 0,
-            text: "Ok".to_string(),
+    fn store(e: StoreError) -> Self {
+        let status = match e {
+            StoreError::NotFound(_) => StatusCode::NOT_FOUND,
+            StoreError::AlreadyExists(_) | StoreError::Conflict(_) => {
+                StatusCode::CONFLICT
+            }
+            StoreError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            StoreError::Backend(_) => StatusCode::BAD_GATEWAY,
+            StoreError::NotSupported => StatusCode::NOT_IMPLEMENTED,
+            StoreError::Io(_) | StoreError::Corrupt(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        Self::new(status, e)
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status);
+        if self.status == StatusCode::UNAUTHORIZED {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        if let Some(secs) = self.retry_after {
+            response.insert_header((header::RETRY_AFTER, secs));
+        }
+        response.json(ErrorBody {
+            error: &self.message,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+/// Errors of the login endpoints. Lockouts carry a `Retry-After` header
+/// with the seconds to wait.
+fn login_error(e: AuthError) -> ApiError {
+    match e {
+        AuthError::TooManyAttempts { retry_after_secs } => ApiError {
+            retry_after: Some(retry_after_secs),
+            ..ApiError::new(StatusCode::TOO_MANY_REQUESTS, e)
         },
-        times,
-    };
+        _ => ApiError::unauthorized(e),
+    }
+}
+
+struct Context {
+    store: Arc<Mutex<dyn Store>>,
+    auth_service: Arc<AuthService>,
+    events: Arc<EventHub>,
+    max_file_size: u64,
+}
+
+impl Context {
+    async fn validate_token(
+        &self,
+        req: &HttpRequest,
+    ) -> Result<timesman_type::Claims, ApiError> {
+        let auth_str = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .ok_or_else(|| {
+                ApiError::unauthorized("Missing authorization header")
+            })?
+            .to_str()
+            .map_err(|_| {
+                ApiError::unauthorized("Invalid authorization header")
+            })?;
+
+        let token = auth_str.strip_prefix("Bearer ").ok_or_else(|| {
+            ApiError::unauthorized("Invalid authorization format")
+        })?;
+
+        self.auth_service
+            .validate_token(token)
+            .await
+            .map_err(ApiError::unauthorized)
+    }
+
+    /// Validates the request's token and checks that the caller's role
+    /// allows `access`.
+    async fn authenticate(
+        &self,
+        req: &HttpRequest,
+        access: Access,
+    ) -> Result<Caller, ApiError> {
+        let claims = self.validate_token(req).await?;
+
+        let caller =
+            Caller::from_claims(&claims).map_err(ApiError::unauthorized)?;
+        caller.authorize(access).map_err(ApiError::forbidden)?;
+
+        Ok(caller)
+    }
+
+    /// Looks up the times `tid` and checks that `caller` may access it.
+    async fn times_store(
+        &self,
+        caller: &Caller,
+        tid: Tid,
+    ) -> Result<Arc<Mutex<dyn TimesStore + Send + Sync>>, ApiError> {
+        let (_, times_store) = self.find_times(caller, tid).await?;
+        Ok(times_store)
+    }
+
+    /// Like `times_store`, also returning the times itself.
+    async fn find_times(
+        &self,
+        caller: &Caller,
+        tid: Tid,
+    ) -> Result<(Times, Arc<Mutex<dyn TimesStore + Send + Sync>>), ApiError>
+    {
+        let mut store = self.store.lock().await;
+
+        let times_stores = store.get().await.map_err(ApiError::store)?;
 
-    HttpResponse::Ok().body(serde_json::to_string(&resp).unwrap())
+        for times_store in times_stores {
+            let times = times_store
+                .lock()
+                .await
+                .get()
+                .await
+                .map_err(ApiError::store)?;
+
+            if times.id == tid {
+                if !caller.can_access(&times) {
+                    return Err(ApiError::forbidden(AuthzError::NotOwner));
+                }
+                return Ok((times, times_store));
+            }
+        }
+
+        Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("Times with id {} not found", tid),
+        ))
+    }
 }
 
 #[derive(Deserialize)]
-struct CreateTimesRequest {
+struct TimesTitle {
     title: String,
 }
 
+async fn get_times(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+) -> Result<web::Json<Vec<Times>>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Read).await?;
+    let mut store = ctx.store.lock().await;
+
+    let times_stores = store.get().await.map_err(ApiError::store)?;
+
+    let mut timeses = Vec::new();
+    for times_store in times_stores {
+        let times = times_store
+            .lock()
+            .await
+            .get()
+            .await
+            .map_err(ApiError::store)?;
+        if caller.can_access(&times) {
+            timeses.push(times);
+        }
+    }
+
+    Ok(web::Json(timeses))
+}
+
 async fn create_times(
     ctx: web::Data<Context>,
-    req: web::Json<CreateTimesRequest>,
-) -> impl Responder {
-    let mut store = ctx.store.lock().await; //.unwrap();
-    let times = match store.create_times(req.title.clone()).await {
-        Ok(times) => times,
-        Err(e) => {
-            tracing::info!("failed to create title: {e}");
-            let resp = ResponseBase { status: 1, text: e };
-
-            return HttpResponse::Ok()
-                .body(serde_json::to_string(&resp).unwrap());
-        }
-    };
-
-    tracing::info!(
-        "create times with title: {}, id: {}",
-        &req.title,
-        &times.id
-    );
-    #[derive(Serialize)]
-    struct Response {
-        base: ResponseBase,
-        times: Times,
-    }
-
-    let resp = Response {
-        base: ResponseBase {
-            status: 0,
-            text: "Ok".to_string(),
-        },
-        times,
-    };
+    req: HttpRequest,
+    body: web::Json<TimesTitle>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    caller.can_create_times().map_err(ApiError::forbidden)?;
+    let mut store = ctx.store.lock().await;
 
-    HttpResponse::Ok().body(serde_json::to_string(&resp).unwrap())
+    let times_store = store
+        .create(body.into_inner().title, Some(caller.id))
+        .await
+        .map_err(ApiError::store)?;
+    let times = times_store
+        .lock()
+        .await
+        .get()
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(HttpResponse::Created().json(times))
+}
+
+async fn get_times_by_id(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<Tid>,
+) -> Result<web::Json<Times>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Read).await?;
+
+    let times_store = ctx.times_store(&caller, path.into_inner()).await?;
+    let times = times_store
+        .lock()
+        .await
+        .get()
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(web::Json(times))
+}
+
+async fn update_times(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<Tid>,
+    body: web::Json<TimesTitle>,
+) -> Result<web::Json<Times>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+
+    let times_store = ctx.times_store(&caller, path.into_inner()).await?;
+    let mut ts = times_store.lock().await;
+
+    let mut times = ts.get().await.map_err(ApiError::store)?;
+    times.title = body.into_inner().title;
+    let times = ts.update(times).await.map_err(ApiError::store)?;
+
+    Ok(web::Json(times))
 }
 
 async fn delete_times(
     ctx: web::Data<Context>,
-    path: web::Path<u64>,
-) -> impl Responder {
+    req: HttpRequest,
+    path: web::Path<Tid>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
     let tid = path.into_inner();
+
+    ctx.times_store(&caller, tid).await?;
+
     let mut store = ctx.store.lock().await;
+    store.delete(tid).await.map_err(ApiError::store)?;
 
-    match store.delete_times(tid).await {
-        Ok(()) => {}
-        Err(e) => {
-            tracing::info!("failed to delete times: {e}");
-            let resp = ResponseBase { status: 1, text: e };
+    Ok(HttpResponse::NoContent().finish())
+}
 
-            return HttpResponse::Ok()
-                .body(serde_json::to_string(&resp).unwrap());
-        }
+#[derive(Deserialize)]
+struct PostBody {
+    post: String,
+    #[serde(default)]
+    tag: Option<TagId>,
+}
+
+/// The body of a post update. A missing `tag` keeps the tag of the post and
+/// `null` clears it.
+#[derive(Deserialize)]
+struct UpdatePostBody {
+    post: String,
+    #[serde(default, deserialize_with = "present")]
+    tag: Option<Option<TagId>>,
+}
+
+/// Tells a field given as `null` from a missing one, which is `None` by
+/// `#[serde(default)]`.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Fails with 404 unless `tag` is a tag of the times of `ps`.
+async fn check_tag(
+    ps: &mut (dyn PostStore + Send + Sync),
+    tag: TagId,
+) -> Result<(), ApiError> {
+    let tags = ps.get_tags().await.map_err(ApiError::store)?;
+    if !tags.iter().any(|t| t.id == tag) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("tag {tag} is not found"),
+        ));
     }
-    /* TODO: asynchronous delete */
 
-    tracing::info!("mark as delete the times {}", tid);
-    let resp = ResponseBase {
-        status: 0,
-        text: "Ok".to_string(),
-    };
+    Ok(())
+}
+
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// Which posts `GET /times/{tid}/posts` returns. Without `limit` they all
+/// come at once; with it, the `Link` header points at the next page.
+#[derive(Deserialize)]
+struct PostsQuery {
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    cursor: Option<String>,
+    #[serde(default)]
+    newest_first: bool,
+    #[serde(default)]
+    since: Option<chrono::NaiveDateTime>,
+    #[serde(default)]
+    until: Option<chrono::NaiveDateTime>,
+}
 
-    HttpResponse::Ok().body(serde_json::to_string(&resp).unwrap())
+impl PostsQuery {
+    /// The posts to ask the store for. One more than fits on the page tells
+    /// whether there is a next page.
+    fn to_query(&self) -> Result<PostQuery, ApiError> {
+        let bad_request = |e| ApiError::new(StatusCode::BAD_REQUEST, e);
+
+        if self.limit == Some(0) {
+            return Err(bad_request("limit must be positive"));
+        }
+        let after = match &self.cursor {
+            None => None,
+            Some(token) => {
+                let cursor = Cursor::decode(token)
+                    .ok_or_else(|| bad_request("Invalid cursor"))?;
+                if cursor.newest_first != self.newest_first {
+                    return Err(bad_request("The cursor is for another order"));
+                }
+                Some((cursor.created_at, cursor.pid))
+            }
+        };
+
+        Ok(PostQuery {
+            newest_first: self.newest_first,
+            since: self.since,
+            until: self.until,
+            after,
+            limit: self.page_size().map(|size| size + 1),
+        })
+    }
+
+    fn page_size(&self) -> Option<usize> {
+        self.limit.map(|limit| limit.min(MAX_PAGE_SIZE))
+    }
 }
 
-#[derive(Serialize)]
-struct GetPostResponse {
-    base: ResponseBase,
-    posts: Vec<Post>,
+/// The `Link` header value pointing at the page after `cursor`, keeping the
+/// other parameters of `req`.
+fn next_link(req: &HttpRequest, cursor: &str) -> String {
+    let cursor = format!("cursor={cursor}");
+    let mut params: Vec<&str> = req
+        .query_string()
+        .split('&')
+        .filter(|p| !p.is_empty() && !p.starts_with("cursor="))
+        .collect();
+    params.push(&cursor);
+
+    format!("<{}?{}>; rel=\"next\"", req.path(), params.join("&"))
 }
 
 async fn get_posts(
     ctx: web::Data<Context>,
-    path: web::Path<u64>,
-) -> impl Responder {
-    let tid = path.into_inner();
+    req: HttpRequest,
+    path: web::Path<Tid>,
+    query: web::Query<PostsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Read).await?;
+    let query = query.into_inner();
+    let post_query = query.to_query()?;
 
-    let mut store = ctx.store.lock().await;
-    let posts = match store.get_posts(tid).await {
-        Ok(posts) => posts,
-        Err(e) => {
-            tracing::info!("failed to get posts for times {}: {}", tid, &e);
-            let resp = ResponseBase { status: 1, text: e };
+    let times_store = ctx.times_store(&caller, path.into_inner()).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let mut posts = post_store
+        .lock()
+        .await
+        .list(&post_query)
+        .await
+        .map_err(ApiError::store)?;
 
-            return HttpResponse::Ok()
-                .body(serde_json::to_string(&resp).unwrap());
+    let mut response = HttpResponse::Ok();
+    if let Some(size) = query.page_size() {
+        if posts.len() > size {
+            posts.truncate(size);
+            let cursor = Cursor::after(&posts[size - 1], query.newest_first);
+            response.insert_header((
+                header::LINK,
+                next_link(&req, &cursor.encode()),
+            ));
         }
-    };
+    }
 
-    tracing::info!("get posts ({}) for times {}", posts.iter().len(), tid);
+    Ok(response.json(posts))
+}
 
-    let resp = GetPostResponse {
-        base: ResponseBase {
-            status: 0,
-            text: "Ok".to_string(),
-        },
-        posts,
-    };
+async fn create_post(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<Tid>,
+    body: web::Json<PostBody>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let body = body.into_inner();
+
+    let times_store = ctx.times_store(&caller, path.into_inner()).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let mut ps = post_store.lock().await;
+    if let Some(tag) = body.tag {
+        check_tag(&mut *ps, tag).await?;
+    }
+    let mut post = ps.post(body.post, None).await.map_err(ApiError::store)?;
 
-    HttpResponse::Ok().body(serde_json::to_string(&resp).unwrap())
+    if body.tag.is_some() {
+        post.tag = body.tag;
+        post = ps.update(post).await.map_err(ApiError::store)?;
+    }
+
+    Ok(HttpResponse::Created().json(post))
 }
 
-#[derive(Serialize)]
-struct PostPostResponse {
-    base: ResponseBase,
-    pid: u64,
+async fn get_post(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, Pid)>,
+) -> Result<web::Json<Post>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Read).await?;
+    let (tid, pid) = path.into_inner();
+
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let post = post_store
+        .lock()
+        .await
+        .get(pid)
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(web::Json(post))
+}
+
+async fn update_post(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, Pid)>,
+    body: web::Json<UpdatePostBody>,
+) -> Result<web::Json<Post>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let (tid, pid) = path.into_inner();
+    let body = body.into_inner();
+
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let mut ps = post_store.lock().await;
+
+    if let Some(Some(tag)) = body.tag {
+        check_tag(&mut *ps, tag).await?;
+    }
+    let post = ps
+        .edit(pid, body.post, body.tag)
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(web::Json(post))
+}
+
+async fn delete_post(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, Pid)>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let (tid, pid) = path.into_inner();
+
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    post_store
+        .lock()
+        .await
+        .delete(pid)
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(HttpResponse::NoContent().finish())
 }
 
+/// The body of a tag assignment. A missing or `null` tag takes the tag off.
 #[derive(Deserialize)]
-struct PostPostRequest {
-    post: String,
+struct AssignTagBody {
+    #[serde(default)]
+    tag: Option<TagId>,
 }
 
-async fn post_post(
+async fn assign_tag(
     ctx: web::Data<Context>,
-    path: web::Path<u64>,
-    req: web::Json<PostPostRequest>,
-) -> impl Responder {
-    let tid = path.into_inner();
-    let post = req.post.clone();
+    req: HttpRequest,
+    path: web::Path<(Tid, Pid)>,
+    body: web::Json<AssignTagBody>,
+) -> Result<web::Json<Post>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let (tid, pid) = path.into_inner();
 
-    let mut store = ctx.store.lock().await;
-    let post = match store.create_post(tid, post).await {
-        Ok(post) => post,
-        Err(e) => {
-            tracing::info!("failed to create a post for times {}: {}", tid, &e);
-            let resp = ResponseBase { status: 1, text: e };
-
-            return HttpResponse::Ok()
-                .body(serde_json::to_string(&resp).unwrap());
-        }
-    };
-
-    tracing::info!(
-        "create a post ({}, {}) for times {}",
-        post.id,
-        post.post,
-        tid
-    );
-
-    let resp = PostPostResponse {
-        base: ResponseBase {
-            status: 0,
-            text: "Ok".to_string(),
-        },
-        pid: post.id,
-    };
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let post = post_store
+        .lock()
+        .await
+        .assign_tag(pid, body.into_inner().tag)
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(web::Json(post))
+}
+
+/// How much of a file is read at a time to send it.
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The name of an uploaded file.
+#[derive(Deserialize)]
+struct FileName {
+    name: String,
+}
+
+/// The type of an uploaded file, told by its `Content-Type`.
+fn file_type(req: &HttpRequest, data: Vec<u8>) -> Result<FileType, ApiError> {
+    let mime = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    if mime.starts_with("text/") {
+        String::from_utf8(data).map(FileType::Text).map_err(|_| {
+            ApiError::new(StatusCode::BAD_REQUEST, "Text files must be UTF-8")
+        })
+    } else if mime.starts_with("image/") {
+        Ok(FileType::Image(data))
+    } else {
+        Ok(FileType::Other(data))
+    }
+}
+
+fn mime_type(file: &File) -> String {
+    match file.ftype.kind() {
+        FileKind::Text => "text/plain; charset=utf-8".to_string(),
+        FileKind::Other => "application/octet-stream".to_string(),
+        FileKind::Image => {
+            let ext = std::path::Path::new(&file.name)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(str::to_ascii_lowercase);
+            match ext.as_deref() {
+                Some("jpg") => "image/jpeg".to_string(),
+                Some("svg") => "image/svg+xml".to_string(),
+                Some(ext) => format!("image/{ext}"),
+                None => "application/octet-stream".to_string(),
+            }
+        }
+    }
+}
+
+/// The data read from `reader`, as it is sent.
+fn file_body(
+    mut reader: FileReader,
+) -> ReceiverStream<Result<web::Bytes, std::io::Error>> {
+    let (tx, rx) = mpsc::channel(4);
+
+    tokio::spawn(async move {
+        loop {
+            let mut data = vec![0; FILE_CHUNK_SIZE];
+            let item = match reader.read(&mut data).await {
+                Ok(0) => return,
+                Ok(len) => {
+                    data.truncate(len);
+                    Ok(web::Bytes::from(data))
+                }
+                Err(e) => Err(e),
+            };
+
+            let last = item.is_err();
+            if tx.send(item).await.is_err() || last {
+                return;
+            }
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
+
+async fn get_file(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, Pid)>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Read).await?;
+    let (tid, pid) = path.into_inner();
+
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let (file, reader) = post_store
+        .lock()
+        .await
+        .open_file(pid)
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(mime_type(&file))
+        .insert_header(header::ContentDisposition::attachment(&file.name))
+        .no_chunking(file.size())
+        .streaming(file_body(reader)))
+}
+
+/// Attaches the request body to a post as a file, replacing the one it had.
+async fn put_file(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, Pid)>,
+    query: web::Query<FileName>,
+    mut payload: web::Payload,
+) -> Result<web::Json<Post>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let (tid, pid) = path.into_inner();
+
+    // Checked before taking the data.
+    let times_store = ctx.times_store(&caller, tid).await?;
+
+    let mut data = Vec::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk =
+            chunk.map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+        if (data.len() + chunk.len()) as u64 > ctx.max_file_size {
+            return Err(ApiError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Files are limited to {} bytes", ctx.max_file_size),
+            ));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    let file = File::new(query.into_inner().name, file_type(&req, data)?);
+
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let mut ps = post_store.lock().await;
+    let post = ps.get(pid).await.map_err(ApiError::store)?;
+    let post = ps
+        .update(Post {
+            file: Some(file),
+            ..post
+        })
+        .await
+        .map_err(ApiError::store)?;
+
+    // The data was just sent; only the info goes back.
+    Ok(web::Json(Post {
+        file: post.file.map(|file| file.info()),
+        ..post
+    }))
+}
+
+#[derive(Deserialize)]
+struct TagName {
+    name: String,
+}
+
+async fn get_tags(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<Tid>,
+) -> Result<web::Json<Vec<Tag>>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Read).await?;
+
+    let times_store = ctx.times_store(&caller, path.into_inner()).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let tags = post_store
+        .lock()
+        .await
+        .get_tags()
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(web::Json(tags))
+}
+
+async fn create_tag(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<Tid>,
+    body: web::Json<TagName>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+
+    let times_store = ctx.times_store(&caller, path.into_inner()).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let tag = post_store
+        .lock()
+        .await
+        .create_tag(body.into_inner().name)
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(HttpResponse::Created().json(tag))
+}
+
+async fn rename_tag(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, TagId)>,
+    body: web::Json<TagName>,
+) -> Result<web::Json<Tag>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let (tid, tagid) = path.into_inner();
+
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    let tag = post_store
+        .lock()
+        .await
+        .rename_tag(tagid, body.into_inner().name)
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(web::Json(tag))
+}
+
+/// Also takes the tag off the posts that have it.
+async fn delete_tag(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, TagId)>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let (tid, tagid) = path.into_inner();
+
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let post_store = times_store
+        .lock()
+        .await
+        .pstore()
+        .await
+        .map_err(ApiError::store)?;
+    post_store
+        .lock()
+        .await
+        .delete_tag(tagid)
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+struct CreateTodoBody {
+    content: String,
+    #[serde(default)]
+    detail: Option<String>,
+}
+
+/// Fields left out are not changed.
+#[derive(Deserialize)]
+struct UpdateTodoBody {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    detail: Option<String>,
+    #[serde(default)]
+    done: Option<bool>,
+}
+
+fn find_todo(todos: Vec<Todo>, tdid: Tdid) -> Result<Todo, ApiError> {
+    todos.into_iter().find(|t| t.id == tdid).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("Todo with id {} not found", tdid),
+        )
+    })
+}
+
+async fn get_todos(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<Tid>,
+) -> Result<web::Json<Vec<Todo>>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Read).await?;
+
+    let times_store = ctx.times_store(&caller, path.into_inner()).await?;
+    let todo_store = times_store
+        .lock()
+        .await
+        .tdstore()
+        .await
+        .map_err(ApiError::store)?;
+    let todos = todo_store
+        .lock()
+        .await
+        .get()
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(web::Json(todos))
+}
+
+async fn create_todo(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<Tid>,
+    body: web::Json<CreateTodoBody>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let body = body.into_inner();
+
+    let times_store = ctx.times_store(&caller, path.into_inner()).await?;
+    let todo_store = times_store
+        .lock()
+        .await
+        .tdstore()
+        .await
+        .map_err(ApiError::store)?;
+    let mut tds = todo_store.lock().await;
+    let mut todo = tds.new(body.content).await.map_err(ApiError::store)?;
+
+    if body.detail.is_some() {
+        todo.detail = body.detail;
+        todo = tds.update(todo).await.map_err(ApiError::store)?;
+    }
+
+    Ok(HttpResponse::Created().json(todo))
+}
+
+async fn get_todo(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, Tdid)>,
+) -> Result<web::Json<Todo>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Read).await?;
+    let (tid, tdid) = path.into_inner();
+
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let todo_store = times_store
+        .lock()
+        .await
+        .tdstore()
+        .await
+        .map_err(ApiError::store)?;
+    let todos = todo_store
+        .lock()
+        .await
+        .get()
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(web::Json(find_todo(todos, tdid)?))
+}
+
+async fn update_todo(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, Tdid)>,
+    body: web::Json<UpdateTodoBody>,
+) -> Result<web::Json<Todo>, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let (tid, tdid) = path.into_inner();
+    let body = body.into_inner();
+
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let todo_store = times_store
+        .lock()
+        .await
+        .tdstore()
+        .await
+        .map_err(ApiError::store)?;
+    let mut tds = todo_store.lock().await;
+    let mut todo = find_todo(tds.get().await.map_err(ApiError::store)?, tdid)?;
+
+    if body.content.is_some() || body.detail.is_some() {
+        if let Some(content) = body.content {
+            todo.content = content;
+        }
+        if body.detail.is_some() {
+            todo.detail = body.detail;
+        }
+        todo = tds.update(todo).await.map_err(ApiError::store)?;
+    }
+    if let Some(done) = body.done {
+        if done != todo.done_at.is_some() {
+            todo = tds.done(tdid, done).await.map_err(ApiError::store)?;
+        }
+    }
+
+    Ok(web::Json(todo))
+}
+
+async fn delete_todo(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<(Tid, Tdid)>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Write).await?;
+    let (tid, tdid) = path.into_inner();
+
+    let times_store = ctx.times_store(&caller, tid).await?;
+    let todo_store = times_store
+        .lock()
+        .await
+        .tdstore()
+        .await
+        .map_err(ApiError::store)?;
+    todo_store
+        .lock()
+        .await
+        .delete(tdid)
+        .await
+        .map_err(ApiError::store)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{Change, EventKind};
+    use actix_web::dev::ServiceResponse;
+    use actix_web::test;
+    use serde_json::{json, Value};
+
+    /// Small, so that the tests can go over it.
+    const MAX_FILE_SIZE: u64 = 1024;
+
+    struct TestApp {
+        ctx: web::Data<Context>,
+        events: Arc<EventHub>,
+    }
+
+    impl TestApp {
+        async fn new() -> Self {
+            let store =
+                timesman_bstore::StoreType::Memory.to_store().await.unwrap();
+            let auth_service = Arc::new(AuthService::new("test-secret-key"));
+            auth_service
+                .create_admin_user(
+                    "admin",
+                    "admin@example.com",
+                    "adminpassword",
+                )
+                .await
+                .unwrap();
+
+            let events = EventHub::follow(store.clone()).await.unwrap();
+
+            Self {
+                ctx: web::Data::new(Context {
+                    store,
+                    auth_service,
+                    events: events.clone(),
+                    max_file_size: MAX_FILE_SIZE,
+                }),
+                events,
+            }
+        }
+
+        async fn send(
+            &self,
+            request: test::TestRequest,
+            token: Option<&str>,
+        ) -> ServiceResponse {
+            let app = test::init_service(
+                App::new().app_data(self.ctx.clone()).configure(routes),
+            )
+            .await;
+
+            let mut request = request;
+            if let Some(token) = token {
+                request = request.insert_header((
+                    header::AUTHORIZATION,
+                    format!("Bearer {token}"),
+                ));
+            }
+            test::call_service(&app, request.to_request()).await
+        }
+
+        async fn call(
+            &self,
+            request: test::TestRequest,
+            token: Option<&str>,
+        ) -> (StatusCode, Value) {
+            let response = self.send(request, token).await;
+            let status = response.status();
+            let body = test::read_body(response).await;
+            let value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+            (status, value)
+        }
+
+        async fn register(&self, username: &str) -> String {
+            let request = test::TestRequest::post()
+                .uri("/auth/register")
+                .set_json(json!({
+                    "username": username,
+                    "email": format!("{username}@example.com"),
+                    "password": "testpassword",
+                }));
+            let (status, body) = self.call(request, None).await;
+            assert_eq!(status, StatusCode::CREATED);
+            body["access_token"].as_str().unwrap().to_string()
+        }
+
+        async fn login(
+            &self,
+            username: &str,
+            password: &str,
+        ) -> (StatusCode, Value) {
+            let request =
+                test::TestRequest::post().uri("/auth/login").set_json(
+                    json!({ "username": username, "password": password }),
+                );
+            self.call(request, None).await
+        }
+
+        async fn create_times(&self, token: &str) -> u64 {
+            let request = test::TestRequest::post()
+                .uri("/times")
+                .set_json(json!({ "title": "Test Times" }));
+            let (status, body) = self.call(request, Some(token)).await;
+            assert_eq!(status, StatusCode::CREATED);
+            body["id"].as_u64().unwrap()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_changes_are_published() {
+        let app = TestApp::new().await;
+        let token = app.register("testuser").await;
+        let mut subscription = app.events.subscribe(None).unwrap();
+
+        let tid = app.create_times(&token).await;
+        let request = test::TestRequest::post()
+            .uri(&format!("/times/{tid}/posts"))
+            .set_json(json!({ "post": "hello" }));
+        let (_, post) = app.call(request, Some(&token)).await;
+        let request =
+            test::TestRequest::delete().uri(&format!("/times/{tid}"));
+        app.call(request, Some(&token)).await;
+
+        let event = subscription.next().await.unwrap().unwrap();
+        assert_eq!(event.kind, EventKind::Created);
+        assert!(matches!(event.change, Change::Times(Some(_))));
+        let event = subscription.next().await.unwrap().unwrap();
+        assert_eq!(event.kind, EventKind::Created);
+        assert!(matches!(
+            event.change,
+            Change::Post { pid, .. } if Some(pid) == post["id"].as_u64()
+        ));
+        let event = subscription.next().await.unwrap().unwrap();
+        assert_eq!(event.kind, EventKind::Deleted);
+        assert_eq!(event.tid, tid);
+        assert!(matches!(event.change, Change::Times(None)));
+    }
+
+    #[actix_web::test]
+    async fn test_times_posts_tags_and_todos() {
+        let app = TestApp::new().await;
+        let token = app.register("testuser").await;
+        let tid = app.create_times(&token).await;
+
+        let request = test::TestRequest::put()
+            .uri(&format!("/times/{tid}"))
+            .set_json(json!({ "title": "Renamed" }));
+        let (status, times) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(times["title"], "Renamed");
+        assert!(times["updated_at"].is_string());
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/times/{tid}/tags"))
+            .set_json(json!({ "name": "work" }));
+        let (status, tag) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/times/{tid}/posts"))
+            .set_json(json!({ "post": "hello", "tag": tag["id"] }));
+        let (status, post) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(post["tag"], tag["id"]);
+        let pid = post["id"].as_u64().unwrap();
+
+        let request = test::TestRequest::put()
+            .uri(&format!("/times/{tid}/posts/{pid}"))
+            .set_json(json!({ "post": "edited" }));
+        let (status, post) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(post["post"], "edited");
+        assert_eq!(post["tag"], tag["id"]);
+
+        let request = test::TestRequest::put()
+            .uri(&format!("/times/{tid}/posts/{pid}"))
+            .set_json(json!({ "post": "edited", "tag": 9999 }));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/times/{tid}/posts"))
+            .set_json(json!({ "post": "other", "tag": 9999 }));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let request = test::TestRequest::put()
+            .uri(&format!("/times/{tid}/posts/{pid}"))
+            .set_json(json!({ "post": "edited", "tag": null }));
+        let (status, post) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(post["tag"].is_null());
+
+        let request =
+            test::TestRequest::get().uri(&format!("/times/{tid}/posts"));
+        let (_, posts) = app.call(request, Some(&token)).await;
+        assert_eq!(posts.as_array().unwrap().len(), 1);
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/times/{tid}/posts/{pid}"));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let request =
+            test::TestRequest::get().uri(&format!("/times/{tid}/posts/{pid}"));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/times/{tid}/todos"))
+            .set_json(json!({ "content": "todo", "detail": "details" }));
+        let (status, todo) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(todo["detail"], "details");
+        let tdid = todo["id"].as_u64().unwrap();
+
+        let request = test::TestRequest::patch()
+            .uri(&format!("/times/{tid}/todos/{tdid}"))
+            .set_json(json!({ "done": true }));
+        let (status, todo) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(todo["done_at"].is_string());
+        assert_eq!(todo["detail"], "details");
+
+        let request =
+            test::TestRequest::get().uri(&format!("/times/{tid}/todos/{tdid}"));
+        let (_, todo) = app.call(request, Some(&token)).await;
+        assert!(todo["done_at"].is_string());
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/times/{tid}/todos/{tdid}"));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let request =
+            test::TestRequest::get().uri(&format!("/times/{tid}/todos/{tdid}"));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let request = test::TestRequest::delete().uri(&format!("/times/{tid}"));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let request = test::TestRequest::get().uri("/times");
+        let (_, timeses) = app.call(request, Some(&token)).await;
+        assert!(timeses.as_array().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_tag_assignment_renaming_and_deletion() {
+        let app = TestApp::new().await;
+        let token = app.register("testuser").await;
+        let tid = app.create_times(&token).await;
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/times/{tid}/tags"))
+            .set_json(json!({ "name": "work" }));
+        let (_, tag) = app.call(request, Some(&token)).await;
+        let tagid = tag["id"].as_u64().unwrap();
+        let request = test::TestRequest::post()
+            .uri(&format!("/times/{tid}/posts"))
+            .set_json(json!({ "post": "hello" }));
+        let (_, post) = app.call(request, Some(&token)).await;
+        let pid = post["id"].as_u64().unwrap();
+
+        let request = test::TestRequest::put()
+            .uri(&format!("/times/{tid}/posts/{pid}/tag"))
+            .set_json(json!({ "tag": tagid }));
+        let (status, post) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(post["tag"], tagid);
+        assert_eq!(post["post"], "hello");
+
+        let request = test::TestRequest::put()
+            .uri(&format!("/times/{tid}/posts/{pid}/tag"))
+            .set_json(json!({ "tag": 9999 }));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let request = test::TestRequest::put()
+            .uri(&format!("/times/{tid}/tags/{tagid}"))
+            .set_json(json!({ "name": "play" }));
+        let (status, tag) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(tag["name"], "play");
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/times/{tid}/tags/{tagid}"));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let request =
+            test::TestRequest::get().uri(&format!("/times/{tid}/posts/{pid}"));
+        let (_, post) = app.call(request, Some(&token)).await;
+        assert!(post["tag"].is_null());
+        let request =
+            test::TestRequest::get().uri(&format!("/times/{tid}/tags"));
+        let (_, tags) = app.call(request, Some(&token)).await;
+        assert!(tags.as_array().unwrap().is_empty());
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/times/{tid}/tags/{tagid}"));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_file_upload_and_download() {
+        let app = TestApp::new().await;
+        let token = app.register("testuser").await;
+        let tid = app.create_times(&token).await;
+        let request = test::TestRequest::post()
+            .uri(&format!("/times/{tid}/posts"))
+            .set_json(json!({ "post": "hello" }));
+        let (_, post) = app.call(request, Some(&token)).await;
+        let pid = post["id"].as_u64().unwrap();
+        let uri = format!("/times/{tid}/posts/{pid}/file");
+
+        let request = test::TestRequest::get().uri(&uri);
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let request = test::TestRequest::put()
+            .uri(&format!("{uri}?name=note.txt"))
+            .insert_header((header::CONTENT_TYPE, "text/plain"))
+            .set_payload("file contents");
+        let (status, post) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(post["file"]["name"], "note.txt");
+        assert_eq!(post["post"], "hello");
+
+        let request = test::TestRequest::get().uri(&uri);
+        let response = app.send(request, Some(&token)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers.get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        let disposition = headers.get(header::CONTENT_DISPOSITION).unwrap();
+        assert!(disposition.to_str().unwrap().contains("note.txt"));
+        assert_eq!(test::read_body(response).await, "file contents");
+
+        let request = test::TestRequest::put()
+            .uri(&format!("{uri}?name=note.txt"))
+            .insert_header((header::CONTENT_TYPE, "text/plain"))
+            .set_payload(vec![0xff, 0xfe]);
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let request = test::TestRequest::put()
+            .uri(&format!("{uri}?name=big.bin"))
+            .set_payload(vec![0; MAX_FILE_SIZE as usize + 1]);
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
 
-    HttpResponse::Ok().body(serde_json::to_string(&resp).unwrap())
+        let request = test::TestRequest::put().uri(&uri).set_payload("data");
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let request = test::TestRequest::get().uri(&uri);
+        let response = app.send(request, Some(&token)).await;
+        assert_eq!(test::read_body(response).await, "file contents");
+    }
+
+    #[actix_web::test]
+    async fn test_posts_paging() {
+        let app = TestApp::new().await;
+        let token = app.register("testuser").await;
+        let tid = app.create_times(&token).await;
+        for text in ["first", "second", "third"] {
+            let request = test::TestRequest::post()
+                .uri(&format!("/times/{tid}/posts"))
+                .set_json(json!({ "post": text }));
+            app.call(request, Some(&token)).await;
+        }
+
+        // Follows the `Link` headers from `uri`, collecting the posts.
+        let pages = |uri: String| {
+            let app = &app;
+            let token = &token;
+            async move {
+                let mut texts = Vec::new();
+                let mut next = Some(uri);
+                while let Some(uri) = next.take() {
+                    let request = test::TestRequest::get().uri(&uri);
+                    let response = app.send(request, Some(token)).await;
+                    assert_eq!(response.status(), StatusCode::OK);
+                    next = response.headers().get(header::LINK).map(|link| {
+                        let link = link.to_str().unwrap();
+                        assert!(link.ends_with(">; rel=\"next\""));
+                        link[1..link.find('>').unwrap()].to_string()
+                    });
+                    let posts: Value = test::read_body_json(response).await;
+                    let posts = posts.as_array().unwrap();
+                    assert!(posts.len() <= 2);
+                    for post in posts {
+                        texts.push(post["post"].as_str().unwrap().to_string());
+                    }
+                }
+                texts
+            }
+        };
+
+        let texts = pages(format!("/times/{tid}/posts?limit=2")).await;
+        assert_eq!(texts, ["first", "second", "third"]);
+        let texts =
+            pages(format!("/times/{tid}/posts?newest_first=true&limit=2"))
+                .await;
+        assert_eq!(texts, ["third", "second", "first"]);
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/times/{tid}/posts?limit=2"));
+        let response = app.send(request, Some(&token)).await;
+        let link = response.headers().get(header::LINK).unwrap();
+        let link = link.to_str().unwrap();
+        let cursor =
+            &link[link.find("cursor=").unwrap() + 7..link.find('>').unwrap()];
+
+        for query in [
+            "limit=0".to_string(),
+            "limit=2&cursor=bad".to_string(),
+            format!("limit=2&newest_first=true&cursor={cursor}"),
+        ] {
+            let request = test::TestRequest::get()
+                .uri(&format!("/times/{tid}/posts?{query}"));
+            let (status, _) = app.call(request, Some(&token)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_watch() {
+        let app = TestApp::new().await;
+        let token = app.register("testuser").await;
+        let other = app.register("otheruser").await;
+        // Waits for the creation to be published, so the watch misses it.
+        let mut subscription = app.events.subscribe(None).unwrap();
+        let tid = app.create_times(&token).await;
+        subscription.next().await.unwrap();
+
+        let request =
+            test::TestRequest::get().uri(&format!("/watch?tid={tid}"));
+        let (status, _) = app.call(request, Some(&other)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let request =
+            test::TestRequest::get().uri(&format!("/watch?tid={tid}"));
+        let watch = app.send(request, Some(&token)).await;
+        assert_eq!(watch.status(), StatusCode::OK);
+        assert_eq!(
+            watch.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/times/{tid}/posts"))
+            .set_json(json!({ "post": "hello" }));
+        let (_, post) = app.call(request, Some(&token)).await;
+        app.create_times(&other).await;
+        let request = test::TestRequest::delete().uri(&format!("/times/{tid}"));
+        app.call(request, Some(&token)).await;
+
+        // Ends once the times is deleted.
+        let body = test::read_body(watch).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        let messages: Vec<(&str, Value)> = body
+            .split_terminator("\n\n")
+            .map(|message| {
+                let lines: Vec<&str> = message.lines().collect();
+                assert!(lines[0].starts_with("id: "));
+                let event = lines[1].strip_prefix("event: ").unwrap();
+                let data = lines[2].strip_prefix("data: ").unwrap();
+                (event, serde_json::from_str(data).unwrap())
+            })
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].0, "post");
+        assert_eq!(messages[0].1["kind"], "created");
+        assert_eq!(messages[0].1["id"], post["id"]);
+        assert_eq!(messages[0].1["item"]["post"], "hello");
+        assert_eq!(messages[1].0, "times");
+        assert_eq!(messages[1].1["kind"], "deleted");
+        assert!(messages[1].1["item"].is_null());
+
+        let request =
+            test::TestRequest::get().uri(&format!("/watch?after={}", u64::MAX));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let request = test::TestRequest::get()
+            .uri("/watch")
+            .insert_header(("Last-Event-ID", "abc"));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_errors() {
+        let app = TestApp::new().await;
+        let token = app.register("testuser").await;
+        let other = app.register("otheruser").await;
+        let tid = app.create_times(&token).await;
+
+        let request = test::TestRequest::get().uri("/times");
+        let response = app.send(request, None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+            "Bearer"
+        );
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "Missing authorization header");
+
+        let request = test::TestRequest::get().uri(&format!("/times/{tid}"));
+        let (status, _) = app.call(request, Some(&other)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let request = test::TestRequest::get().uri("/times/99999");
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let request = test::TestRequest::post()
+            .uri("/times")
+            .set_json(json!({ "name": "missing title" }));
+        let (status, body) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].is_string());
+
+        let request = test::TestRequest::get().uri("/times/abc");
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_login_refresh_and_logout() {
+        let app = TestApp::new().await;
+        app.register("testuser").await;
+
+        let (status, _) = app.login("testuser", "wrongpassword").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, tokens) = app.login("testuser", "testpassword").await;
+        assert_eq!(status, StatusCode::OK);
+        let access_token = tokens["access_token"].as_str().unwrap();
+        let refresh_token = tokens["refresh_token"].as_str().unwrap();
+
+        let request = test::TestRequest::post()
+            .uri("/auth/refresh")
+            .set_json(json!({ "refresh_token": refresh_token }));
+        let (status, refreshed) = app.call(request, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let refreshed_token = refreshed["access_token"].as_str().unwrap();
+
+        let request = test::TestRequest::post().uri("/auth/logout");
+        let (status, _) = app.call(request, Some(refreshed_token)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let request = test::TestRequest::get().uri("/times");
+        let (status, _) = app.call(request, Some(refreshed_token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let request = test::TestRequest::get().uri("/times");
+        let (status, _) = app.call(request, Some(access_token)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_personal_access_tokens() {
+        let app = TestApp::new().await;
+        let token = app.register("testuser").await;
+        let tid = app.create_times(&token).await;
+
+        let request = test::TestRequest::post()
+            .uri("/auth/tokens")
+            .set_json(json!({ "name": "ci", "read_only": true }));
+        let (status, created) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let pat = created["token"].as_str().unwrap();
+        let id = created["info"]["id"].as_str().unwrap();
+
+        let request = test::TestRequest::get().uri(&format!("/times/{tid}"));
+        let (status, _) = app.call(request, Some(pat)).await;
+        assert_eq!(status, StatusCode::OK);
+        let request = test::TestRequest::delete().uri(&format!("/times/{tid}"));
+        let (status, _) = app.call(request, Some(pat)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let request = test::TestRequest::get().uri("/auth/tokens");
+        let (status, _) = app.call(request, Some(pat)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let request =
+            test::TestRequest::delete().uri(&format!("/auth/tokens/{id}"));
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let request = test::TestRequest::get().uri("/times");
+        let (status, _) = app.call(request, Some(pat)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_users() {
+        let app = TestApp::new().await;
+        let token = app.register("testuser").await;
+        let (_, tokens) = app.login("admin", "adminpassword").await;
+        let admin = tokens["access_token"].as_str().unwrap();
+
+        let request = test::TestRequest::get().uri("/admin/users");
+        let (status, _) = app.call(request, Some(&token)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let request = test::TestRequest::get().uri("/admin/users");
+        let (status, users) = app.call(request, Some(admin)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(users.as_array().unwrap().len(), 2);
+        assert!(users[0].get("password_hash").is_none());
+
+        let request = test::TestRequest::patch()
+            .uri("/admin/users/testuser")
+            .set_json(json!({ "role": "ReadOnly" }));
+        let (status, user) = app.call(request, Some(admin)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(user["role"], "ReadOnly");
+
+        let request = test::TestRequest::patch()
+            .uri("/admin/users/admin")
+            .set_json(json!({ "is_active": false }));
+        let (status, _) = app.call(request, Some(admin)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let request = test::TestRequest::delete().uri("/admin/users/testuser");
+        let (status, _) = app.call(request, Some(admin)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = app.login("testuser", "testpassword").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_run_returns_bind_error() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let listen = Listen::Tcp(taken.local_addr().unwrap().to_string());
+        let store =
+            timesman_bstore::StoreType::Memory.to_store().await.unwrap();
+        let server = HttpServer {
+            auth_service: Arc::new(AuthService::new("test-secret-key")),
+            events: Arc::new(EventHub::new()),
+            max_file_size: crate::DEFAULT_MAX_FILE_SIZE,
+        };
+
+        assert!(server.run(&listen, store).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_openapi_covers_routes() {
+        let app = TestApp::new().await;
+
+        let request = test::TestRequest::get().uri("/openapi.json");
+        let (status, doc) = app.call(request, None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let paths = doc["paths"].as_object().unwrap();
+        for (path, methods) in [
+            ("/auth/login", &["post"][..]),
+            ("/auth/tokens/{id}", &["delete"]),
+            ("/admin/users/{username}", &["patch", "delete"]),
+            ("/times", &["get", "post"]),
+            ("/times/{tid}", &["get", "put", "delete"]),
+            ("/times/{tid}/posts", &["get", "post"]),
+            ("/times/{tid}/posts/{pid}", &["get", "put", "delete"]),
+            ("/times/{tid}/posts/{pid}/tag", &["put"]),
+            ("/times/{tid}/posts/{pid}/file", &["get", "put"]),
+            ("/times/{tid}/tags", &["get", "post"]),
+            ("/times/{tid}/tags/{tagid}", &["put", "delete"]),
+            ("/times/{tid}/todos", &["get", "post"]),
+            ("/times/{tid}/todos/{tdid}", &["get", "patch", "delete"]),
+            ("/watch", &["get"]),
+        ] {
+            for method in methods {
+                assert!(paths[path].get(*method).is_some(), "{method} {path}");
+            }
+        }
+    }
 }
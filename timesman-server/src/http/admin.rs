@@ -0,0 +1,230 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use timesman_type::{User, UserId, UserRole};
+
+use super::super::auth::AuthError;
+use super::super::authz::{Access, Caller};
+use super::{ApiError, Context};
+
+pub(super) fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/admin/users", web::get().to(list_users))
+        .route("/admin/users/{username}", web::patch().to(update_user))
+        .route("/admin/users/{username}", web::delete().to(delete_user))
+        .route(
+            "/admin/users/{username}/password",
+            web::put().to(reset_password),
+        )
+        .route("/admin/users/{username}/totp", web::delete().to(reset_totp));
+}
+
+fn auth_error(e: AuthError) -> ApiError {
+    let status = match e {
+        AuthError::UserNotFound => StatusCode::NOT_FOUND,
+        AuthError::WeakPassword(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    ApiError::new(status, e)
+}
+
+/// A user as shown to admins, without the password hash.
+#[derive(Serialize)]
+struct UserView {
+    id: UserId,
+    username: String,
+    email: String,
+    role: UserRole,
+    is_active: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: Option<chrono::NaiveDateTime>,
+}
+
+impl From<User> for UserView {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            role: user.role,
+            is_active: user.is_active,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+/// Fields left out are not changed.
+#[derive(Deserialize)]
+struct UpdateUserBody {
+    #[serde(default)]
+    role: Option<UserRole>,
+    #[serde(default)]
+    is_active: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct PasswordBody {
+    password: String,
+}
+
+impl Context {
+    async fn authenticate_admin(
+        &self,
+        req: &HttpRequest,
+    ) -> Result<Caller, ApiError> {
+        let caller = self.authenticate(req, Access::Write).await?;
+        caller.require_admin().map_err(ApiError::forbidden)?;
+        Ok(caller)
+    }
+
+    /// Keeps admins from locking themselves out.
+    async fn not_self(
+        &self,
+        caller: &Caller,
+        username: &str,
+    ) -> Result<(), ApiError> {
+        let user = self
+            .auth_service
+            .get_user_by_id(&caller.id)
+            .await
+            .map_err(auth_error)?;
+
+        if user.username == username {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                "Cannot do this to your own account",
+            ));
+        }
+        Ok(())
+    }
+}
+
+async fn list_users(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+) -> Result<web::Json<Vec<UserView>>, ApiError> {
+    ctx.authenticate_admin(&req).await?;
+
+    let users = ctx.auth_service.list_users().await.map_err(auth_error)?;
+
+    Ok(web::Json(users.into_iter().map(UserView::from).collect()))
+}
+
+async fn update_user(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<UpdateUserBody>,
+) -> Result<web::Json<UserView>, ApiError> {
+    let caller = ctx.authenticate_admin(&req).await?;
+    let username = path.into_inner();
+    let body = body.into_inner();
+
+    if body.is_active == Some(false)
+        || body.role.as_ref().is_some_and(|r| *r != UserRole::Admin)
+    {
+        ctx.not_self(&caller, &username).await?;
+    }
+
+    let mut user = None;
+    if let Some(role) = body.role {
+        user = Some(
+            ctx.auth_service
+                .set_user_role(&username, role)
+                .await
+                .map_err(auth_error)?,
+        );
+    }
+    if let Some(is_active) = body.is_active {
+        user = Some(
+            ctx.auth_service
+                .set_user_active(&username, is_active)
+                .await
+                .map_err(auth_error)?,
+        );
+    }
+    let user = user.ok_or_else(|| {
+        ApiError::new(StatusCode::BAD_REQUEST, "Nothing to update")
+    })?;
+
+    Ok(web::Json(user.into()))
+}
+
+async fn reset_password(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<PasswordBody>,
+) -> Result<HttpResponse, ApiError> {
+    ctx.authenticate_admin(&req).await?;
+
+    ctx.auth_service
+        .reset_password(&path, &body.password)
+        .await
+        .map_err(auth_error)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Deletes the user along with the times they own.
+async fn delete_user(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate_admin(&req).await?;
+    let username = path.into_inner();
+    ctx.not_self(&caller, &username).await?;
+
+    // The account is locked first and removed last, so that its times are
+    // never left without an owner. If deleting them fails, the account
+    // stays for another try.
+    let user = ctx
+        .auth_service
+        .set_user_active(&username, false)
+        .await
+        .map_err(auth_error)?;
+
+    let mut store = ctx.store.lock().await;
+    let times_stores = store.get().await.map_err(ApiError::store)?;
+
+    let mut owned = Vec::new();
+    for times_store in times_stores {
+        let times = times_store
+            .lock()
+            .await
+            .get()
+            .await
+            .map_err(ApiError::store)?;
+        if times.owner == Some(user.id) {
+            owned.push(times.id);
+        }
+    }
+
+    for tid in owned {
+        store.delete(tid).await.map_err(ApiError::store)?;
+    }
+
+    ctx.auth_service
+        .delete_user(&username)
+        .await
+        .map_err(auth_error)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn reset_totp(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    ctx.authenticate_admin(&req).await?;
+
+    ctx.auth_service
+        .reset_totp(&path)
+        .await
+        .map_err(auth_error)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,297 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use timesman_type::{
+    AuthResponse, LoginRequest, PersonalAccessToken, RegisterRequest,
+    TokenScope,
+};
+
+use super::super::auth::AuthError;
+use super::super::authz::{Access, Caller};
+use super::{login_error, ApiError, Context};
+
+pub(super) fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/auth/register", web::post().to(register))
+        .route("/auth/login", web::post().to(login))
+        .route("/auth/login/totp", web::post().to(verify_totp))
+        .route("/auth/refresh", web::post().to(refresh))
+        .route("/auth/logout", web::post().to(logout))
+        .route("/auth/tokens", web::get().to(list_tokens))
+        .route("/auth/tokens", web::post().to(create_token))
+        .route("/auth/tokens/{id}", web::delete().to(revoke_token))
+        .route("/auth/totp/enroll", web::post().to(enroll_totp))
+        .route("/auth/totp/confirm", web::post().to(confirm_totp))
+        .route("/auth/totp/disable", web::post().to(disable_totp));
+}
+
+fn totp_error(e: AuthError) -> ApiError {
+    let status = match e {
+        AuthError::InvalidTotpCode => StatusCode::BAD_REQUEST,
+        AuthError::TotpAlreadyEnabled | AuthError::TotpNotEnabled => {
+            StatusCode::CONFLICT
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    ApiError::new(status, e)
+}
+
+impl Context {
+    /// Like `authenticate`, but refuses personal access tokens.
+    async fn authenticate_session(
+        &self,
+        req: &HttpRequest,
+        access: Access,
+    ) -> Result<Caller, ApiError> {
+        let caller = self.authenticate(req, access).await?;
+        caller.require_session().map_err(ApiError::forbidden)?;
+        Ok(caller)
+    }
+}
+
+/// A login either succeeds or, with TOTP enabled, asks for a code.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum LoginResponse {
+    Tokens(AuthResponse),
+    SecondFactor { totp_challenge: String },
+}
+
+#[derive(Deserialize)]
+struct VerifyTotpBody {
+    challenge: String,
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct RefreshBody {
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct LogoutBody {
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CreateTokenBody {
+    name: String,
+    #[serde(default)]
+    read_only: bool,
+    #[serde(default)]
+    tid: Option<timesman_type::Tid>,
+    #[serde(default)]
+    expires_in_days: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct CreatedToken {
+    token: String,
+    info: PersonalAccessToken,
+}
+
+#[derive(Serialize)]
+struct TotpEnrollment {
+    secret: String,
+    otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TotpCode {
+    code: String,
+}
+
+#[derive(Serialize)]
+struct TotpBackupCodes {
+    codes: Vec<String>,
+}
+
+async fn register(
+    ctx: web::Data<Context>,
+    body: web::Json<RegisterRequest>,
+) -> Result<HttpResponse, ApiError> {
+    match ctx.auth_service.register(body.into_inner()).await {
+        Ok(auth_response) => Ok(HttpResponse::Created().json(auth_response)),
+        Err(e @ AuthError::UserAlreadyExists) => {
+            Err(ApiError::new(StatusCode::CONFLICT, e))
+        }
+        Err(e) => Err(ApiError::new(StatusCode::BAD_REQUEST, e)),
+    }
+}
+
+async fn login(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    body: web::Json<LoginRequest>,
+) -> Result<web::Json<LoginResponse>, ApiError> {
+    let peer = req.peer_addr().map(|addr| addr.ip());
+
+    match ctx.auth_service.login_from(body.into_inner(), peer).await {
+        Ok(auth_response) => {
+            Ok(web::Json(LoginResponse::Tokens(auth_response)))
+        }
+        Err(AuthError::SecondFactorRequired { challenge }) => {
+            Ok(web::Json(LoginResponse::SecondFactor {
+                totp_challenge: challenge,
+            }))
+        }
+        Err(e) => Err(login_error(e)),
+    }
+}
+
+async fn verify_totp(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    body: web::Json<VerifyTotpBody>,
+) -> Result<web::Json<AuthResponse>, ApiError> {
+    let peer = req.peer_addr().map(|addr| addr.ip());
+
+    ctx.auth_service
+        .verify_totp_from(&body.challenge, &body.code, peer)
+        .await
+        .map(web::Json)
+        .map_err(login_error)
+}
+
+async fn refresh(
+    ctx: web::Data<Context>,
+    body: web::Json<RefreshBody>,
+) -> Result<web::Json<AuthResponse>, ApiError> {
+    ctx.auth_service
+        .refresh(&body.refresh_token)
+        .await
+        .map(web::Json)
+        .map_err(ApiError::unauthorized)
+}
+
+/// The body is optional; without a refresh token only the access token is
+/// revoked.
+async fn logout(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    body: Option<web::Json<LogoutBody>>,
+) -> Result<HttpResponse, ApiError> {
+    let claims = ctx.validate_token(&req).await?;
+    let refresh_token = body.and_then(|body| body.into_inner().refresh_token);
+
+    ctx.auth_service
+        .logout(&claims, refresh_token.as_deref())
+        .await
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn list_tokens(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+) -> Result<web::Json<Vec<PersonalAccessToken>>, ApiError> {
+    let caller = ctx.authenticate_session(&req, Access::Read).await?;
+
+    ctx.auth_service
+        .list_personal_tokens(&caller.id)
+        .await
+        .map(web::Json)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+async fn create_token(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    body: web::Json<CreateTokenBody>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate_session(&req, Access::Write).await?;
+    let body = body.into_inner();
+    let scope = TokenScope {
+        read_only: body.read_only,
+        tid: body.tid,
+    };
+
+    if let Some(tid) = scope.tid {
+        ctx.times_store(&caller, tid).await?;
+    }
+
+    let (info, token) = ctx
+        .auth_service
+        .create_personal_token(
+            &caller.id,
+            body.name,
+            scope,
+            body.expires_in_days,
+        )
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    Ok(HttpResponse::Created().json(CreatedToken { token, info }))
+}
+
+async fn revoke_token(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate_session(&req, Access::Read).await?;
+    let id = path.parse().map_err(|_| {
+        ApiError::new(StatusCode::BAD_REQUEST, "Invalid token id")
+    })?;
+
+    ctx.auth_service
+        .revoke_personal_token(&caller.id, &id)
+        .await
+        .map_err(|e| match e {
+            AuthError::TokenNotFound => ApiError::new(StatusCode::NOT_FOUND, e),
+            _ => ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e),
+        })?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn enroll_totp(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+) -> Result<web::Json<TotpEnrollment>, ApiError> {
+    let caller = ctx.authenticate_session(&req, Access::Read).await?;
+
+    let enrollment = ctx
+        .auth_service
+        .enroll_totp(&caller.id)
+        .await
+        .map_err(totp_error)?;
+
+    Ok(web::Json(TotpEnrollment {
+        secret: enrollment.secret,
+        otpauth_uri: enrollment.uri,
+    }))
+}
+
+async fn confirm_totp(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    body: web::Json<TotpCode>,
+) -> Result<web::Json<TotpBackupCodes>, ApiError> {
+    let caller = ctx.authenticate_session(&req, Access::Read).await?;
+
+    let codes = ctx
+        .auth_service
+        .confirm_totp(&caller.id, &body.code)
+        .await
+        .map_err(totp_error)?;
+
+    Ok(web::Json(TotpBackupCodes { codes }))
+}
+
+async fn disable_totp(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    body: web::Json<TotpCode>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate_session(&req, Access::Read).await?;
+
+    ctx.auth_service
+        .disable_totp(&caller.id, &body.code)
+        .await
+        .map_err(totp_error)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
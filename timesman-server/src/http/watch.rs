@@ -0,0 +1,145 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use timesman_type::Tid;
+
+use super::super::authz::{Access, Caller};
+use super::super::events::{
+    Change, Event, EventKind, Subscription, WatchError,
+};
+use super::{ApiError, Context};
+
+pub(super) fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/watch", web::get().to(watch));
+}
+
+/// Where to start watching, and what. Without `after`, the `Last-Event-ID`
+/// header sent by reconnecting `EventSource`s is used.
+#[derive(Deserialize)]
+struct WatchQuery {
+    #[serde(default)]
+    after: Option<u64>,
+    #[serde(default)]
+    tid: Option<Tid>,
+}
+
+fn watch_error(e: WatchError) -> ApiError {
+    let status = match e {
+        WatchError::Expired(_) => StatusCode::GONE,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    ApiError::new(status, e)
+}
+
+/// The sequence number given in the `Last-Event-ID` header, if any.
+fn last_event_id(req: &HttpRequest) -> Result<Option<u64>, ApiError> {
+    let Some(value) = req.headers().get("Last-Event-ID") else {
+        return Ok(None);
+    };
+
+    value
+        .to_str()
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Some)
+        .ok_or_else(|| {
+            ApiError::new(StatusCode::BAD_REQUEST, "Invalid Last-Event-ID")
+        })
+}
+
+/// Streams the changes the caller may see as server-sent events, each
+/// named after what changed. Watching a times ends once it is deleted.
+async fn watch(
+    ctx: web::Data<Context>,
+    req: HttpRequest,
+    query: web::Query<WatchQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let caller = ctx.authenticate(&req, Access::Read).await?;
+    let query = query.into_inner();
+
+    if let Some(tid) = query.tid {
+        ctx.times_store(&caller, tid).await?;
+    }
+    let after = match query.after {
+        Some(after) => Some(after),
+        None => last_event_id(&req)?,
+    };
+    let subscription = ctx.events.subscribe(after).map_err(watch_error)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(stream(subscription, caller, query.tid)))
+}
+
+type SseStream = ReceiverStream<Result<web::Bytes, std::io::Error>>;
+
+fn stream(
+    mut subscription: Subscription,
+    caller: Caller,
+    within: Option<Tid>,
+) -> SseStream {
+    let (tx, rx) = mpsc::channel(16);
+
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                _ = tx.closed() => return,
+                event = subscription.next() => event,
+            };
+
+            let (message, last) = match event {
+                Ok(Some(event)) => {
+                    if !caller.can_access_times(event.tid, event.owner)
+                        || within.is_some_and(|tid| tid != event.tid)
+                    {
+                        continue;
+                    }
+                    let deleted = within.is_some()
+                        && matches!(event.change, Change::Times(None));
+                    (message(&event), deleted)
+                }
+                Ok(None) => return,
+                Err(e) => (error_message(e), true),
+            };
+
+            if tx.send(Ok(message.into())).await.is_err() || last {
+                return;
+            }
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
+
+fn message(event: &Event) -> String {
+    let kind = match event.kind {
+        EventKind::Created => "created",
+        EventKind::Updated => "updated",
+        EventKind::Deleted => "deleted",
+    };
+    let (name, id, item) = match &event.change {
+        Change::Times(times) => ("times", event.tid, json!(times)),
+        Change::Post { pid, post } => ("post", *pid, json!(post)),
+        Change::Tag { tagid, tag } => ("tag", *tagid, json!(tag)),
+        Change::Todo { tdid, todo } => ("todo", *tdid, json!(todo)),
+    };
+    let data = json!({
+        "seq": event.seq,
+        "kind": kind,
+        "tid": event.tid,
+        "id": id,
+        "item": item,
+    });
+
+    format!("id: {}\nevent: {name}\ndata: {data}\n\n", event.seq)
+}
+
+fn error_message(e: WatchError) -> String {
+    let data: Value = json!({ "error": e.to_string() });
+    format!("event: error\ndata: {data}\n\n")
+}
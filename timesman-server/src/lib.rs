@@ -1,20 +1,227 @@
-//pub mod http;
 pub mod auth;
+pub mod authz;
+pub mod events;
+
+mod cursor;
 
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio::task::JoinSet;
 
 #[cfg(feature = "grpc")]
 mod grpc;
 #[cfg(feature = "grpc")]
 pub use grpc::GrpcServer;
 
+mod http;
+pub use http::HttpServer;
+
 use async_trait::async_trait;
 
 use timesman_bstore::Store;
 pub use auth::AuthService;
+pub use events::EventHub;
+
+/// Largest attachment the fronts accept unless configured otherwise,
+/// in bytes.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// PEM certificate chain and key to serve TLS with. With `client_ca`,
+/// clients have to present a certificate it signed, unless
+/// `client_auth_optional` is set.
+#[derive(Clone)]
+pub struct TlsSettings {
+    pub cert: Vec<u8>,
+    pub key: Vec<u8>,
+    pub client_ca: Option<Vec<u8>>,
+    pub client_auth_optional: bool,
+}
+
+/// Where a front listens. Written as a TCP address such as
+/// `127.0.0.1:8080`, or as `unix:<path>` for a Unix socket.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Listen {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl std::str::FromStr for Listen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) if !path.is_empty() => Ok(Self::Unix(path.into())),
+            #[cfg(unix)]
+            Some(_) => Err("Unix socket path is empty".to_string()),
+            #[cfg(not(unix))]
+            Some(_) => Err("Unix sockets are not supported here".to_string()),
+            None => Ok(Self::Tcp(s.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for Listen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Removes a socket file left behind by an earlier run, so that the path
+/// can be bound again. Sockets something still listens on are kept.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            if std::os::unix::net::UnixStream::connect(path).is_err() {
+                std::fs::remove_file(path)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
 
 #[async_trait]
-pub trait TimesManServer {
-    async fn run(&self, listen: &str, store: Arc<Mutex<dyn Store>>);
+pub trait TimesManServer: Send + Sync {
+    /// Serves on `listen` until the server stops. Fails when `listen`
+    /// cannot be bound.
+    async fn run(
+        &self,
+        listen: &Listen,
+        store: Arc<Mutex<dyn Store>>,
+    ) -> std::io::Result<()>;
+}
+
+/// Runs every front on its address against the same store. Returns when
+/// one of them stops, stopping the others, with what it returned.
+pub async fn serve(
+    fronts: Vec<(Box<dyn TimesManServer>, Listen)>,
+    store: Arc<Mutex<dyn Store>>,
+) -> std::io::Result<()> {
+    let mut running = JoinSet::new();
+    for (server, listen) in fronts {
+        let store = store.clone();
+        running.spawn(async move { server.run(&listen, store).await });
+    }
+
+    match running.join_next().await {
+        Some(Ok(result)) => result,
+        Some(Err(e)) if e.is_panic() => {
+            std::panic::resume_unwind(e.into_panic())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_listen() {
+        assert_eq!(
+            "127.0.0.1:8080".parse::<Listen>().unwrap(),
+            Listen::Tcp("127.0.0.1:8080".to_string())
+        );
+        assert_eq!(
+            "unix:/run/timesman.sock".parse::<Listen>().unwrap(),
+            Listen::Unix("/run/timesman.sock".into())
+        );
+        assert!("unix:".parse::<Listen>().is_err());
+        assert_eq!(
+            Listen::Unix("/tmp/a.sock".into()).to_string(),
+            "unix:/tmp/a.sock"
+        );
+    }
+
+    /// Serves gRPC on TCP and HTTP on a Unix socket, registers over one
+    /// and logs in over the other.
+    #[cfg(all(unix, feature = "grpc"))]
+    #[tokio::test]
+    async fn test_fronts_share_auth_service() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let socket = std::env::temp_dir()
+            .join(format!("timesman-test-{}.sock", std::process::id()));
+
+        let auth_service = Arc::new(AuthService::new("test-secret-key"));
+        let events = Arc::new(EventHub::new());
+        let store =
+            timesman_bstore::StoreType::Memory.to_store().await.unwrap();
+        let fronts: Vec<(Box<dyn TimesManServer>, Listen)> = vec![
+            (
+                Box::new(GrpcServer {
+                    auth_service: auth_service.clone(),
+                    events: events.clone(),
+                    tls: None,
+                    max_file_size: DEFAULT_MAX_FILE_SIZE,
+                }),
+                Listen::Tcp(format!("127.0.0.1:{port}")),
+            ),
+            (
+                Box::new(HttpServer {
+                    auth_service,
+                    events,
+                    max_file_size: DEFAULT_MAX_FILE_SIZE,
+                }),
+                Listen::Unix(socket.clone()),
+            ),
+        ];
+        tokio::spawn(serve(fronts, store));
+
+        let url = format!("http://127.0.0.1:{port}");
+        let channel = loop {
+            if let Ok(channel) =
+                timesman_grpc::tls::connect(url.clone(), None).await
+            {
+                break channel;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        let mut client =
+            timesman_grpc::grpc::times_man_client::TimesManClient::new(channel);
+        client
+            .register(timesman_grpc::grpc::RegisterRequest {
+                username: "testuser".to_string(),
+                email: "test@example.com".to_string(),
+                password: "testpassword".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let mut stream = loop {
+            if let Ok(stream) = tokio::net::UnixStream::connect(&socket).await {
+                break stream;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        let body = r#"{"username":"testuser","password":"testpassword"}"#;
+        let request = format!(
+            "POST /auth/login HTTP/1.1\r\nHost: localhost\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.contains("access_token"));
+        std::fs::remove_file(&socket).unwrap();
+    }
 }
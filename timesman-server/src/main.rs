@@ -1,12 +1,14 @@
 mod config;
 //#[cfg(feature = "grpc")]
 //mod grpc;
-//mod http;
 
 use config::FrontType;
 
+use std::sync::Arc;
+
 use clap::Parser;
-use timesman_server::TimesManServer;
+use timesman_server::auth::MemoryUserRepository;
+use timesman_server::{AuthService, EventHub, Listen, TimesManServer};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -28,23 +30,73 @@ async fn main() -> std::io::Result<()> {
     let store_type = config.store.to_store_type().unwrap();
     let store = store_type.to_store().await.unwrap();
 
-    let server: Box<dyn TimesManServer> = match config.front_type {
-        FrontType::Grpc => {
-            #[cfg(feature = "grpc")]
-            {
-                Box::new(timesman_server::GrpcServer {})
-            }
-            #[cfg(not(feature = "grpc"))]
-            {
-                panic!("gRPC feature not enabled");
-            }
-        }
-        FrontType::Http => {
-            panic!("HTTP server not implemented");
-        }
+    let users = match &config.users {
+        Some(users) => users.to_repository().await.unwrap(),
+        None => Arc::new(MemoryUserRepository::new()),
     };
+    let auth_settings = config
+        .auth
+        .clone()
+        .unwrap_or_default()
+        .to_settings()
+        .unwrap();
+    let auth_service =
+        Arc::new(AuthService::with_settings(auth_settings, users));
+
+    if let Some(admin) = &config.admin {
+        let password = admin.password().unwrap();
+        let created = auth_service
+            .bootstrap_admin(&admin.username, &admin.email, &password)
+            .await
+            .unwrap();
+        if created {
+            tracing::info!("Created admin user {}", admin.username);
+        }
+    }
+
+    // Fed from the store, so that watchers on any front see every change,
+    // whoever made it.
+    let events = EventHub::follow(store.clone()).await.unwrap();
+    let max_file_size = config
+        .max_file_size
+        .unwrap_or(timesman_server::DEFAULT_MAX_FILE_SIZE);
+
+    let mut fronts: Vec<(Box<dyn TimesManServer>, Listen)> = Vec::new();
+    for front in config.fronts().unwrap() {
+        let listen: Listen = front.listen.parse().unwrap();
+        let auth_service = auth_service.clone();
+        let events = events.clone();
+
+        let server: Box<dyn TimesManServer> = match front.front_type {
+            FrontType::Grpc => {
+                #[cfg(feature = "grpc")]
+                {
+                    let tls = front
+                        .tls
+                        .as_ref()
+                        .map(|tls| tls.to_settings().unwrap());
+                    Box::new(timesman_server::GrpcServer {
+                        auth_service,
+                        events,
+                        tls,
+                        max_file_size,
+                    })
+                }
+                #[cfg(not(feature = "grpc"))]
+                {
+                    panic!("gRPC feature not enabled");
+                }
+            }
+            FrontType::Http => Box::new(timesman_server::HttpServer {
+                auth_service,
+                events,
+                max_file_size,
+            }),
+        };
 
-    server.run(&config.listen, store).await;
+        tracing::info!("Serving {:?} on {listen}", front.front_type);
+        fronts.push((server, listen));
+    }
 
-    Ok(())
+    timesman_server::serve(fronts, store).await
 }
@@ -1,10 +1,22 @@
-use timesman_grpc::grpc::times_man_client::TimesManClient;
+use timesman_grpc::client::{Client, Credentials};
 use timesman_grpc::grpc::{TimesTitle, TimesId, CreatePostPrams, DeletePostParam, UpdatePostParam, 
-                         CreateTodoParams, TodoDetailParams, UpdateTodoDetailParams, DoneTodoParams};
+                         CreateTodoParams, TodoDetailParams, UpdateTodoDetailParams, DoneTodoParams,
+                         UpdateTodoParams, DeleteTodoParams};
+use timesman_grpc::tls::ClientTls;
+use timesman_grpc::ConversionError;
 use timesman_type::{Post, Times, Todo};
 
+/// Turns a reply into its domain value, or into a readable error if the
+/// server sent something malformed.
+fn from_reply<T, U>(reply: T) -> Result<U, String>
+where
+    U: TryFrom<T, Error = ConversionError>,
+{
+    U::try_from(reply).map_err(|e| format!("Invalid reply from the server: {e}"))
+}
+
 pub struct GrpcClient {
-    client: TimesManClient<tonic::transport::channel::Channel>,
+    client: Client,
     rt: tokio::runtime::Runtime,
 }
 
@@ -12,14 +24,14 @@ impl super::Client for GrpcClient {
     fn get_times(&mut self) -> Result<Vec<Times>, String> {
         let tary = self
             .rt
-            .block_on(async { self.client.get_times(()).await.unwrap() })
-            .into_inner();
+            .block_on(self.client.call_idempotent((), |mut c, r| async move { c.get_times(r).await }))
+            .map_err(|e| format!("gRPC error: {}", e))?;
 
         let r = tary
             .timeses
-            .iter()
-            .map(|t| t.clone().into())
-            .collect::<Vec<Times>>();
+            .into_iter()
+            .map(from_reply)
+            .collect::<Result<Vec<Times>, _>>()?;
         Ok(r)
     }
 
@@ -27,16 +39,16 @@ impl super::Client for GrpcClient {
         let request = TimesTitle { title };
         let response = self
             .rt
-            .block_on(async { self.client.create_times(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.create_times(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
-        Ok(response.into_inner().into())
+        from_reply(response)
     }
 
     fn delete_times(&mut self, tid: u64) -> Result<(), String> {
         let request = TimesId { id: tid };
         self.rt
-            .block_on(async { self.client.delete_times(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.delete_times(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
         Ok(())
@@ -46,25 +58,24 @@ impl super::Client for GrpcClient {
         let request: timesman_grpc::grpc::Times = times.into();
         let response = self
             .rt
-            .block_on(async { self.client.update_times(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.update_times(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
-        Ok(response.into_inner().into())
+        from_reply(response)
     }
 
     fn get_posts(&mut self, tid: u64) -> Result<Vec<Post>, String> {
         let request = TimesId { id: tid };
         let response = self
             .rt
-            .block_on(async { self.client.get_posts(request).await })
+            .block_on(self.client.call_idempotent(request, |mut c, r| async move { c.get_posts(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
         let posts = response
-            .into_inner()
             .posts
-            .iter()
-            .map(|p| p.clone().into())
-            .collect::<Vec<Post>>();
+            .into_iter()
+            .map(from_reply)
+            .collect::<Result<Vec<Post>, _>>()?;
         
         Ok(posts)
     }
@@ -72,40 +83,29 @@ impl super::Client for GrpcClient {
         let request = CreatePostPrams { id: tid, text };
         let response = self
             .rt
-            .block_on(async { self.client.create_post(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.create_post(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
-        Ok(response.into_inner().into())
+        from_reply(response)
     }
 
     fn create_post_with_file(&mut self, tid: u64, text: String, file: timesman_type::File) -> Result<Post, String> {
-        let file_info = match &file.ftype {
-            timesman_type::FileType::Text(content) => {
-                format!("\n\n--- File: {} ---\n{}", file.name, content)
-            }
-            timesman_type::FileType::Image(data) => {
-                format!("\n\n--- Image File: {} ({} bytes) ---", file.name, data.len())
-            }
-            timesman_type::FileType::Other(data) => {
-                format!("\n\n--- Binary File: {} ({} bytes) ---", file.name, data.len())
-            }
-        };
-        
-        let full_text = format!("{}{}", text, file_info);
-        
-        let request = CreatePostPrams { id: tid, text: full_text };
         let response = self
             .rt
-            .block_on(async { self.client.create_post(request).await })
+            .block_on(self.client.call((text, file.clone()), |mut c, (text, file)| async move {
+                c.create_post_with_file(timesman_grpc::file::upload(tid, text, file)).await
+            }))
             .map_err(|e| format!("gRPC error: {}", e))?;
-        
-        Ok(response.into_inner().into())
+
+        let mut post: Post = from_reply(response)?;
+        post.file = Some(file);
+        Ok(post)
     }
 
     fn delete_post(&mut self, tid: u64, pid: u64) -> Result<(), String> {
         let request = DeletePostParam { tid, pid };
         self.rt
-            .block_on(async { self.client.delete_post(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.delete_post(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
         Ok(())
@@ -118,25 +118,24 @@ impl super::Client for GrpcClient {
         };
         let response = self
             .rt
-            .block_on(async { self.client.update_post(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.update_post(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
-        Ok(response.into_inner().into())
+        from_reply(response)
     }
 
     fn get_todos(&mut self, tid: u64) -> Result<Vec<Todo>, String> {
         let request = TimesId { id: tid };
         let response = self
             .rt
-            .block_on(async { self.client.get_todos(request).await })
+            .block_on(self.client.call_idempotent(request, |mut c, r| async move { c.get_todos(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
         let todos = response
-            .into_inner()
             .todos
-            .iter()
-            .map(|t| t.clone().into())
-            .collect::<Vec<Todo>>();
+            .into_iter()
+            .map(from_reply)
+            .collect::<Result<Vec<Todo>, _>>()?;
         
         Ok(todos)
     }
@@ -149,10 +148,10 @@ impl super::Client for GrpcClient {
         };
         let response = self
             .rt
-            .block_on(async { self.client.create_todo(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.create_todo(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
-        Ok(response.into_inner().into())
+        from_reply(response)
     }
 
     fn create_todo_with_detail(&mut self, tid: u64, content: String, detail: Option<String>) -> Result<Todo, String> {
@@ -163,89 +162,87 @@ impl super::Client for GrpcClient {
         };
         let response = self
             .rt
-            .block_on(async { self.client.create_todo(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.create_todo(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
-        Ok(response.into_inner().into())
+        from_reply(response)
     }
 
     fn delete_todo(&mut self, tid: u64, tdid: u64) -> Result<(), String> {
-        // Note: There's no specific delete_todo endpoint in the proto, 
-        // so we'll use done_todo to mark as done for now
-        let request = DoneTodoParams { 
-            tid, 
-            tdid, 
-            done: true 
-        };
+        let request = DeleteTodoParams { tid, tdid };
         self.rt
-            .block_on(async { self.client.done_todo(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.delete_todo(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
         Ok(())
     }
 
     fn update_todo(&mut self, tid: u64, todo: Todo) -> Result<Todo, String> {
-        // For basic todo update, we can use update_todo_detail if detail exists
-        if let Some(detail) = &todo.detail {
-            let request = UpdateTodoDetailParams { 
-                tid, 
-                tdid: todo.id, 
-                detail: detail.clone() 
-            };
-            let response = self
-                .rt
-                .block_on(async { self.client.update_todo_detail(request).await })
-                .map_err(|e| format!("gRPC error: {}", e))?;
-            
-            Ok(response.into_inner().into())
-        } else {
-            // If no detail, just return the original todo
-            // In a full implementation, we'd need an update_todo_content endpoint
-            Ok(todo)
-        }
+        let request = UpdateTodoParams {
+            tid,
+            tdid: todo.id,
+            content: todo.content,
+            detail: todo.detail,
+            done: todo.done_at.is_some(),
+        };
+        let response = self
+            .rt
+            .block_on(self.client.call(request, |mut c, r| async move { c.update_todo(r).await }))
+            .map_err(|e| format!("gRPC error: {}", e))?;
+        
+        from_reply(response)
     }
 
     fn get_todo_detail(&mut self, tid: u64, tdid: u64) -> Result<Todo, String> {
         let request = TodoDetailParams { tid, tdid };
         let response = self
             .rt
-            .block_on(async { self.client.get_todo_detail(request).await })
+            .block_on(self.client.call_idempotent(request, |mut c, r| async move { c.get_todo_detail(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
-        Ok(response.into_inner().into())
+        from_reply(response)
     }
 
     fn update_todo_detail(&mut self, tid: u64, tdid: u64, detail: String) -> Result<Todo, String> {
         let request = UpdateTodoDetailParams { tid, tdid, detail };
         let response = self
             .rt
-            .block_on(async { self.client.update_todo_detail(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.update_todo_detail(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
-        Ok(response.into_inner().into())
+        from_reply(response)
     }
 
     fn mark_todo_done(&mut self, tid: u64, tdid: u64, done: bool) -> Result<Todo, String> {
         let request = DoneTodoParams { tid, tdid, done };
         let response = self
             .rt
-            .block_on(async { self.client.done_todo(request).await })
+            .block_on(self.client.call(request, |mut c, r| async move { c.done_todo(r).await }))
             .map_err(|e| format!("gRPC error: {}", e))?;
         
-        Ok(response.into_inner().into())
+        from_reply(response)
     }
 }
 
 impl GrpcClient {
-    pub fn new(server: &String) -> Self {
+    pub fn new(
+        server: &String,
+        credentials: Option<Credentials>,
+        tls: Option<ClientTls>,
+    ) -> Result<Self, String> {
         let rt = tokio::runtime::Builder::new_current_thread()
-            .enable_io()
+            .enable_all()
             .build()
             .unwrap();
 
-        let server: String = server.parse().unwrap();
-        let client = rt
-            .block_on(async { TimesManClient::connect(server).await.unwrap() });
-        Self { client, rt }
+        let client = rt.block_on(async {
+            let client = Client::connect(server.clone(), tls.as_ref()).await?;
+            if let Some(credentials) = credentials {
+                client.authenticate(credentials).await?;
+            }
+            Ok::<_, String>(client)
+        })?;
+
+        Ok(Self { client, rt })
     }
 }
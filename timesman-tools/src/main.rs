@@ -4,6 +4,8 @@ mod tui;
 use clap::{Parser, Subcommand};
 use chrono;
 
+use timesman_grpc::client::Credentials;
+use timesman_grpc::tls::ClientTls;
 use timesman_type::{File, FileType, Post, Times, Todo};
 use std::fs;
 use std::path::Path;
@@ -42,6 +44,24 @@ struct Args {
     conn_type: String,
     #[arg(short, long)]
     server: Option<String>,
+    /// Access token or personal access token to send with every request
+    #[arg(long, env = "TIMESMAN_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    /// User to log in as, instead of sending the token
+    #[arg(long, requires = "password")]
+    username: Option<String>,
+    /// Password of the user to log in as
+    #[arg(long, env = "TIMESMAN_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+    /// PEM CA bundle to verify the server with, instead of the system roots
+    #[arg(long)]
+    ca_cert: Option<String>,
+    /// PEM client certificate, for servers that require one
+    #[arg(long, requires = "client_key")]
+    client_cert: Option<String>,
+    /// PEM key of the client certificate
+    #[arg(long, requires = "client_cert")]
+    client_key: Option<String>,
     #[command(subcommand)]
     command: Command,
 }
@@ -228,10 +248,10 @@ fn load_file_from_path(file_path: &str) -> Result<File, String> {
         }
     };
     
-    Ok(File {
-        name: file_name,
-        ftype: file_type,
-    })
+    Ok(File::new(
+        file_name,
+        file_type,
+    ))
 }
 
 fn run_command(mut c: Box<dyn Client>, cmd: &Command) -> Result<(), String> {
@@ -256,6 +276,7 @@ fn run_command(mut c: Box<dyn Client>, cmd: &Command) -> Result<(), String> {
                 title: title.clone(),
                 created_at: chrono::Utc::now().naive_utc(),
                 updated_at: Some(chrono::Utc::now().naive_utc()),
+                owner: None,
             };
             let updated_times = c.update_times(times)?;
             println!("Updated times: {}", updated_times);
@@ -306,13 +327,9 @@ fn run_command(mut c: Box<dyn Client>, cmd: &Command) -> Result<(), String> {
             println!("Deleted todo with ID: {} from times: {}", tdid, tid);
         }
         Command::UpdateTodo { tid, tdid, content } => {
-            let todo = Todo {
-                id: *tdid,
-                content: content.clone(),
-                detail: None,
-                created_at: chrono::Utc::now().naive_utc(),
-                done_at: None,
-            };
+            // Only the content changes; the detail and done state are kept.
+            let mut todo = c.get_todo_detail(*tid, *tdid)?;
+            todo.content = content.clone();
             let updated_todo = c.update_todo(*tid, todo)?;
             println!("Updated todo: ID {}, Content: {}", updated_todo.id, updated_todo.content);
         }
@@ -353,8 +370,38 @@ fn main() {
         "http://127.0.0.1:8080/".to_string()
     };
 
+    let tls = if args.ca_cert.is_some() || args.client_cert.is_some() {
+        let tls = ClientTls::from_files(
+            args.ca_cert.as_deref(),
+            args.client_cert.as_deref(),
+            args.client_key.as_deref(),
+        );
+        match tls {
+            Ok(tls) => Some(tls),
+            Err(e) => {
+                println!("{e}");
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    let credentials = match (args.username, args.password, args.token) {
+        (Some(username), Some(password), _) => {
+            Some(Credentials::Password { username, password })
+        }
+        (_, _, token) => token.map(Credentials::Token),
+    };
+
     let client = match &*args.conn_type {
-        "grpc" => Box::new(grpc::GrpcClient::new(&server)),
+        "grpc" => match grpc::GrpcClient::new(&server, credentials, tls) {
+            Ok(client) => Box::new(client),
+            Err(e) => {
+                println!("{e}");
+                return;
+            }
+        },
         _ => {
             unimplemented!();
         }
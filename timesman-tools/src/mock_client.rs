@@ -42,12 +42,14 @@ impl MockClient {
             title: "Test Project".to_string(),
             created_at: now,
             updated_at: None,
+            owner: None,
         };
         let times2 = Times {
             id: 2,
             title: "Another Project".to_string(),
             created_at: now,
             updated_at: Some(now),
+            owner: None,
         };
         
         self.times.insert(1, times1);
@@ -130,6 +132,7 @@ impl Client for MockClient {
             title,
             created_at: now,
             updated_at: None,
+            owner: None,
         };
         
         self.times.insert(self.next_times_id, times.clone());
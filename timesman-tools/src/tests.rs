@@ -62,6 +62,7 @@ mod client_tests {
             title: "Updated Project".to_string(),
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: None,
+            owner: None,
         };
         
         let result = client.update_times(updated_times).unwrap();
@@ -74,6 +75,7 @@ mod client_tests {
             title: "Non-existent".to_string(),
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: None,
+            owner: None,
         };
         
         let result = client.update_times(non_existent_times);
@@ -184,6 +186,7 @@ mod client_tests {
             title: "test".to_string(),
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: None,
+            owner: None,
         };
         assert!(client.update_times(times.clone()).is_err());
         
@@ -11,6 +11,10 @@ pub struct Times {
     pub title: String,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    /// User that owns this times on a multi-user server. `None` for
+    /// single-user stores and for data created before ownership existed.
+    #[serde(default)]
+    pub owner: Option<UserId>,
 }
 
 impl std::fmt::Display for Times {
@@ -27,11 +31,35 @@ impl std::fmt::Display for Times {
     }
 }
 
+/// What a file holds, whether its data is here or not.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileKind {
+    Image,
+    Text,
+    Other,
+}
+
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum FileType {
     Image(Vec<u8>),
     Text(String),
     Other(Vec<u8>),
+    /// The data is left out, as in post listings.
+    Omitted {
+        kind: FileKind,
+        size: u64,
+    },
+}
+
+impl FileType {
+    pub fn kind(&self) -> FileKind {
+        match self {
+            FileType::Image(_) => FileKind::Image,
+            FileType::Text(_) => FileKind::Text,
+            FileType::Other(_) => FileKind::Other,
+            FileType::Omitted { kind, .. } => *kind,
+        }
+    }
 }
 
 impl Debug for FileType {
@@ -46,6 +74,9 @@ impl Debug for FileType {
             FileType::Other(data) => {
                 write!(f, "Unknown file:  size {}", data.len())
             }
+            FileType::Omitted { kind, size } => {
+                write!(f, "{kind:?} file without data: size {size}")
+            }
         }
     }
 }
@@ -56,6 +87,45 @@ pub struct File {
     pub ftype: FileType,
 }
 
+impl File {
+    pub fn new(name: String, ftype: FileType) -> Self {
+        Self { name, ftype }
+    }
+
+    /// Whether the data is here, rather than left out.
+    pub fn has_data(&self) -> bool {
+        !matches!(self.ftype, FileType::Omitted { .. })
+    }
+
+    /// The size of the data in bytes, whether it is here or not.
+    pub fn size(&self) -> u64 {
+        match &self.ftype {
+            FileType::Text(text) => text.len() as u64,
+            FileType::Image(data) | FileType::Other(data) => data.len() as u64,
+            FileType::Omitted { size, .. } => *size,
+        }
+    }
+
+    /// The data, or `None` when it is left out.
+    pub fn into_data(self) -> Option<Vec<u8>> {
+        match self.ftype {
+            FileType::Text(text) => Some(text.into_bytes()),
+            FileType::Image(data) | FileType::Other(data) => Some(data),
+            FileType::Omitted { .. } => None,
+        }
+    }
+
+    /// The file without its data.
+    pub fn info(&self) -> Self {
+        let ftype = FileType::Omitted {
+            kind: self.ftype.kind(),
+            size: self.size(),
+        };
+
+        Self::new(self.name.clone(), ftype)
+    }
+}
+
 pub type Pid = u64;
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -70,7 +140,7 @@ pub struct Post {
 
 pub type TagId = u64;
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Tag {
     pub id: TagId,
     pub name: String,
@@ -123,6 +193,32 @@ pub struct Claims {
     pub exp: usize,         // Expiration time (as UTC timestamp)
     pub iat: usize,         // Issued at (as UTC timestamp)
     pub iss: String,        // Issuer
+    #[serde(default)]
+    pub jti: String,        // Token ID, used to revoke the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<TokenScope>, // Set for personal access tokens
+    #[serde(default)]
+    pub epoch: u64,         // Token epoch of the user when issued
+}
+
+/// What a personal access token is allowed to do, on top of its owner's
+/// role.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct TokenScope {
+    pub read_only: bool,
+    /// Restricts the token to this one times.
+    pub tid: Option<Tid>,
+}
+
+/// A personal access token as listed to its owner. The token itself is
+/// only returned once, when it is created.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PersonalAccessToken {
+    pub id: Uuid,
+    pub name: String,
+    pub scope: TokenScope,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -143,6 +239,7 @@ pub struct AuthResponse {
     pub access_token: String,
     pub token_type: String,
     pub expires_in: usize,
+    pub refresh_token: String,
     pub user: UserInfo,
 }
 
@@ -169,6 +266,7 @@ mod tests {
             title: "Test Times".to_string(),
             created_at: created,
             updated_at: Some(updated),
+            owner: None,
         };
         
         let display = format!("{}", times);
@@ -187,6 +285,7 @@ mod tests {
             title: "Test Times".to_string(),
             created_at: created,
             updated_at: None,
+            owner: None,
         };
         
         let display = format!("{}", times);
@@ -205,6 +304,7 @@ mod tests {
             title: "Test".to_string(),
             created_at: created,
             updated_at: None,
+            owner: None,
         };
         
         let times2 = Times {
@@ -212,6 +312,7 @@ mod tests {
             title: "Test".to_string(),
             created_at: created,
             updated_at: None,
+            owner: None,
         };
         
         assert_eq!(times1, times2);
@@ -260,10 +361,10 @@ mod tests {
 
     #[test]
     fn file_creation() {
-        let file = File {
-            name: "test.txt".to_string(),
-            ftype: FileType::Text("content".to_string()),
-        };
+        let file = File::new(
+            "test.txt".to_string(),
+            FileType::Text("content".to_string()),
+        );
         
         assert_eq!(file.name, "test.txt");
         match file.ftype {
@@ -272,13 +373,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn file_info() {
+        let file = File::new(
+            "photo.png".to_string(),
+            FileType::Image(vec![1, 2, 3, 4]),
+        );
+        assert!(file.has_data());
+        assert_eq!(file.size(), 4);
+
+        let info = file.info();
+        assert!(!info.has_data());
+        assert_eq!(info.size(), 4);
+        assert_eq!(info.ftype.kind(), FileKind::Image);
+        assert_eq!(info.info(), info);
+    }
+
+    #[test]
+    fn empty_file_has_data() {
+        let file =
+            File::new("empty.txt".to_string(), FileType::Text(String::new()));
+        assert!(file.has_data());
+        assert_eq!(file.size(), 0);
+        assert!(!file.info().has_data());
+    }
+
     #[test]
     fn post_with_file() {
         let created = NaiveDateTime::parse_from_str("2023-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
-        let file = File {
-            name: "attachment.jpg".to_string(),
-            ftype: FileType::Image(vec![1, 2, 3, 4]),
-        };
+        let file = File::new(
+            "attachment.jpg".to_string(),
+            FileType::Image(vec![1, 2, 3, 4]),
+        );
         
         let post = Post {
             id: 1,
@@ -446,6 +572,7 @@ mod tests {
             title: "Test".to_string(),
             created_at: created,
             updated_at: None,
+            owner: None,
         };
         
         let json = serde_json::to_string(&times).unwrap();